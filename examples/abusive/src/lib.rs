@@ -0,0 +1,52 @@
+//! A contract abusing its sandbox, for testing the execution limits.
+#![no_std]
+
+use retroshade_emit::Retroshade;
+use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Bytes, Env, Symbol};
+
+#[contracttype]
+pub struct FloodEvent {
+    index: u32,
+}
+
+impl Retroshade for FloodEvent {
+    fn target(_: &Env) -> Symbol {
+        symbol_short!("flood")
+    }
+}
+
+#[contract]
+pub struct AbusiveContract;
+
+#[contractimpl]
+impl AbusiveContract {
+    /// Emits `count` retroshades.
+    pub fn flood(env: Env, count: u32) {
+        for index in 0..count {
+            FloodEvent { index }.emit(&env)
+        }
+    }
+
+    /// Grows a host bytes object by a KiB `kib` times, each growth copying
+    /// the whole object.
+    pub fn hoard(env: Env, kib: u32) -> u32 {
+        let chunk = Bytes::from_array(&env, &[7; 1024]);
+        let mut hoard = Bytes::new(&env);
+        for _ in 0..kib {
+            hoard.append(&chunk);
+        }
+
+        hoard.len()
+    }
+
+    /// Grows the linear memory by `pages` of 64 KiB, panicking if the
+    /// growth is refused.
+    pub fn grow(_: Env, pages: u32) -> u32 {
+        let previous = core::arch::wasm32::memory_grow(0, pages as usize);
+        if previous == usize::MAX {
+            panic!("memory growth refused")
+        }
+
+        previous as u32
+    }
+}
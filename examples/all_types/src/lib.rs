@@ -0,0 +1,72 @@
+#![no_std]
+use retroshade_emit::Retroshade;
+use soroban_sdk::{
+    contract, contractimpl, contracttype, map, symbol_short, vec, Address, Bytes, BytesN, Duration,
+    Env, Map, String, Symbol, Timepoint, Vec, I256, U256,
+};
+
+/// Every type a retroshade field can have, at the edges of their ranges
+/// where they have any.
+#[contracttype]
+pub struct AllTypesEvent {
+    flag: bool,
+    small: u32,
+    signed: i32,
+    big: u64,
+    negative: i64,
+    unsigned_128: u128,
+    signed_128: i128,
+    unsigned_256: U256,
+    signed_256: I256,
+    duration: Duration,
+    timepoint: Timepoint,
+    bytes: Bytes,
+    hash: BytesN<32>,
+    text: String,
+    symbol: Symbol,
+    address: Address,
+    some: Option<i128>,
+    none: Option<u32>,
+    addresses: Vec<Address>,
+    nested: Vec<Vec<u32>>,
+    map: Map<Symbol, u32>,
+}
+
+impl Retroshade for AllTypesEvent {
+    fn target(_: &Env) -> Symbol {
+        symbol_short!("alltypes")
+    }
+}
+
+#[contract]
+pub struct AllTypesContract;
+
+#[contractimpl]
+impl AllTypesContract {
+    pub fn emit(env: Env, address: Address) {
+        AllTypesEvent {
+            flag: true,
+            small: 7,
+            signed: -7,
+            big: u64::MAX,
+            negative: i64::MIN,
+            unsigned_128: u128::MAX,
+            signed_128: i128::MIN,
+            unsigned_256: U256::from_parts(&env, u64::MAX, u64::MAX, u64::MAX, u64::MAX),
+            signed_256: I256::from_parts(&env, i64::MIN, 0, 0, 0),
+            duration: Duration::from_seconds(&env, 3600),
+            timepoint: Timepoint::from_unix(&env, 1_700_000_000),
+            bytes: Bytes::from_array(&env, &[0xde, 0xad, 0xbe, 0xef]),
+            hash: BytesN::from_array(&env, &[7; 32]),
+            text: String::from_str(&env, "retroshade"),
+            symbol: symbol_short!("mercury"),
+            addresses: vec![&env, address.clone(), env.current_contract_address()],
+            address,
+            some: Some(5),
+            none: None,
+            nested: vec![&env, vec![&env, 1, 2], vec![&env, 3]],
+            map: map![&env, (symbol_short!("a"), 1), (symbol_short!("b"), 2)],
+        }
+        .emit(&env)
+    }
+}
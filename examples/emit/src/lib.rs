@@ -0,0 +1,95 @@
+//! Emitting retroshades from contracts built for Mercury.
+//!
+//! Contracts call [`emit`], or [`Retroshade::emit`] on their events,
+//! unconditionally. Only builds with the `mercury` feature import Mercury's
+//! host function and emit anything, without it the call compiles to nothing
+//! and the wasm is the one to deploy.
+//!
+//! Events name their target, rather than the table taking the name of
+//! their type, with [`retroshade_target!`].
+#![no_std]
+
+use soroban_sdk::{Env, IntoVal, Symbol, Val};
+
+#[cfg(feature = "mercury")]
+#[link(wasm_import_module = "x")]
+extern "C" {
+    #[allow(improper_ctypes)]
+    #[link_name = "9"]
+    fn zephyr_emit(target: i64, event: i64) -> i64;
+}
+
+/// Emits `event` as a retroshade for `target`, the table it ends up in.
+#[inline(always)]
+pub fn emit<E: IntoVal<Env, Val>>(env: &Env, target: Symbol, event: E) {
+    #[cfg(feature = "mercury")]
+    {
+        let target = target.as_val().get_payload() as i64;
+        let event: Val = event.into_val(env);
+
+        unsafe { zephyr_emit(target, event.get_payload() as i64) };
+    }
+
+    #[cfg(not(feature = "mercury"))]
+    let _ = (env, target, event);
+}
+
+/// Events with a fixed target.
+pub trait Retroshade: IntoVal<Env, Val> + Sized {
+    fn target(env: &Env) -> Symbol;
+
+    fn emit(self, env: &Env) {
+        emit(env, Self::target(env), self)
+    }
+}
+
+/// Longest target, the length limit of symbols.
+pub const MAX_TARGET_LEN: usize = 32;
+
+/// Whether `target` is a symbol a target can be: at most [`MAX_TARGET_LEN`]
+/// characters out of `a-zA-Z0-9_`, and not empty since it names a table.
+pub const fn is_valid_target(target: &str) -> bool {
+    let bytes = target.as_bytes();
+    if bytes.is_empty() || bytes.len() > MAX_TARGET_LEN {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if !matches!(bytes[i], b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_') {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// Implements [`Retroshade`] for `$event` with `$target` as its target.
+/// Invalid targets fail the build rather than the contract call.
+///
+/// ```ignore
+/// retroshade_target!(SwapEvent, "swaps_v2");
+/// ```
+#[macro_export]
+macro_rules! retroshade_target {
+    ($event:ty, $target:literal) => {
+        const _: () = assert!(
+            $crate::is_valid_target($target),
+            concat!("invalid retroshade target \"", $target, "\"")
+        );
+
+        impl $crate::Retroshade for $event {
+            fn target(env: &$crate::__private::Env) -> $crate::__private::Symbol {
+                $crate::__private::Symbol::new(env, $target)
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use soroban_sdk::{Env, Symbol};
+}
+
+mod test;
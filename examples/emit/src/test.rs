@@ -0,0 +1,35 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{contracttype, Env};
+
+#[contracttype]
+pub struct SwapEvent {
+    amount: i128,
+}
+
+retroshade_target!(SwapEvent, "swaps_v2");
+
+#[test]
+fn custom_target() {
+    let env = Env::default();
+
+    assert_eq!(SwapEvent::target(&env), Symbol::new(&env, "swaps_v2"));
+}
+
+#[test]
+fn valid_targets() {
+    assert!(is_valid_target("swaps_v2"));
+    assert!(is_valid_target("A_9"));
+    assert!(is_valid_target("abcdefghijklmnopqrstuvwxyz_12345"));
+}
+
+#[test]
+fn invalid_targets_are_rejected() {
+    assert!(!is_valid_target(""));
+    assert!(!is_valid_target("abcdefghijklmnopqrstuvwxyz_123456"));
+    assert!(!is_valid_target("swaps-v2"));
+    assert!(!is_valid_target("swaps v2"));
+    assert!(!is_valid_target("swaps\"; --"));
+    assert!(!is_valid_target("échanges"));
+}
@@ -0,0 +1,60 @@
+#![no_std]
+use retroshade_emit::Retroshade;
+use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, Env, Symbol};
+
+#[contracttype]
+#[derive(Clone, Copy)]
+pub enum Side {
+    Buy = 0,
+    Sell = 1,
+}
+
+#[contracttype]
+pub enum OrderStatus {
+    Open,
+    Filled(i128),
+}
+
+#[contracttype]
+pub struct Fill {
+    maker: Address,
+    amount: i128,
+}
+
+#[contracttype]
+pub struct OrderEvent {
+    side: Side,
+    status: OrderStatus,
+    fill: Fill,
+    ledger: u32,
+}
+
+impl Retroshade for OrderEvent {
+    fn target(_: &Env) -> Symbol {
+        symbol_short!("orders")
+    }
+}
+
+#[contract]
+pub struct OrdersContract;
+
+#[contractimpl]
+impl OrdersContract {
+    pub fn fill(env: Env, maker: Address, side: Side, amount: i128) {
+        let filled = env.storage().instance().get(&0).unwrap_or(0_i128) + amount;
+        env.storage().instance().set(&0, &filled);
+
+        let status = if amount > 0 {
+            OrderStatus::Filled(filled)
+        } else {
+            OrderStatus::Open
+        };
+        OrderEvent {
+            side,
+            status,
+            fill: Fill { maker, amount },
+            ledger: env.ledger().sequence(),
+        }
+        .emit(&env)
+    }
+}
@@ -0,0 +1,163 @@
+//! State shared by the benchmarks needing `testutils`, built from the
+//! checked-in fixtures and the contracts in `../examples`.
+
+#![allow(dead_code)]
+
+use std::{env, path::PathBuf, process::Command};
+
+use retroshade::{
+    fixture,
+    snapshot::MemorySnapshot,
+    soroban_env_host::xdr::{
+        ContractDataDurability, ContractDataEntry, ContractExecutable, ExtensionPoint, Hash,
+        Int128Parts, LedgerEntry, LedgerEntryChanges, LedgerEntryData, LedgerEntryExt, LedgerKey,
+        LedgerKeyContractData, ScAddress, ScContractInstance, ScMap, ScMapEntry, ScVal,
+        SorobanTransactionMeta, SorobanTransactionMetaExt, TransactionEnvelope, TransactionMeta,
+        TransactionMetaV3, TransactionV1Envelope,
+    },
+    testutils::{code_key, instance_key, EnvelopeBuilder, MockSnapshot},
+};
+
+/// The smallest valid module, calls to it fail once the host is fed the state.
+pub const EMPTY_WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+/// The first mainnet transaction emitting retroshades.
+pub fn mainnet_fixture() -> (MemorySnapshot, TransactionV1Envelope, TransactionMeta) {
+    let (snapshot, envelope, meta) = fixture::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/mainnet_first_retroshade.json"
+    ))
+    .unwrap();
+    let TransactionEnvelope::Tx(envelope) = envelope else {
+        panic!("fixture envelope is not a v1 envelope")
+    };
+
+    (snapshot, envelope, meta)
+}
+
+/// Meta of a transaction that didn't change any entry.
+pub fn empty_meta() -> TransactionMeta {
+    TransactionMeta::V3(TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value: ScVal::Void,
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+        operations: vec![].try_into().unwrap(),
+    })
+}
+
+fn data_key(idx: u32) -> LedgerKey {
+    LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::Contract(Hash([0; 32]).into()),
+        key: ScVal::U32(idx),
+        durability: ContractDataDurability::Persistent,
+    })
+}
+
+fn data_entry(idx: u32) -> LedgerEntry {
+    LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            key: ScVal::U32(idx),
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::U64(idx as u64),
+        }),
+        ext: LedgerEntryExt::V0,
+    }
+}
+
+/// A call to an empty contract reading `entries` contract data entries.
+pub fn large_footprint(entries: u32) -> (MockSnapshot, TransactionV1Envelope, TransactionMeta) {
+    let mut snapshot = MockSnapshot::with_contract(Hash([0; 32]), EMPTY_WASM, ScMap::default());
+    let mut envelope = EnvelopeBuilder::new()
+        .function("noop")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])));
+
+    for idx in 0..entries {
+        snapshot.insert(data_entry(idx));
+        envelope = envelope.read_only_key(data_key(idx));
+    }
+
+    (snapshot, envelope.build(), empty_meta())
+}
+
+/// Builds `example` for `wasm32-unknown-unknown` into its own `target/` and
+/// reads the wasm of `crate_name`.
+pub fn example_wasm(example: &str, crate_name: &str) -> Vec<u8> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../examples")
+        .join(example);
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--release", "--target", "wasm32-unknown-unknown"])
+        .arg("--target-dir")
+        .arg(dir.join("target"))
+        .current_dir(&dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(
+        status.success(),
+        "building the {} example failed, is the wasm32-unknown-unknown target installed?",
+        example
+    );
+
+    std::fs::read(
+        dir.join("target/wasm32-unknown-unknown/release")
+            .join(format!("{}.wasm", crate_name)),
+    )
+    .unwrap()
+}
+
+/// The storage example about to emit its retroshade: its instance holds
+/// `0 -> 1`. The code is keyed by its actual hash so that engines cache it.
+pub fn storage_example() -> (MockSnapshot, TransactionV1Envelope, TransactionMeta) {
+    storage_example_writing(0)
+}
+
+/// Same as [`storage_example`] with `entries` more contract data entries in
+/// the read-write footprint, each reported as a ledger change.
+pub fn storage_example_writing(
+    entries: u32,
+) -> (MockSnapshot, TransactionV1Envelope, TransactionMeta) {
+    let wasm = example_wasm("storage", "soroban_hello_world_contract");
+    let (mut snapshot, code_hash) = MockSnapshot::with_real_hash(Hash([0; 32]), &wasm);
+    snapshot.insert(LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(code_hash.clone()),
+                storage: Some(ScMap(
+                    vec![ScMapEntry {
+                        key: ScVal::I32(0),
+                        val: ScVal::I128(Int128Parts { hi: 0, lo: 1 }),
+                    }]
+                    .try_into()
+                    .unwrap(),
+                )),
+            }),
+        }),
+        ext: LedgerEntryExt::V0,
+    });
+
+    let mut envelope = EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(code_hash))
+        .read_write_key(instance_key(Hash([0; 32])));
+    for idx in 0..entries {
+        snapshot.insert(data_entry(idx));
+        envelope = envelope.read_write_key(data_key(idx));
+    }
+
+    (snapshot, envelope.build(), empty_meta())
+}
@@ -0,0 +1,88 @@
+//! Repeated executions of the same built state, as when a transaction is
+//! re-run with different seeds or settings, and batches of transactions run
+//! through a shared [`RetroshadeEngine`] or a fresh one each. Run with
+//! `cargo bench`.
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use retroshade::{
+    engine::RetroshadeEngine,
+    fixture,
+    soroban_env_host::{xdr::TransactionEnvelope, LedgerInfo},
+    RetroshadesExecution,
+};
+
+const RUNS: usize = 1000;
+
+fn built_mainnet_fixture() -> RetroshadesExecution {
+    let (snapshot, envelope, meta) = fixture::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/mainnet_first_retroshade.json"
+    ))
+    .unwrap();
+    let TransactionEnvelope::Tx(envelope) = envelope else {
+        panic!("fixture envelope is not a v1 envelope")
+    };
+
+    let mut ledger_info = LedgerInfo::default();
+    ledger_info.protocol_version = 25;
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info);
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+
+    retroshades
+}
+
+fn repeated_execution(c: &mut Criterion) {
+    let retroshades = built_mainnet_fixture();
+
+    let mut group = c.benchmark_group("repeated_execution");
+    group.sample_size(10);
+    group.bench_function("retroshade", |b| {
+        b.iter(|| {
+            for _ in 0..RUNS {
+                retroshades.retroshade().unwrap();
+            }
+        })
+    });
+    group.bench_function("retroshade_packed", |b| {
+        b.iter(|| {
+            for _ in 0..RUNS {
+                retroshades.retroshade_packed().unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn engine_reuse(c: &mut Criterion) {
+    let retroshades = built_mainnet_fixture();
+
+    let mut group = c.benchmark_group("engine_reuse");
+    group.sample_size(10);
+    group.bench_function("shared_engine", |b| {
+        let engine = RetroshadeEngine::new().unwrap();
+        b.iter(|| {
+            for _ in 0..RUNS {
+                engine.execute_packed(&retroshades).unwrap();
+            }
+        })
+    });
+    group.bench_function("engine_per_tx", |b| {
+        b.iter(|| {
+            for _ in 0..RUNS {
+                RetroshadeEngine::new()
+                    .unwrap()
+                    .execute_packed(&retroshades)
+                    .unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, repeated_execution, engine_reuse);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+//! Repeated executions of a transaction with a large footprint, where the
+//! keys of the pre-execution entries and the hashes of their TTL entries are
+//! derived once when building rather than by every execution. Run with
+//! `cargo bench --features testutils`.
+
+mod common;
+
+use std::{collections::HashMap, rc::Rc};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use retroshade::{testutils::ledger_info, RetroshadesExecution};
+
+const FOOTPRINT: u32 = 1000;
+const RUNS: usize = 100;
+
+fn large_footprint_executions(c: &mut Criterion) {
+    let (snapshot, envelope, meta) = common::large_footprint(FOOTPRINT);
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+    let snapshot = Rc::new(snapshot);
+
+    let mut group = c.benchmark_group("large_footprint");
+    group.sample_size(10);
+    group.bench_function("retroshade", |b| {
+        b.iter(|| {
+            for _ in 0..RUNS {
+                let _ = retroshades.retroshade();
+            }
+        })
+    });
+    group.bench_function("retroshade_recording", |b| {
+        b.iter(|| {
+            for _ in 0..RUNS {
+                let _ = retroshades.retroshade_recording(snapshot.clone());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, large_footprint_executions);
+criterion_main!(benches);
@@ -0,0 +1,152 @@
+//! Throughput of the execution pipeline's phases, to catch regressions of
+//! the performance work: building the state, executing with and without a
+//! module cache or ledger changes, and packing. Run with
+//! `cargo bench --features testutils,bench-internals`.
+
+mod common;
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use retroshade::{
+    engine::RetroshadeEngine,
+    soroban_env_host::{
+        xdr::{Hash, ScMap, ScMapEntry, ScSymbol, ScVal, TransactionMeta, TransactionV1Envelope},
+        zephyr::RetroshadeExport,
+        LedgerInfo,
+    },
+    testutils::{ledger_info, ledger_info_protocol, MockSnapshot},
+    RetroshadeExecutionResult, RetroshadesExecution,
+};
+
+const FOOTPRINT: u32 = 1000;
+const EXPORTS: u32 = 1000;
+
+fn built(
+    snapshot: MockSnapshot,
+    envelope: TransactionV1Envelope,
+    meta: TransactionMeta,
+) -> RetroshadesExecution {
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+
+    retroshades
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+
+    let (snapshot, envelope, meta) = common::mainnet_fixture();
+    let mut mainnet_ledger_info = LedgerInfo::default();
+    mainnet_ledger_info.protocol_version = 25;
+    let build_mainnet = |envelope, meta| {
+        let mut retroshades = RetroshadesExecution::new(mainnet_ledger_info.clone());
+        retroshades
+            .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+            .unwrap();
+        retroshades
+    };
+    // note: the build encodes the state for the host, once.
+    assert_eq!(build_mainnet(envelope.clone(), meta.clone()).encodings(), 1);
+    group.bench_function("mainnet_fixture", |b| {
+        b.iter_batched(
+            || (envelope.clone(), meta.clone()),
+            |(envelope, meta)| build_mainnet(envelope, meta),
+            BatchSize::SmallInput,
+        )
+    });
+
+    let (snapshot, envelope, meta) = common::large_footprint(FOOTPRINT);
+    group.bench_function("large_footprint", |b| {
+        b.iter_batched(
+            || (envelope.clone(), meta.clone()),
+            |(envelope, meta)| built(snapshot.clone(), envelope, meta),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn module_cache(c: &mut Criterion) {
+    let (snapshot, envelope, meta) = common::storage_example();
+    let retroshades = built(snapshot, envelope, meta);
+    let engine = RetroshadeEngine::new().unwrap();
+
+    let mut group = c.benchmark_group("storage_example");
+    group.bench_function("without_module_cache", |b| {
+        b.iter(|| retroshades.retroshade().unwrap())
+    });
+    group.bench_function("with_module_cache", |b| {
+        b.iter(|| engine.execute(&retroshades).unwrap())
+    });
+    group.finish();
+
+    // note: the comparison is meaningless if the engine kept parsing the contract.
+    assert_eq!(engine.modules_parsed(), 1);
+    assert!(engine.module_cache_hits() > 0);
+}
+
+fn ledger_changes(c: &mut Criterion) {
+    let (snapshot, envelope, meta) = common::storage_example_writing(FOOTPRINT);
+    let mut retroshades = built(snapshot, envelope, meta);
+    assert!(retroshades.retroshade().unwrap().ledger_changes.len() > FOOTPRINT as usize);
+
+    let mut group = c.benchmark_group("write_heavy");
+    group.bench_function("with_ledger_changes", |b| {
+        b.iter(|| retroshades.retroshade().unwrap())
+    });
+    retroshades.set_ledger_changes(false);
+    group.bench_function("without_ledger_changes", |b| {
+        b.iter(|| retroshades.retroshade().unwrap())
+    });
+    group.finish();
+}
+
+fn synthetic_exports(count: u32) -> Vec<RetroshadeExport> {
+    (0..count)
+        .map(|idx| RetroshadeExport {
+            contract_id: Hash([0; 32]),
+            target: ScVal::Symbol(ScSymbol("settlement".try_into().unwrap())),
+            event_object: ScVal::Map(Some(ScMap(
+                vec![ScMapEntry {
+                    key: ScVal::Symbol(ScSymbol("amount".try_into().unwrap())),
+                    val: ScVal::U32(idx),
+                }]
+                .try_into()
+                .unwrap(),
+            ))),
+        })
+        .collect()
+}
+
+fn packing(c: &mut Criterion) {
+    let (snapshot, envelope, meta) = common::mainnet_fixture();
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+    let exports = synthetic_exports(EXPORTS);
+
+    let mut group = c.benchmark_group("packing");
+    group.bench_function("pack_result_1k", |b| {
+        b.iter_batched(
+            || RetroshadeExecutionResult {
+                retroshades: exports.clone(),
+                diagnostic: vec![],
+                invoke_result: Ok(ScVal::Void),
+                ledger_changes: vec![],
+                timings: Default::default(),
+                state: Default::default(),
+                truncated: false,
+            },
+            |result| retroshades.pack_result(result).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, build, module_cache, ledger_changes, packing);
+criterion_main!(benches);
@@ -0,0 +1 @@
+CREATE TABLE IF NOT EXISTS "mydeposit" ("amount" numeric NOT NULL, "from" text NOT NULL, "ledger" numeric NOT NULL, "now_tvl" numeric NOT NULL, "previous_tvl" numeric NOT NULL, "timestamp" numeric NOT NULL);
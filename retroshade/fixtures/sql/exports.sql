@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "test" ("amount" numeric, "test" text);
+INSERT INTO "test" ("amount", "test") VALUES ('2'::numeric, 'CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4');
+CREATE TABLE IF NOT EXISTS "batch" ("amounts" numeric[]);
+INSERT INTO "batch" ("amounts") VALUES (ARRAY['1'::numeric, '2'::numeric]::numeric[]);
+CREATE TABLE IF NOT EXISTS "batch" ("amounts" text, "memo" text);
+INSERT INTO "batch" ("amounts", "memo") VALUES (NULL, 'a, "quoted" memo');
@@ -0,0 +1,3 @@
+CREATE TABLE IF NOT EXISTS "payout" ("amounts" numeric[] NOT NULL, "approved" boolean NOT NULL, "hash" bytea NOT NULL, "memo" text, "recipients" text[] NOT NULL, "to" text NOT NULL);
+CREATE SCHEMA IF NOT EXISTS "caaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabsc4";
+CREATE TABLE IF NOT EXISTS "caaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabsc4"."payout" ("amounts" numeric[] NOT NULL, "approved" boolean NOT NULL, "hash" bytea NOT NULL, "memo" text, "recipients" text[] NOT NULL, "to" text NOT NULL);
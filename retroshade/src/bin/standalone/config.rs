@@ -0,0 +1,219 @@
+//! `--config` file support. Flags always take precedence over the file:
+//!
+//! ```toml
+//! # pubnet, testnet, futurenet or standalone.
+//! network = "testnet"
+//! # for custom networks, overrides the preset's passphrase.
+//! # network_passphrase = "My Network ; 2024"
+//! db = "/tmp/rs_ingestion_temp/stellar.db"
+//!
+//! [output]
+//! format = "pretty"
+//! jsonl = "exports.jsonl"
+//!
+//! [replacements]
+//! # contract strkey or hex wasm hash = wasm path, relative to this file. A
+//! # wasm hash replaces the code of every contract deployed with it.
+//! "CAS3J7GYLGXMF6TDJBBYYSE3HQ6BBSMLNUQ34T6TZMYMW2EVH34XOWMA" = "swaps.wasm"
+//!
+//! [filters]
+//! # only keep these targets for the contract.
+//! "CAS3J7GYLGXMF6TDJBBYYSE3HQ6BBSMLNUQ34T6TZMYMW2EVH34XOWMA" = ["swaps"]
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use retroshade::{
+    network::{Network, NetworkDefaults, STANDALONE_PASSPHRASE},
+    ReplacementEntry,
+};
+use serde::Deserialize;
+use soroban_env_host::xdr::Hash;
+
+use crate::run::{ExecutionArgs, Output, Replacement};
+
+pub const DEFAULT_DB: &str = "/tmp/rs_ingestion_temp/stellar.db";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub network: Option<Network>,
+    pub network_passphrase: Option<String>,
+    pub db: Option<PathBuf>,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub replacements: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    pub filters: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    pub format: Option<Output>,
+    /// Also appends the exports to this JSON Lines file.
+    pub jsonl: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|error| error.to_string())
+    }
+
+    /// Reads the config, resolving its paths relative to the file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("couldn't read {}: {error}", path.display()))?;
+        let mut config =
+            Self::parse(&contents).map_err(|error| format!("{}: {error}", path.display()))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.db = config.db.map(|db| base.join(db));
+        config.output.jsonl = config.output.jsonl.map(|jsonl| base.join(jsonl));
+        for wasm in config.replacements.values_mut() {
+            *wasm = base.join(&*wasm);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Settings a command runs with, after merging the flags over the config.
+pub struct Settings {
+    pub db: PathBuf,
+    /// Network preset from the flags or config, see [`Self::passphrase`].
+    pub network: Option<Network>,
+    /// Custom passphrase, overriding the preset's.
+    pub network_passphrase: Option<String>,
+    pub output: Output,
+    pub jsonl: Option<PathBuf>,
+    pub replacements: Vec<Replacement>,
+    /// Targets to keep, by contract strkey. Contracts without an entry keep
+    /// all of their targets.
+    pub filters: HashMap<String, HashSet<String>>,
+}
+
+impl Settings {
+    pub fn resolve(args: &ExecutionArgs, config: Config) -> Result<Self, String> {
+        let mut replacements = Vec::new();
+        for (key, path) in config.replacements {
+            // note: a flag for the same key replaces the file's mapping.
+            let hash = parse_key(&key)?;
+            if args.replacements.iter().all(|flag| flag.key != hash) {
+                replacements.push(Replacement::parse(&key, &path)?);
+            }
+        }
+        replacements.extend(args.replacements.iter().cloned());
+
+        for contract in config.filters.keys() {
+            stellar_strkey::Contract::from_string(contract)
+                .map_err(|_| format!("filter key {contract} is not a contract strkey"))?;
+        }
+
+        // note: the preset and passphrase go together, a flag for either
+        // ignores both of the file's.
+        let (network, network_passphrase) =
+            if args.network.is_some() || args.network_passphrase.is_some() {
+                (args.network, args.network_passphrase.clone())
+            } else {
+                (config.network, config.network_passphrase)
+            };
+
+        Ok(Self {
+            db: args
+                .db
+                .clone()
+                .or(config.db)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_DB)),
+            network,
+            network_passphrase,
+            output: args.output.or(config.output.format).unwrap_or(Output::Json),
+            jsonl: args.jsonl.clone().or(config.output.jsonl),
+            replacements,
+            filters: config
+                .filters
+                .into_iter()
+                .map(|(contract, targets)| (contract, targets.into_iter().collect()))
+                .collect(),
+        })
+    }
+
+    /// Passphrase given through the flags or config, if any.
+    pub fn explicit_passphrase(&self) -> Option<&str> {
+        self.network_passphrase
+            .as_deref()
+            .or(self.network.map(Network::passphrase))
+    }
+
+    /// Network passphrase, defaulting to the standalone network.
+    pub fn passphrase(&self) -> &str {
+        self.explicit_passphrase().unwrap_or(STANDALONE_PASSPHRASE)
+    }
+
+    /// Defaults of the preset, or of the network the passphrase belongs to.
+    /// Custom networks get the standalone ones.
+    pub fn network_defaults(&self) -> NetworkDefaults {
+        self.network
+            .or_else(|| Network::from_passphrase(self.passphrase()))
+            .unwrap_or(Network::Standalone)
+            .defaults()
+    }
+
+    pub fn keeps(&self, contract_id: &str, target: &str) -> bool {
+        self.filters
+            .get(contract_id)
+            .map_or(true, |targets| targets.contains(target))
+    }
+}
+
+/// A replacement key is either a contract strkey or a hex wasm hash.
+pub fn parse_key(key: &str) -> Result<Hash, String> {
+    if let Ok(contract) = stellar_strkey::Contract::from_string(key) {
+        return Ok(Hash(contract.0));
+    }
+
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(Hash)
+        .ok_or_else(|| format!("{key} is neither a contract strkey nor a hex wasm hash"))
+}
+
+impl Replacement {
+    /// Reads the wasm and checks that it's a valid module.
+    pub fn load(key: Hash, path: &Path) -> Result<Self, String> {
+        let wasm =
+            fs::read(path).map_err(|error| format!("couldn't read {}: {error}", path.display()))?;
+        wasmparser::validate(&wasm)
+            .map_err(|error| format!("{} is not a valid wasm: {error}", path.display()))?;
+
+        Ok(Self {
+            key,
+            path: path.to_path_buf(),
+            wasm: wasm.into(),
+            any_contract: false,
+        })
+    }
+
+    /// Parses `key` and loads the wasm at `path`.
+    pub fn parse(key: &str, path: &Path) -> Result<Self, String> {
+        let mut replacement = Self::load(parse_key(key)?, path)?;
+        replacement.any_contract = stellar_strkey::Contract::from_string(key).is_err();
+
+        Ok(replacement)
+    }
+
+    pub fn entry(&self) -> ReplacementEntry {
+        let entry = ReplacementEntry::new(self.wasm.clone());
+        if self.any_contract {
+            entry.allow_any()
+        } else {
+            entry
+        }
+    }
+}
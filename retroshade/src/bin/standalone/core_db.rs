@@ -0,0 +1,277 @@
+//! Reads ledger state straight from a stellar-core SQLite database, as found in
+//! a quickstart node's `stellar.db`.
+
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use retroshade::snapshot::SnapshotSourceExt;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use soroban_env_host::{
+    storage::{EntryWithLiveUntil, SnapshotSource},
+    xdr::{
+        AccountEntry, Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerHeader, LedgerKey,
+        Limits, PublicKey, ReadXdr, SequenceNumber, Thresholds, TransactionEnvelope,
+        TransactionMeta, Uint256, WriteXdr,
+    },
+};
+
+/// Latest closed ledger sequence and close time, `(0, 0)` while the database
+/// doesn't exist or has no ledgers (e.g. the node is (re)starting).
+pub fn get_current_ledger_sequence(db: &Path) -> (i32, i64) {
+    let Ok(conn) = Connection::open(db) else {
+        return (0, 0);
+    };
+    let query_string =
+        "SELECT ledgerseq, closetime FROM ledgerheaders ORDER BY ledgerseq DESC LIMIT 1"
+            .to_string();
+
+    let Ok(mut stmt) = conn.prepare(&query_string) else {
+        return (0, 0);
+    };
+    let Ok(mut entries) = stmt.query(params![]) else {
+        return (0, 0);
+    };
+
+    let Ok(Some(row)) = entries.next() else {
+        // Unrecoverable: no ledger is running
+        return (0, 0);
+    };
+
+    (row.get(0).unwrap_or(0), row.get(1).unwrap_or(0))
+}
+
+/// Header of the given ledger, `None` if the database doesn't have it.
+pub fn get_header(db: &Path, ledger_seq: u32) -> Option<LedgerHeader> {
+    let conn = Connection::open(db).ok()?;
+    let mut stmt = conn
+        .prepare("SELECT data FROM ledgerheaders WHERE ledgerseq = ?1")
+        .ok()?;
+    let mut entries = stmt.query(params![ledger_seq]).ok()?;
+    let data: String = entries.next().ok()??.get(0).ok()?;
+
+    LedgerHeader::from_xdr_base64(data, Limits::none()).ok()
+}
+
+/// Ledger the transaction with the given hash was applied in, `None` if the
+/// database's history doesn't have it.
+pub fn get_transaction_ledger(db: &Path, tx_hash: &Hash) -> Option<u32> {
+    let conn = Connection::open(db).ok()?;
+    let mut stmt = conn
+        .prepare("SELECT ledgerseq FROM txhistory WHERE txid = ?1")
+        .ok()?;
+    let mut entries = stmt.query(params![hex::encode(tx_hash.0)]).ok()?;
+
+    entries.next().ok()??.get(0).ok()
+}
+
+/// Envelopes and metas of the transactions applied in the given ledger, in
+/// application order.
+pub fn get_transactions(
+    db: &Path,
+    ledger_seq: u32,
+) -> Result<Vec<(TransactionEnvelope, TransactionMeta)>, String> {
+    let conn = Connection::open(db).map_err(|error| error.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT txbody, txmeta FROM txhistory WHERE ledgerseq = ?1 ORDER BY txindex")
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map(params![ledger_seq], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut transactions = Vec::new();
+    for row in rows {
+        let (body, meta) = row.map_err(|error| error.to_string())?;
+        transactions.push((
+            TransactionEnvelope::from_xdr_base64(body, Limits::none())
+                .map_err(|error| error.to_string())?,
+            TransactionMeta::from_xdr_base64(meta, Limits::none())
+                .map_err(|error| error.to_string())?,
+        ));
+    }
+
+    Ok(transactions)
+}
+
+pub fn get_ttl(db: &Path, key: LedgerKey) -> u32 {
+    let mut hasher = Sha256::new();
+    hasher.update(key.to_xdr(Limits::none()).unwrap());
+    let result = {
+        let hashed = hasher.finalize().as_slice().try_into().unwrap();
+        Hash(hashed).to_xdr_base64(Limits::none()).unwrap()
+    };
+
+    let conn = Connection::open(db).unwrap();
+    let query_string = "SELECT ledgerentry FROM ttl WHERE keyhash = ?1".to_string();
+
+    let mut stmt = conn.prepare(&query_string).unwrap();
+    let mut entries = stmt.query(params![result]).unwrap();
+
+    let row = entries.next().unwrap();
+
+    if row.is_none() {
+        // TODO: error log
+        return 0;
+    }
+
+    let entry = {
+        let string: String = row.unwrap().get(0).unwrap();
+        LedgerEntry::from_xdr_base64(&string, Limits::none()).unwrap()
+    };
+
+    let LedgerEntryData::Ttl(ttl) = entry.data else {
+        return 0;
+    };
+    ttl.live_until_ledger_seq
+}
+
+/// Snapshot source reading the latest state from a stellar-core SQLite database.
+pub struct DynamicSnapshot {
+    pub db: PathBuf,
+}
+
+impl SnapshotSource for DynamicSnapshot {
+    fn get(
+        &self,
+        key: &std::rc::Rc<soroban_env_host::xdr::LedgerKey>,
+    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
+    {
+        let entry: Option<EntryWithLiveUntil> = match key.as_ref() {
+            LedgerKey::Trustline(trustline) => {
+                let PublicKey::PublicKeyTypeEd25519(Uint256(bytes)) = trustline.account_id.0;
+                let account_id = stellar_strkey::ed25519::PublicKey(bytes).to_string();
+                let asset_xdr = trustline.asset.to_xdr_base64(Limits::none()).unwrap();
+
+                let conn = Connection::open(&self.db).unwrap();
+                let query_string =
+                    "SELECT ledgerentry FROM trustlines where accountid = ?1 AND asset = ?2"
+                        .to_string();
+
+                let mut stmt = conn.prepare(&query_string).unwrap();
+                let mut entries = stmt.query(params![account_id, asset_xdr]).unwrap();
+
+                let row = entries.next().unwrap();
+
+                if row.is_none() {
+                    return Ok(None);
+                }
+                let row = row.unwrap();
+
+                let xdr_entry: String = row.get(0).unwrap();
+                let xdr_entry = LedgerEntry::from_xdr_base64(xdr_entry, Limits::none()).unwrap();
+
+                Some((Rc::new(xdr_entry), None))
+            }
+
+            LedgerKey::Account(key) => {
+                let PublicKey::PublicKeyTypeEd25519(ed25519) = key.account_id.0.clone();
+                let id = stellar_strkey::ed25519::PublicKey(ed25519.0).to_string();
+
+                let conn = Connection::open(&self.db).unwrap();
+                let query_string = "SELECT balance FROM accounts where accountid = ?1".to_string();
+
+                let mut stmt = conn.prepare(&query_string).unwrap();
+                let mut entries = stmt.query(params![id]).unwrap();
+
+                let row = entries.next().unwrap();
+
+                if row.is_none() {
+                    return Ok(None);
+                }
+                let row = row.unwrap();
+
+                let entry = LedgerEntry {
+                    last_modified_ledger_seq: 0,
+                    ext: LedgerEntryExt::V0,
+                    data: soroban_env_host::xdr::LedgerEntryData::Account(AccountEntry {
+                        account_id: key.account_id.clone(),
+                        balance: row.get(0).unwrap(),
+                        seq_num: SequenceNumber(0),
+                        num_sub_entries: 0,
+                        inflation_dest: None,
+                        flags: 0,
+                        home_domain: Default::default(),
+                        thresholds: Thresholds([0; 4]),
+                        signers: vec![].try_into().unwrap(),
+                        ext: soroban_env_host::xdr::AccountEntryExt::V0,
+                    }),
+                };
+
+                Some((Rc::new(entry), None))
+            }
+
+            LedgerKey::ContractCode(key) => {
+                let hash = key.hash.clone();
+                let conn = Connection::open(&self.db).unwrap();
+                let query_string =
+                    "SELECT ledgerentry FROM contractcode where hash = ?1".to_string();
+
+                let mut stmt = conn.prepare(&query_string).unwrap();
+                let mut entries = stmt
+                    .query(params![hash.to_xdr_base64(Limits::none()).unwrap()])
+                    .unwrap();
+
+                let row = entries.next().unwrap();
+
+                if row.is_none() {
+                    return Ok(None);
+                }
+                let row = row.unwrap();
+
+                let xdr_entry: String = row.get(0).unwrap();
+                let xdr_entry = LedgerEntry::from_xdr_base64(xdr_entry, Limits::none()).unwrap();
+
+                Some((
+                    Rc::new(xdr_entry),
+                    Some(get_ttl(&self.db, LedgerKey::ContractCode(key.clone()))),
+                ))
+            }
+
+            LedgerKey::ContractData(key) => {
+                let contract = key.contract.clone();
+                let scval = key.key.clone();
+
+                let conn = Connection::open(&self.db).unwrap();
+                let query_string =
+                    "SELECT ledgerentry FROM contractdata where contractid = ?1 AND key = ?2"
+                        .to_string();
+
+                let mut stmt = conn.prepare(&query_string).unwrap();
+                let mut entries = stmt
+                    .query(params![
+                        contract.to_xdr_base64(Limits::none()).unwrap(),
+                        scval.to_xdr_base64(Limits::none()).unwrap()
+                    ])
+                    .unwrap();
+                let row = entries.next().unwrap();
+
+                if row.is_none() {
+                    return Ok(None);
+                }
+                let row = row.unwrap();
+
+                let xdr_entry: String = row.get(0).unwrap();
+                let xdr_entry = LedgerEntry::from_xdr_base64(xdr_entry, Limits::none()).unwrap();
+
+                Some((
+                    Rc::new(xdr_entry),
+                    Some(get_ttl(&self.db, LedgerKey::ContractData(key.clone()))),
+                ))
+            }
+
+            _ => None,
+        };
+
+        Ok(entry)
+    }
+}
+
+impl SnapshotSourceExt for DynamicSnapshot {
+    fn label(&self) -> String {
+        format!("core db {}", self.db.display())
+    }
+}
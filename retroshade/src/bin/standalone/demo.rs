@@ -0,0 +1,82 @@
+//! Hard-coded run of the hello world example contract, handy to check that the
+//! host fork works on a machine without a core database. Needs the
+//! `testutils` feature.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use clap::Args;
+
+use retroshade::{
+    testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+    RetroshadesExecution,
+};
+use soroban_env_host::xdr::{
+    ExtensionPoint, Hash, LedgerEntryChanges, OperationMeta, ScMap, ScSymbol, ScVal, ScVec,
+    SorobanTransactionMeta, TransactionMeta, TransactionMetaV3,
+};
+
+use crate::error::CliError;
+
+#[derive(Args)]
+pub struct DemoArgs {
+    /// The hello world example's wasm, built with `make` in
+    /// `examples/hello_world`.
+    #[arg(long)]
+    wasm: PathBuf,
+}
+
+/// Runs the hello world contract against a mocked state.
+pub fn demo(args: DemoArgs) -> Result<(), CliError> {
+    let wasm = fs::read(&args.wasm).map_err(|error| {
+        CliError::usage(format!("couldn't read {}: {error}", args.wasm.display()))
+    })?;
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+
+    let snapshot_source = MockSnapshot::with_contract(Hash([0; 32]), &wasm, ScMap::default());
+
+    let envelope = EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let meta = TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: soroban_env_host::xdr::SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value: ScVal::Vec(Some(ScVec(
+                vec![
+                    ScVal::Symbol(ScSymbol("hello".try_into().unwrap())),
+                    ScVal::Symbol(ScSymbol("tdep".try_into().unwrap())),
+                ]
+                .try_into()
+                .unwrap(),
+            ))),
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(vec![].try_into().unwrap()), // the hello world contract doesn't change anything
+        }]
+        .try_into()
+        .unwrap(),
+    };
+
+    retroshades.build_from_envelope_and_meta(
+        &snapshot_source,
+        envelope,
+        TransactionMeta::V3(meta),
+        HashMap::new(),
+    )?;
+    let retroshades = retroshades.retroshade()?;
+
+    println!(
+        "{}",
+        serde_json::to_string(&retroshades.retroshades).unwrap()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Short human readable forms of ledger keys and entries, with addresses as
+//! strkeys.
+
+use soroban_env_host::xdr::{
+    ContractDataDurability, LedgerEntry, LedgerEntryData, LedgerKey, PublicKey, ScAddress,
+    TrustLineAsset, Uint256,
+};
+
+fn account(key: &PublicKey) -> String {
+    let PublicKey::PublicKeyTypeEd25519(Uint256(bytes)) = key;
+    stellar_strkey::ed25519::PublicKey(*bytes).to_string()
+}
+
+fn contract(address: &ScAddress) -> String {
+    match address {
+        ScAddress::Contract(id) => stellar_strkey::Contract(id.0.into()).to_string(),
+        ScAddress::Account(id) => account(&id.0),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Short human readable form of a footprint key.
+pub fn describe_key(key: &LedgerKey) -> String {
+    match key {
+        LedgerKey::Account(key) => format!("account {}", account(&key.account_id.0)),
+        LedgerKey::Trustline(key) => {
+            let asset = match &key.asset {
+                TrustLineAsset::Native => "native".to_string(),
+                TrustLineAsset::CreditAlphanum4(asset) => {
+                    String::from_utf8_lossy(&asset.asset_code.0).to_string()
+                }
+                TrustLineAsset::CreditAlphanum12(asset) => {
+                    String::from_utf8_lossy(&asset.asset_code.0).to_string()
+                }
+                TrustLineAsset::PoolShare(pool) => hex::encode(pool.0 .0),
+            };
+            format!(
+                "trustline {} {}",
+                account(&key.account_id.0),
+                asset.trim_end_matches('\0')
+            )
+        }
+        LedgerKey::ContractData(key) => {
+            let durability = match key.durability {
+                ContractDataDurability::Temporary => "temporary",
+                ContractDataDurability::Persistent => "persistent",
+            };
+            format!(
+                "contract data {} {} {}",
+                contract(&key.contract),
+                durability,
+                serde_json::to_string(&key.key).unwrap_or_default()
+            )
+        }
+        LedgerKey::ContractCode(key) => format!("contract code {}", hex::encode(key.hash.0)),
+        other => format!("{:?}", other),
+    }
+}
+
+/// The interesting part of an entry's value.
+pub fn describe_entry(entry: &LedgerEntry) -> String {
+    match &entry.data {
+        LedgerEntryData::Account(account) => format!("balance {}", account.balance),
+        LedgerEntryData::Trustline(trustline) => format!("balance {}", trustline.balance),
+        LedgerEntryData::ContractData(data) => serde_json::to_string(&data.val).unwrap_or_default(),
+        LedgerEntryData::ContractCode(code) => format!("wasm of {} bytes", code.code.len()),
+        other => format!("{:?}", other),
+    }
+}
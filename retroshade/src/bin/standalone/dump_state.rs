@@ -0,0 +1,113 @@
+//! `dump-state`: prints, for each footprint key, the snapshot's entry, what
+//! the meta did to it and the entry fed to the host.
+
+use std::collections::HashMap;
+
+use retroshade::{
+    dump::{EntryOrigin, StateEntry},
+    ReplacementEntry, RetroshadesExecution,
+};
+use serde_json::json;
+use soroban_env_host::xdr::{Hash, LedgerEntry};
+
+use crate::{
+    core_db::DynamicSnapshot,
+    display::{describe_entry, describe_key},
+    error::CliError,
+    run::{ledger_info, report_network, Output, RunArgs},
+};
+
+fn origin_name(origin: EntryOrigin) -> &'static str {
+    match origin {
+        EntryOrigin::Unchanged => "unchanged",
+        EntryOrigin::Reverted => "reverted",
+        EntryOrigin::Removed => "removed",
+        EntryOrigin::Restored => "restored",
+        EntryOrigin::Replaced => "replaced",
+        EntryOrigin::Missing => "missing",
+    }
+}
+
+fn describe(entry: Option<&LedgerEntry>, live_until: Option<u32>) -> String {
+    match (entry, live_until) {
+        (None, _) => "-".to_string(),
+        (Some(entry), None) => describe_entry(entry),
+        (Some(entry), Some(live_until)) => {
+            format!("{} (live until {live_until})", describe_entry(entry))
+        }
+    }
+}
+
+fn print_table(entries: &[StateEntry]) {
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                describe_key(&entry.key),
+                origin_name(entry.origin).to_string(),
+                entry.source.clone(),
+                describe(entry.snapshot.as_ref(), entry.snapshot_live_until),
+                describe(entry.fed.as_ref(), entry.fed_live_until),
+            ]
+        })
+        .collect();
+
+    let header = ["KEY", "ORIGIN", "SOURCE", "SNAPSHOT", "FED"].map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+pub fn dump_state(args: RunArgs) -> Result<(), CliError> {
+    let settings = args.execution.settings()?;
+    if matches!(settings.output, Output::Sql) {
+        return Err(CliError::usage("dump-state doesn't support sql output"));
+    }
+    let header = args.header(&settings)?;
+    report_network(settings.passphrase());
+
+    let replacements: HashMap<Hash, ReplacementEntry> = settings
+        .replacements
+        .iter()
+        .map(|replacement| (replacement.key.clone(), replacement.entry()))
+        .collect();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info(&header, &settings));
+    let entries = retroshades.build_with_state_dump(
+        &DynamicSnapshot {
+            db: settings.db.clone(),
+        },
+        args.envelope_xdr,
+        args.meta_xdr,
+        replacements,
+    )?;
+
+    match settings.output {
+        Output::Json => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|entry| json!({ "key_display": describe_key(&entry.key), "entry": entry }))
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&entries).map_err(CliError::sink)?
+            );
+        }
+        Output::Pretty => print_table(&entries),
+        Output::Sql => unreachable!("sql output is rejected above"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+//! Command failures, their exit codes and their JSON form.
+
+use std::{fmt, process::ExitCode};
+
+use retroshade::{diagnostics::DisplayDiagnostics, RetroshadeError};
+use serde_json::{json, Map, Value};
+use soroban_env_host::xdr::{Limits, WriteXdr};
+
+#[derive(Debug)]
+pub enum CliError {
+    /// Bad flags, config or inputs.
+    Usage(String),
+    /// The database couldn't provide the state.
+    Snapshot(String),
+    /// Printing or delivering the result failed.
+    Sink(String),
+    Retroshade(RetroshadeError),
+}
+
+impl CliError {
+    /// Same as clap's own usage errors.
+    pub const USAGE: u8 = 2;
+    pub const SNAPSHOT: u8 = 3;
+    pub const HOST: u8 = 4;
+    pub const SINK: u8 = 5;
+
+    pub fn usage(message: impl fmt::Display) -> Self {
+        Self::Usage(message.to_string())
+    }
+
+    pub fn snapshot(message: impl fmt::Display) -> Self {
+        Self::Snapshot(message.to_string())
+    }
+
+    pub fn sink(message: impl fmt::Display) -> Self {
+        Self::Sink(message.to_string())
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => Self::USAGE,
+            CliError::Snapshot(_) => Self::SNAPSHOT,
+            CliError::Sink(_) => Self::SINK,
+            CliError::Retroshade(error) => match error {
+                RetroshadeError::NotSorobanTx
+                | RetroshadeError::MalformedXdr
+                | RetroshadeError::UnsupportedProtocol { .. }
+                | RetroshadeError::Spec(_)
+                | RetroshadeError::UnregisteredContract(_)
+                | RetroshadeError::ReplacementNotAllowed(_)
+                | RetroshadeError::ConflictingReplacements { .. } => Self::USAGE,
+                RetroshadeError::EntryNotFound(_)
+                | RetroshadeError::MissingContext
+                | RetroshadeError::Fixture(_)
+                | RetroshadeError::SnapshotFile(_)
+                | RetroshadeError::Rpc(_)
+                | RetroshadeError::LedgerMetaFile(_)
+                | RetroshadeError::StateTooLarge { .. }
+                | RetroshadeError::InsufficientDeclaredResources { .. }
+                | RetroshadeError::StaleSnapshotEntry(_) => Self::SNAPSHOT,
+                RetroshadeError::Export(_) => Self::SINK,
+                RetroshadeError::SVMHost { .. }
+                | RetroshadeError::ContractCallFailed { .. }
+                | RetroshadeError::MalformedRetroshadeEvent { .. }
+                | RetroshadeError::ReservedColumn(_)
+                | RetroshadeError::EventSpecMismatch { .. }
+                | RetroshadeError::TypedDecode { .. }
+                | RetroshadeError::SandboxLimit { .. }
+                | RetroshadeError::Decoded(_) => Self::HOST,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CliError::Usage(_) => "Usage",
+            CliError::Snapshot(_) => "Snapshot",
+            CliError::Sink(_) => "Sink",
+            CliError::Retroshade(error) => match error {
+                RetroshadeError::SVMHost { .. } => "SVMHost",
+                RetroshadeError::NotSorobanTx => "NotSorobanTx",
+                RetroshadeError::EntryNotFound(_) => "EntryNotFound",
+                RetroshadeError::MissingContext => "MissingContext",
+                RetroshadeError::MalformedXdr => "MalformedXdr",
+                RetroshadeError::MalformedRetroshadeEvent { .. } => "MalformedRetroshadeEvent",
+                RetroshadeError::ReservedColumn(_) => "ReservedColumn",
+                RetroshadeError::ContractCallFailed { .. } => "ContractCallFailed",
+                RetroshadeError::Fixture(_) => "Fixture",
+                RetroshadeError::SnapshotFile(_) => "SnapshotFile",
+                RetroshadeError::Export(_) => "Export",
+                RetroshadeError::Rpc(_) => "Rpc",
+                RetroshadeError::LedgerMetaFile(_) => "LedgerMetaFile",
+                RetroshadeError::Decoded(_) => "Decoded",
+                RetroshadeError::StateTooLarge { .. } => "StateTooLarge",
+                RetroshadeError::UnsupportedProtocol { .. } => "UnsupportedProtocol",
+                RetroshadeError::Spec(_) => "Spec",
+                RetroshadeError::EventSpecMismatch { .. } => "EventSpecMismatch",
+                RetroshadeError::TypedDecode { .. } => "TypedDecode",
+                RetroshadeError::UnregisteredContract(_) => "UnregisteredContract",
+                RetroshadeError::SandboxLimit { .. } => "SandboxLimit",
+                RetroshadeError::StaleSnapshotEntry(_) => "StaleSnapshotEntry",
+                RetroshadeError::ReplacementNotAllowed(_) => "ReplacementNotAllowed",
+                RetroshadeError::ConflictingReplacements { .. } => "ConflictingReplacements",
+                RetroshadeError::InsufficientDeclaredResources { .. } => {
+                    "InsufficientDeclaredResources"
+                }
+            },
+        }
+    }
+
+    /// `{ "error": { "kind": ..., "message": ..., ... } }`, with the fields
+    /// of the library error when there are any.
+    pub fn to_json(&self) -> Value {
+        let mut error = Map::new();
+        error.insert("kind".to_string(), json!(self.kind()));
+        error.insert("message".to_string(), json!(self.to_string()));
+
+        if let CliError::Retroshade(retroshade_error) = self {
+            match retroshade_error {
+                RetroshadeError::EntryNotFound(key) => {
+                    error.insert(
+                        "key".to_string(),
+                        json!(key.to_xdr_base64(Limits::none()).unwrap_or_default()),
+                    );
+                }
+                RetroshadeError::SVMHost { error: host, .. } => {
+                    error.insert("host_error".to_string(), json!(format!("{:?}", host.error)));
+                }
+                RetroshadeError::MalformedRetroshadeEvent {
+                    contract_id,
+                    reason,
+                    ..
+                } => {
+                    error.insert("contract_id".to_string(), json!(contract_id));
+                    error.insert("reason".to_string(), json!(reason.to_string()));
+                }
+                RetroshadeError::ReservedColumn(column) => {
+                    error.insert("column".to_string(), json!(column));
+                }
+                RetroshadeError::EventSpecMismatch {
+                    contract_id,
+                    mismatches,
+                } => {
+                    let mismatches: Vec<String> =
+                        mismatches.iter().map(ToString::to_string).collect();
+                    error.insert("contract_id".to_string(), json!(contract_id));
+                    error.insert("mismatches".to_string(), json!(mismatches));
+                }
+                RetroshadeError::TypedDecode { field, reason } => {
+                    error.insert("field".to_string(), json!(field));
+                    error.insert("reason".to_string(), json!(reason));
+                }
+                RetroshadeError::UnregisteredContract(contract_id) => {
+                    error.insert("contract_id".to_string(), json!(contract_id));
+                }
+                RetroshadeError::SandboxLimit {
+                    memory_bytes,
+                    limit,
+                } => {
+                    error.insert("memory_bytes".to_string(), json!(memory_bytes));
+                    error.insert("limit".to_string(), json!(limit));
+                }
+                RetroshadeError::InsufficientDeclaredResources {
+                    needed,
+                    declared,
+                    dimension,
+                } => {
+                    error.insert("needed".to_string(), json!(needed));
+                    error.insert("declared".to_string(), json!(declared));
+                    error.insert("dimension".to_string(), json!(dimension));
+                }
+                RetroshadeError::StaleSnapshotEntry(stale) => {
+                    error.insert(
+                        "key".to_string(),
+                        json!(stale.key.to_xdr_base64(Limits::none()).unwrap_or_default()),
+                    );
+                    error.insert(
+                        "last_modified_ledger_seq".to_string(),
+                        json!(stale.last_modified_ledger_seq),
+                    );
+                    error.insert("ledger_seq".to_string(), json!(stale.ledger_seq));
+                }
+                RetroshadeError::ReplacementNotAllowed(skipped) => {
+                    error.insert("key".to_string(), json!(hex::encode(skipped.key.0)));
+                    error.insert(
+                        "wasm_hash".to_string(),
+                        json!(hex::encode(skipped.wasm_hash.0)),
+                    );
+                    error.insert(
+                        "contract_id".to_string(),
+                        json!(stellar_strkey::Contract(skipped.contract_id.0).to_string()),
+                    );
+                }
+                RetroshadeError::ConflictingReplacements { wasm_hash, keys } => {
+                    error.insert("wasm_hash".to_string(), json!(hex::encode(wasm_hash.0)));
+                    let keys: Vec<String> = keys
+                        .iter()
+                        .map(|key| stellar_strkey::Contract(key.0).to_string())
+                        .collect();
+                    error.insert("keys".to_string(), json!(keys));
+                }
+                RetroshadeError::StateTooLarge { bytes, limit } => {
+                    error.insert("bytes".to_string(), json!(bytes));
+                    error.insert("limit".to_string(), json!(limit));
+                }
+                RetroshadeError::UnsupportedProtocol { got, supported } => {
+                    error.insert("got".to_string(), json!(got));
+                    error.insert("min_protocol".to_string(), json!(supported.start()));
+                    error.insert("max_protocol".to_string(), json!(supported.end()));
+                }
+                _ => {}
+            }
+
+            if let RetroshadeError::SVMHost { diagnostics, .. }
+            | RetroshadeError::ContractCallFailed { diagnostics } = retroshade_error
+            {
+                error.insert(
+                    "diagnostics".to_string(),
+                    json!(DisplayDiagnostics(diagnostics).to_string()),
+                );
+            }
+        }
+
+        json!({ "error": error })
+    }
+
+    /// Prints the error, as JSON on stdout when the output is JSON so that
+    /// consumers only have to parse one stream.
+    pub fn report(&self, json: bool) -> ExitCode {
+        if json {
+            println!("{}", self.to_json());
+        } else {
+            eprintln!("error: {self}");
+        }
+
+        ExitCode::from(self.exit_code())
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Snapshot(message) | CliError::Sink(message) => {
+                write!(f, "{}", message)
+            }
+            CliError::Retroshade(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<RetroshadeError> for CliError {
+    fn from(error: RetroshadeError) -> Self {
+        Self::Retroshade(error)
+    }
+}
@@ -0,0 +1,97 @@
+//! `estimate`: runs a transaction in recording mode only and reports the
+//! resources it needed against the ones its envelope declared.
+
+use std::rc::Rc;
+
+use crate::{
+    core_db::DynamicSnapshot,
+    display::describe_key,
+    error::CliError,
+    run::{build, ledger_info, report_network, report_replacements, Output, RunArgs},
+};
+use retroshade::estimate::{ResourceEstimate, Shortfall};
+use serde_json::json;
+
+fn describe_shortfall(shortfall: &Shortfall) -> String {
+    match shortfall {
+        Shortfall::Instructions { declared, recorded } => {
+            format!("instructions: {recorded} recorded, {declared} declared")
+        }
+        Shortfall::DiskReadBytes { declared, recorded } => {
+            format!("disk read bytes: {recorded} recorded, {declared} declared")
+        }
+        Shortfall::WriteBytes { declared, recorded } => {
+            format!("write bytes: {recorded} recorded, {declared} declared")
+        }
+        Shortfall::Footprint { key, read_write } => format!(
+            "{} not in the declared {}footprint",
+            describe_key(key),
+            if *read_write { "read-write " } else { "" }
+        ),
+    }
+}
+
+fn print_pretty(estimate: &ResourceEstimate) {
+    let recorded = &estimate.recorded;
+    println!("call succeeded: {}", estimate.call_succeeded);
+    println!("instructions: {}", recorded.instructions);
+    println!("disk read bytes: {}", recorded.disk_read_bytes);
+    println!("write bytes: {}", recorded.write_bytes);
+    println!("memory bytes: {}", estimate.memory_bytes);
+
+    println!("footprint:");
+    for key in recorded.footprint.read_only.iter() {
+        println!("  ro {}", describe_key(key));
+    }
+    for key in recorded.footprint.read_write.iter() {
+        println!("  rw {}", describe_key(key));
+    }
+
+    let shortfalls = estimate.shortfalls();
+    if shortfalls.is_empty() {
+        println!("declared resources are sufficient");
+    } else {
+        println!("declared resources are insufficient:");
+        for shortfall in &shortfalls {
+            println!("  {}", describe_shortfall(shortfall));
+        }
+    }
+}
+
+pub fn estimate(args: RunArgs) -> Result<(), CliError> {
+    let settings = args.execution.settings()?;
+    if matches!(settings.output, Output::Sql) {
+        return Err(CliError::usage("estimate doesn't support sql output"));
+    }
+    let header = args.header(&settings)?;
+
+    report_network(settings.passphrase());
+    let snapshot = DynamicSnapshot {
+        db: settings.db.clone(),
+    };
+    let (retroshades, report) = build(
+        &settings,
+        &snapshot,
+        ledger_info(&header, &settings),
+        args.envelope_xdr,
+        args.meta_xdr,
+    )?;
+    report_replacements(&settings, &report);
+
+    let estimate = retroshades.estimate_resources(Rc::new(snapshot))?;
+
+    match settings.output {
+        Output::Json => println!(
+            "{}",
+            json!({
+                "estimate": estimate,
+                "sufficient": estimate.sufficient(),
+                "shortfalls": estimate.shortfalls(),
+            })
+        ),
+        Output::Pretty => print_pretty(&estimate),
+        Output::Sql => unreachable!("sql output is rejected above"),
+    }
+
+    Ok(())
+}
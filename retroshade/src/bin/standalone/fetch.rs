@@ -0,0 +1,71 @@
+//! `fetch-and-run`: re-executes a transaction given only its hash, fetching
+//! everything else from a soroban-rpc.
+
+use clap::Args;
+use retroshade::{
+    engine::RetroshadeEngine,
+    network::network_id,
+    rpc::{RpcClient, RpcSnapshot},
+};
+use soroban_env_host::xdr::{Hash, TransactionMeta};
+
+use crate::{
+    error::CliError,
+    run::{execute, print, report_network, report_replacements, v1_envelope, ExecutionArgs},
+};
+
+/// `--db` is ignored as entries are fetched from the RPC, the network defaults
+/// to the RPC's.
+#[derive(Args)]
+pub struct FetchArgs {
+    #[arg(long)]
+    rpc_url: String,
+
+    /// Hex hash of the transaction.
+    #[arg(long, value_parser = parse_hash)]
+    tx_hash: Hash,
+
+    #[command(flatten)]
+    pub execution: ExecutionArgs,
+}
+
+fn parse_hash(value: &str) -> Result<Hash, String> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(Hash)
+        .ok_or_else(|| "expected a 32 bytes hex hash".to_string())
+}
+
+pub fn fetch_and_run(args: FetchArgs) -> Result<(), CliError> {
+    let settings = args.execution.settings()?;
+    let client = RpcClient::new(args.rpc_url);
+
+    let transaction = client.get_transaction(&args.tx_hash)?;
+    if !matches!(transaction.meta, TransactionMeta::V3(_)) {
+        return Err(CliError::usage(format!(
+            "transaction {} predates Soroban (protocol 20)",
+            hex::encode(args.tx_hash.0)
+        )));
+    }
+    let envelope = v1_envelope(transaction.envelope.clone()).map_err(CliError::Usage)?;
+
+    let passphrase = match settings.explicit_passphrase() {
+        Some(passphrase) => passphrase.to_string(),
+        None => client.get_network_passphrase()?,
+    };
+    let ledger_info = client.ledger_info(&transaction, network_id(&passphrase))?;
+
+    report_network(&passphrase);
+    let (result, report) = execute(
+        &RetroshadeEngine::new()?,
+        &settings,
+        &RpcSnapshot::new(client),
+        ledger_info,
+        envelope,
+        transaction.meta,
+    )?;
+    report_replacements(&settings, &report);
+
+    print(&result, &settings)
+}
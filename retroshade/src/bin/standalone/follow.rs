@@ -0,0 +1,288 @@
+//! `follow`: tails the core database and re-executes every Soroban transaction
+//! of each newly closed ledger.
+
+use std::{collections::HashSet, num::NonZeroUsize, panic, path::PathBuf, thread, time::Duration};
+
+use clap::Args;
+use retroshade::{
+    engine::RetroshadeEngine,
+    sink::dedup::{
+        record_delivered, retain_unseen, DedupStore, DedupWindow, Eviction, FileDedupStore,
+    },
+    BuildReport,
+};
+use soroban_env_host::xdr::{
+    LedgerHeader, OperationBody, TransactionEnvelope, TransactionExt, TransactionMeta,
+    TransactionV1Envelope,
+};
+
+use crate::{
+    config::Settings,
+    core_db::{get_current_ledger_sequence, get_header, get_transactions, DynamicSnapshot},
+    error::CliError,
+    run::{
+        execute, ledger_info, print, report_network, v1_envelope, ExecutionArgs, ReplacementStatus,
+    },
+};
+
+#[derive(Args)]
+pub struct FollowArgs {
+    /// First ledger to process. Defaults to the ledger after the latest
+    /// closed one.
+    #[arg(long)]
+    start_ledger: Option<u32>,
+
+    /// How often to poll the database for new ledgers.
+    #[arg(long, default_value_t = 1000)]
+    poll_interval_ms: u64,
+
+    /// How many of a ledger's transactions to re-execute concurrently. The
+    /// results are still printed in application order.
+    #[arg(long, default_value_t = NonZeroUsize::MIN)]
+    jobs: NonZeroUsize,
+
+    /// Remembers the exports already output in this file, so that ledgers
+    /// re-executed after a restart don't output them twice.
+    #[arg(long)]
+    dedup_file: Option<PathBuf>,
+
+    /// How many exports the dedup file remembers.
+    #[arg(long, default_value_t = DedupWindow::default().capacity)]
+    dedup_window: usize,
+
+    /// Which export the dedup file forgets when full: fifo or lru.
+    #[arg(long, default_value = "fifo", value_parser = parse_eviction)]
+    dedup_eviction: Eviction,
+
+    #[command(flatten)]
+    pub execution: ExecutionArgs,
+}
+
+fn is_soroban(envelope: &TransactionV1Envelope) -> bool {
+    matches!(envelope.tx.ext, TransactionExt::V1(_))
+        && envelope
+            .tx
+            .operations
+            .iter()
+            .any(|op| matches!(op.body, OperationBody::InvokeHostFunction(_)))
+}
+
+fn parse_eviction(value: &str) -> Result<Eviction, String> {
+    match value {
+        "fifo" => Ok(Eviction::Fifo),
+        "lru" => Ok(Eviction::Lru),
+        _ => Err(format!("{value} is not fifo or lru")),
+    }
+}
+
+pub fn follow(args: FollowArgs) -> Result<(), CliError> {
+    let settings = args.execution.settings()?;
+    report_network(settings.passphrase());
+    let poll_interval = Duration::from_millis(args.poll_interval_ms);
+
+    let mut next_ledger = args
+        .start_ledger
+        .unwrap_or_else(|| get_current_ledger_sequence(&settings.db).0 as u32 + 1);
+    let mut latest = 0;
+    let mut reported = HashSet::new();
+    // note: shared by every ledger, so each contract is parsed once per process.
+    let engine = RetroshadeEngine::new()?;
+    let window = DedupWindow {
+        capacity: args.dedup_window,
+        eviction: args.dedup_eviction,
+    };
+    let open_dedup = || {
+        args.dedup_file
+            .as_ref()
+            .map(|path| FileDedupStore::open(path, window))
+            .transpose()
+            .map_err(CliError::sink)
+    };
+    let mut dedup = open_dedup()?;
+
+    loop {
+        let (current, _) = get_current_ledger_sequence(&settings.db);
+        let current = current as u32;
+
+        if current == 0 {
+            // note: the database is missing or empty while the node starts.
+            thread::sleep(poll_interval);
+            continue;
+        }
+
+        if current < latest {
+            // note: the node was restarted with a fresh database, follow the new chain
+            // from its start.
+            eprintln!("warning: database was reset, restarting from ledger 1");
+            next_ledger = 1;
+        }
+        latest = current;
+
+        while next_ledger <= current {
+            if let Err(error) = process_ledger(
+                &engine,
+                &settings,
+                next_ledger,
+                args.jobs.get(),
+                &mut reported,
+                dedup.as_mut().map(|store| store as &mut dyn DedupStore),
+            ) {
+                // note: most likely the node is restarting, retry on the next poll.
+                eprintln!("error: ledger {next_ledger}: {error}");
+                // note: the exports recorded since the last flush may not
+                // have been output, forget them.
+                dedup = open_dedup()?;
+                break;
+            }
+            next_ledger += 1;
+        }
+
+        thread::sleep(poll_interval);
+    }
+}
+
+/// Re-executes the ledger's Soroban transactions. `reported` holds the
+/// replacements already reported. Fails if the ledger isn't in the
+/// database yet, for it to be retried.
+pub fn process_ledger(
+    engine: &RetroshadeEngine,
+    settings: &Settings,
+    ledger_seq: u32,
+    jobs: usize,
+    reported: &mut HashSet<PathBuf>,
+    dedup: Option<&mut dyn DedupStore>,
+) -> Result<(), CliError> {
+    let Some(header) = get_header(&settings.db, ledger_seq) else {
+        // note: the node may have been reset between the two reads, the ledger
+        // is retried on the next poll, which notices a reset.
+        return Err(CliError::snapshot(format!(
+            "header of ledger {ledger_seq} not found"
+        )));
+    };
+    let transactions = get_transactions(&settings.db, ledger_seq).map_err(CliError::Snapshot)?;
+
+    process_transactions(
+        engine,
+        settings,
+        &header,
+        transactions,
+        jobs,
+        reported,
+        dedup,
+    )
+}
+
+/// Re-executes the Soroban transactions among the ledger's `transactions`,
+/// given in application order. Exports already in `dedup` aren't output.
+pub fn process_transactions(
+    engine: &RetroshadeEngine,
+    settings: &Settings,
+    header: &LedgerHeader,
+    transactions: Vec<(TransactionEnvelope, TransactionMeta)>,
+    jobs: usize,
+    reported: &mut HashSet<PathBuf>,
+    mut dedup: Option<&mut dyn DedupStore>,
+) -> Result<(), CliError> {
+    let ledger_seq = header.ledger_seq;
+    let info = ledger_info(header, settings);
+
+    let mut soroban_transactions = Vec::new();
+    for (idx, (envelope, meta)) in transactions.into_iter().enumerate() {
+        // note: v0 envelopes are classic-only.
+        let Ok(envelope) = v1_envelope(envelope) else {
+            continue;
+        };
+        if !is_soroban(&envelope) {
+            continue;
+        }
+
+        soroban_transactions.push((idx, envelope, meta));
+    }
+
+    // note: one source serves every transaction of the ledger.
+    let snapshot = DynamicSnapshot {
+        db: settings.db.clone(),
+    };
+    let execute_tx = |(idx, envelope, meta): (usize, TransactionV1Envelope, TransactionMeta)| {
+        (
+            idx,
+            execute(engine, settings, &snapshot, info.clone(), envelope, meta),
+        )
+    };
+
+    process_ordered(soroban_transactions, jobs, execute_tx, |(idx, result)| {
+        // note: one bad transaction shouldn't stop the follower.
+        match result {
+            Ok((mut result, report)) => {
+                report_new_replacements(settings, &report, reported);
+                let Some(dedup) = dedup.as_deref_mut() else {
+                    return print(&result, settings);
+                };
+                retain_unseen(&mut result, dedup);
+                print(&result, settings)?;
+                record_delivered(&result, dedup);
+                dedup.flush().map_err(CliError::sink)
+            }
+            Err(error) => {
+                eprintln!("error: ledger {ledger_seq} tx {idx}: {error}");
+                Ok(())
+            }
+        }
+    })
+}
+
+/// Reports on stderr the first transaction each replacement was applied to or
+/// skipped for.
+fn report_new_replacements(
+    settings: &Settings,
+    report: &BuildReport,
+    reported: &mut HashSet<PathBuf>,
+) {
+    for replacement in &settings.replacements {
+        let status = ReplacementStatus::of(&replacement.key, report);
+        if status != ReplacementStatus::Unused && reported.insert(replacement.path.clone()) {
+            eprintln!("replacement {}: {status}", replacement.path.display());
+        }
+    }
+}
+
+/// Runs `work` on the items with up to `jobs` threads, handing the results to
+/// `sink` in the items' order. Items are taken `jobs` at a time, so at most
+/// `jobs` results wait for a slow sink.
+pub fn process_ordered<T: Send, R: Send>(
+    items: Vec<T>,
+    jobs: usize,
+    work: impl Fn(T) -> R + Sync,
+    mut sink: impl FnMut(R) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    if jobs <= 1 {
+        return items.into_iter().try_for_each(|item| sink(work(item)));
+    }
+
+    let work = &work;
+    let mut items = items.into_iter().peekable();
+    while items.peek().is_some() {
+        let chunk: Vec<T> = items.by_ref().take(jobs).collect();
+        let results: Vec<R> = thread::scope(|scope| {
+            let handles: Vec<_> = chunk
+                .into_iter()
+                .map(|item| scope.spawn(move || work(item)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|error| panic::resume_unwind(error))
+                })
+                .collect()
+        });
+
+        for result in results {
+            sink(result)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+
+mod config;
+mod core_db;
+#[cfg(feature = "testutils")]
+mod demo;
+mod display;
+mod dump_state;
+mod error;
+mod estimate;
+#[cfg(feature = "http")]
+mod fetch;
+mod follow;
+mod replay;
+mod run;
+#[cfg(test)]
+mod test;
+
+/// Re-executes Soroban transactions and prints the retroshades they emit.
+#[derive(Parser)]
+#[command(name = "retroshade", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Re-executes a transaction against a stellar-core database.
+    Run(run::RunArgs),
+
+    /// Records the resources a transaction needs without enforcing them and
+    /// compares them with the declared ones.
+    Estimate(run::RunArgs),
+
+    /// Prints the state a transaction is re-executed against, key by key.
+    DumpState(run::RunArgs),
+
+    /// Tails a stellar-core database and re-executes each new Soroban
+    /// transaction.
+    Follow(follow::FollowArgs),
+
+    /// Re-executes the Soroban transactions of a ledger close meta file.
+    Replay(replay::ReplayArgs),
+
+    /// Fetches a transaction from a soroban-rpc and re-executes it.
+    #[cfg(feature = "http")]
+    FetchAndRun(fetch::FetchArgs),
+
+    /// Runs the hello world example contract against a mocked state.
+    #[cfg(feature = "testutils")]
+    Demo(demo::DemoArgs),
+}
+
+impl Command {
+    /// Whether errors are reported as JSON, following the output format.
+    fn json_errors(&self) -> bool {
+        let execution = match self {
+            Command::Run(args) | Command::Estimate(args) | Command::DumpState(args) => {
+                &args.execution
+            }
+            Command::Follow(args) => &args.execution,
+            Command::Replay(args) => &args.execution,
+            #[cfg(feature = "http")]
+            Command::FetchAndRun(args) => &args.execution,
+            #[cfg(feature = "testutils")]
+            Command::Demo(_) => return false,
+        };
+
+        matches!(execution.output(), run::Output::Json)
+    }
+}
+
+fn main() -> ExitCode {
+    // note: clap prints usage errors itself and exits with code 2, same as
+    // `CliError::Usage`.
+    let cli = Cli::parse();
+    let json_errors = cli.command.json_errors();
+
+    let result = match cli.command {
+        Command::Run(args) => run::run(args),
+        Command::Estimate(args) => estimate::estimate(args),
+        Command::DumpState(args) => dump_state::dump_state(args),
+        Command::Follow(args) => follow::follow(args),
+        Command::Replay(args) => replay::replay(args),
+        #[cfg(feature = "http")]
+        Command::FetchAndRun(args) => fetch::fetch_and_run(args),
+        #[cfg(feature = "testutils")]
+        Command::Demo(args) => demo::demo(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => error.report(json_errors),
+    }
+}
@@ -0,0 +1,112 @@
+//! `replay`: re-executes the Soroban transactions of the ledgers in a
+//! `LedgerCloseMeta` stream file, e.g. dumped from captive core's metadata
+//! pipe. The state still comes from the core database.
+
+use std::{collections::HashSet, fs::File, io::BufReader, num::NonZeroUsize, path::PathBuf};
+
+use clap::Args;
+use retroshade::{
+    engine::RetroshadeEngine,
+    ledger_meta::{ledger_header, transactions, LedgerMetaReader},
+    network::network_id,
+};
+
+use crate::{
+    error::CliError,
+    follow::process_transactions,
+    run::{report_network, ExecutionArgs},
+};
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Framed `LedgerCloseMeta` stream to read the ledgers from.
+    #[arg(long)]
+    ledger_meta_file: PathBuf,
+
+    /// Only processes the ledgers in `START..END`, both inclusive. Either
+    /// end can be left out.
+    #[arg(long, value_name = "START..END", value_parser = parse_range)]
+    ledger_range: Option<LedgerRange>,
+
+    /// How many of a ledger's transactions to re-execute concurrently.
+    #[arg(long, default_value_t = NonZeroUsize::MIN)]
+    jobs: NonZeroUsize,
+
+    #[command(flatten)]
+    pub execution: ExecutionArgs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LedgerRange {
+    pub start: Option<u32>,
+    pub end: Option<u32>,
+}
+
+impl LedgerRange {
+    pub fn contains(&self, ledger_seq: u32) -> bool {
+        self.start.map_or(true, |start| ledger_seq >= start)
+            && self.end.map_or(true, |end| ledger_seq <= end)
+    }
+}
+
+pub fn parse_range(value: &str) -> Result<LedgerRange, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| "expected START..END".to_string())?;
+    let bound = |bound: &str| {
+        if bound.is_empty() {
+            return Ok(None);
+        }
+        bound
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{bound} is not a ledger sequence"))
+    };
+
+    Ok(LedgerRange {
+        start: bound(start)?,
+        end: bound(end)?,
+    })
+}
+
+pub fn replay(args: ReplayArgs) -> Result<(), CliError> {
+    let settings = args.execution.settings()?;
+    report_network(settings.passphrase());
+    let network_id = network_id(settings.passphrase());
+
+    let file = File::open(&args.ledger_meta_file).map_err(|error| {
+        CliError::usage(format!(
+            "couldn't open {}: {error}",
+            args.ledger_meta_file.display()
+        ))
+    })?;
+
+    let mut reported = HashSet::new();
+    let engine = RetroshadeEngine::new()?;
+    for ledger in LedgerMetaReader::new(BufReader::new(file)) {
+        let ledger = ledger?;
+        let header = ledger_header(&ledger);
+
+        if let Some(range) = args.ledger_range {
+            if range.end.is_some_and(|end| header.ledger_seq > end) {
+                // note: the stream is in ledger order.
+                break;
+            }
+            if !range.contains(header.ledger_seq) {
+                continue;
+            }
+        }
+
+        process_transactions(
+            &engine,
+            &settings,
+            header,
+            transactions(&ledger, network_id)?,
+            args.jobs.get(),
+            &mut reported,
+            None,
+        )?;
+    }
+
+    Ok(())
+}
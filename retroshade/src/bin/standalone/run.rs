@@ -0,0 +1,415 @@
+//! `run`: re-executes a single transaction against a core database.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::{Args, ValueEnum};
+use retroshade::{
+    engine::RetroshadeEngine,
+    export::{jsonl::JsonlSink, sql},
+    ledger_meta::envelope_hash,
+    naming::TableNaming,
+    network::{network_id, Network},
+    sink::deliver_all,
+    BuildReport, ReplacementEntry, RetroshadeExecutionResultPretty, RetroshadesExecution,
+};
+use serde::Deserialize;
+use soroban_env_host::{
+    storage::SnapshotSource,
+    xdr::{
+        FeeBumpTransactionInnerTx, Hash, LedgerHeader, Limits, ReadXdr, TransactionEnvelope,
+        TransactionMeta, TransactionV1Envelope,
+    },
+    LedgerInfo,
+};
+
+use crate::{
+    config::{Config, Settings},
+    core_db::{get_header, get_transaction_ledger, DynamicSnapshot},
+    error::CliError,
+};
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Transaction envelope as base64 XDR, or `@path` to a file containing it.
+    #[arg(long, value_name = "XDR|@FILE", value_parser = parse_envelope)]
+    pub envelope_xdr: TransactionV1Envelope,
+
+    /// Transaction meta as base64 XDR, or `@path` to a file containing it.
+    #[arg(long, value_name = "XDR|@FILE", value_parser = parse_meta)]
+    pub meta_xdr: TransactionMeta,
+
+    /// Ledger the transaction was applied in, whose header the execution
+    /// runs with. Defaults to the one the database's history has it in.
+    #[arg(long)]
+    pub ledger: Option<u32>,
+
+    #[command(flatten)]
+    pub execution: ExecutionArgs,
+}
+
+impl RunArgs {
+    /// Header of the ledger the transaction was applied in, so that it
+    /// re-executes with that ledger's sequence, close time and protocol.
+    pub fn header(&self, settings: &Settings) -> Result<LedgerHeader, CliError> {
+        let ledger_seq = match self.ledger {
+            Some(ledger_seq) => ledger_seq,
+            None => {
+                // note: core stores fee bumps under the outer hash, which the
+                // inner envelope doesn't give.
+                let envelope = TransactionEnvelope::Tx(self.envelope_xdr.clone());
+                let tx_hash = envelope_hash(&envelope, network_id(settings.passphrase()))?;
+                get_transaction_ledger(&settings.db, &tx_hash).ok_or_else(|| {
+                    CliError::snapshot(format!(
+                        "transaction {} not found in {}, pass its ledger with --ledger",
+                        hex::encode(tx_hash.0),
+                        settings.db.display()
+                    ))
+                })?
+            }
+        };
+
+        get_header(&settings.db, ledger_seq).ok_or_else(|| {
+            CliError::snapshot(format!(
+                "header of ledger {ledger_seq} not found in {}",
+                settings.db.display()
+            ))
+        })
+    }
+}
+
+/// Arguments shared by the subcommands that execute against a core database.
+#[derive(Args)]
+pub struct ExecutionArgs {
+    /// TOML config file, see the `config` module. Flags override it.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Path to the stellar-core SQLite database. Defaults to the quickstart
+    /// one.
+    #[arg(long)]
+    pub db: Option<PathBuf>,
+
+    /// Replaces a contract's code with the given wasm. The key is either the
+    /// contract strkey or the hex hash of the wasm to replace for every
+    /// contract deployed with it. Can be repeated.
+    #[arg(long = "wasm", value_name = "KEY=PATH", value_parser = parse_replacement)]
+    pub replacements: Vec<Replacement>,
+
+    /// Network the transaction was signed for, one of pubnet, testnet,
+    /// futurenet or standalone. Defaults to standalone.
+    #[arg(long)]
+    pub network: Option<Network>,
+
+    /// Passphrase of a custom network, overriding the preset's.
+    #[arg(long)]
+    pub network_passphrase: Option<String>,
+
+    /// Defaults to json.
+    #[arg(long, value_enum)]
+    pub output: Option<Output>,
+
+    /// Also appends the exports to this JSON Lines file.
+    #[arg(long)]
+    pub jsonl: Option<PathBuf>,
+}
+
+impl ExecutionArgs {
+    pub fn settings(&self) -> Result<Settings, CliError> {
+        let config = match &self.config {
+            Some(path) => Config::load(path).map_err(CliError::Usage)?,
+            None => Config::default(),
+        };
+
+        Settings::resolve(self, config).map_err(CliError::Usage)
+    }
+
+    /// Output format from the flag or the config, for reporting errors that
+    /// may happen before the settings are resolved.
+    pub fn output(&self) -> Output {
+        self.output
+            .or_else(|| {
+                let config = Config::load(self.config.as_ref()?).ok()?;
+                config.output.format
+            })
+            .unwrap_or(Output::Json)
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// The packed result as JSON.
+    Json,
+    /// One block per retroshade, human readable.
+    Pretty,
+    /// DDL and inserts for each retroshade, ready to pipe into psql.
+    Sql,
+}
+
+#[derive(Clone, Debug)]
+pub struct Replacement {
+    pub key: Hash,
+    pub path: PathBuf,
+    pub wasm: Arc<[u8]>,
+    /// Keyed by a wasm hash, replacing the code of every contract deployed
+    /// with it.
+    pub any_contract: bool,
+}
+
+/// Reads an XDR argument, either inline or from the file following `@`.
+fn read_xdr_arg(value: &str) -> Result<String, String> {
+    match value.strip_prefix('@') {
+        Some(path) => fs::read_to_string(path)
+            .map(|contents| contents.trim().to_string())
+            .map_err(|error| format!("couldn't read {path}: {error}")),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// The transaction that carries the invocation, unwrapping fee bumps.
+pub fn v1_envelope(envelope: TransactionEnvelope) -> Result<TransactionV1Envelope, String> {
+    match envelope {
+        TransactionEnvelope::Tx(envelope) => Ok(envelope),
+        TransactionEnvelope::TxFeeBump(envelope) => {
+            let FeeBumpTransactionInnerTx::Tx(inner) = envelope.tx.inner_tx;
+            Ok(inner)
+        }
+        TransactionEnvelope::TxV0(_) => {
+            Err("v0 envelopes can't carry Soroban invocations".to_string())
+        }
+    }
+}
+
+fn parse_envelope(value: &str) -> Result<TransactionV1Envelope, String> {
+    let xdr = read_xdr_arg(value)?;
+    let envelope = TransactionEnvelope::from_xdr_base64(xdr, Limits::none())
+        .map_err(|error| format!("not a base64 transaction envelope: {error}"))?;
+
+    v1_envelope(envelope)
+}
+
+fn parse_meta(value: &str) -> Result<TransactionMeta, String> {
+    let xdr = read_xdr_arg(value)?;
+    TransactionMeta::from_xdr_base64(xdr, Limits::none())
+        .map_err(|error| format!("not a base64 transaction meta: {error}"))
+}
+
+fn parse_replacement(value: &str) -> Result<Replacement, String> {
+    let (key, path) = value
+        .split_once('=')
+        .ok_or_else(|| "expected KEY=PATH".to_string())?;
+
+    Replacement::parse(key, Path::new(path))
+}
+
+/// Ledger info for `header`. The core database doesn't store the network
+/// config in a form we can read cheaply, so the TTLs are the network's
+/// defaults.
+pub fn ledger_info(header: &LedgerHeader, settings: &Settings) -> LedgerInfo {
+    let defaults = settings.network_defaults();
+
+    LedgerInfo {
+        protocol_version: header.ledger_version,
+        sequence_number: header.ledger_seq,
+        timestamp: header.scp_value.close_time.0,
+        network_id: network_id(settings.passphrase()),
+        base_reserve: header.base_reserve,
+        min_temp_entry_ttl: defaults.min_temp_entry_ttl,
+        min_persistent_entry_ttl: defaults.min_persistent_entry_ttl,
+        max_entry_ttl: defaults.max_entry_ttl,
+    }
+}
+
+/// Builds the pre-execution state with the replacements applied, along with
+/// the report of which ones were.
+pub fn build(
+    settings: &Settings,
+    snapshot: &dyn SnapshotSource,
+    ledger_info: LedgerInfo,
+    envelope: TransactionV1Envelope,
+    meta: TransactionMeta,
+) -> Result<(RetroshadesExecution, BuildReport), CliError> {
+    let mut retroshades = RetroshadesExecution::new(ledger_info);
+
+    let replacements: HashMap<Hash, ReplacementEntry> = settings
+        .replacements
+        .iter()
+        .map(|replacement| (replacement.key.clone(), replacement.entry()))
+        .collect();
+
+    let report = retroshades.build_from_envelope_and_meta_with_report(
+        snapshot,
+        envelope,
+        meta,
+        replacements,
+    )?;
+
+    Ok((retroshades, report))
+}
+
+/// Re-executes the transaction with the replacements applied, dropping the
+/// filtered out targets.
+pub fn execute(
+    engine: &RetroshadeEngine,
+    settings: &Settings,
+    snapshot: &dyn SnapshotSource,
+    ledger_info: LedgerInfo,
+    envelope: TransactionV1Envelope,
+    meta: TransactionMeta,
+) -> Result<(RetroshadeExecutionResultPretty, BuildReport), CliError> {
+    let (retroshades, report) = build(settings, snapshot, ledger_info, envelope, meta)?;
+
+    let mut result = engine.execute_packed(&retroshades)?;
+    result
+        .retroshades
+        .retain(|export| settings.keeps(&export.contract_id, &export.target));
+
+    Ok((result, report))
+}
+
+pub fn print(
+    result: &RetroshadeExecutionResultPretty,
+    settings: &Settings,
+) -> Result<(), CliError> {
+    match settings.output {
+        Output::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(result).map_err(CliError::sink)?
+        ),
+        Output::Pretty => print_pretty(result),
+        Output::Sql => sql::write(&result.retroshades, TableNaming::TargetOnly, io::stdout())?,
+    }
+
+    if let Some(path) = &settings.jsonl {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|error| {
+                CliError::sink(format!("couldn't open {}: {error}", path.display()))
+            })?;
+        deliver_all(&mut JsonlSink::new(file), result).map_err(CliError::sink)?;
+    }
+
+    Ok(())
+}
+
+pub fn run(args: RunArgs) -> Result<(), CliError> {
+    let settings = args.execution.settings()?;
+    let header = args.header(&settings)?;
+
+    report_network(settings.passphrase());
+
+    let snapshot = DynamicSnapshot {
+        db: settings.db.clone(),
+    };
+    let (result, report) = execute(
+        &RetroshadeEngine::new()?,
+        &settings,
+        &snapshot,
+        ledger_info(&header, &settings),
+        args.envelope_xdr,
+        args.meta_xdr,
+    )?;
+    report_replacements(&settings, &report);
+
+    print(&result, &settings)
+}
+
+/// Echoes on stderr the network executions run for, since a mismatch only
+/// shows up as failing auth.
+pub fn report_network(passphrase: &str) {
+    match Network::from_passphrase(passphrase) {
+        Some(network) => eprintln!("network: {network} ({passphrase})"),
+        None => eprintln!("network: custom ({passphrase})"),
+    }
+}
+
+/// What building did with a replacement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplacementStatus {
+    /// Its binary replaced a code entry of the state.
+    Applied,
+    /// Left out for the contracts outside its allowlist.
+    Skipped(Vec<Hash>),
+    /// The state holds no code it replaces.
+    Unused,
+}
+
+impl ReplacementStatus {
+    pub fn of(key: &Hash, report: &BuildReport) -> Self {
+        let denied: Vec<Hash> = report
+            .skipped_replacements
+            .iter()
+            .filter(|skipped| &skipped.key == key)
+            .map(|skipped| skipped.contract_id.clone())
+            .collect();
+        if !denied.is_empty() {
+            return Self::Skipped(denied);
+        }
+
+        if report
+            .applied_replacements
+            .iter()
+            .any(|applied| &applied.key == key)
+        {
+            Self::Applied
+        } else {
+            Self::Unused
+        }
+    }
+}
+
+impl fmt::Display for ReplacementStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Applied => write!(f, "applied"),
+            Self::Skipped(contracts) => {
+                write!(f, "skipped, not allowed for")?;
+                for contract in contracts {
+                    write!(f, " {}", stellar_strkey::Contract(contract.0))?;
+                }
+                Ok(())
+            }
+            Self::Unused => write!(f, "unused"),
+        }
+    }
+}
+
+/// Reports on stderr what building did with each replacement.
+pub fn report_replacements(settings: &Settings, report: &BuildReport) {
+    for replacement in &settings.replacements {
+        eprintln!(
+            "replacement {}: {}",
+            replacement.path.display(),
+            ReplacementStatus::of(&replacement.key, report)
+        );
+    }
+}
+
+fn print_pretty(result: &RetroshadeExecutionResultPretty) {
+    if let Err(error) = &result.invoke_result {
+        println!("call failed: {error}");
+    }
+
+    for export in &result.retroshades {
+        println!(
+            "{} ({}) #{}",
+            export.target, export.contract_id, export.emit_index
+        );
+        for entry in &export.event {
+            println!("  {} = {}", entry.name, entry.value.kind);
+        }
+    }
+
+    for (idx, error) in &result.conversion_errors {
+        println!("retroshade #{idx} not packed: {error}");
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use retroshade::{
+    engine::RetroshadeEngine,
+    fixture,
+    ledger_meta::{envelope_hash, ledger_header, LedgerMetaReader},
+    network::{Network, STANDALONE_PASSPHRASE, TESTNET_PASSPHRASE},
+    AppliedReplacement, BuildReport, RetroshadeError, SkippedReplacement,
+};
+use rusqlite::{params, Connection};
+use soroban_env_host::xdr::{
+    Hash, LedgerKey, LedgerKeyContractCode, Limits, TransactionMeta, TransactionV1Envelope,
+    WriteXdr,
+};
+
+use crate::{
+    config::{parse_key, Config, Settings, DEFAULT_DB},
+    error::CliError,
+    follow::{process_ledger, process_ordered},
+    replay::{parse_range, LedgerRange},
+    run::{v1_envelope, ExecutionArgs, Output, Replacement, ReplacementStatus, RunArgs},
+};
+
+const LEDGERS_FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/ledger_meta/ledgers_100_101.xdr"
+);
+
+const MAINNET_FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/mainnet_first_retroshade.json"
+);
+
+const CONTRACT: &str = "CAS3J7GYLGXMF6TDJBBYYSE3HQ6BBSMLNUQ34T6TZMYMW2EVH34XOWMA";
+
+// note: the smallest valid module, enough for the validation.
+const EMPTY_WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("retroshade-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn no_flags() -> ExecutionArgs {
+    ExecutionArgs {
+        config: None,
+        db: None,
+        replacements: vec![],
+        network: None,
+        network_passphrase: None,
+        output: None,
+        jsonl: None,
+    }
+}
+
+#[test]
+fn parses_config() {
+    let config = Config::parse(&format!(
+        r#"
+        network = "testnet"
+        db = "stellar.db"
+
+        [output]
+        format = "pretty"
+
+        [replacements]
+        "{CONTRACT}" = "swaps.wasm"
+        "{}" = "pool.wasm"
+
+        [filters]
+        "{CONTRACT}" = ["swaps", "deposits"]
+        "#,
+        "ab".repeat(32)
+    ))
+    .unwrap();
+
+    assert_eq!(config.network, Some(Network::Testnet));
+    assert_eq!(config.db, Some(PathBuf::from("stellar.db")));
+    assert!(matches!(config.output.format, Some(Output::Pretty)));
+    assert_eq!(config.replacements.len(), 2);
+    assert_eq!(config.filters[CONTRACT], vec!["swaps", "deposits"]);
+
+    assert!(Config::parse("unknown = 1").is_err());
+    assert!(Config::parse("network = \"mainnet\"").is_err());
+    assert!(matches!(
+        Config::parse("[output]\nformat = \"sql\"")
+            .unwrap()
+            .output
+            .format,
+        Some(Output::Sql)
+    ));
+    assert!(Config::parse("[output]\nformat = \"csv\"").is_err());
+}
+
+#[test]
+fn replacement_keys() {
+    let contract = stellar_strkey::Contract::from_string(CONTRACT).unwrap();
+    assert_eq!(parse_key(CONTRACT).unwrap(), Hash(contract.0));
+    assert_eq!(parse_key(&"ab".repeat(32)).unwrap(), Hash([0xab; 32]));
+    assert!(parse_key("ab").is_err());
+    assert!(parse_key("GA").is_err());
+}
+
+#[test]
+fn replacement_status_comes_from_the_build() {
+    let report = BuildReport {
+        binaries_replaced: true,
+        footprint: vec![],
+        state_bytes: 0,
+        stale_entries: vec![],
+        applied_replacements: vec![AppliedReplacement {
+            key: Hash([1; 32]),
+            wasm_hash: Hash([0; 32]),
+            contract_ids: vec![Hash([1; 32])],
+        }],
+        skipped_replacements: vec![SkippedReplacement {
+            key: Hash([2; 32]),
+            wasm_hash: Hash([3; 32]),
+            contract_id: Hash([4; 32]),
+        }],
+    };
+
+    assert_eq!(
+        ReplacementStatus::of(&Hash([1; 32]), &report),
+        ReplacementStatus::Applied
+    );
+    assert_eq!(
+        ReplacementStatus::of(&Hash([2; 32]), &report),
+        ReplacementStatus::Skipped(vec![Hash([4; 32])])
+    );
+    assert_eq!(
+        ReplacementStatus::of(&Hash([0; 32]), &report),
+        ReplacementStatus::Unused
+    );
+    assert_eq!(
+        ReplacementStatus::Skipped(vec![Hash([4; 32])]).to_string(),
+        format!(
+            "skipped, not allowed for {}",
+            stellar_strkey::Contract([4; 32])
+        )
+    );
+}
+
+#[test]
+fn validates_wasm() {
+    let dir = temp_dir("config-wasm");
+    fs::write(dir.join("valid.wasm"), EMPTY_WASM).unwrap();
+    fs::write(dir.join("invalid.wasm"), b"not wasm").unwrap();
+
+    assert!(Replacement::load(Hash([0; 32]), &dir.join("valid.wasm")).is_ok());
+    assert!(Replacement::load(Hash([0; 32]), &dir.join("invalid.wasm")).is_err());
+    assert!(Replacement::load(Hash([0; 32]), &dir.join("missing.wasm")).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn defaults_without_config() {
+    let settings = Settings::resolve(&no_flags(), Config::default()).unwrap();
+
+    assert_eq!(settings.db, PathBuf::from(DEFAULT_DB));
+    assert_eq!(settings.passphrase(), STANDALONE_PASSPHRASE);
+    assert_eq!(settings.network_defaults(), Network::Standalone.defaults());
+    assert!(matches!(settings.output, Output::Json));
+    assert!(settings.replacements.is_empty());
+    assert!(settings.keeps(CONTRACT, "anything"));
+}
+
+#[test]
+fn flags_override_config() {
+    let dir = temp_dir("config-precedence");
+    fs::write(dir.join("from_file.wasm"), EMPTY_WASM).unwrap();
+    fs::write(dir.join("from_flag.wasm"), EMPTY_WASM).unwrap();
+    fs::write(
+        dir.join("retroshade.toml"),
+        format!(
+            r#"
+            network = "testnet"
+            db = "file.db"
+
+            [output]
+            format = "pretty"
+
+            [replacements]
+            "{CONTRACT}" = "from_file.wasm"
+            "{}" = "from_file.wasm"
+
+            [filters]
+            "{CONTRACT}" = ["swaps"]
+            "#,
+            "ab".repeat(32)
+        ),
+    )
+    .unwrap();
+
+    let config = Config::load(&dir.join("retroshade.toml")).unwrap();
+    // note: paths in the file are relative to it.
+    assert_eq!(config.db, Some(dir.join("file.db")));
+
+    let mut flags = no_flags();
+    flags.network_passphrase = Some("flag network".to_string());
+    flags.replacements =
+        vec![Replacement::load(parse_key(CONTRACT).unwrap(), &dir.join("from_flag.wasm")).unwrap()];
+
+    let settings = Settings::resolve(&flags, config).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // note: the flag's passphrase also drops the file's preset.
+    assert_eq!(settings.passphrase(), "flag network");
+    assert_eq!(settings.network, None);
+    assert_eq!(settings.db, dir.join("file.db"));
+    assert!(matches!(settings.output, Output::Pretty));
+
+    assert_eq!(settings.replacements.len(), 2);
+    let contract = parse_key(CONTRACT).unwrap();
+    let for_contract: Vec<_> = settings
+        .replacements
+        .iter()
+        .filter(|replacement| replacement.key == contract)
+        .collect();
+    assert_eq!(for_contract.len(), 1);
+    assert_eq!(for_contract[0].path, dir.join("from_flag.wasm"));
+    assert!(!for_contract[0].any_contract);
+    // note: wasm hash keys replace the code of every contract running it.
+    let by_hash = settings
+        .replacements
+        .iter()
+        .find(|replacement| replacement.key == Hash([0xab; 32]))
+        .unwrap();
+    assert!(by_hash.any_contract);
+
+    assert!(settings.keeps(CONTRACT, "swaps"));
+    assert!(!settings.keeps(CONTRACT, "deposits"));
+}
+
+#[test]
+fn network_presets() {
+    let mut flags = no_flags();
+    flags.network = Some(Network::Testnet);
+    let settings = Settings::resolve(&flags, Config::default()).unwrap();
+    assert_eq!(settings.passphrase(), TESTNET_PASSPHRASE);
+    assert_eq!(settings.network_defaults(), Network::Testnet.defaults());
+
+    // note: a known passphrase gets its network's defaults even without the
+    // preset.
+    let mut flags = no_flags();
+    flags.network_passphrase = Some(TESTNET_PASSPHRASE.to_string());
+    let settings = Settings::resolve(&flags, Config::default()).unwrap();
+    assert_eq!(settings.network_defaults(), Network::Testnet.defaults());
+
+    let mut flags = no_flags();
+    flags.network = Some(Network::Pubnet);
+    flags.network_passphrase = Some("custom".to_string());
+    let settings = Settings::resolve(&flags, Config::default()).unwrap();
+    assert_eq!(settings.passphrase(), "custom");
+    assert_eq!(settings.network_defaults(), Network::Pubnet.defaults());
+}
+
+#[test]
+fn error_codes_and_json() {
+    let key = LedgerKey::ContractCode(LedgerKeyContractCode {
+        hash: Hash([1; 32]),
+    });
+    let error = CliError::from(RetroshadeError::EntryNotFound(key.clone()));
+    assert_eq!(error.exit_code(), CliError::SNAPSHOT);
+    let json = error.to_json();
+    assert_eq!(json["error"]["kind"], "EntryNotFound");
+    assert_eq!(
+        json["error"]["key"],
+        key.to_xdr_base64(Limits::none()).unwrap()
+    );
+
+    let host = CliError::from(RetroshadeError::ContractCallFailed {
+        diagnostics: vec![],
+    });
+    assert_eq!(host.exit_code(), CliError::HOST);
+    assert_eq!(host.to_json()["error"]["kind"], "ContractCallFailed");
+
+    let sink = CliError::from(RetroshadeError::Export("disk full".to_string()));
+    assert_eq!(sink.exit_code(), CliError::SINK);
+    assert_eq!(CliError::sink("closed pipe").exit_code(), CliError::SINK);
+
+    let usage = CliError::usage("bad flag");
+    assert_eq!(usage.exit_code(), CliError::USAGE);
+    assert_eq!(usage.to_json()["error"]["message"], "bad flag");
+}
+
+#[test]
+fn parallel_ledger_keeps_order() {
+    // note: a synthetic ledger, later txs finish first so that the order has
+    // to be restored.
+    let txs: Vec<usize> = (0..10).collect();
+    let running = AtomicUsize::new(0);
+    let max_running = AtomicUsize::new(0);
+
+    let mut delivered = Vec::new();
+    process_ordered(
+        txs,
+        4,
+        |idx| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10 * (10 - idx as u64)));
+            running.fetch_sub(1, Ordering::SeqCst);
+            idx
+        },
+        |idx| {
+            delivered.push(idx);
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    assert_eq!(delivered, (0..10).collect::<Vec<_>>());
+    assert!(max_running.load(Ordering::SeqCst) <= 4);
+
+    let error = process_ordered(
+        vec![1, 2, 3],
+        2,
+        |idx| idx,
+        |idx| match idx {
+            2 => Err(CliError::sink("closed pipe")),
+            _ => Ok(()),
+        },
+    );
+    assert!(matches!(error, Err(CliError::Sink(_))));
+}
+
+/// A core database whose history has the mainnet fixture's transaction in
+/// ledger 100, the latest closed ledger being 101.
+fn history_db(name: &str) -> (PathBuf, TransactionV1Envelope, TransactionMeta) {
+    let db = temp_dir(name).join("stellar.db");
+    let _ = fs::remove_file(&db);
+    let conn = Connection::open(&db).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE ledgerheaders (ledgerseq INT, data TEXT);
+         CREATE TABLE txhistory (txid TEXT, ledgerseq INT, txindex INT, txbody TEXT, txmeta TEXT);",
+    )
+    .unwrap();
+
+    let ledgers = LedgerMetaReader::new(fs::File::open(LEDGERS_FIXTURE).unwrap());
+    for ledger in ledgers {
+        let header = ledger_header(&ledger.unwrap()).clone();
+        conn.execute(
+            "INSERT INTO ledgerheaders (ledgerseq, data) VALUES (?1, ?2)",
+            params![
+                header.ledger_seq,
+                header.to_xdr_base64(Limits::none()).unwrap()
+            ],
+        )
+        .unwrap();
+    }
+
+    let (_, envelope, meta) = fixture::load(MAINNET_FIXTURE).unwrap();
+    let tx_hash = envelope_hash(&envelope, Network::Pubnet.network_id()).unwrap();
+    conn.execute(
+        "INSERT INTO txhistory (txid, ledgerseq, txindex) VALUES (?1, 100, 1)",
+        params![hex::encode(tx_hash.0)],
+    )
+    .unwrap();
+
+    (db, v1_envelope(envelope).unwrap(), meta)
+}
+
+#[test]
+fn runs_with_the_header_of_the_transaction_ledger() {
+    let (db, envelope, meta) = history_db("tx-ledger");
+    let mut args = RunArgs {
+        envelope_xdr: envelope,
+        meta_xdr: meta,
+        ledger: None,
+        execution: ExecutionArgs {
+            db: Some(db),
+            network: Some(Network::Pubnet),
+            ..no_flags()
+        },
+    };
+    let settings = args.execution.settings().unwrap();
+
+    // note: not the latest closed ledger.
+    assert_eq!(args.header(&settings).unwrap().ledger_seq, 100);
+
+    args.ledger = Some(101);
+    assert_eq!(args.header(&settings).unwrap().ledger_seq, 101);
+
+    args.ledger = Some(102);
+    assert!(matches!(
+        args.header(&settings),
+        Err(CliError::Snapshot(message)) if message.starts_with("header of ledger 102 not found")
+    ));
+
+    // note: on another network the transaction hashes differently.
+    args.ledger = None;
+    args.execution.network = Some(Network::Testnet);
+    let settings = args.execution.settings().unwrap();
+    assert!(matches!(args.header(&settings), Err(CliError::Snapshot(_))));
+}
+
+#[test]
+fn missing_ledgers_are_retried() {
+    let flags = ExecutionArgs {
+        db: Some(temp_dir("missing-ledger").join("stellar.db")),
+        ..no_flags()
+    };
+    let settings = Settings::resolve(&flags, Config::default()).unwrap();
+
+    // note: not skipped, `follow` retries the ledgers that fail on its next poll.
+    let result = process_ledger(
+        &RetroshadeEngine::new().unwrap(),
+        &settings,
+        5,
+        1,
+        &mut HashSet::new(),
+        None,
+    );
+    assert!(matches!(result, Err(CliError::Snapshot(_))));
+}
+
+#[test]
+fn ledger_ranges() {
+    let range = parse_range("100..200").unwrap();
+    assert_eq!(
+        range,
+        LedgerRange {
+            start: Some(100),
+            end: Some(200)
+        }
+    );
+    assert!(range.contains(100) && range.contains(200));
+    assert!(!range.contains(99) && !range.contains(201));
+
+    let open = parse_range("100..").unwrap();
+    assert!(open.contains(u32::MAX) && !open.contains(99));
+    assert!(parse_range("..").unwrap().contains(0));
+
+    assert!(parse_range("100").is_err());
+    assert!(parse_range("a..b").is_err());
+}
@@ -0,0 +1,146 @@
+//! Read-through caching for snapshot sources.
+//!
+//! Unlike a plain LRU over hits, [`ReadThroughSnapshot`] also remembers keys
+//! the inner source reported as missing, so repeated probes for entries that
+//! don't exist (temporary entries, nonces) don't hit the inner source again
+//! within the same ledger.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use soroban_env_host::{
+    storage::{EntryWithLiveUntil, SnapshotSource},
+    xdr::LedgerKey,
+    HostError,
+};
+
+use crate::snapshot::SnapshotSourceExt;
+
+/// Default upper bound on the number of cached misses.
+pub const DEFAULT_MAX_CACHED_MISSES: usize = 4096;
+
+pub struct ReadThroughSnapshot<S: SnapshotSource + ?Sized = dyn SnapshotSource> {
+    inner_source: Rc<S>,
+    ledger: Cell<Option<u32>>,
+    /// Cached entries, with the label of the source that served them when
+    /// fetched through [`SnapshotSourceExt::get_labeled`].
+    hits: RefCell<HashMap<LedgerKey, (EntryWithLiveUntil, Option<String>)>>,
+    misses: RefCell<HashSet<LedgerKey>>,
+    max_cached_misses: usize,
+}
+
+impl<S: SnapshotSource + ?Sized> ReadThroughSnapshot<S> {
+    pub fn new(inner_source: Rc<S>) -> Self {
+        Self::with_max_cached_misses(inner_source, DEFAULT_MAX_CACHED_MISSES)
+    }
+
+    /// Once `max_cached_misses` misses are cached, further misses are passed
+    /// through to the inner source without being remembered.
+    pub fn with_max_cached_misses(inner_source: Rc<S>, max_cached_misses: usize) -> Self {
+        Self {
+            inner_source,
+            ledger: Cell::new(None),
+            hits: RefCell::new(HashMap::new()),
+            misses: RefCell::new(HashSet::new()),
+            max_cached_misses,
+        }
+    }
+
+    /// Marks the ledger the cached values belong to. Moving to a different
+    /// ledger drops everything that was cached for the previous one.
+    pub fn begin_ledger(&self, sequence: u32) {
+        if self.ledger.get() != Some(sequence) {
+            self.invalidate_all();
+            self.ledger.set(Some(sequence));
+        }
+    }
+
+    pub fn invalidate_all(&self) {
+        self.hits.borrow_mut().clear();
+        self.misses.borrow_mut().clear();
+    }
+
+    pub fn invalidate(&self, key: &LedgerKey) {
+        self.hits.borrow_mut().remove(key);
+        self.misses.borrow_mut().remove(key);
+    }
+
+    pub fn cached_hits(&self) -> usize {
+        self.hits.borrow().len()
+    }
+
+    pub fn cached_misses(&self) -> usize {
+        self.misses.borrow().len()
+    }
+
+    /// Serves `key` from the cache, or from `fetch` and caches the result.
+    fn read_through(
+        &self,
+        key: &Rc<LedgerKey>,
+        fetch: impl FnOnce() -> Result<Option<(EntryWithLiveUntil, Option<String>)>, HostError>,
+    ) -> Result<Option<(EntryWithLiveUntil, Option<String>)>, HostError> {
+        if let Some(hit) = self.hits.borrow().get(key.as_ref()) {
+            return Ok(Some(hit.clone()));
+        }
+
+        if self.misses.borrow().contains(key.as_ref()) {
+            return Ok(None);
+        }
+
+        // note: errors are not cached, the next get will retry the inner source.
+        let entry = fetch()?;
+
+        match &entry {
+            Some(hit) => {
+                self.hits
+                    .borrow_mut()
+                    .insert(key.as_ref().clone(), hit.clone());
+            }
+            None => {
+                let mut misses = self.misses.borrow_mut();
+                if misses.len() < self.max_cached_misses {
+                    misses.insert(key.as_ref().clone());
+                }
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+impl<S: SnapshotSource + ?Sized> SnapshotSource for ReadThroughSnapshot<S> {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        let entry = self.read_through(key, || {
+            Ok(self.inner_source.get(key)?.map(|entry| (entry, None)))
+        })?;
+
+        Ok(entry.map(|(entry, _)| entry))
+    }
+}
+
+/// Cached entries keep the label the inner source reported for them.
+impl<S: SnapshotSourceExt + ?Sized> SnapshotSourceExt for ReadThroughSnapshot<S> {
+    fn label(&self) -> String {
+        self.inner_source.label()
+    }
+
+    fn get_labeled(
+        &self,
+        key: &Rc<LedgerKey>,
+    ) -> Result<Option<(EntryWithLiveUntil, String)>, HostError> {
+        let entry = self.read_through(key, || {
+            Ok(self
+                .inner_source
+                .get_labeled(key)?
+                .map(|(entry, label)| (entry, Some(label))))
+        })?;
+
+        // note: entries cached by a plain `get` weren't given a label.
+        Ok(entry.map(|(entry, label)| (entry, label.unwrap_or_else(|| self.label()))))
+    }
+}
+
+impl SnapshotSourceExt for ReadThroughSnapshot {}
@@ -0,0 +1,113 @@
+//! Ledger entry changes produced by the re-execution and their comparison
+//! against the on-chain operation meta. If the two disagree, the reconstructed
+//! pre-execution state was wrong and the retroshades shouldn't be trusted.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::{LedgerEntry, LedgerEntryChange, LedgerKey, TransactionMetaV3};
+
+use crate::snapshot::entry_key;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryTtlChange {
+    pub old_live_until_ledger: u32,
+    pub new_live_until_ledger: u32,
+}
+
+/// A ledger entry read or written by the re-execution.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryChange {
+    pub read_only: bool,
+    pub key: LedgerKey,
+    pub old_entry_size_bytes: u32,
+    /// Entry after the execution, `None` if it was deleted (or is read-only).
+    pub new_value: Option<LedgerEntry>,
+    pub ttl_change: Option<EntryTtlChange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The entry changed on-chain but the re-execution didn't write it.
+    NotWritten { key: LedgerKey },
+
+    /// The re-execution created or removed the entry, the chain left it as it
+    /// was. `None` means removed.
+    NotOnChain {
+        key: LedgerKey,
+        reexecuted: Option<LedgerEntry>,
+    },
+
+    /// Both wrote the entry but with a different outcome. `None` means deleted.
+    ValueDiffers {
+        key: LedgerKey,
+        onchain: Option<LedgerEntry>,
+        reexecuted: Option<LedgerEntry>,
+    },
+}
+
+/// Compares the on-chain entry updates, creations and removals against the
+/// re-executed changes, both ways. The last modified ledger is ignored as it
+/// depends on the ledger info the re-execution ran with.
+///
+/// The host reports every read-write entry as written, changed or not, so
+/// only re-executed creations and removals are checked against the meta.
+pub(crate) fn compare_with_meta(
+    changes: &[EntryChange],
+    meta: &TransactionMetaV3,
+) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut onchain_keys = HashSet::new();
+
+    for op in meta.operations.iter() {
+        for change in op.changes.0.iter() {
+            let (key, onchain) = match change {
+                LedgerEntryChange::Updated(entry) | LedgerEntryChange::Created(entry) => {
+                    match entry_key(entry) {
+                        Some(key) => (key, Some(entry)),
+                        // note: TTL entries and classic entries we don't track.
+                        None => continue,
+                    }
+                }
+                LedgerEntryChange::Removed(key) => (key.clone(), None),
+                LedgerEntryChange::State(_) | LedgerEntryChange::Restored(_) => continue,
+            };
+            onchain_keys.insert(key.clone());
+
+            let Some(reexecuted) = changes
+                .iter()
+                .find(|change| !change.read_only && change.key == key)
+            else {
+                mismatches.push(Mismatch::NotWritten { key });
+                continue;
+            };
+
+            let same_outcome = match (onchain, &reexecuted.new_value) {
+                (Some(onchain), Some(reexecuted)) => onchain.data == reexecuted.data,
+                (None, None) => true,
+                _ => false,
+            };
+
+            if !same_outcome {
+                mismatches.push(Mismatch::ValueDiffers {
+                    key,
+                    onchain: onchain.cloned(),
+                    reexecuted: reexecuted.new_value.clone(),
+                });
+            }
+        }
+    }
+
+    for change in changes.iter().filter(|change| !change.read_only) {
+        let created = change.old_entry_size_bytes == 0 && change.new_value.is_some();
+        let removed = change.old_entry_size_bytes > 0 && change.new_value.is_none();
+        if (created || removed) && !onchain_keys.contains(&change.key) {
+            mismatches.push(Mismatch::NotOnChain {
+                key: change.key.clone(),
+                reexecuted: change.new_value.clone(),
+            });
+        }
+    }
+
+    mismatches
+}
@@ -0,0 +1,145 @@
+//! Executions of a transaction with its original binaries and with the
+//! Mercury replacements, and how they differ. Instrumented binaries are
+//! expected to only add retroshades, anything else in the diff means the
+//! replacement changes the contract's semantics.
+
+use serde::Serialize;
+use soroban_env_host::{
+    xdr::{LedgerEntry, LedgerKey, ScVal},
+    zephyr::RetroshadeExport,
+};
+
+use crate::{changes::EntryChange, RetroshadeExecutionResult};
+
+/// Both executions of [`crate::RetroshadesExecution::retroshade_compare`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Comparison {
+    pub original: RetroshadeExecutionResult,
+    pub replaced: RetroshadeExecutionResult,
+    pub diff: ExecutionDiff,
+}
+
+/// How the execution with the replaced binaries differs from the one with
+/// the original binaries.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ExecutionDiff {
+    /// `None` when both returned the same value or failed the same way.
+    pub invoke_result: Option<InvokeResultDiff>,
+    pub ledger_changes: Vec<LedgerChangeDiff>,
+    /// Retroshades only one of the executions emitted, in emitted order.
+    pub only_original: Vec<RetroshadeExport>,
+    pub only_replaced: Vec<RetroshadeExport>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InvokeResultDiff {
+    pub original: Result<ScVal, String>,
+    pub replaced: Result<ScVal, String>,
+}
+
+/// An entry the executions left in different states. `None` means the
+/// execution didn't write the entry, or deleted it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LedgerChangeDiff {
+    pub key: LedgerKey,
+    pub original: Option<LedgerEntry>,
+    pub replaced: Option<LedgerEntry>,
+}
+
+impl ExecutionDiff {
+    pub fn new(original: &RetroshadeExecutionResult, replaced: &RetroshadeExecutionResult) -> Self {
+        let invoke_result =
+            (original.invoke_result != replaced.invoke_result).then(|| InvokeResultDiff {
+                original: original.invoke_result.clone(),
+                replaced: replaced.invoke_result.clone(),
+            });
+
+        Self {
+            invoke_result,
+            ledger_changes: ledger_change_diffs(&original.ledger_changes, &replaced.ledger_changes),
+            only_original: unmatched(&original.retroshades, &replaced.retroshades),
+            only_replaced: unmatched(&replaced.retroshades, &original.retroshades),
+        }
+    }
+
+    /// Whether the executions only differ in the retroshades the replaced
+    /// binaries emitted, what a release gate usually checks.
+    pub fn same_semantics(&self) -> bool {
+        self.invoke_result.is_none() && self.ledger_changes.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.same_semantics() && self.only_original.is_empty() && self.only_replaced.is_empty()
+    }
+}
+
+/// Written entries whose outcome differs, in the order of the original
+/// changes then those only the replaced execution wrote.
+fn ledger_change_diffs(
+    original: &[EntryChange],
+    replaced: &[EntryChange],
+) -> Vec<LedgerChangeDiff> {
+    // note: `Some(None)` for entries the execution deleted.
+    let written = |changes: &[EntryChange], key: &LedgerKey| {
+        changes
+            .iter()
+            .find(|change| !change.read_only && &change.key == key)
+            .map(|change| change.new_value.clone())
+    };
+
+    let mut keys: Vec<&LedgerKey> = Vec::new();
+    for change in original.iter().chain(replaced) {
+        if !change.read_only && !keys.contains(&&change.key) {
+            keys.push(&change.key);
+        }
+    }
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let original = written(original, key);
+            let replaced = written(replaced, key);
+            // note: same as comparing with the meta, the last modified
+            // ledger doesn't tell the outcomes apart.
+            let same = match (&original, &replaced) {
+                (Some(Some(original)), Some(Some(replaced))) => original.data == replaced.data,
+                (original, replaced) => original == replaced,
+            };
+
+            (!same).then(|| LedgerChangeDiff {
+                key: key.clone(),
+                original: original.flatten(),
+                replaced: replaced.flatten(),
+            })
+        })
+        .collect()
+}
+
+/// Retroshades of `retroshades` without an identical one in `others`, each
+/// of `others` matching at most once.
+fn unmatched(
+    retroshades: &[RetroshadeExport],
+    others: &[RetroshadeExport],
+) -> Vec<RetroshadeExport> {
+    let mut matched = vec![false; others.len()];
+
+    retroshades
+        .iter()
+        .filter(|retroshade| {
+            let other = (0..others.len()).find(|&idx| {
+                let other = &others[idx];
+                !matched[idx]
+                    && other.contract_id == retroshade.contract_id
+                    && other.target == retroshade.target
+                    && other.event_object == retroshade.event_object
+            });
+            match other {
+                Some(idx) => {
+                    matched[idx] = true;
+                    false
+                }
+                None => true,
+            }
+        })
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,40 @@
+//! What this build can execute, for operators running several retroshade
+//! versions across a protocol upgrade.
+
+use std::ops::RangeInclusive;
+
+use serde::Serialize;
+
+/// Oldest ledger protocol the embedded host executes.
+pub const MIN_PROTOCOL: u32 = 22;
+
+/// Newest ledger protocol the embedded host executes.
+pub const MAX_PROTOCOL: u32 = soroban_env_host::meta::INTERFACE_VERSION.protocol;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct VersionInfo {
+    pub crate_version: &'static str,
+    /// Version of the soroban-env-host fork executions run on.
+    pub env_host_version: &'static str,
+    pub min_protocol: u32,
+    pub max_protocol: u32,
+}
+
+impl VersionInfo {
+    pub fn supported_protocols(&self) -> RangeInclusive<u32> {
+        self.min_protocol..=self.max_protocol
+    }
+
+    pub fn supports(&self, protocol_version: u32) -> bool {
+        self.supported_protocols().contains(&protocol_version)
+    }
+}
+
+pub fn version() -> VersionInfo {
+    VersionInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        env_host_version: soroban_env_host::VERSION.pkg,
+        min_protocol: MIN_PROTOCOL,
+        max_protocol: MAX_PROTOCOL,
+    }
+}
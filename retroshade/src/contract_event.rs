@@ -0,0 +1,114 @@
+//! Retroshades as Soroban contract events, for consumers whose pipelines
+//! already process those.
+//!
+//! A retroshade is synthesized as a contract event of the contract that
+//! emitted it, with the target as its single topic and the event object as
+//! its data:
+//!
+//! ```text
+//! ContractEvent {
+//!     ext: V0,
+//!     contract_id: Some(<emitting contract>),
+//!     type_: Contract,
+//!     body: V0 {
+//!         topics: [Symbol(<target>)],
+//!         data: Map(<event object>),
+//!     },
+//! }
+//! ```
+//!
+//! Nothing marks the event as synthetic: any contract event of this shape
+//! converts back with [`RetroshadeExportExt::from_contract_event`], so keep
+//! retroshades in their own stream rather than mixing them with the events
+//! the network emitted.
+
+use soroban_env_host::{
+    xdr::{
+        ContractEvent, ContractEventBody, ContractEventType, ContractEventV0, ExtensionPoint, ScVal,
+    },
+    zephyr::RetroshadeExport,
+};
+
+use crate::{MalformedReason, RetroshadeError};
+
+/// Conversions of [`RetroshadeExport`]s, which are defined by the host.
+pub trait RetroshadeExportExt: Sized {
+    /// The contract event standing for this retroshade, see the
+    /// [module docs](self) for its shape.
+    fn to_contract_event(&self) -> ContractEvent;
+
+    /// The retroshade `event` stands for. Errors with
+    /// [`RetroshadeError::MalformedRetroshadeEvent`] if the event isn't of
+    /// the shape [`Self::to_contract_event`] synthesizes.
+    fn from_contract_event(event: &ContractEvent) -> Result<Self, RetroshadeError>;
+}
+
+impl RetroshadeExportExt for RetroshadeExport {
+    fn to_contract_event(&self) -> ContractEvent {
+        ContractEvent {
+            ext: ExtensionPoint::V0,
+            contract_id: Some(self.contract_id.clone().into()),
+            type_: ContractEventType::Contract,
+            body: ContractEventBody::V0(ContractEventV0 {
+                // note: a single topic always fits.
+                topics: vec![self.target.clone()].try_into().unwrap(),
+                data: self.event_object.clone(),
+            }),
+        }
+    }
+
+    fn from_contract_event(event: &ContractEvent) -> Result<Self, RetroshadeError> {
+        let contract_id = event.contract_id.as_ref().map(|id| id.0.clone());
+        let malformed = |reason, value_debug: String| RetroshadeError::MalformedRetroshadeEvent {
+            contract_id: contract_id
+                .as_ref()
+                .map(|id| stellar_strkey::Contract(id.0).to_string())
+                .unwrap_or_default(),
+            reason,
+            value_debug,
+        };
+
+        let Some(contract_id) = contract_id.clone() else {
+            return Err(malformed(
+                MalformedReason::NotContractEvent,
+                format!("{:?}", event),
+            ));
+        };
+        if event.type_ != ContractEventType::Contract {
+            return Err(malformed(
+                MalformedReason::NotContractEvent,
+                format!("{:?}", event),
+            ));
+        }
+
+        let ContractEventBody::V0(body) = &event.body;
+        let [target] = body.topics.as_slice() else {
+            return Err(malformed(
+                MalformedReason::TopicCount {
+                    count: body.topics.len(),
+                },
+                format!("{:?}", body.topics),
+            ));
+        };
+        if !matches!(target, ScVal::Symbol(_)) {
+            return Err(malformed(
+                MalformedReason::TargetNotSymbol {
+                    found: target.discriminant(),
+                },
+                format!("{:?}", target),
+            ));
+        }
+        if !matches!(body.data, ScVal::Map(Some(_))) {
+            return Err(malformed(
+                MalformedReason::EventNotMap,
+                format!("{:?}", body.data),
+            ));
+        }
+
+        Ok(RetroshadeExport {
+            contract_id,
+            target: target.clone(),
+            event_object: body.data.clone(),
+        })
+    }
+}
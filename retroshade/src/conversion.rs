@@ -1,19 +1,135 @@
 //! This module handles the pretty-print of ScVals in order for them to be
 //! consumed and potentially efficiently filtered within the db.
 
-use std::error::Error;
+use std::{error::Error, fmt};
 
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 use num_bigint::BigInt;
 use num_traits::FromPrimitive;
 use postgres_types::{to_sql_checked, IsNull, ToSql, Type};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use soroban_env_host::xdr::{
     ClaimableBalanceId, Int128Parts, Int256Parts, PublicKey, ScAddress, ScVal, ScVec, UInt128Parts,
     UInt256Parts,
 };
 
+#[cfg(feature = "diesel")]
+pub mod diesel;
+#[cfg(feature = "sqlx")]
+mod sqlx;
+
 const MAX_ALLOWED_RECURSION_DEPTH: usize = 1;
 
+/// How addresses are rendered in packed exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressFormat {
+    /// `G...` and `C...` strkeys.
+    #[default]
+    StrKey,
+
+    /// Hex of the raw key or contract hash.
+    Hex,
+
+    /// Strkeys, with a sibling `<column>_hex` column holding the hex
+    /// rendering of every column containing an address.
+    Both,
+}
+
+/// Options of [`FromScVal::from_scval_with`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConversionOptions {
+    pub address_format: AddressFormat,
+}
+
+impl ConversionOptions {
+    /// The same options rendering addresses as hex.
+    pub fn hex(self) -> Self {
+        Self {
+            address_format: AddressFormat::Hex,
+        }
+    }
+}
+
+/// Whether `value` is or holds an address.
+pub fn contains_address(value: &ScVal) -> bool {
+    match value {
+        ScVal::Address(_) => true,
+        ScVal::Vec(Some(items)) => items.iter().any(contains_address),
+        ScVal::Map(Some(map)) => map
+            .iter()
+            .any(|entry| contains_address(&entry.key) || contains_address(&entry.val)),
+        _ => false,
+    }
+}
+
+/// Strkey of `address`, or its hex with [`AddressFormat::Hex`].
+pub fn address_text(address: &ScAddress, format: AddressFormat) -> String {
+    let hex = format == AddressFormat::Hex;
+    match address {
+        ScAddress::Account(id) => {
+            let PublicKey::PublicKeyTypeEd25519(int) = &id.0;
+            if hex {
+                hex::encode(int.0)
+            } else {
+                stellar_strkey::ed25519::PublicKey(int.0).to_string()
+            }
+        }
+        ScAddress::Contract(id) if hex => hex::encode(id.0 .0),
+        ScAddress::Contract(id) => stellar_strkey::Contract(id.0 .0).to_string(),
+        ScAddress::MuxedAccount(id) if hex => hex::encode(id.ed25519.0),
+        ScAddress::MuxedAccount(id) => stellar_strkey::ed25519::PublicKey(id.ed25519.0).to_string(),
+        ScAddress::ClaimableBalance(ClaimableBalanceId::ClaimableBalanceIdTypeV0(hash)) => {
+            hex::encode(hash.0)
+        }
+        ScAddress::LiquidityPool(pool) => hex::encode(pool.0 .0),
+    }
+}
+
+/// JSON text of a container that couldn't be packed as an array.
+fn json_text(value: &ScVal, options: &ConversionOptions) -> String {
+    let tagged = match options.address_format {
+        AddressFormat::Hex => hex_tagged(value),
+        AddressFormat::StrKey | AddressFormat::Both => serde_json::to_value(value).unwrap(),
+    };
+
+    // note: the container's own variant tag is left out.
+    match tagged {
+        serde_json::Value::Object(mut object) if object.len() == 1 => {
+            object.values_mut().next().unwrap().take()
+        }
+        other => other,
+    }
+    .to_string()
+}
+
+/// Same JSON as the `ScVal`'s, with its addresses rendered as hex. Walks the
+/// value rather than its JSON, so strings that happen to be strkeys are left
+/// as they are.
+fn hex_tagged(value: &ScVal) -> serde_json::Value {
+    match value {
+        ScVal::Address(address) => {
+            serde_json::json!({ "address": address_text(address, AddressFormat::Hex) })
+        }
+        ScVal::Vec(Some(items)) => {
+            let items: Vec<_> = items.iter().map(hex_tagged).collect();
+            serde_json::json!({ "vec": items })
+        }
+        ScVal::Map(Some(map)) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "key": hex_tagged(&entry.key),
+                        "val": hex_tagged(&entry.val),
+                    })
+                })
+                .collect();
+            serde_json::json!({ "map": entries })
+        }
+        other => serde_json::to_value(other).unwrap(),
+    }
+}
+
 pub fn i256_to_bigint(parts: Int256Parts) -> BigInt {
     let hi =
         (BigInt::from_i64(parts.hi_hi).unwrap() << 64) | BigInt::from_u64(parts.hi_lo).unwrap();
@@ -48,7 +164,8 @@ pub fn num_to_string(parts: ScVal) -> String {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TypeKind {
     GenericArray(Vec<FromScVal>), // Note: max allowed recursion depth is one.
     Text(String),
@@ -57,14 +174,78 @@ pub enum TypeKind {
     Numeric(String),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl fmt::Display for TypeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeKind::GenericArray(arr) => {
+                let items: Vec<String> = arr.iter().map(|item| item.kind.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            TypeKind::Text(s) => write!(f, "{}", s),
+            TypeKind::Boolean(b) => write!(f, "{}", b),
+            TypeKind::Void => write!(f, "void"),
+            TypeKind::Numeric(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FromScVal {
+    #[serde(
+        serialize_with = "serialize_dbtype",
+        deserialize_with = "deserialize_dbtype"
+    )]
     pub dbtype: Type,
     pub kind: TypeKind,
 }
 
+/// Database types serialize as their postgres name, e.g. `numeric` or `_text`.
+fn serialize_dbtype<S: Serializer>(dbtype: &Type, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(dbtype.name())
+}
+
+fn deserialize_dbtype<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Type, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    [
+        Type::BOOL,
+        Type::TEXT,
+        Type::NUMERIC,
+        Type::BYTEA,
+        Type::BOOL_ARRAY,
+        Type::NUMERIC_ARRAY,
+        Type::TEXT_ARRAY,
+    ]
+    .into_iter()
+    .find(|dbtype| dbtype.name() == name)
+    .ok_or_else(|| D::Error::custom(format!("unknown database type {}", name)))
+}
+
 impl FromScVal {
+    /// Typed JSON value: numerics are strings so that 128 and 256 bit values
+    /// keep their precision, void is null.
+    pub fn to_json(&self) -> serde_json::Value {
+        match &self.kind {
+            TypeKind::GenericArray(arr) => {
+                serde_json::Value::Array(arr.iter().map(FromScVal::to_json).collect())
+            }
+            TypeKind::Text(s) => serde_json::Value::String(s.clone()),
+            TypeKind::Boolean(b) => serde_json::Value::Bool(*b),
+            TypeKind::Void => serde_json::Value::Null,
+            TypeKind::Numeric(n) => serde_json::Value::String(n.clone()),
+        }
+    }
+
     pub fn from_scval(value: ScVal, recursion_depth: &mut usize) -> Self {
+        Self::from_scval_with(value, recursion_depth, &ConversionOptions::default())
+    }
+
+    /// Same as [`Self::from_scval`] with `options`. Only the strkeys of
+    /// [`AddressFormat::Both`] are converted, packing adds the hex columns.
+    pub fn from_scval_with(
+        value: ScVal,
+        recursion_depth: &mut usize,
+        options: &ConversionOptions,
+    ) -> Self {
         match value {
             ScVal::Bool(b) => FromScVal {
                 dbtype: Type::BOOL,
@@ -125,7 +306,13 @@ impl FromScVal {
                     if let Some(ScVec(vecm)) = &v {
                         let inner_array: Vec<FromScVal> = vecm
                             .iter()
-                            .map(|element| FromScVal::from_scval(element.clone(), recursion_depth))
+                            .map(|element| {
+                                FromScVal::from_scval_with(
+                                    element.clone(),
+                                    recursion_depth,
+                                    options,
+                                )
+                            })
                             .collect();
 
                         if !inner_array.is_empty()
@@ -152,40 +339,21 @@ impl FromScVal {
 
                 FromScVal {
                     dbtype: Type::TEXT,
-                    kind: TypeKind::Text(serde_json::to_string(&v).unwrap()),
+                    kind: TypeKind::Text(json_text(&ScVal::Vec(v), options)),
                 }
             }
-            ScVal::Map(m) => FromScVal {
+            ScVal::Map(_) => FromScVal {
                 dbtype: Type::TEXT,
-                kind: TypeKind::Text(serde_json::to_string(&m).unwrap()),
+                kind: TypeKind::Text(json_text(&value, options)),
             },
             ScVal::Error(e) => FromScVal {
                 dbtype: Type::TEXT,
                 kind: TypeKind::Text(serde_json::to_string(&e).unwrap()),
             },
-            ScVal::Address(addr) => {
-                let address = match addr {
-                    ScAddress::Account(id) => {
-                        let PublicKey::PublicKeyTypeEd25519(int) = id.0;
-                        stellar_strkey::ed25519::PublicKey(int.0).to_string()
-                    }
-                    ScAddress::Contract(id) => stellar_strkey::Contract(id.0.into()).to_string(),
-                    ScAddress::MuxedAccount(id) => {
-                        let id = id.ed25519;
-                        stellar_strkey::ed25519::PublicKey(id.0).to_string()
-                    }
-                    ScAddress::ClaimableBalance(cb) => {
-                        let ClaimableBalanceId::ClaimableBalanceIdTypeV0(hash) = cb;
-                        hex::encode(hash.0)
-                    }
-                    ScAddress::LiquidityPool(pool) => hex::encode(pool.0 .0),
-                };
-
-                FromScVal {
-                    dbtype: Type::TEXT,
-                    kind: TypeKind::Text(address),
-                }
-            }
+            ScVal::Address(addr) => FromScVal {
+                dbtype: Type::TEXT,
+                kind: TypeKind::Text(address_text(&addr, options.address_format)),
+            },
             ScVal::I128(_) => FromScVal {
                 dbtype: Type::NUMERIC,
                 kind: TypeKind::Numeric(num_to_string(value)),
@@ -224,6 +392,16 @@ impl ToSql for FromScVal {
                             .collect();
                         bool_array.to_sql(ty, out)
                     }
+                    Type::NUMERIC_ARRAY if *ty == Type::NUMERIC_ARRAY => {
+                        let num_array: Vec<Numeric> = arr
+                            .iter()
+                            .filter_map(|item| match &item.kind {
+                                TypeKind::Numeric(n) => Some(Numeric(n)),
+                                _ => None,
+                            })
+                            .collect();
+                        num_array.to_sql(ty, out)
+                    }
                     Type::NUMERIC_ARRAY => {
                         let num_array: Vec<f64> = arr
                             .iter()
@@ -250,6 +428,7 @@ impl ToSql for FromScVal {
             TypeKind::Text(s) => s.to_sql(ty, out),
             TypeKind::Boolean(b) => b.to_sql(ty, out),
             TypeKind::Void => Ok(IsNull::Yes),
+            TypeKind::Numeric(n) if *ty == Type::NUMERIC => Numeric(n).to_sql(ty, out),
             TypeKind::Numeric(n) => {
                 let n: f64 = n.parse().unwrap_or(0.0);
                 n.to_sql(ty, out)
@@ -262,13 +441,73 @@ impl ToSql for FromScVal {
             ty,
             &Type::BOOL
                 | &Type::TEXT
+                | &Type::NUMERIC
                 | &Type::FLOAT8
                 | &Type::BYTEA
                 | &Type::BOOL_ARRAY
                 | &Type::TEXT_ARRAY
+                | &Type::NUMERIC_ARRAY
                 | &Type::FLOAT8_ARRAY
         )
     }
 
     to_sql_checked!();
 }
+
+/// Postgres' binary `numeric` encoding of the integers numerics hold: base
+/// 10000 digits, most significant first, with trailing zero digits dropped.
+#[derive(Debug)]
+struct Numeric<'a>(&'a str);
+
+impl ToSql for Numeric<'_> {
+    fn to_sql(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let (negative, digits) = match self.0.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, self.0),
+        };
+        if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+            return Err(format!("{} isn't an integer", self.0).into());
+        }
+
+        let digits = digits.trim_start_matches('0');
+        let padded = format!("{:0>1$}", digits, digits.len().div_ceil(4) * 4);
+        let mut groups: Vec<i16> = padded
+            .as_bytes()
+            .chunks(4)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |group, digit| group * 10 + (digit - b'0') as i16)
+            })
+            .collect();
+        let weight = groups.len().saturating_sub(1) as i16;
+        while groups.last() == Some(&0) {
+            groups.pop();
+        }
+
+        out.put_i16(groups.len() as i16);
+        out.put_i16(weight);
+        out.put_u16(if negative && !groups.is_empty() {
+            0x4000
+        } else {
+            0
+        });
+        // display scale, integers have no fractional digits.
+        out.put_u16(0);
+        for group in groups {
+            out.put_i16(group);
+        }
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+
+    to_sql_checked!();
+}
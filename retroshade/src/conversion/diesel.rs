@@ -0,0 +1,156 @@
+//! Diesel support: [`DieselValue`] serializes a `FromScVal` as the SQL type it
+//! was converted to, and [`insert_query`] builds a dynamic insert for a packed
+//! export with one bind per column. Maps and vectors that aren't arrays are
+//! packed as JSON text and bound as text, as the tables store them.
+
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use diesel::{
+    pg::Pg,
+    query_builder::{BoxedSqlQuery, SqlQuery},
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::{Array, Bool, Bytea, Numeric, Text},
+};
+use postgres_types::Type;
+
+use super::{FromScVal, TypeKind};
+use crate::{export::sql::quote_ident, RetroshadeExportPretty};
+
+#[derive(Clone, Debug)]
+pub struct DieselValue(pub FromScVal);
+
+fn numeric(n: &str) -> Result<BigDecimal, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(BigDecimal::from_str(n)?)
+}
+
+impl ToSql<Text, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match &self.0.kind {
+            TypeKind::Void => Ok(IsNull::Yes),
+            TypeKind::Text(s) | TypeKind::Numeric(s) => ToSql::<Text, Pg>::to_sql(s, out),
+            other => ToSql::<Text, Pg>::to_sql(&other.to_string(), &mut out.reborrow()),
+        }
+    }
+}
+
+impl ToSql<Numeric, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match &self.0.kind {
+            TypeKind::Void => Ok(IsNull::Yes),
+            TypeKind::Numeric(n) => ToSql::<Numeric, Pg>::to_sql(&numeric(n)?, &mut out.reborrow()),
+            other => Err(format!("{} is not numeric", other).into()),
+        }
+    }
+}
+
+impl ToSql<Bool, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match &self.0.kind {
+            TypeKind::Void => Ok(IsNull::Yes),
+            TypeKind::Boolean(b) => ToSql::<Bool, Pg>::to_sql(b, out),
+            other => Err(format!("{} is not a bool", other).into()),
+        }
+    }
+}
+
+impl ToSql<Bytea, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match &self.0.kind {
+            TypeKind::Void => Ok(IsNull::Yes),
+            // note: bytes are converted to hex text.
+            TypeKind::Text(hex) => {
+                ToSql::<Bytea, Pg>::to_sql(&hex::decode(hex)?, &mut out.reborrow())
+            }
+            other => Err(format!("{} is not bytes", other).into()),
+        }
+    }
+}
+
+impl ToSql<Array<Text>, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let text_array: Vec<String> = array_items(&self.0)?
+            .iter()
+            .filter_map(|item| match &item.kind {
+                TypeKind::Text(s) => Some(s.clone()),
+                _ => None,
+            })
+            .collect();
+        ToSql::<Array<Text>, Pg>::to_sql(&text_array, &mut out.reborrow())
+    }
+}
+
+impl ToSql<Array<Numeric>, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let num_array = array_items(&self.0)?
+            .iter()
+            .filter_map(|item| match &item.kind {
+                TypeKind::Numeric(n) => Some(numeric(n)),
+                _ => None,
+            })
+            .collect::<Result<Vec<BigDecimal>, _>>()?;
+        ToSql::<Array<Numeric>, Pg>::to_sql(&num_array, &mut out.reborrow())
+    }
+}
+
+impl ToSql<Array<Bool>, Pg> for DieselValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let bool_array: Vec<bool> = array_items(&self.0)?
+            .iter()
+            .filter_map(|item| match &item.kind {
+                TypeKind::Boolean(b) => Some(*b),
+                _ => None,
+            })
+            .collect();
+        ToSql::<Array<Bool>, Pg>::to_sql(&bool_array, &mut out.reborrow())
+    }
+}
+
+fn array_items(
+    value: &FromScVal,
+) -> Result<&[FromScVal], Box<dyn std::error::Error + Send + Sync>> {
+    match &value.kind {
+        TypeKind::GenericArray(items) => Ok(items),
+        other => Err(format!("{} is not an array", other).into()),
+    }
+}
+
+/// `INSERT INTO {table} ({columns}) VALUES ($1, ...)` with each column bound
+/// as its converted type. `table` is used as is, quote it with
+/// [`quote_table`](crate::export::sql::quote_table) if needed.
+pub fn insert_query(
+    table: &str,
+    export: &RetroshadeExportPretty,
+) -> BoxedSqlQuery<'static, Pg, SqlQuery> {
+    let columns: Vec<String> = export
+        .event
+        .iter()
+        .map(|entry| quote_ident(&entry.name))
+        .collect();
+    let placeholders: Vec<String> = (1..=export.event.len())
+        .map(|idx| format!("${}", idx))
+        .collect();
+
+    let mut query = diesel::sql_query(format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table,
+        columns.join(", "),
+        placeholders.join(", ")
+    ))
+    .into_boxed();
+
+    for entry in &export.event {
+        let value = DieselValue(entry.value.clone());
+        query = match entry.value.dbtype {
+            Type::NUMERIC => query.bind::<Numeric, _>(value),
+            Type::BOOL => query.bind::<Bool, _>(value),
+            Type::BYTEA => query.bind::<Bytea, _>(value),
+            Type::TEXT_ARRAY => query.bind::<Array<Text>, _>(value),
+            Type::NUMERIC_ARRAY => query.bind::<Array<Numeric>, _>(value),
+            Type::BOOL_ARRAY => query.bind::<Array<Bool>, _>(value),
+            _ => query.bind::<Text, _>(value),
+        };
+    }
+
+    query
+}
@@ -0,0 +1,92 @@
+//! sqlx support: `FromScVal` binds as the postgres type it was converted to,
+//! so `query(...).bind(value)` works for every column of a packed export.
+
+use std::str::FromStr;
+
+use postgres_types::Type;
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo},
+    types::BigDecimal,
+    Encode, Postgres,
+};
+
+use super::{FromScVal, TypeKind};
+
+fn type_name(dbtype: &Type) -> &'static str {
+    match *dbtype {
+        Type::BOOL => "bool",
+        Type::NUMERIC => "numeric",
+        Type::BYTEA => "bytea",
+        Type::BOOL_ARRAY => "_bool",
+        Type::NUMERIC_ARRAY => "_numeric",
+        Type::TEXT_ARRAY => "_text",
+        _ => "text",
+    }
+}
+
+fn numeric(n: &str) -> Result<BigDecimal, BoxDynError> {
+    Ok(BigDecimal::from_str(n)?)
+}
+
+impl sqlx::Type<Postgres> for FromScVal {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("text")
+    }
+
+    // note: the actual type is per value, see `Encode::produces`.
+    fn compatible(_ty: &PgTypeInfo) -> bool {
+        true
+    }
+}
+
+impl Encode<'_, Postgres> for FromScVal {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match &self.kind {
+            TypeKind::GenericArray(arr) => match self.dbtype {
+                Type::BOOL_ARRAY => {
+                    let bool_array: Vec<bool> = arr
+                        .iter()
+                        .filter_map(|item| match &item.kind {
+                            TypeKind::Boolean(b) => Some(*b),
+                            _ => None,
+                        })
+                        .collect();
+                    bool_array.encode_by_ref(buf)
+                }
+                Type::NUMERIC_ARRAY => {
+                    let num_array = arr
+                        .iter()
+                        .filter_map(|item| match &item.kind {
+                            TypeKind::Numeric(n) => Some(numeric(n)),
+                            _ => None,
+                        })
+                        .collect::<Result<Vec<BigDecimal>, _>>()?;
+                    num_array.encode_by_ref(buf)
+                }
+                Type::TEXT_ARRAY => {
+                    let text_array: Vec<String> = arr
+                        .iter()
+                        .filter_map(|item| match &item.kind {
+                            TypeKind::Text(s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                    text_array.encode_by_ref(buf)
+                }
+                _ => Err("Unsupported array type".into()),
+            },
+            // note: bytes are converted to hex text, decode them back for bytea columns.
+            TypeKind::Text(s) if self.dbtype == Type::BYTEA => hex::decode(s)?.encode_by_ref(buf),
+            TypeKind::Text(s) => s.encode_by_ref(buf),
+            TypeKind::Boolean(b) => b.encode_by_ref(buf),
+            TypeKind::Void => Ok(IsNull::Yes),
+            TypeKind::Numeric(n) => numeric(n)?.encode_by_ref(buf),
+        }
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(PgTypeInfo::with_name(type_name(&self.dbtype)))
+    }
+}
@@ -0,0 +1,318 @@
+//! Typed decoding of packed exports for consumers processing them in Rust.
+//!
+//! A [`TypedDecoder`] is keyed by a struct of the contract [`spec`](crate::spec)
+//! and turns the fields of a [`RetroshadeExportPretty`] back into
+//! [`TypedValue`]s of the declared types: numerics into integers, hex into
+//! bytes, strkeys into [`Address`]es. Implement [`FromTypedEvent`] on a
+//! struct mirroring the contract's to decode straight into it with
+//! [`TypedDecoder::decode_as`].
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use soroban_env_host::xdr::{ScSpecTypeDef, ScVec};
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    spec::ContractSpec,
+    RetroshadeError, RetroshadeExportPretty,
+};
+
+/// Strkey of an account or contract address field.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Address(pub String);
+
+impl Address {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Value of a field decoded with its spec type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypedValue {
+    Bool(bool),
+    Void,
+    U32(u32),
+    I32(i32),
+    /// Also timepoints and durations.
+    U64(u64),
+    I64(i64),
+    U128(u128),
+    I128(i128),
+    /// 256 bit integers in decimal, Rust has no native type for them.
+    U256(String),
+    I256(String),
+    Bytes(Vec<u8>),
+    /// Strings and symbols.
+    String(String),
+    Address(Address),
+    Option(Option<Box<TypedValue>>),
+    Vec(Vec<TypedValue>),
+    /// Maps, tuples and user-defined types, as packed.
+    Packed(FromScVal),
+}
+
+/// Decoded fields of an export, in the order of the struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypedEvent {
+    structure: String,
+    fields: Vec<(String, TypedValue)>,
+}
+
+impl TypedEvent {
+    /// Name of the spec struct the event was decoded as.
+    pub fn structure(&self) -> &str {
+        &self.structure
+    }
+
+    pub fn fields(&self) -> &[(String, TypedValue)] {
+        &self.fields
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypedValue> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Removes the field `name` from the event and converts it to `T`.
+    pub fn take<T: FromTypedValue>(&mut self, name: &str) -> Result<T, RetroshadeError> {
+        let idx = self
+            .fields
+            .iter()
+            .position(|(field, _)| field == name)
+            .ok_or_else(|| typed_decode(name, "no such field"))?;
+        let (_, value) = self.fields.remove(idx);
+
+        T::from_typed_value(value).map_err(|reason| typed_decode(name, reason))
+    }
+}
+
+/// Rust types a [`TypedValue`] converts to.
+pub trait FromTypedValue: Sized {
+    /// Errors with the reason the value doesn't convert.
+    fn from_typed_value(value: TypedValue) -> Result<Self, String>;
+}
+
+/// Structs an event decodes into, usually built by [`TypedEvent::take`]-ing
+/// each of their fields.
+pub trait FromTypedEvent: Sized {
+    fn from_typed_event(event: TypedEvent) -> Result<Self, RetroshadeError>;
+}
+
+macro_rules! from_typed_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl FromTypedValue for $ty {
+                fn from_typed_value(value: TypedValue) -> Result<Self, String> {
+                    match value {
+                        TypedValue::$variant(value) => Ok(value),
+                        other => Err(mismatch(stringify!($ty), &other)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_typed_value!(
+    bool => Bool,
+    u32 => U32,
+    i32 => I32,
+    u64 => U64,
+    i64 => I64,
+    u128 => U128,
+    i128 => I128,
+    Vec<u8> => Bytes,
+    String => String,
+    Address => Address,
+);
+
+impl<T: FromTypedValue> FromTypedValue for Option<T> {
+    fn from_typed_value(value: TypedValue) -> Result<Self, String> {
+        match value {
+            TypedValue::Option(None) | TypedValue::Void => Ok(None),
+            TypedValue::Option(Some(value)) => T::from_typed_value(*value).map(Some),
+            other => Err(mismatch("Option", &other)),
+        }
+    }
+}
+
+// note: `Vec<u8>` is bytes, vectors of `u8` don't exist in contracts.
+impl<T: FromTypedValue> FromTypedValue for Vec<T> {
+    fn from_typed_value(value: TypedValue) -> Result<Self, String> {
+        match value {
+            TypedValue::Vec(items) => items.into_iter().map(T::from_typed_value).collect(),
+            other => Err(mismatch("Vec", &other)),
+        }
+    }
+}
+
+impl FromTypedValue for TypedValue {
+    fn from_typed_value(value: TypedValue) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+fn mismatch(expected: &str, found: &TypedValue) -> String {
+    format!("expected {} but found {:?}", expected, found)
+}
+
+fn typed_decode(field: &str, reason: impl Into<String>) -> RetroshadeError {
+    RetroshadeError::TypedDecode {
+        field: field.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Decodes the exports of a spec struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypedDecoder {
+    structure: String,
+    fields: Vec<(String, ScSpecTypeDef)>,
+}
+
+impl TypedDecoder {
+    /// Decoder of the struct `structure` of `spec`.
+    pub fn new(spec: &ContractSpec, structure: &str) -> Result<Self, RetroshadeError> {
+        let fields = spec.spec_for_struct(structure);
+        if fields.is_empty() {
+            return Err(RetroshadeError::Spec(format!(
+                "the spec has no struct {}",
+                structure
+            )));
+        }
+
+        Ok(Self {
+            structure: structure.to_string(),
+            fields,
+        })
+    }
+
+    /// Decoder of the struct the spec of `wasm` declares as `structure`.
+    pub fn from_wasm(wasm: &[u8], structure: &str) -> Result<Self, RetroshadeError> {
+        Self::new(&ContractSpec::from_wasm(wasm)?, structure)
+    }
+
+    pub fn structure(&self) -> &str {
+        &self.structure
+    }
+
+    /// Decodes the fields of the struct from `export`. Optional fields the
+    /// export lacks are `None`, while columns that aren't part of the struct,
+    /// e.g. the context columns, are ignored.
+    pub fn decode(&self, export: &RetroshadeExportPretty) -> Result<TypedEvent, RetroshadeError> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+
+        for (field, type_def) in &self.fields {
+            let entry = export.event.iter().find(|entry| entry.name == *field);
+            let value = match (entry, type_def) {
+                (Some(entry), type_def) => decode_value(&entry.value, type_def)
+                    .map_err(|reason| typed_decode(field, reason))?,
+                (None, ScSpecTypeDef::Option(_)) => TypedValue::Option(None),
+                (None, _) => return Err(typed_decode(field, "missing from the export")),
+            };
+            fields.push((field.clone(), value));
+        }
+
+        Ok(TypedEvent {
+            structure: self.structure.clone(),
+            fields,
+        })
+    }
+
+    pub fn decode_as<T: FromTypedEvent>(
+        &self,
+        export: &RetroshadeExportPretty,
+    ) -> Result<T, RetroshadeError> {
+        T::from_typed_event(self.decode(export)?)
+    }
+}
+
+/// Decodes a packed value, which [`FromScVal::from_scval`] converted from a
+/// value of `type_def`.
+pub fn decode_value(value: &FromScVal, type_def: &ScSpecTypeDef) -> Result<TypedValue, String> {
+    let decoded = match (type_def, &value.kind) {
+        (ScSpecTypeDef::Option(_), TypeKind::Void) => TypedValue::Option(None),
+        (ScSpecTypeDef::Option(option), _) => {
+            TypedValue::Option(Some(Box::new(decode_value(value, &option.value_type)?)))
+        }
+        (ScSpecTypeDef::Vec(vec), TypeKind::GenericArray(items)) => TypedValue::Vec(
+            items
+                .iter()
+                .map(|item| decode_value(item, &vec.element_type))
+                .collect::<Result<_, _>>()?,
+        ),
+        // note: vectors that couldn't be packed as arrays, e.g. empty ones,
+        // are packed as the JSON of the vector.
+        (ScSpecTypeDef::Vec(vec), TypeKind::Text(json)) => {
+            let items: Option<ScVec> = serde_json::from_str(json)
+                .map_err(|error| format!("invalid packed vector: {}", error))?;
+            TypedValue::Vec(
+                items
+                    .map(|items| items.0.to_vec())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|item| {
+                        decode_value(&FromScVal::from_scval(item, &mut 0), &vec.element_type)
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        (ScSpecTypeDef::Bool, TypeKind::Boolean(b)) => TypedValue::Bool(*b),
+        (ScSpecTypeDef::Void, TypeKind::Void) => TypedValue::Void,
+        (ScSpecTypeDef::U32, TypeKind::Numeric(n)) => TypedValue::U32(parse(n)?),
+        (ScSpecTypeDef::I32, TypeKind::Numeric(n)) => TypedValue::I32(parse(n)?),
+        (
+            ScSpecTypeDef::U64 | ScSpecTypeDef::Timepoint | ScSpecTypeDef::Duration,
+            TypeKind::Numeric(n),
+        ) => TypedValue::U64(parse(n)?),
+        (ScSpecTypeDef::I64, TypeKind::Numeric(n)) => TypedValue::I64(parse(n)?),
+        (ScSpecTypeDef::U128, TypeKind::Numeric(n)) => TypedValue::U128(parse(n)?),
+        (ScSpecTypeDef::I128, TypeKind::Numeric(n)) => TypedValue::I128(parse(n)?),
+        (ScSpecTypeDef::U256, TypeKind::Numeric(n)) => TypedValue::U256(n.clone()),
+        (ScSpecTypeDef::I256, TypeKind::Numeric(n)) => TypedValue::I256(n.clone()),
+        (ScSpecTypeDef::Bytes | ScSpecTypeDef::BytesN(_), TypeKind::Text(hex)) => {
+            TypedValue::Bytes(hex::decode(hex).map_err(|error| error.to_string())?)
+        }
+        (ScSpecTypeDef::String | ScSpecTypeDef::Symbol, TypeKind::Text(text)) => {
+            TypedValue::String(text.clone())
+        }
+        (ScSpecTypeDef::Address, TypeKind::Text(strkey)) => {
+            TypedValue::Address(Address(strkey.clone()))
+        }
+        (
+            ScSpecTypeDef::Map(_)
+            | ScSpecTypeDef::Tuple(_)
+            | ScSpecTypeDef::Udt(_)
+            | ScSpecTypeDef::Val,
+            _,
+        ) => TypedValue::Packed(value.clone()),
+        (type_def, kind) => {
+            return Err(format!(
+                "{} can't be decoded as {}",
+                kind,
+                type_def.discriminant().name()
+            ))
+        }
+    };
+
+    Ok(decoded)
+}
+
+fn parse<T: std::str::FromStr>(numeric: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    numeric
+        .parse()
+        .map_err(|error| format!("{} is out of range: {}", numeric, error))
+}
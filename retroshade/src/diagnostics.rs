@@ -0,0 +1,59 @@
+//! Human-readable rendering of diagnostic events, with values rendered through
+//! the same conversion used for the packed retroshades.
+
+use std::fmt;
+
+use soroban_env_host::xdr::{ContractEventBody, DiagnosticEvent};
+
+use crate::conversion::FromScVal;
+
+pub fn format_event(event: &DiagnosticEvent) -> String {
+    let status = if event.in_successful_contract_call {
+        "ok"
+    } else {
+        "failed"
+    };
+
+    let contract = match &event.event.contract_id {
+        Some(id) => stellar_strkey::Contract(id.0.into()).to_string(),
+        None => "host".to_string(),
+    };
+
+    let ContractEventBody::V0(body) = &event.event.body;
+    let topics: Vec<String> = body
+        .topics
+        .iter()
+        .map(|topic| {
+            FromScVal::from_scval(topic.clone(), &mut 0)
+                .kind
+                .to_string()
+        })
+        .collect();
+    let data = FromScVal::from_scval(body.data.clone(), &mut 0).kind;
+
+    format!(
+        "[{}] {} event from {}: topics [{}], data {}",
+        status,
+        event.event.type_.name(),
+        contract,
+        topics.join(", "),
+        data
+    )
+}
+
+pub fn format(events: &[DiagnosticEvent]) -> Vec<String> {
+    events.iter().map(format_event).collect()
+}
+
+/// Displays diagnostic events one per line.
+pub struct DisplayDiagnostics<'a>(pub &'a [DiagnosticEvent]);
+
+impl fmt::Display for DisplayDiagnostics<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, line) in format(self.0).iter().enumerate() {
+            writeln!(f, "{}: {}", idx, line)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Per-key view of the state an execution runs against, to debug state
+//! resets: what the snapshot served, what the host is fed and why they
+//! differ.
+
+use std::{collections::HashMap, rc::Rc};
+
+use serde::Serialize;
+use soroban_env_host::{
+    budget::Budget,
+    e2e_invoke::ledger_entry_to_ledger_key,
+    xdr::{Hash, LedgerEntry, LedgerKey, TransactionMeta, TransactionV1Envelope},
+};
+
+use crate::{snapshot::SnapshotSourceExt, ReplacementEntry, RetroshadeError, RetroshadesExecution};
+
+/// How a footprint entry got from the snapshot to the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryOrigin {
+    /// Fed as served by the snapshot.
+    Unchanged,
+    /// Reset to the value the meta reports before the transaction.
+    Reverted,
+    /// Created by the transaction, so it didn't exist before it.
+    Removed,
+    /// Deleted by the transaction, re-added from the meta.
+    Restored,
+    /// Contract code swapped with a replacement.
+    Replaced,
+    /// Neither the snapshot nor the meta have it.
+    Missing,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StateEntry {
+    pub key: LedgerKey,
+    /// Label of the source that served the key.
+    pub source: String,
+    pub snapshot: Option<LedgerEntry>,
+    pub snapshot_live_until: Option<u32>,
+    /// The entry the host runs against.
+    pub fed: Option<LedgerEntry>,
+    pub fed_live_until: Option<u32>,
+    pub origin: EntryOrigin,
+}
+
+impl RetroshadesExecution {
+    /// Same as [`Self::build_from_envelope_and_meta_with_report`], returning
+    /// for each footprint key the snapshot's entry next to the one the host
+    /// is fed.
+    pub fn build_with_state_dump(
+        &mut self,
+        snapshot_source: &dyn SnapshotSourceExt,
+        tx_envelope: TransactionV1Envelope,
+        tx_meta: TransactionMeta,
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<Vec<StateEntry>, RetroshadeError> {
+        let report = self.build(snapshot_source, tx_envelope, tx_meta, mercury_contracts)?;
+
+        let mut fed = HashMap::new();
+        for keyed in &self.target_pre_execution_state {
+            let key = match &keyed.key {
+                Some(key) => key.key.clone(),
+                None => ledger_entry_to_ledger_key(&keyed.entry, &Budget::default())?,
+            };
+            fed.entry(key)
+                .or_insert((keyed.fed_entry(), keyed.live_until));
+        }
+
+        let mut entries = Vec::new();
+        for provenance in report.footprint {
+            let snapshot = snapshot_source.get(&Rc::new(provenance.key.clone()))?;
+            let snapshot = snapshot.map(|(entry, live_until)| (entry.as_ref().clone(), live_until));
+            let fed = fed
+                .get(&provenance.key)
+                .map(|(entry, live_until)| ((**entry).clone(), *live_until));
+
+            let origin = match (&snapshot, &fed) {
+                (None, None) => EntryOrigin::Missing,
+                (Some(_), None) => EntryOrigin::Removed,
+                (None, Some(_)) => EntryOrigin::Restored,
+                (Some((snapshot, _)), Some((fed, _))) if snapshot == fed => EntryOrigin::Unchanged,
+                (Some(_), Some(_)) if matches!(provenance.key, LedgerKey::ContractCode(_)) => {
+                    EntryOrigin::Replaced
+                }
+                (Some(_), Some(_)) => EntryOrigin::Reverted,
+            };
+
+            let (snapshot, snapshot_live_until) = snapshot.unzip();
+            let (fed, fed_live_until) = fed.unzip();
+            entries.push(StateEntry {
+                key: provenance.key,
+                source: provenance.source,
+                snapshot,
+                snapshot_live_until: snapshot_live_until.flatten(),
+                fed,
+                fed_live_until: fed_live_until.flatten(),
+                origin,
+            });
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,215 @@
+//! Pieces of an execution that outlive a single transaction.
+//!
+//! Every [`RetroshadesExecution::retroshade`] parses the wasm of the contracts
+//! it calls from scratch. A [`RetroshadeEngine`] keeps the parsed modules in
+//! caches shared by the executions it runs, together with the
+//! [`Interner`] for their packed names, so batch processors should construct
+//! one per ledger or per process and run every transaction through it.
+
+#[cfg(feature = "bench-internals")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{collections::HashMap, sync::Mutex};
+
+use sha2::{Digest, Sha256};
+use soroban_env_host::{
+    budget::{AsBudget, Budget},
+    xdr::LedgerEntryData,
+    CompilationContext, Error, ErrorHandler, HostError, ModuleCache, Val,
+};
+
+use crate::{
+    intern::Interner, RetroshadeError, RetroshadeExecutionResult, RetroshadeExecutionResultPretty,
+    RetroshadesExecution,
+};
+
+/// Context for parsing modules into the cache. The budget is unlimited as
+/// parsing happens outside of any transaction, and is created per execution
+/// since budgets can't be shared across threads.
+struct ParsingContext {
+    budget: Budget,
+}
+
+impl ParsingContext {
+    fn new() -> Result<Self, HostError> {
+        let budget = Budget::default();
+        budget.reset_unlimited()?;
+
+        Ok(Self { budget })
+    }
+}
+
+impl ErrorHandler for ParsingContext {
+    fn map_err<T, E>(&self, res: Result<T, E>) -> Result<T, HostError>
+    where
+        Error: From<E>,
+        E: std::fmt::Debug,
+    {
+        res.map_err(|error| HostError::from(Error::from(error)))
+    }
+
+    fn error(&self, error: Error, _msg: &str, _args: &[Val]) -> HostError {
+        HostError::from(error)
+    }
+}
+
+impl AsBudget for ParsingContext {
+    fn as_budget(&self) -> &Budget {
+        &self.budget
+    }
+}
+
+impl CompilationContext for ParsingContext {}
+
+/// Code entries whose binary was replaced, as their hash and the hash of the
+/// binary they hold, sorted.
+type Replacements = Vec<([u8; 32], [u8; 32])>;
+
+pub struct RetroshadeEngine {
+    /// One cache per set of replacements. The host looks modules up by the
+    /// hash of their code entry, a module parsed for an execution where the
+    /// entry holds its deployed code must not run in one where it's replaced.
+    module_caches: Mutex<HashMap<Replacements, ModuleCache>>,
+    interner: Interner,
+    #[cfg(feature = "bench-internals")]
+    counters: EngineCounters,
+}
+
+/// What the module cache saved, for benchmarks.
+#[cfg(feature = "bench-internals")]
+#[derive(Default)]
+struct EngineCounters {
+    modules_parsed: AtomicUsize,
+    module_cache_hits: AtomicUsize,
+}
+
+impl RetroshadeEngine {
+    pub fn new() -> Result<Self, RetroshadeError> {
+        Ok(Self {
+            module_caches: Mutex::new(HashMap::new()),
+            interner: Interner::new(),
+            #[cfg(feature = "bench-internals")]
+            counters: EngineCounters::default(),
+        })
+    }
+
+    /// Contracts parsed into the module cache so far.
+    #[cfg(feature = "bench-internals")]
+    pub fn modules_parsed(&self) -> usize {
+        self.counters.modules_parsed.load(Ordering::Relaxed)
+    }
+
+    /// Contracts executions found already parsed in the module cache.
+    #[cfg(feature = "bench-internals")]
+    pub fn module_cache_hits(&self) -> usize {
+        self.counters.module_cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Interner shared by the packed results of this engine.
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    /// Executes `retroshades`, parsing only the contracts this engine hasn't
+    /// seen yet.
+    ///
+    /// Only deployed code is cached: replaced binaries don't match the hash
+    /// they're deployed under and are parsed by the host on every execution,
+    /// as they would be without an engine. Executions replacing different
+    /// binaries don't share their cache.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn execute(
+        &self,
+        retroshades: &RetroshadesExecution,
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.execute_seeded(retroshades, crate::random_seed())
+    }
+
+    /// Same as [`Self::execute`] with `seed` for the host's PRNG.
+    pub fn execute_seeded(
+        &self,
+        retroshades: &RetroshadesExecution,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        let module_cache = self.cache_modules(retroshades)?;
+        retroshades.retroshade_with_module_cache(Some(module_cache), seed)
+    }
+
+    /// Executes and packs `retroshades`, interning the packed names with
+    /// [`Self::interner`] instead of the execution's own interner.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn execute_packed(
+        &self,
+        retroshades: &RetroshadesExecution,
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        self.execute_packed_seeded(retroshades, crate::random_seed())
+    }
+
+    /// Same as [`Self::execute_packed`] with `seed` for the host's PRNG.
+    pub fn execute_packed_seeded(
+        &self,
+        retroshades: &RetroshadesExecution,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        retroshades.pack_result_interned(self.execute_seeded(retroshades, seed)?, &self.interner)
+    }
+
+    /// Parses the deployed code of `retroshades` into the cache of its
+    /// replacements, returning that cache.
+    fn cache_modules(
+        &self,
+        retroshades: &RetroshadesExecution,
+    ) -> Result<ModuleCache, RetroshadeError> {
+        let protocol_version = retroshades.ledger_info.protocol_version;
+        let context = ParsingContext::new()?;
+
+        let mut deployed = Vec::new();
+        let mut replacements = Replacements::new();
+        for keyed in &retroshades.target_pre_execution_state {
+            let LedgerEntryData::ContractCode(code) = &keyed.entry.data else {
+                continue;
+            };
+            let hash: [u8; 32] = Sha256::digest(keyed.code().unwrap_or_default()).into();
+            if hash == code.hash.0 {
+                deployed.push(code);
+            } else {
+                replacements.push((code.hash.0, hash));
+            }
+        }
+        replacements.sort_unstable();
+
+        let module_cache = {
+            let mut module_caches = self
+                .module_caches
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            match module_caches.get(&replacements) {
+                Some(module_cache) => module_cache.clone(),
+                None => {
+                    let module_cache = ModuleCache::new(&context)?;
+                    module_caches.insert(replacements, module_cache.clone());
+                    module_cache
+                }
+            }
+        };
+
+        for code in deployed {
+            if module_cache.contains_module(&code.hash)? {
+                #[cfg(feature = "bench-internals")]
+                self.counters
+                    .module_cache_hits
+                    .fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            module_cache.parse_and_cache_module_simple(
+                &context,
+                protocol_version,
+                code.code.as_slice(),
+            )?;
+            #[cfg(feature = "bench-internals")]
+            self.counters.modules_parsed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(module_cache)
+    }
+}
@@ -0,0 +1,94 @@
+//! Resources a recording-mode execution needed, compared with the ones the
+//! original envelope declared. Replaced binaries usually cost more than the
+//! originals, this tells whether the declared budget still covers them.
+
+use serde::Serialize;
+use soroban_env_host::xdr::{LedgerKey, SorobanResources};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ResourceEstimate {
+    /// Resources recorded by the execution.
+    pub recorded: SorobanResources,
+    /// Resources declared by the original envelope.
+    pub declared: SorobanResources,
+    /// Budget consumption of the execution.
+    pub cpu_instructions: u64,
+    pub memory_bytes: u64,
+    pub call_succeeded: bool,
+}
+
+/// A recorded resource that the declared ones don't cover.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Shortfall {
+    Instructions {
+        declared: u32,
+        recorded: u32,
+    },
+    DiskReadBytes {
+        declared: u32,
+        recorded: u32,
+    },
+    WriteBytes {
+        declared: u32,
+        recorded: u32,
+    },
+    /// The key isn't in the declared footprint, or only as read-only while
+    /// the execution wrote it.
+    Footprint {
+        key: LedgerKey,
+        read_write: bool,
+    },
+}
+
+impl ResourceEstimate {
+    pub fn shortfalls(&self) -> Vec<Shortfall> {
+        let (declared, recorded) = (&self.declared, &self.recorded);
+        let mut shortfalls = Vec::new();
+
+        if recorded.instructions > declared.instructions {
+            shortfalls.push(Shortfall::Instructions {
+                declared: declared.instructions,
+                recorded: recorded.instructions,
+            });
+        }
+        if recorded.disk_read_bytes > declared.disk_read_bytes {
+            shortfalls.push(Shortfall::DiskReadBytes {
+                declared: declared.disk_read_bytes,
+                recorded: recorded.disk_read_bytes,
+            });
+        }
+        if recorded.write_bytes > declared.write_bytes {
+            shortfalls.push(Shortfall::WriteBytes {
+                declared: declared.write_bytes,
+                recorded: recorded.write_bytes,
+            });
+        }
+
+        let declared_footprint = &declared.footprint;
+        for key in recorded.footprint.read_only.iter() {
+            if !declared_footprint.read_only.contains(key)
+                && !declared_footprint.read_write.contains(key)
+            {
+                shortfalls.push(Shortfall::Footprint {
+                    key: key.clone(),
+                    read_write: false,
+                });
+            }
+        }
+        for key in recorded.footprint.read_write.iter() {
+            if !declared_footprint.read_write.contains(key) {
+                shortfalls.push(Shortfall::Footprint {
+                    key: key.clone(),
+                    read_write: true,
+                });
+            }
+        }
+
+        shortfalls
+    }
+
+    /// Whether the original envelope's resources would have been enough.
+    pub fn sufficient(&self) -> bool {
+        self.shortfalls().is_empty()
+    }
+}
@@ -0,0 +1,7 @@
+//! File exports of packed retroshades, for when there is no database.
+
+#[cfg(feature = "avro")]
+pub mod avro;
+pub mod csv;
+pub mod jsonl;
+pub mod sql;
@@ -0,0 +1,112 @@
+//! Avro schemas and datums for packed exports, so they can go through a
+//! schema registry. One record schema per target, named after it, with a field
+//! per event column:
+//!
+//! - numerics are strings, as 128 and 256 bit values don't fit Avro's longs.
+//! - `BYTEA` columns are bytes.
+//! - void values are a `["null", "string"]` union.
+//! - arrays whose items differ in type, e.g. text with some void items, have a
+//!   union of the item types.
+
+use apache_avro::{from_avro_datum, to_avro_datum, types::Value, Schema};
+use postgres_types::Type;
+use serde_json::json;
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    RetroshadeError, RetroshadeExportPretty,
+};
+
+/// Schema of the export's target as JSON, for registering it.
+pub fn schema_json(export: &RetroshadeExportPretty) -> serde_json::Value {
+    let fields: Vec<serde_json::Value> = export
+        .event
+        .iter()
+        .map(|entry| json!({ "name": entry.name, "type": field_type(&entry.value) }))
+        .collect();
+
+    json!({
+        "type": "record",
+        "name": export.target,
+        "fields": fields,
+    })
+}
+
+pub fn schema(export: &RetroshadeExportPretty) -> Result<Schema, RetroshadeError> {
+    Schema::parse(&schema_json(export)).map_err(avro_error)
+}
+
+/// Encodes the export as a single Avro datum, without container or registry framing.
+pub fn encode(
+    schema: &Schema,
+    export: &RetroshadeExportPretty,
+) -> Result<Vec<u8>, RetroshadeError> {
+    let record = Value::Record(
+        export
+            .event
+            .iter()
+            .map(|entry| Ok((entry.name.to_string(), value(&entry.value)?)))
+            .collect::<Result<_, RetroshadeError>>()?,
+    );
+
+    // note: resolving wraps values in the union variants of nullable fields.
+    let record = record.resolve(schema).map_err(avro_error)?;
+    to_avro_datum(schema, record).map_err(avro_error)
+}
+
+pub fn decode(schema: &Schema, mut datum: &[u8]) -> Result<Value, RetroshadeError> {
+    from_avro_datum(schema, &mut datum, None).map_err(avro_error)
+}
+
+fn field_type(value: &FromScVal) -> serde_json::Value {
+    match &value.kind {
+        TypeKind::GenericArray(items) => json!({ "type": "array", "items": items_type(items) }),
+        TypeKind::Text(_) if value.dbtype == Type::BYTEA => json!("bytes"),
+        TypeKind::Text(_) | TypeKind::Numeric(_) => json!("string"),
+        TypeKind::Boolean(_) => json!("boolean"),
+        TypeKind::Void => json!(["null", "string"]),
+    }
+}
+
+/// Type of all the `items`, the union of their types if they differ. Unions
+/// can't nest nor repeat a type, item unions are flattened into it.
+fn items_type(items: &[FromScVal]) -> serde_json::Value {
+    let mut types = Vec::new();
+    for item in items {
+        let item_types = match field_type(item) {
+            serde_json::Value::Array(union) => union,
+            single => vec![single],
+        };
+        for item_type in item_types {
+            if !types.contains(&item_type) {
+                types.push(item_type);
+            }
+        }
+    }
+
+    // note: null first, as in the union of void values.
+    types.sort_by_key(|item_type| *item_type != json!("null"));
+    match types.len() {
+        0 => json!("string"),
+        1 => types.remove(0),
+        _ => serde_json::Value::Array(types),
+    }
+}
+
+fn value(value: &FromScVal) -> Result<Value, RetroshadeError> {
+    Ok(match &value.kind {
+        TypeKind::GenericArray(items) => {
+            Value::Array(items.iter().map(self::value).collect::<Result<_, _>>()?)
+        }
+        TypeKind::Text(hex) if value.dbtype == Type::BYTEA => {
+            Value::Bytes(hex::decode(hex).map_err(|e| RetroshadeError::Export(e.to_string()))?)
+        }
+        TypeKind::Text(s) | TypeKind::Numeric(s) => Value::String(s.clone()),
+        TypeKind::Boolean(b) => Value::Boolean(*b),
+        TypeKind::Void => Value::Null,
+    })
+}
+
+fn avro_error(error: apache_avro::Error) -> RetroshadeError {
+    RetroshadeError::Export(error.to_string())
+}
@@ -0,0 +1,145 @@
+//! CSV export, one CSV per target.
+
+use std::io;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use postgres_types::Type;
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    RetroshadeError, RetroshadeExportPretty, TxContext,
+};
+
+/// How bytes values are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BytesEncoding {
+    /// Lowercase hex, as packed.
+    #[default]
+    Hex,
+
+    /// Standard base64, with padding.
+    Base64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub bytes_encoding: BytesEncoding,
+}
+
+/// Columns written before the event's own, taken from the export itself.
+pub const METADATA_COLUMNS: [&str; 6] = [
+    "contract_id",
+    "tx_hash",
+    "ledger_seq",
+    "closed_at",
+    "op_index",
+    "emit_index",
+];
+
+/// Writes the exports grouped by target, asking `writer_for` for a writer the
+/// first time each target is seen. The header is the metadata columns followed
+/// by the union of the target's event columns in the order they appear; rows
+/// lacking a column leave it empty.
+///
+/// Each export comes with the context it's delivered with, as for
+/// [`RetroshadeSink::deliver`](crate::sink::RetroshadeSink::deliver), which
+/// fills the transaction columns. Dumps of packed results pass the exports'
+/// own:
+///
+/// ```ignore
+/// csv::write(
+///     result.retroshades.iter().map(|export| (export, &export.context)),
+///     &CsvOptions::default(),
+///     |target| File::create(format!("{target}.csv")),
+/// )?;
+/// ```
+pub fn write<'a, W: io::Write>(
+    exports: impl IntoIterator<Item = (&'a RetroshadeExportPretty, &'a TxContext)>,
+    options: &CsvOptions,
+    mut writer_for: impl FnMut(&str) -> io::Result<W>,
+) -> Result<(), RetroshadeError> {
+    let mut targets: Vec<(&str, Vec<(&RetroshadeExportPretty, &TxContext)>)> = Vec::new();
+    for (export, ctx) in exports {
+        match targets
+            .iter_mut()
+            .find(|(target, _)| *target == export.target)
+        {
+            Some((_, rows)) => rows.push((export, ctx)),
+            None => targets.push((export.target.as_str(), vec![(export, ctx)])),
+        }
+    }
+
+    for (target, rows) in targets {
+        let mut header: Vec<&str> = METADATA_COLUMNS.to_vec();
+        for (row, _) in &rows {
+            for entry in &row.event {
+                // note: context columns added to the event duplicate the metadata.
+                if !header.contains(&entry.name.as_str()) {
+                    header.push(&entry.name);
+                }
+            }
+        }
+
+        let writer = writer_for(target).map_err(|e| RetroshadeError::Export(e.to_string()))?;
+        let mut csv = ::csv::Writer::from_writer(writer);
+        csv.write_record(&header)
+            .map_err(|e| RetroshadeError::Export(e.to_string()))?;
+
+        for (row, ctx) in rows {
+            let record = header.iter().map(|column| match *column {
+                "contract_id" => row.contract_id.to_string(),
+                "tx_hash" => hex::encode(ctx.tx_hash.0),
+                "ledger_seq" => ctx.ledger_seq.to_string(),
+                "closed_at" => ctx.closed_at.to_string(),
+                "op_index" => ctx.op_index.to_string(),
+                "emit_index" => row.emit_index.to_string(),
+                name => row
+                    .event
+                    .iter()
+                    .find(|entry| entry.name == name)
+                    .map(|entry| render(&entry.value, options))
+                    .unwrap_or_default(),
+            });
+            csv.write_record(record)
+                .map_err(|e| RetroshadeError::Export(e.to_string()))?;
+        }
+
+        csv.flush()
+            .map_err(|e| RetroshadeError::Export(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Arrays are rendered as JSON, void as an empty field.
+fn render(value: &FromScVal, options: &CsvOptions) -> String {
+    match &value.kind {
+        TypeKind::GenericArray(_) => json(value, options).to_string(),
+        TypeKind::Void => String::new(),
+        TypeKind::Text(hex) if value.dbtype == Type::BYTEA => bytes(hex, options),
+        other => other.to_string(),
+    }
+}
+
+/// Same as [`FromScVal::to_json`] with the bytes in `options`' encoding.
+fn json(value: &FromScVal, options: &CsvOptions) -> serde_json::Value {
+    match &value.kind {
+        TypeKind::GenericArray(items) => {
+            serde_json::Value::Array(items.iter().map(|item| json(item, options)).collect())
+        }
+        TypeKind::Text(hex) if value.dbtype == Type::BYTEA => {
+            serde_json::Value::String(bytes(hex, options))
+        }
+        _ => value.to_json(),
+    }
+}
+
+/// Bytes are packed as hex.
+fn bytes(hex: &str, options: &CsvOptions) -> String {
+    match options.bytes_encoding {
+        BytesEncoding::Hex => hex.to_string(),
+        BytesEncoding::Base64 => {
+            STANDARD.encode(hex::decode(hex).expect("bytes are packed as hex"))
+        }
+    }
+}
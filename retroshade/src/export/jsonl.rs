@@ -0,0 +1,101 @@
+//! JSON Lines export, one object per packed export:
+//!
+//! ```json
+//! {"target":"test","contract_id":"C...","context":{"tx_hash":"..","ledger_seq":1000,"closed_at":200,"op_index":0},"emit_index":0,"fields":{"amount":"2"}}
+//! ```
+//!
+//! Field values use [`FromScVal::to_json`](crate::conversion::FromScVal::to_json).
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{RetroshadeError, RetroshadeExportPretty, TxContext};
+
+#[derive(Serialize)]
+struct JsonlLine<'a> {
+    target: &'a str,
+    contract_id: &'a str,
+    context: &'a TxContext,
+    emit_index: u32,
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Streams exports to a writer, flushing it whenever a new ledger starts.
+pub struct JsonlSink<W: Write> {
+    writer: W,
+    ledger: Option<u32>,
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            ledger: None,
+        }
+    }
+
+    pub fn write(&mut self, export: &RetroshadeExportPretty) -> Result<(), RetroshadeError> {
+        self.write_with_context(export, &export.context)
+    }
+
+    /// Same as [`Self::write`] with `ctx` as the context, as delivered to
+    /// [`RetroshadeSink::deliver`](crate::sink::RetroshadeSink::deliver).
+    /// Context columns added to the event are taken from it too.
+    pub fn write_with_context(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), RetroshadeError> {
+        let ledger = ctx.ledger_seq;
+        if self.ledger.is_some_and(|current| current != ledger) {
+            self.flush()?;
+        }
+        self.ledger = Some(ledger);
+
+        let line = JsonlLine {
+            target: &export.target,
+            contract_id: &export.contract_id,
+            context: ctx,
+            emit_index: export.emit_index,
+            fields: export
+                .event
+                .iter()
+                .map(|entry| {
+                    let value =
+                        context_field(ctx, &entry.name).unwrap_or_else(|| entry.value.to_json());
+                    (entry.name.to_string(), value)
+                })
+                .collect(),
+        };
+
+        serde_json::to_writer(&mut self.writer, &line)
+            .map_err(|e| RetroshadeError::Export(e.to_string()))?;
+        self.writer.write_all(b"\n").map_err(export_error)
+    }
+
+    pub fn flush(&mut self) -> Result<(), RetroshadeError> {
+        self.writer.flush().map_err(export_error)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Value of the context column `name`, rendered as its packed column is.
+fn context_field(ctx: &TxContext, name: &str) -> Option<serde_json::Value> {
+    let value = match name {
+        "tx_hash" => hex::encode(ctx.tx_hash.0),
+        "ledger_seq" => ctx.ledger_seq.to_string(),
+        "closed_at" => ctx.closed_at.to_string(),
+        "op_index" => ctx.op_index.to_string(),
+        _ => return None,
+    };
+
+    Some(serde_json::Value::String(value))
+}
+
+fn export_error(error: io::Error) -> RetroshadeError {
+    RetroshadeError::Export(error.to_string())
+}
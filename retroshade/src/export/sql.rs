@@ -0,0 +1,213 @@
+//! Plain SQL export: a `CREATE TABLE IF NOT EXISTS` and an `INSERT` with the
+//! values inlined for each export, meant to be piped into `psql` for quick
+//! experiments. Writers binding parameters should use the driver integrations
+//! in [`crate::conversion`] instead.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+use postgres_types::Type;
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    naming::{TableName, TableNaming},
+    spec::ColumnType,
+    RetroshadeError, RetroshadeExportPretty,
+};
+
+/// Postgres column type of a converted value.
+pub fn column_type(dbtype: &Type) -> &'static str {
+    match *dbtype {
+        Type::BOOL => "boolean",
+        Type::NUMERIC => "numeric",
+        Type::BYTEA => "bytea",
+        Type::BOOL_ARRAY => "boolean[]",
+        Type::NUMERIC_ARRAY => "numeric[]",
+        Type::TEXT_ARRAY => "text[]",
+        _ => "text",
+    }
+}
+
+/// Escapes a string literal. Assumes `standard_conforming_strings`, the
+/// default since Postgres 9.1, so only quotes need doubling.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quoted identifier, double quotes in it are doubled. Targets and field
+/// names come from contracts, so every identifier goes through here.
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Quoted table name, qualified by its schema if any.
+pub fn quote_table(name: &TableName) -> String {
+    match &name.schema {
+        Some(schema) => format!("{}.{}", quote_ident(schema), quote_ident(&name.table)),
+        None => quote_ident(&name.table),
+    }
+}
+
+/// Inlined SQL literal for a converted value. Bytes are `\x` hex literals,
+/// numerics quoted and cast, arrays `ARRAY[...]` cast to their column type
+/// and void `NULL`.
+pub fn literal(value: &FromScVal) -> String {
+    match &value.kind {
+        TypeKind::GenericArray(items) => {
+            let items: Vec<String> = items.iter().map(literal).collect();
+            format!(
+                "ARRAY[{}]::{}",
+                items.join(", "),
+                column_type(&value.dbtype)
+            )
+        }
+        TypeKind::Text(hex) if value.dbtype == Type::BYTEA => quote(&format!("\\x{}", hex)),
+        TypeKind::Text(text) => quote(text),
+        TypeKind::Boolean(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        TypeKind::Void => "NULL".to_string(),
+        // note: numerics come from integers, but a decoded result could hold
+        // anything.
+        TypeKind::Numeric(n) => format!("{}::numeric", quote(n)),
+    }
+}
+
+fn columns(export: &RetroshadeExportPretty) -> Vec<String> {
+    export
+        .event
+        .iter()
+        .map(|entry| quote_ident(&entry.name))
+        .collect()
+}
+
+pub fn create_table(export: &RetroshadeExportPretty, naming: TableNaming) -> String {
+    let name = naming.table_name(export);
+    let definitions: Vec<String> = columns(export)
+        .into_iter()
+        .zip(&export.event)
+        .map(|(column, entry)| format!("{} {}", column, column_type(&entry.value.dbtype)))
+        .collect();
+
+    table_ddl(&name, &definitions)
+}
+
+/// `CREATE TABLE` for columns known before any export, e.g. from the
+/// contract spec. Columns that aren't nullable are `NOT NULL`.
+pub fn create_table_for_columns(name: &TableName, columns: &[(String, ColumnType)]) -> String {
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|(column, column_ty)| {
+            let not_null = if column_ty.nullable { "" } else { " NOT NULL" };
+            format!(
+                "{} {}{}",
+                quote_ident(column),
+                column_type(&column_ty.dbtype),
+                not_null
+            )
+        })
+        .collect();
+
+    table_ddl(name, &definitions)
+}
+
+fn table_ddl(name: &TableName, definitions: &[String]) -> String {
+    let table = format!(
+        "CREATE TABLE IF NOT EXISTS {} ({});",
+        quote_table(name),
+        definitions.join(", ")
+    );
+    match &name.schema {
+        Some(schema) => format!(
+            "CREATE SCHEMA IF NOT EXISTS {};\n{}",
+            quote_ident(schema),
+            table
+        ),
+        None => table,
+    }
+}
+
+/// Columns and [schema hashes](RetroshadeExportPretty::schema_hash) seen
+/// per table, to tell exports that need their table altered from exports of
+/// a schema the table already covers.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaHistory {
+    tables: HashMap<String, TableHistory>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct TableHistory {
+    hashes: HashSet<[u8; 32]>,
+    columns: HashSet<String>,
+}
+
+impl SchemaHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `ALTER TABLE`s adding the columns of `export` that its table lacks.
+    /// Exports of a schema seen before need none, neither does the first
+    /// export of a table, which [`create_table`] covers. Exports of an older
+    /// generation lacking some columns need none either, they insert `NULL`s.
+    ///
+    /// Columns changing type aren't altered, they're a new column for the
+    /// writer to decide on.
+    pub fn alter_table(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        naming: TableNaming,
+    ) -> Vec<String> {
+        let name = naming.table_name(export);
+        let hash = export.schema_hash();
+        let first = !self.tables.contains_key(&name.to_string());
+        let table = self.tables.entry(name.to_string()).or_default();
+        if !table.hashes.insert(hash) {
+            return vec![];
+        }
+
+        let mut statements = Vec::new();
+        for entry in &export.event {
+            if table.columns.insert(entry.name.to_string()) && !first {
+                statements.push(format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {};",
+                    quote_table(&name),
+                    quote_ident(&entry.name),
+                    column_type(&entry.value.dbtype)
+                ));
+            }
+        }
+
+        statements
+    }
+}
+
+pub fn insert(export: &RetroshadeExportPretty, naming: TableNaming) -> String {
+    let values: Vec<String> = export
+        .event
+        .iter()
+        .map(|entry| literal(&entry.value))
+        .collect();
+
+    format!(
+        "INSERT INTO {} ({}) VALUES ({});",
+        quote_table(&naming.table_name(export)),
+        columns(export).join(", "),
+        values.join(", ")
+    )
+}
+
+/// Writes the DDL and insert of each export, in order.
+pub fn write(
+    exports: &[RetroshadeExportPretty],
+    naming: TableNaming,
+    mut writer: impl io::Write,
+) -> Result<(), RetroshadeError> {
+    for export in exports {
+        writeln!(writer, "{}", create_table(export, naming))
+            .and_then(|_| writeln!(writer, "{}", insert(export, naming)))
+            .map_err(|e| RetroshadeError::Export(e.to_string()))?;
+    }
+
+    Ok(())
+}
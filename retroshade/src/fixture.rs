@@ -0,0 +1,170 @@
+//! Self-contained execution fixtures: the ledger entries (with their TTLs), the
+//! transaction envelope and the transaction meta, stored as JSON with base64
+//! XDR fields so they can be attached to bug reports and replayed in tests.
+//! Fixtures may also record the ledger settings to re-execute with.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use soroban_env_host::{
+    xdr::{LedgerEntry, Limits, ReadXdr, TransactionEnvelope, TransactionMeta, WriteXdr},
+    LedgerInfo,
+};
+
+use crate::{snapshot::MemorySnapshot, RetroshadeError};
+
+#[derive(Serialize, Deserialize)]
+struct FixtureEntry {
+    entry: String,
+    live_until: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureLedgerInfo {
+    protocol_version: u32,
+    sequence_number: u32,
+    timestamp: u64,
+    /// Hex encoded.
+    network_id: String,
+    base_reserve: u32,
+    min_temp_entry_ttl: u32,
+    min_persistent_entry_ttl: u32,
+    max_entry_ttl: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    entries: Vec<FixtureEntry>,
+    envelope: String,
+    meta: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ledger_info: Option<FixtureLedgerInfo>,
+}
+
+fn read(path: impl AsRef<Path>) -> Result<Fixture, RetroshadeError> {
+    let json = fs::read_to_string(path).map_err(|e| RetroshadeError::Fixture(e.to_string()))?;
+    parse(&json)
+}
+
+fn parse(json: &str) -> Result<Fixture, RetroshadeError> {
+    serde_json::from_str(json).map_err(|e| RetroshadeError::Fixture(e.to_string()))
+}
+
+pub fn dump(
+    path: impl AsRef<Path>,
+    entries: &[(LedgerEntry, Option<u32>)],
+    envelope: &TransactionEnvelope,
+    meta: &TransactionMeta,
+) -> Result<(), RetroshadeError> {
+    write(path, entries, envelope, meta, None)
+}
+
+/// Same as [`dump`], also recording the ledger settings to re-execute with.
+pub fn dump_with_ledger_info(
+    path: impl AsRef<Path>,
+    entries: &[(LedgerEntry, Option<u32>)],
+    envelope: &TransactionEnvelope,
+    meta: &TransactionMeta,
+    ledger_info: &LedgerInfo,
+) -> Result<(), RetroshadeError> {
+    write(path, entries, envelope, meta, Some(ledger_info))
+}
+
+fn write(
+    path: impl AsRef<Path>,
+    entries: &[(LedgerEntry, Option<u32>)],
+    envelope: &TransactionEnvelope,
+    meta: &TransactionMeta,
+    ledger_info: Option<&LedgerInfo>,
+) -> Result<(), RetroshadeError> {
+    let mut fixture_entries = Vec::new();
+    for (entry, live_until) in entries {
+        fixture_entries.push(FixtureEntry {
+            entry: entry
+                .to_xdr_base64(Limits::none())
+                .map_err(|_| RetroshadeError::MalformedXdr)?,
+            live_until: *live_until,
+        });
+    }
+
+    let fixture = Fixture {
+        entries: fixture_entries,
+        envelope: envelope
+            .to_xdr_base64(Limits::none())
+            .map_err(|_| RetroshadeError::MalformedXdr)?,
+        meta: meta
+            .to_xdr_base64(Limits::none())
+            .map_err(|_| RetroshadeError::MalformedXdr)?,
+        ledger_info: ledger_info.map(|ledger_info| FixtureLedgerInfo {
+            protocol_version: ledger_info.protocol_version,
+            sequence_number: ledger_info.sequence_number,
+            timestamp: ledger_info.timestamp,
+            network_id: hex::encode(ledger_info.network_id),
+            base_reserve: ledger_info.base_reserve,
+            min_temp_entry_ttl: ledger_info.min_temp_entry_ttl,
+            min_persistent_entry_ttl: ledger_info.min_persistent_entry_ttl,
+            max_entry_ttl: ledger_info.max_entry_ttl,
+        }),
+    };
+
+    let json = serde_json::to_string_pretty(&fixture)
+        .map_err(|e| RetroshadeError::Fixture(e.to_string()))?;
+    fs::write(path, json).map_err(|e| RetroshadeError::Fixture(e.to_string()))
+}
+
+pub fn load(
+    path: impl AsRef<Path>,
+) -> Result<(MemorySnapshot, TransactionEnvelope, TransactionMeta), RetroshadeError> {
+    decode(read(path)?)
+}
+
+/// Same as [`load`] for a fixture already read, e.g. pasted in a browser
+/// tool without filesystem access.
+pub fn load_str(
+    json: &str,
+) -> Result<(MemorySnapshot, TransactionEnvelope, TransactionMeta), RetroshadeError> {
+    decode(parse(json)?)
+}
+
+fn decode(
+    fixture: Fixture,
+) -> Result<(MemorySnapshot, TransactionEnvelope, TransactionMeta), RetroshadeError> {
+    let mut snapshot = MemorySnapshot::new();
+    for fixture_entry in fixture.entries {
+        let entry = LedgerEntry::from_xdr_base64(fixture_entry.entry, Limits::none())
+            .map_err(|_| RetroshadeError::MalformedXdr)?;
+        snapshot
+            .insert(entry, fixture_entry.live_until)
+            .map_err(RetroshadeError::from)?;
+    }
+
+    let envelope = TransactionEnvelope::from_xdr_base64(fixture.envelope, Limits::none())
+        .map_err(|_| RetroshadeError::MalformedXdr)?;
+    let meta = TransactionMeta::from_xdr_base64(fixture.meta, Limits::none())
+        .map_err(|_| RetroshadeError::MalformedXdr)?;
+
+    Ok((snapshot, envelope, meta))
+}
+
+/// The ledger settings recorded in the fixture, if any.
+pub fn load_ledger_info(path: impl AsRef<Path>) -> Result<Option<LedgerInfo>, RetroshadeError> {
+    let Some(ledger_info) = read(path)?.ledger_info else {
+        return Ok(None);
+    };
+
+    let network_id = hex::decode(&ledger_info.network_id)
+        .ok()
+        .and_then(|id| <[u8; 32]>::try_from(id).ok())
+        .ok_or_else(|| RetroshadeError::Fixture("network id isn't 32 hex bytes".to_string()))?;
+
+    Ok(Some(LedgerInfo {
+        protocol_version: ledger_info.protocol_version,
+        sequence_number: ledger_info.sequence_number,
+        timestamp: ledger_info.timestamp,
+        network_id,
+        base_reserve: ledger_info.base_reserve,
+        min_temp_entry_ttl: ledger_info.min_temp_entry_ttl,
+        min_persistent_entry_ttl: ledger_info.min_persistent_entry_ttl,
+        max_entry_ttl: ledger_info.max_entry_ttl,
+    }))
+}
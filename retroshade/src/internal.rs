@@ -1,29 +1,34 @@
 use std::rc::Rc;
 
-use sha2::{Digest, Sha256};
 use soroban_env_host::{
     budget::Budget,
     e2e_invoke::{
-        invoke_host_function, invoke_host_function_in_recording_mode, ledger_entry_to_ledger_key,
-        LedgerEntryChange, LedgerEntryLiveUntilChange, RecordingInvocationAuthMode,
-        RecordingInvocationAuthParams,
+        invoke_host_function, invoke_host_function_in_recording_mode, LedgerEntryChange,
+        LedgerEntryLiveUntilChange, RecordingInvocationAuthMode, RecordingInvocationAuthParams,
     },
     storage::SnapshotSource,
     xdr::{
-        AccountId, ContractDataDurability, ContractEvent, DiagnosticEvent, HostFunction,
-        LedgerEntry, LedgerEntryData, LedgerKey, LedgerKeyContractCode, LedgerKeyContractData,
-        Limits, ReadXdr, ScVal, SorobanAuthorizationEntry, SorobanResources, TtlEntry, WriteXdr,
+        AccountId, ContractEvent, DiagnosticEvent, Hash, HostFunction, LedgerEntry,
+        LedgerEntryData, LedgerKey, Limits, ReadXdr, ScVal, SorobanAuthorizationEntry,
+        SorobanResources, TtlEntry, WriteXdr,
     },
     zephyr::RetroshadeExport,
-    HostError, LedgerInfo,
+    HostError, LedgerInfo, ModuleCache,
 };
 
+use crate::{
+    changes::{EntryChange, EntryTtlChange},
+    state::KeyedEntry,
+};
+
+/// A ledger change as the host reports it, with the key and new value still
+/// encoded. Callers only after the retroshades never pay for decoding them.
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct LedgerEntryChangeHelper {
+pub struct LedgerEntryChangeHelper {
     read_only: bool,
-    key: LedgerKey,
+    encoded_key: Vec<u8>,
     old_entry_size_bytes: u32,
-    new_value: Option<LedgerEntry>,
+    encoded_new_value: Option<Vec<u8>>,
     ttl_change: Option<LedgerEntryLiveUntilChange>,
 }
 
@@ -31,36 +36,36 @@ impl From<LedgerEntryChange> for LedgerEntryChangeHelper {
     fn from(c: LedgerEntryChange) -> Self {
         Self {
             read_only: c.read_only,
-            key: LedgerKey::from_xdr(c.encoded_key, Limits::none()).unwrap(),
+            encoded_key: c.encoded_key,
             old_entry_size_bytes: c.old_entry_size_bytes_for_rent,
-            new_value: c
-                .encoded_new_value
-                .map(|v| LedgerEntry::from_xdr(v, Limits::none()).unwrap()),
+            encoded_new_value: c.encoded_new_value,
             ttl_change: c.ttl_change,
         }
     }
 }
 
 impl LedgerEntryChangeHelper {
-    fn no_op_change(entry: &LedgerEntry, live_until_ledger: u32) -> Self {
-        let ledger_key = ledger_entry_to_ledger_key(entry, &Budget::default()).unwrap();
-        let ledger_entry_type = ledger_key.discriminant();
-        let durability = match &ledger_key {
-            LedgerKey::ContractData(cd) => Some(cd.durability),
-            LedgerKey::ContractCode(_) => Some(ContractDataDurability::Persistent),
-            _ => None,
-        };
+    pub fn key(&self) -> LedgerKey {
+        LedgerKey::from_xdr(&self.encoded_key, Limits::none()).unwrap()
+    }
+
+    pub fn new_value(&self) -> Option<LedgerEntry> {
+        self.encoded_new_value
+            .as_ref()
+            .map(|v| LedgerEntry::from_xdr(v, Limits::none()).unwrap())
+    }
+}
+
+impl From<LedgerEntryChangeHelper> for EntryChange {
+    fn from(c: LedgerEntryChangeHelper) -> Self {
         Self {
-            read_only: true,
-            key: ledger_key.clone(),
-            old_entry_size_bytes: entry.to_xdr(Limits::none()).unwrap().len() as u32,
-            new_value: None,
-            ttl_change: durability.map(|durability| LedgerEntryLiveUntilChange {
-                entry_type: ledger_entry_type,
-                key_hash: compute_key_hash(&ledger_key),
-                durability,
-                old_live_until_ledger: live_until_ledger,
-                new_live_until_ledger: live_until_ledger,
+            read_only: c.read_only,
+            key: c.key(),
+            old_entry_size_bytes: c.old_entry_size_bytes,
+            new_value: c.new_value(),
+            ttl_change: c.ttl_change.map(|ttl| EntryTtlChange {
+                old_live_until_ledger: ttl.old_live_until_ledger,
+                new_live_until_ledger: ttl.new_live_until_ledger,
             }),
         }
     }
@@ -74,21 +79,39 @@ pub struct InvokeHostFunctionHelperResult {
     pub diagnostic_events: Vec<DiagnosticEvent>,
     pub retroshades: Vec<RetroshadeExport>,
     pub budget: Budget,
+    /// Resources recorded by a recording-mode execution, `None` when enforcing.
+    pub recorded_resources: Option<SorobanResources>,
 }
 
-fn compute_key_hash(key: &LedgerKey) -> Vec<u8> {
-    let key_xdr = key.to_xdr(Limits::none()).unwrap();
-    let hash: [u8; 32] = Sha256::digest(&key_xdr).into();
-    hash.to_vec()
+/// A failed host invocation along with the diagnostic events collected before the failure.
+#[derive(Debug)]
+pub struct InvokeHostFunctionFailure {
+    pub error: HostError,
+    pub diagnostic_events: Vec<DiagnosticEvent>,
+    /// Memory the budget counted up to the failure.
+    pub memory_bytes: u64,
 }
 
-fn ttl_entry(key: &LedgerKey, ttl: u32) -> TtlEntry {
-    TtlEntry {
-        key_hash: compute_key_hash(key).try_into().unwrap(),
-        live_until_ledger_seq: ttl,
+impl From<HostError> for InvokeHostFunctionFailure {
+    fn from(error: HostError) -> Self {
+        Self {
+            error,
+            diagnostic_events: vec![],
+            memory_bytes: 0,
+        }
     }
 }
 
+/// A budget with unlimited CPU and `max_memory_bytes` of memory. The host
+/// charges its allocations and the growth of wasm linear memory to it, so
+/// going over fails the invocation.
+fn sandbox_budget(max_memory_bytes: u64) -> Result<Budget, HostError> {
+    let budget = Budget::default();
+    budget.reset_limits(u64::MAX, max_memory_bytes)?;
+
+    Ok(budget)
+}
+
 pub fn execute_svm_in_recording_mode(
     enable_diagnostics: bool,
     host_fn: &HostFunction,
@@ -96,14 +119,13 @@ pub fn execute_svm_in_recording_mode(
     ledger_info: LedgerInfo,
     prng_seed: [u8; 32],
     ledger_snapshot: Rc<dyn SnapshotSource>,
-) -> Result<InvokeHostFunctionHelperResult, HostError> {
+    max_memory_bytes: u64,
+) -> Result<InvokeHostFunctionHelperResult, InvokeHostFunctionFailure> {
     let limits = Limits::none();
     let encoded_host_fn = host_fn.to_xdr(limits.clone()).unwrap();
     let encoded_source_account = source_account.to_xdr(limits.clone()).unwrap();
 
-    let budget = Budget::default();
-
-    budget.reset_unlimited()?;
+    let budget = sandbox_budget(max_memory_bytes)?;
 
     let mut diagnostic_events = Vec::<DiagnosticEvent>::new();
     let res = invoke_host_function_in_recording_mode(
@@ -118,7 +140,12 @@ pub fn execute_svm_in_recording_mode(
         ledger_snapshot,
         prng_seed,
         &mut diagnostic_events,
-    )?;
+    )
+    .map_err(|error| InvokeHostFunctionFailure {
+        error,
+        diagnostic_events: diagnostic_events.clone(),
+        memory_bytes: budget.get_mem_bytes_consumed().unwrap_or_default(),
+    })?;
 
     Ok(InvokeHostFunctionHelperResult {
         invoke_result: res.invoke_result,
@@ -127,78 +154,136 @@ pub fn execute_svm_in_recording_mode(
         diagnostic_events,
         budget,
         retroshades: res.retroshades,
+        recorded_resources: Some(res.resources),
     })
 }
 
-pub fn execute_svm(
-    enable_diagnostics: bool,
-    host_fn: &HostFunction,
-    resources: &SorobanResources,
-    source_account: &AccountId,
-    auth_entries: Vec<SorobanAuthorizationEntry>,
-    ledger_info: &LedgerInfo,
-    ledger_entries_with_ttl: Vec<(LedgerEntry, Option<u32>)>,
-    prng_seed: &[u8; 32],
-) -> Result<InvokeHostFunctionHelperResult, HostError> {
-    let limits = Limits::none();
-    let encoded_host_fn = host_fn.to_xdr(limits.clone()).unwrap();
-    let encoded_resources = resources.to_xdr(limits.clone()).unwrap();
-    let encoded_source_account = source_account.to_xdr(limits.clone()).unwrap();
-    let encoded_auth_entries: Vec<Vec<u8>> = auth_entries
-        .iter()
-        .map(|e| e.to_xdr(limits.clone()).unwrap())
-        .collect();
-    let encoded_ledger_entries: Vec<Vec<u8>> = ledger_entries_with_ttl
-        .iter()
-        .map(|e| e.0.to_xdr(limits.clone()).unwrap())
-        .collect();
-    let encoded_ttl_entries: Vec<Vec<u8>> = ledger_entries_with_ttl
-        .iter()
-        .map(|e| {
-            let (le, ttl) = e;
-            let key = match &le.data {
-                LedgerEntryData::ContractData(cd) => {
-                    LedgerKey::ContractData(LedgerKeyContractData {
-                        contract: cd.contract.clone(),
-                        key: cd.key.clone(),
-                        durability: cd.durability,
-                    })
-                }
-                LedgerEntryData::ContractCode(code) => {
-                    LedgerKey::ContractCode(LedgerKeyContractCode {
-                        hash: code.hash.clone(),
-                    })
-                }
-                _ => {
+/// XDR of everything the host is fed, encoded once per built state and reused
+/// by every execution of it.
+#[derive(Debug)]
+pub struct EncodedInputs {
+    host_fn: Vec<u8>,
+    resources: Vec<u8>,
+    source_account: Vec<u8>,
+    auth_entries: Vec<Vec<u8>>,
+    ledger_entries: Vec<Vec<u8>>,
+    ttl_entries: Vec<Vec<u8>>,
+    /// Serialized size of the contract code entries among the ledger ones.
+    code_bytes: usize,
+}
+
+impl EncodedInputs {
+    pub fn encode(
+        host_fn: &HostFunction,
+        resources: &SorobanResources,
+        source_account: &AccountId,
+        auth_entries: &[SorobanAuthorizationEntry],
+        ledger_entries_with_ttl: &[KeyedEntry],
+    ) -> Self {
+        let limits = Limits::none();
+        let encoded_ttl_entries = ledger_entries_with_ttl
+            .iter()
+            .map(|keyed| {
+                let (
+                    LedgerEntryData::ContractData(_) | LedgerEntryData::ContractCode(_),
+                    Some(key),
+                ) = (&keyed.entry.data, &keyed.key)
+                else {
                     return vec![];
+                };
+                TtlEntry {
+                    key_hash: Hash(key.hash),
+                    live_until_ledger_seq: keyed.live_until.unwrap(),
                 }
-            };
-            ttl_entry(&key, ttl.unwrap())
                 .to_xdr(limits.clone())
                 .unwrap()
-        })
-        .collect();
-    let budget = Budget::default();
+            })
+            .collect();
+
+        let ledger_entries: Vec<Vec<u8>> = ledger_entries_with_ttl
+            .iter()
+            .map(KeyedEntry::to_xdr)
+            .collect();
+        let code_bytes = ledger_entries_with_ttl
+            .iter()
+            .zip(&ledger_entries)
+            .filter(|(keyed, _)| matches!(keyed.entry.data, LedgerEntryData::ContractCode(_)))
+            .map(|(_, encoded)| encoded.len())
+            .sum();
+
+        Self {
+            host_fn: host_fn.to_xdr(limits.clone()).unwrap(),
+            resources: resources.to_xdr(limits.clone()).unwrap(),
+            source_account: source_account.to_xdr(limits.clone()).unwrap(),
+            auth_entries: auth_entries
+                .iter()
+                .map(|e| e.to_xdr(limits.clone()).unwrap())
+                .collect(),
+            ledger_entries,
+            ttl_entries: encoded_ttl_entries,
+            code_bytes,
+        }
+    }
+
+    /// Replaces the resources the host is fed.
+    pub fn set_resources(&mut self, resources: &SorobanResources) {
+        self.resources = resources.to_xdr(Limits::none()).unwrap();
+    }
+
+    /// Serialized size of the ledger entries and their TTLs.
+    pub fn state_bytes(&self) -> usize {
+        self.ledger_entries
+            .iter()
+            .chain(&self.ttl_entries)
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Serialized size of the ledger entries, what reading them costs.
+    pub fn entry_bytes(&self) -> usize {
+        self.ledger_entries.iter().map(Vec::len).sum()
+    }
 
-    budget.reset_unlimited()?;
+    /// Serialized size of the contract code entries, replaced binaries
+    /// included.
+    pub fn code_bytes(&self) -> usize {
+        self.code_bytes
+    }
+}
+
+pub fn execute_svm(
+    enable_diagnostics: bool,
+    inputs: &EncodedInputs,
+    ledger_info: &LedgerInfo,
+    prng_seed: &[u8; 32],
+    module_cache: Option<ModuleCache>,
+    max_memory_bytes: u64,
+) -> Result<InvokeHostFunctionHelperResult, InvokeHostFunctionFailure> {
+    let limits = Limits::none();
+    let budget = sandbox_budget(max_memory_bytes)?;
 
     let mut diagnostic_events = Vec::<DiagnosticEvent>::new();
     let res = invoke_host_function(
         &budget,
         enable_diagnostics,
-        encoded_host_fn,
-        encoded_resources,
+        inputs.host_fn.as_slice(),
+        inputs.resources.as_slice(),
         &[],
-        encoded_source_account,
-        encoded_auth_entries.into_iter(),
+        inputs.source_account.as_slice(),
+        inputs.auth_entries.iter().map(Vec::as_slice),
         ledger_info.clone(),
-        encoded_ledger_entries.into_iter(),
-        encoded_ttl_entries.into_iter(),
-        prng_seed.to_vec(),
+        inputs.ledger_entries.iter().map(Vec::as_slice),
+        inputs.ttl_entries.iter().map(Vec::as_slice),
+        prng_seed.as_slice(),
         &mut diagnostic_events,
         None,
-        None,
-    )?;
+        module_cache,
+    )
+    .map_err(|error| InvokeHostFunctionFailure {
+        error,
+        diagnostic_events: diagnostic_events.clone(),
+        memory_bytes: budget.get_mem_bytes_consumed().unwrap_or_default(),
+    })?;
 
     Ok(InvokeHostFunctionHelperResult {
         invoke_result: res
@@ -213,38 +298,6 @@ pub fn execute_svm(
         diagnostic_events,
         budget,
         retroshades: res.retroshades,
+        recorded_resources: None,
     })
 }
-
-#[cfg(test)]
-mod test {
-    use soroban_env_host::{
-        xdr::{AccountId, PublicKey, Uint256},
-        LedgerInfo,
-    };
-
-    use super::execute_svm;
-
-    #[test]
-    fn execute_mainnet() {
-        let mut ledger_info = LedgerInfo::default();
-        ledger_info.protocol_version = 25;
-
-        let execution = execute_svm(
-            true,
-            &serde_json::from_str(
-                r#"{"invoke_contract":{"contract_address":"CB6WUNOICTMDMEBS7E7AGC3MEN43UA53QT4OT355F22VWMLOUJWWKMHH","function_name":"t","args":[]}}"#,
-            ).unwrap(),
-            &serde_json::from_str(
-                r#"{"footprint":{"read_only":[{"contract_data":{"contract":"CB6WUNOICTMDMEBS7E7AGC3MEN43UA53QT4OT355F22VWMLOUJWWKMHH","key":"ledger_key_contract_instance","durability":"persistent"}},{"contract_code":{"hash":"5bf30f4ebf6e399a0f6cf8c7d134f2e6741ab78455aa6bcb20e3dc01261ea5e3"}}],"read_write":[]},"instructions":492586,"read_bytes":864,"write_bytes":80000,"disk_read_bytes":864}"#,
-            ).unwrap(),
-            &AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([0;32]))),
-            vec![],
-            &ledger_info,
-            serde_json::from_str(r#"[[{"last_modified_ledger_seq":1470890,"data":{"contract_data":{"ext":"v0","contract":"CB6WUNOICTMDMEBS7E7AGC3MEN43UA53QT4OT355F22VWMLOUJWWKMHH","key":"ledger_key_contract_instance","durability":"persistent","val":{"contract_instance":{"executable":{"wasm":"5bf30f4ebf6e399a0f6cf8c7d134f2e6741ab78455aa6bcb20e3dc01261ea5e3"},"storage":null}}}},"ext":"v0"},3544489],[{"last_modified_ledger_seq":1470885,"data":{"contract_code":{"ext":{"v1":{"ext":"v0","cost_inputs":{"ext":"v0","n_instructions":3,"n_functions":2,"n_globals":3,"n_table_entries":0,"n_types":2,"n_data_segments":0,"n_elem_segments":0,"n_imports":0,"n_exports":5,"n_data_segment_bytes":0}}},"hash":"5bf30f4ebf6e399a0f6cf8c7d134f2e6741ab78455aa6bcb20e3dc01261ea5e3","code":"0061736d010000000115046000017e60037e7e7e017e60027e7e017e600000021303017801370000016d01390001017801390002030302000305030100110619037f01418080c0000b7f00418c80c0000b7f00419080c0000b072d05066d656d6f7279020001740003015f00040a5f5f646174615f656e6403010b5f5f686561705f6261736503020a64025f01017f23808080800041106b22002480808080002000108080808000370308428ef2b8b50e418480c08000ad422086420484200041086aad4220864204844284808080101081808080001082808080001a200041106a24808080800042020b02000b0b150100418080c0000b0c74657374000010000400000000630e636f6e747261637473706563763000000001000000000000000000000000f46697273745265747269736861646500000000010000000000000004746573740000001300000000000000000000000174000000000000000000000100000365000000000020e636f6e7472616374656e766d6574617630000000000000001500000000006f0e636f6e74726163746d65746176300000000000000005727376657200000000000006312e38302e3100000000000000000008727373646b766572000000002f32312e342e30236436663536333966363433643736653735386265656362623063613339316638636433303463323400"}},"ext":"v0"},3544484]]"#).unwrap(),
-            &[0;32],
-        );
-
-        println!("{:?}", execution)
-    }
-}
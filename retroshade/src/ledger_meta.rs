@@ -0,0 +1,221 @@
+//! Reads `LedgerCloseMeta` streams, as written by captive core's metadata pipe
+//! or exported to files: each ledger is an XDR record prefixed with its
+//! 4 bytes big-endian length, the high bit marking the last fragment of the
+//! record.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    GeneralizedTransactionSet, Hash, LedgerCloseMeta, LedgerHeader, Limits, MuxedAccount,
+    Preconditions, ReadXdr, Transaction, TransactionEnvelope, TransactionExt, TransactionMeta,
+    TransactionPhase, TransactionSignaturePayload, TransactionSignaturePayloadTaggedTransaction,
+    TransactionV0, TxSetComponent, WriteXdr,
+};
+
+use crate::RetroshadeError;
+
+const LAST_FRAGMENT: u32 = 0x8000_0000;
+
+fn meta_error(reason: impl ToString) -> RetroshadeError {
+    RetroshadeError::LedgerMetaFile(reason.to_string())
+}
+
+/// Reads the next record, `None` at the end of the stream.
+pub fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>, RetroshadeError> {
+    let mut record = Vec::new();
+
+    loop {
+        let mut header = [0; 4];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof && record.is_empty() => {
+                return Ok(None)
+            }
+            Err(error) => return Err(meta_error(error)),
+        }
+
+        let header = u32::from_be_bytes(header);
+        let start = record.len();
+        record.resize(start + (header & !LAST_FRAGMENT) as usize, 0);
+        reader
+            .read_exact(&mut record[start..])
+            .map_err(|error| meta_error(format!("truncated record: {}", error)))?;
+
+        if header & LAST_FRAGMENT != 0 {
+            return Ok(Some(record));
+        }
+    }
+}
+
+/// Writes `record` as a single fragment.
+pub fn write_frame(writer: &mut impl Write, record: &[u8]) -> Result<(), RetroshadeError> {
+    let len = u32::try_from(record.len())
+        .ok()
+        .filter(|len| len & LAST_FRAGMENT == 0)
+        .ok_or_else(|| meta_error("record too long"))?;
+
+    writer
+        .write_all(&(len | LAST_FRAGMENT).to_be_bytes())
+        .and_then(|_| writer.write_all(record))
+        .map_err(meta_error)
+}
+
+/// Iterates the ledgers of a framed stream.
+pub struct LedgerMetaReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> LedgerMetaReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> Iterator for LedgerMetaReader<R> {
+    type Item = Result<LedgerCloseMeta, RetroshadeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match read_frame(&mut self.reader) {
+            Ok(record) => record?,
+            Err(error) => return Some(Err(error)),
+        };
+
+        Some(
+            LedgerCloseMeta::from_xdr(record, Limits::none())
+                .map_err(|error| meta_error(format!("not a ledger close meta: {}", error))),
+        )
+    }
+}
+
+pub fn ledger_header(meta: &LedgerCloseMeta) -> &LedgerHeader {
+    match meta {
+        LedgerCloseMeta::V0(v0) => &v0.ledger_header.header,
+        LedgerCloseMeta::V1(v1) => &v1.ledger_header.header,
+        LedgerCloseMeta::V2(v2) => &v2.ledger_header.header,
+    }
+}
+
+/// The transaction core hashes for a v0 envelope.
+fn v0_as_v1(tx: &TransactionV0) -> Transaction {
+    Transaction {
+        source_account: MuxedAccount::Ed25519(tx.source_account_ed25519.clone()),
+        fee: tx.fee,
+        seq_num: tx.seq_num.clone(),
+        cond: tx
+            .time_bounds
+            .clone()
+            .map_or(Preconditions::None, Preconditions::Time),
+        memo: tx.memo.clone(),
+        operations: tx.operations.clone(),
+        ext: TransactionExt::V0,
+    }
+}
+
+/// Hash of the envelope's transaction on the network with id `network_id`,
+/// the outer hash for fee bumps.
+pub fn envelope_hash(
+    envelope: &TransactionEnvelope,
+    network_id: [u8; 32],
+) -> Result<Hash, RetroshadeError> {
+    let tagged_transaction = match envelope {
+        TransactionEnvelope::TxV0(v0) => {
+            TransactionSignaturePayloadTaggedTransaction::Tx(v0_as_v1(&v0.tx))
+        }
+        TransactionEnvelope::Tx(v1) => {
+            TransactionSignaturePayloadTaggedTransaction::Tx(v1.tx.clone())
+        }
+        TransactionEnvelope::TxFeeBump(fee_bump) => {
+            TransactionSignaturePayloadTaggedTransaction::TxFeeBump(fee_bump.tx.clone())
+        }
+    };
+    let payload = TransactionSignaturePayload {
+        network_id: Hash(network_id),
+        tagged_transaction,
+    }
+    .to_xdr(Limits::none())
+    .map_err(|_| RetroshadeError::MalformedXdr)?;
+
+    Ok(Hash(Sha256::digest(payload).into()))
+}
+
+fn generalized_envelopes(tx_set: &GeneralizedTransactionSet) -> Vec<&TransactionEnvelope> {
+    let GeneralizedTransactionSet::V1(tx_set) = tx_set;
+
+    let mut envelopes = Vec::new();
+    for phase in tx_set.phases.iter() {
+        match phase {
+            TransactionPhase::V0(components) => {
+                for component in components.iter() {
+                    let TxSetComponent::TxsetCompTxsMaybeDiscountedFee(component) = component;
+                    envelopes.extend(component.txs.iter());
+                }
+            }
+            TransactionPhase::V1(parallel) => {
+                for stage in parallel.execution_stages.iter() {
+                    for cluster in stage.0.iter() {
+                        envelopes.extend(cluster.0.iter());
+                    }
+                }
+            }
+        }
+    }
+
+    envelopes
+}
+
+/// The ledger's transactions with their meta, in application order. The tx
+/// set isn't in that order, so envelopes are matched to their results by
+/// hash, which needs the network id.
+pub fn transactions(
+    meta: &LedgerCloseMeta,
+    network_id: [u8; 32],
+) -> Result<Vec<(TransactionEnvelope, TransactionMeta)>, RetroshadeError> {
+    let (envelopes, applied): (Vec<&TransactionEnvelope>, Vec<(&Hash, &TransactionMeta)>) =
+        match meta {
+            LedgerCloseMeta::V0(v0) => (
+                v0.tx_set.txs.iter().collect(),
+                v0.tx_processing
+                    .iter()
+                    .map(|tx| (&tx.result.transaction_hash, &tx.tx_apply_processing))
+                    .collect(),
+            ),
+            LedgerCloseMeta::V1(v1) => (
+                generalized_envelopes(&v1.tx_set),
+                v1.tx_processing
+                    .iter()
+                    .map(|tx| (&tx.result.transaction_hash, &tx.tx_apply_processing))
+                    .collect(),
+            ),
+            LedgerCloseMeta::V2(v2) => (
+                generalized_envelopes(&v2.tx_set),
+                v2.tx_processing
+                    .iter()
+                    .map(|tx| (&tx.result.transaction_hash, &tx.tx_apply_processing))
+                    .collect(),
+            ),
+        };
+
+    let mut by_hash = HashMap::new();
+    for envelope in envelopes {
+        by_hash.insert(envelope_hash(envelope, network_id)?, envelope);
+    }
+
+    applied
+        .into_iter()
+        .map(|(hash, tx_meta)| {
+            let envelope = by_hash.get(hash).ok_or_else(|| {
+                meta_error(format!(
+                    "no envelope in ledger {} hashes to {}, is the network right?",
+                    ledger_header(meta).ledger_seq,
+                    hex::encode(hash.0)
+                ))
+            })?;
+
+            Ok(((*envelope).clone(), tx_meta.clone()))
+        })
+        .collect()
+}
@@ -0,0 +1,96 @@
+//! Snapshot source backed by the JSON ledger snapshots produced by
+//! `stellar snapshot create` (soroban-cli).
+
+use std::{collections::HashMap, fs, path::Path, rc::Rc};
+
+use serde::Deserialize;
+use soroban_env_host::{
+    storage::{EntryWithLiveUntil, SnapshotSource},
+    xdr::{LedgerEntry, LedgerKey},
+    HostError, LedgerInfo,
+};
+
+use crate::{snapshot::SnapshotSourceExt, RetroshadeError};
+
+#[derive(Deserialize)]
+struct LedgerSnapshotFile {
+    protocol_version: u32,
+    sequence_number: u32,
+    timestamp: u64,
+    network_id: String,
+    base_reserve: u32,
+    min_persistent_entry_ttl: u32,
+    min_temp_entry_ttl: u32,
+    max_entry_ttl: u32,
+    ledger_entries: Vec<(LedgerKey, (LedgerEntry, Option<u32>))>,
+}
+
+pub struct LedgerSnapshotFileSource {
+    ledger_info: LedgerInfo,
+    entries: HashMap<LedgerKey, EntryWithLiveUntil>,
+}
+
+impl LedgerSnapshotFileSource {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RetroshadeError> {
+        let json =
+            fs::read_to_string(path).map_err(|e| RetroshadeError::SnapshotFile(e.to_string()))?;
+        let file: LedgerSnapshotFile = serde_json::from_str(&json)
+            .map_err(|e| RetroshadeError::SnapshotFile(e.to_string()))?;
+
+        let network_id = hex::decode(&file.network_id)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| {
+                RetroshadeError::SnapshotFile(format!("invalid network id {}", file.network_id))
+            })?;
+
+        let entries = file
+            .ledger_entries
+            .into_iter()
+            .map(|(key, (entry, live_until))| (key, (Rc::new(entry), live_until)))
+            .collect();
+
+        Ok(Self {
+            ledger_info: LedgerInfo {
+                protocol_version: file.protocol_version,
+                sequence_number: file.sequence_number,
+                timestamp: file.timestamp,
+                network_id,
+                base_reserve: file.base_reserve,
+                min_temp_entry_ttl: file.min_temp_entry_ttl,
+                min_persistent_entry_ttl: file.min_persistent_entry_ttl,
+                max_entry_ttl: file.max_entry_ttl,
+            },
+            entries,
+        })
+    }
+
+    pub fn sequence_number(&self) -> u32 {
+        self.ledger_info.sequence_number
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.ledger_info.timestamp
+    }
+
+    /// Ledger info matching the ledger the snapshot was captured at.
+    pub fn ledger_info(&self) -> LedgerInfo {
+        self.ledger_info.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl SnapshotSource for LedgerSnapshotFileSource {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        Ok(self.entries.get(key.as_ref()).cloned())
+    }
+}
+
+impl SnapshotSourceExt for LedgerSnapshotFileSource {}
@@ -1,29 +1,90 @@
-use std::{collections::HashMap, rc::Rc};
+#[cfg(any(test, feature = "bench-internals"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::RangeInclusive,
+    rc::Rc,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+// note: std's Instant panics on wasm32-unknown-unknown.
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
-use conversion::FromScVal;
-use internal::{execute_svm, execute_svm_in_recording_mode};
-use snapshot::InternalSnapshot;
+use changes::{EntryChange, Mismatch};
+use compare::{Comparison, ExecutionDiff};
+pub use compat::{version, VersionInfo};
+use conversion::{AddressFormat, ConversionOptions, FromScVal, TypeKind};
+use diagnostics::DisplayDiagnostics;
+use estimate::ResourceEstimate;
+use intern::{Interner, SharedStr};
+use internal::{
+    execute_svm, execute_svm_in_recording_mode, EncodedInputs, InvokeHostFunctionFailure,
+    InvokeHostFunctionHelperResult,
+};
+use postgres_types::Type;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use snapshot::{InternalSnapshot, LabeledRef, SharedSnapshot, SnapshotSourceExt};
 pub use soroban_env_host;
 use soroban_env_host::{
     storage::SnapshotSource,
     xdr::{
-        AccountId, DiagnosticEvent, Hash, HostFunction, LedgerEntry, LedgerKey, ScVal,
-        SorobanAuthorizationEntry, SorobanResources, TransactionMeta, TransactionV1Envelope,
+        AccountId, ContractExecutable, DiagnosticEvent, Hash, HostFunction, LedgerEntry,
+        LedgerEntryData, LedgerKey, Limits, MuxedAccount, ScAddress, ScVal, ScValType,
+        SorobanAuthorizationEntry, SorobanResources, TransactionMeta, TransactionMetaV3,
+        TransactionV1Envelope, WriteXdr,
     },
     zephyr::RetroshadeExport,
-    HostError, LedgerInfo,
+    HostError, LedgerInfo, ModuleCache,
 };
+use spec::{ContractSpec, SpecMismatch};
+use state::KeyedEntry;
+use stream::ResultStream;
+pub mod cache;
+pub mod changes;
+pub mod compare;
+pub mod compat;
+pub mod contract_event;
 pub mod conversion;
+pub mod decode;
+pub mod diagnostics;
+pub mod dump;
+pub mod engine;
+pub mod estimate;
+pub mod export;
+pub mod fixture;
+pub mod intern;
 mod internal;
-mod snapshot;
+pub mod ledger_meta;
+pub mod ledger_snapshot;
+pub mod msgpack;
+pub mod naming;
+pub mod network;
+pub mod owners;
+#[cfg(feature = "http")]
+pub mod rpc;
+pub mod schema;
+pub mod sink;
+pub mod snapshot;
+pub mod spec;
 mod state;
+pub mod stream;
+#[cfg(feature = "metrics")]
+pub mod telemetry;
+#[cfg(any(test, feature = "testutils"))]
+pub mod testutils;
 
 #[cfg(test)]
 mod test;
 
 pub struct RetroshadesExecution {
     /// Pre-tx-execution state.
-    target_pre_execution_state: Vec<(LedgerEntry, Option<u32>)>,
+    target_pre_execution_state: Vec<KeyedEntry>,
 
     /// For recording mode only. Forces entries to be removed from the retro snapshot.
     force_remove: Vec<LedgerEntry>,
@@ -40,44 +101,832 @@ pub struct RetroshadesExecution {
     /// Operation's source account.
     source_account: Option<AccountId>,
 
+    /// Operation's source account, preserving the muxed id if any.
+    muxed_source_account: Option<MuxedAccount>,
+
     /// Ledger information.
     ledger_info: LedgerInfo,
+
+    /// Time spent building the state, carried over to the execution results.
+    timings: ExecutionTimings,
+
+    /// What building the state changed, carried over to the execution results.
+    state_summary: StateSummary,
+
+    /// What the execution returns when the re-executed contract call fails.
+    on_call_failure: OnCallFailure,
+
+    /// What packing does with targets that are too long to be table names.
+    long_target_policy: LongTargetPolicy,
+
+    /// Longest target packing accepts as is.
+    max_target_len: usize,
+
+    /// Hash of the transaction, computed while building the state.
+    tx_hash: Option<Hash>,
+
+    /// Caller-provided transaction context, derived from the ledger info if unset.
+    tx_context: Option<TxContext>,
+
+    /// Whether the transaction context is also added as event columns.
+    context_columns: bool,
+
+    /// Whether the source account is also added as an event column.
+    source_account_column: bool,
+
+    /// Whether a single malformed retroshade fails the whole packing.
+    fail_fast: bool,
+
+    /// Whether the emit index is also added as an event column.
+    emit_index_column: bool,
+
+    /// Whether byte-identical retroshades are dropped before packing.
+    dedup: bool,
+
+    /// Whether the idempotency key is also added as a hex event column.
+    idempotency_key_column: bool,
+
+    /// Whether the schema hash is also added as a hex event column.
+    schema_hash_column: bool,
+
+    /// How values are converted when packing.
+    conversion_options: ConversionOptions,
+
+    /// Caps on what an execution may use or produce.
+    sandbox_limits: SandboxLimits,
+
+    /// What executions do with declared resources the state doesn't fit in.
+    resource_validation: ResourceValidation,
+
+    /// What building does with snapshot entries newer than the transaction.
+    staleness_guard: StalenessGuard,
+
+    /// What building does with replacements outside their allowlist.
+    replacement_policy: ReplacementPolicy,
+
+    /// Whether packing types columns from the spec of the emitting contract.
+    spec_column_types: bool,
+
+    /// What packing does with events that don't match the contract spec.
+    spec_validation: SpecValidation,
+
+    /// Shares the names of packed exports, across executions if set.
+    interner: Interner,
+
+    /// Largest serialized pre-execution state builds accept.
+    max_state_bytes: Option<usize>,
+
+    /// Whether execution results carry the ledger changes.
+    ledger_changes: bool,
+
+    /// Mercury binaries taken out of the state while it runs the ones they
+    /// replaced, by code hash.
+    swapped_binaries: HashMap<Hash, Arc<[u8]>>,
+
+    /// XDR of the built state, encoded when building it. Emptied whenever
+    /// building changes the state.
+    encoded_inputs: OnceLock<EncodedInputs>,
+
+    /// How many times the state was encoded.
+    #[cfg(any(test, feature = "bench-internals"))]
+    encodings: AtomicUsize,
+}
+
+/// Column names reserved for the transaction context when added to the events.
+pub const CONTEXT_COLUMNS: [&str; 4] = ["tx_hash", "ledger_seq", "closed_at", "op_index"];
+
+/// Column name reserved for the source account when added to the events.
+pub const SOURCE_ACCOUNT_COLUMN: &str = "source_account";
+
+/// Column name reserved for the emit index when added to the events.
+pub const EMIT_INDEX_COLUMN: &str = "emit_index";
+
+/// Column name reserved for the idempotency key when added to the events.
+pub const IDEMPOTENCY_KEY_COLUMN: &str = "idempotency_key";
+
+/// Column name reserved for the schema hash when added to the events.
+pub const SCHEMA_HASH_COLUMN: &str = "schema_hash";
+
+/// Longest target name that can be used as a Postgres identifier.
+pub const MAX_TARGET_LEN: usize = 63;
+
+/// What to do with targets longer than the maximum target length.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LongTargetPolicy {
+    /// Fail packing the export with [`MalformedReason::TargetTooLong`].
+    #[default]
+    Error,
+
+    /// Keep the start of the name and replace the rest with `_` and a short
+    /// hash of the full name, so distinct long targets stay distinct.
+    TruncateWithHash,
+}
+
+/// What to return when the re-executed contract call fails. The retroshades
+/// recorded before the failure come from logic that was rolled back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnCallFailure {
+    /// Return the retroshades recorded before the failure.
+    #[default]
+    ReturnPartial,
+
+    /// Return [`RetroshadeError::ContractCallFailed`].
+    Error,
+
+    /// Return no retroshades. The failure is still visible through the
+    /// invoke result and `call_succeeded`.
+    ReturnEmpty,
+}
+
+/// Hard caps on executions of mercury contracts, which are third-party
+/// code. Independent of the transaction's resources since the CPU budget of
+/// re-executions is unlimited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SandboxLimits {
+    /// Retroshades kept per execution, the rest are dropped and the result
+    /// is flagged as truncated.
+    pub max_retroshades: usize,
+
+    /// Memory the host may allocate, wasm linear memory included. Going
+    /// over aborts the execution with [`RetroshadeError::SandboxLimit`].
+    pub max_memory_bytes: u64,
+
+    /// Diagnostic events kept in the results of an execution, the rest are
+    /// dropped and the result is flagged as truncated. These include the
+    /// host-to-guest calls of the contracts.
+    ///
+    /// Only truncates the output, the host still records every event while
+    /// executing.
+    pub max_output_events: usize,
+}
+
+impl Default for SandboxLimits {
+    /// No limits.
+    fn default() -> Self {
+        Self {
+            max_retroshades: usize::MAX,
+            max_memory_bytes: u64::MAX,
+            max_output_events: usize::MAX,
+        }
+    }
+}
+
+/// What executions do when the transaction's declared disk read bytes are
+/// fewer than the serialized state, replaced binaries included. From
+/// protocol 23 the host only counts classic and restored entries as disk
+/// reads, so mostly useful with earlier protocols or to attribute failures.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResourceValidation {
+    /// Execute with the declared resources.
+    #[default]
+    Off,
+
+    /// Fail before executing with
+    /// [`RetroshadeError::InsufficientDeclaredResources`].
+    Error,
+
+    /// Execute with the declared disk read bytes raised to the state's size.
+    Inflate,
+}
+
+/// The declared resource a state doesn't fit in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ResourceDimension {
+    /// The contract code entries alone need more than the declared disk
+    /// read bytes, usually because of a larger replaced binary.
+    CodeBytes,
+
+    /// The whole state needs more than the declared disk read bytes.
+    DiskReadBytes,
+}
+
+impl fmt::Display for ResourceDimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceDimension::CodeBytes => write!(f, "disk read bytes for contract code"),
+            ResourceDimension::DiskReadBytes => write!(f, "disk read bytes"),
+        }
+    }
+}
+
+/// Transaction-level information attached to each packed export.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxContext {
+    pub tx_hash: Hash,
+    pub ledger_seq: u32,
+    pub closed_at: u64,
+    pub op_index: u32,
+}
+
+/// How the state the execution ran against differs from the snapshot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSummary {
+    /// Whether any entry was reset to its pre-execution value.
+    pub state_changed: bool,
+    pub entries_reset: usize,
+    /// Whether any contract code was replaced with its Mercury counterpart.
+    pub binaries_replaced: bool,
+    pub binaries_replaced_count: usize,
+}
+
+/// Wall time spent in each phase of a retroshade execution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionTimings {
+    /// Fetching the footprint entries from the snapshot.
+    pub build: Duration,
+    /// Resetting the state to pre-execution and replacing the binaries.
+    pub reset: Duration,
+    /// Running the host function.
+    pub execute: Duration,
+    /// Converting the retroshades into their packed form.
+    pub convert: Duration,
 }
 
 #[derive(Clone, Debug)]
 pub enum RetroshadeError {
-    SVMHost(HostError),
+    /// The host failed, `diagnostics` holds the events collected up to the failure.
+    SVMHost {
+        error: HostError,
+        diagnostics: Vec<DiagnosticEvent>,
+    },
     NotSorobanTx,
     EntryNotFound(LedgerKey),
     MissingContext,
     MalformedXdr,
-    MalformedRetroshadeEvent,
-    NonSuccessfulContractCall(Vec<DiagnosticEvent>),
+    MalformedRetroshadeEvent {
+        contract_id: String,
+        reason: MalformedReason,
+        value_debug: String,
+    },
+    /// An event field uses one of the column names reserved for the context or source account.
+    ReservedColumn(String),
+    /// Strict mode only: the re-executed contract call failed.
+    ContractCallFailed {
+        diagnostics: Vec<DiagnosticEvent>,
+    },
+    Fixture(String),
+    SnapshotFile(String),
+    Export(String),
+    Rpc(String),
+    LedgerMetaFile(String),
+    /// Decoded from a serialized result, only the message is known.
+    Decoded(String),
+    /// The serialized pre-execution state is over the configured limit, see
+    /// [`RetroshadesExecution::set_max_state_bytes`].
+    StateTooLarge {
+        bytes: usize,
+        limit: usize,
+    },
+    /// The ledger's protocol is outside of the ones this build executes, see
+    /// [`version`].
+    UnsupportedProtocol {
+        got: u32,
+        supported: RangeInclusive<u32>,
+    },
+    /// The contract spec couldn't be read from the wasm.
+    Spec(String),
+    /// The event doesn't match the contract spec with [`SpecValidation::Error`].
+    EventSpecMismatch {
+        contract_id: String,
+        mismatches: Vec<SpecMismatch>,
+    },
+    /// A field of an export couldn't be decoded as its spec type, see
+    /// [`decode::TypedDecoder`].
+    TypedDecode {
+        field: String,
+        reason: String,
+    },
+    /// The contract has no registered owner with
+    /// [`owners::UnregisteredContracts::Error`].
+    UnregisteredContract(String),
+    /// The execution allocated more memory than
+    /// [`SandboxLimits::max_memory_bytes`].
+    SandboxLimit {
+        memory_bytes: u64,
+        limit: u64,
+    },
+    /// The state needs more than the transaction declared with
+    /// [`ResourceValidation::Error`].
+    InsufficientDeclaredResources {
+        needed: usize,
+        declared: u32,
+        dimension: ResourceDimension,
+    },
+    /// A snapshot entry is newer than the transaction with
+    /// [`StalenessGuard::Error`].
+    StaleSnapshotEntry(StaleSnapshotEntry),
+    /// A replacement's code is run by a contract outside its allowlist with
+    /// [`ReplacementPolicy::Error`].
+    ReplacementNotAllowed(SkippedReplacement),
+    /// Contracts running the same code entry are given different
+    /// replacements.
+    ConflictingReplacements {
+        wasm_hash: Hash,
+        /// Contract ids the replacements are keyed by.
+        keys: Vec<Hash>,
+    },
 }
 
-#[derive(Clone, Debug)]
+/// What packing does with events that don't match the contract spec, see
+/// [`spec::ContractSpec::validate`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpecValidation {
+    /// Don't validate events.
+    #[default]
+    Off,
+
+    /// Pack the event as usual and report the mismatches in
+    /// [`RetroshadeExecutionResultPretty::spec_warnings`].
+    Warn,
+
+    /// Fail packing the event with [`RetroshadeError::EventSpecMismatch`].
+    Error,
+}
+
+/// What was wrong with a retroshade that couldn't be packed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MalformedReason {
+    TargetNotSymbol {
+        found: ScValType,
+    },
+    /// The target is longer than the maximum target length with
+    /// [`LongTargetPolicy::Error`].
+    TargetTooLong {
+        len: usize,
+        max: usize,
+    },
+    EventNotMap,
+    FieldNameNotSymbol,
+    /// A contract event converted back to a retroshade isn't of the
+    /// contract type or has no contract id, see [`contract_event`].
+    NotContractEvent,
+    /// A contract event converted back to a retroshade doesn't have the
+    /// target as its single topic.
+    TopicCount {
+        count: usize,
+    },
+}
+
+impl fmt::Display for MalformedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MalformedReason::TargetNotSymbol { found } => {
+                write!(f, "target is not a symbol but {}", found.name())
+            }
+            MalformedReason::TargetTooLong { len, max } => {
+                write!(f, "target is {} characters long, the limit is {}", len, max)
+            }
+            MalformedReason::EventNotMap => write!(f, "event object is not a map"),
+            MalformedReason::FieldNameNotSymbol => write!(f, "event field name is not a symbol"),
+            MalformedReason::NotContractEvent => {
+                write!(f, "event is not a contract event of a contract")
+            }
+            MalformedReason::TopicCount { count } => {
+                write!(f, "event has {} topics instead of the target alone", count)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RetroshadeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetroshadeError::SVMHost { error, diagnostics } => {
+                writeln!(f, "host error: {}", error)?;
+                write!(f, "{}", DisplayDiagnostics(diagnostics))
+            }
+            RetroshadeError::NotSorobanTx => write!(f, "not a soroban transaction"),
+            RetroshadeError::EntryNotFound(key) => write!(f, "entry not found: {:?}", key),
+            RetroshadeError::MissingContext => write!(f, "execution context is not built"),
+            RetroshadeError::MalformedXdr => write!(f, "malformed xdr"),
+            RetroshadeError::MalformedRetroshadeEvent {
+                contract_id,
+                reason,
+                value_debug,
+            } => write!(
+                f,
+                "malformed retroshade event from {}: {}, got {}",
+                contract_id, reason, value_debug
+            ),
+            RetroshadeError::ReservedColumn(name) => {
+                write!(f, "event field {} uses a reserved column name", name)
+            }
+            RetroshadeError::ContractCallFailed { diagnostics } => {
+                writeln!(f, "contract call failed")?;
+                write!(f, "{}", DisplayDiagnostics(diagnostics))
+            }
+            RetroshadeError::Fixture(reason) => write!(f, "fixture error: {}", reason),
+            RetroshadeError::SnapshotFile(reason) => write!(f, "snapshot file error: {}", reason),
+            RetroshadeError::Export(reason) => write!(f, "export error: {}", reason),
+            RetroshadeError::Rpc(reason) => write!(f, "rpc error: {}", reason),
+            RetroshadeError::LedgerMetaFile(reason) => {
+                write!(f, "ledger meta file error: {}", reason)
+            }
+            RetroshadeError::Decoded(message) => write!(f, "{}", message),
+            RetroshadeError::Spec(reason) => write!(f, "contract spec error: {}", reason),
+            RetroshadeError::EventSpecMismatch {
+                contract_id,
+                mismatches,
+            } => {
+                let mismatches: Vec<String> = mismatches.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "event of {} doesn't match its spec: {}",
+                    contract_id,
+                    mismatches.join("; ")
+                )
+            }
+            RetroshadeError::TypedDecode { field, reason } => {
+                write!(f, "couldn't decode field {}: {}", field, reason)
+            }
+            RetroshadeError::UnregisteredContract(contract_id) => {
+                write!(f, "contract {} has no registered owner", contract_id)
+            }
+            RetroshadeError::SandboxLimit {
+                memory_bytes,
+                limit,
+            } => write!(
+                f,
+                "execution allocated {} bytes, over the sandbox limit of {} bytes",
+                memory_bytes, limit
+            ),
+            RetroshadeError::InsufficientDeclaredResources {
+                needed,
+                declared,
+                dimension,
+            } => write!(
+                f,
+                "transaction declares {} {} but the state needs {}",
+                declared, dimension, needed
+            ),
+            RetroshadeError::StaleSnapshotEntry(stale) => write!(f, "stale snapshot: {}", stale),
+            RetroshadeError::ReplacementNotAllowed(skipped) => write!(f, "{}", skipped),
+            RetroshadeError::ConflictingReplacements { wasm_hash, keys } => write!(
+                f,
+                "contracts {} run code {} but are given different replacements",
+                keys.iter()
+                    .map(|key| stellar_strkey::Contract(key.0).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                hex::encode(wasm_hash.0)
+            ),
+            RetroshadeError::StateTooLarge { bytes, limit } => write!(
+                f,
+                "pre-execution state is {} bytes, over the {} bytes limit",
+                bytes, limit
+            ),
+            RetroshadeError::UnsupportedProtocol { got, supported } => write!(
+                f,
+                "ledger protocol {} is not supported, this build executes protocols {} to {}",
+                got,
+                supported.start(),
+                supported.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RetroshadeError {}
+
+impl From<HostError> for RetroshadeError {
+    fn from(error: HostError) -> Self {
+        Self::SVMHost {
+            error,
+            diagnostics: vec![],
+        }
+    }
+}
+
+impl From<InvokeHostFunctionFailure> for RetroshadeError {
+    fn from(failure: InvokeHostFunctionFailure) -> Self {
+        Self::SVMHost {
+            error: failure.error,
+            diagnostics: failure.diagnostic_events,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct RetroshadeExecutionResult {
     pub retroshades: Vec<RetroshadeExport>,
     pub diagnostic: Vec<DiagnosticEvent>,
+    /// Value returned by the re-executed invocation, or the host error it failed with.
+    pub invoke_result: Result<ScVal, String>,
+    /// Entries read or written by the re-execution.
+    pub ledger_changes: Vec<EntryChange>,
+    pub timings: ExecutionTimings,
+    pub state: StateSummary,
+    /// Whether retroshades or diagnostics were dropped over the
+    /// [`SandboxLimits`].
+    pub truncated: bool,
+}
+
+impl RetroshadeExecutionResult {
+    /// Whether the re-executed invocation returned the same value as the on-chain one.
+    pub fn matches_onchain_return(&self, meta: &TransactionMetaV3) -> bool {
+        matches_onchain_return(&self.invoke_result, meta)
+    }
+
+    /// Diffs the re-executed ledger changes against the on-chain operation meta.
+    /// A non-empty result means the reconstructed state didn't match the chain.
+    pub fn compare_with_meta(&self, meta: &TransactionMetaV3) -> Vec<Mismatch> {
+        changes::compare_with_meta(&self.ledger_changes, meta)
+    }
+}
+
+fn matches_onchain_return(invoke_result: &Result<ScVal, String>, meta: &TransactionMetaV3) -> bool {
+    match (invoke_result, &meta.soroban_meta) {
+        (Ok(value), Some(soroban_meta)) => value == &soroban_meta.return_value,
+        _ => false,
+    }
+}
+
+/// Which snapshot source served a footprint key while building the state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FootprintProvenance {
+    pub key: LedgerKey,
+    pub source: String,
+    /// `None` when the source didn't have the entry.
+    pub last_modified_ledger_seq: Option<u32>,
+    pub live_until: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildReport {
+    pub binaries_replaced: bool,
+    pub footprint: Vec<FootprintProvenance>,
+    /// Serialized size of the state the host is fed, entries and TTLs.
+    pub state_bytes: usize,
+    /// Entries newer than the transaction with [`StalenessGuard::Warn`].
+    pub stale_entries: Vec<StaleSnapshotEntry>,
+    /// Replacements whose binary replaced a code entry of the state.
+    pub applied_replacements: Vec<AppliedReplacement>,
+    /// Replacements left out with [`ReplacementPolicy::Skip`].
+    pub skipped_replacements: Vec<SkippedReplacement>,
+}
+
+/// What building does with snapshot entries modified at or after the
+/// transaction's ledger that the meta doesn't reset, e.g. because the
+/// snapshot is a few ledgers ahead. The state would hold changes the
+/// transaction never saw.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StalenessGuard {
+    /// Don't check the entries.
+    #[default]
+    Off,
+
+    /// Build as usual, logging the stale entries and reporting them in
+    /// [`BuildReport::stale_entries`].
+    Warn,
+
+    /// Fail building with [`RetroshadeError::StaleSnapshotEntry`], the
+    /// strict mode of the guard.
+    Error,
+}
+
+/// A footprint entry the snapshot has as modified by a later transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleSnapshotEntry {
+    pub key: LedgerKey,
+    pub last_modified_ledger_seq: u32,
+    /// Ledger of the transaction.
+    pub ledger_seq: u32,
+}
+
+impl fmt::Display for StaleSnapshotEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "snapshot entry {:?} was modified in ledger {}, the transaction is in ledger {}",
+            self.key, self.last_modified_ledger_seq, self.ledger_seq
+        )
+    }
+}
+
+/// A Mercury binary and the contracts it may be substituted for.
+///
+/// Code entries are shared by every contract deployed with the same wasm, so
+/// a replacement only applies when all the contracts of the state running
+/// the replaced code are allowed. An entry keyed by a contract id allows that
+/// contract, one keyed by a wasm hash allows none until told otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplacementEntry {
+    pub wasm: Arc<[u8]>,
+    /// Contract ids the binary may run as, besides the one it's keyed by.
+    pub allowed_contracts: HashSet<Hash>,
+    /// Whether the binary may run as any contract, see [`Self::allow_any`].
+    pub any_contract: bool,
+}
+
+impl ReplacementEntry {
+    /// A replacement for the contract it's keyed by.
+    pub fn new(wasm: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            wasm: wasm.into(),
+            allowed_contracts: HashSet::new(),
+            any_contract: false,
+        }
+    }
+
+    /// Also allows `contracts`.
+    pub fn allow(mut self, contracts: impl IntoIterator<Item = Hash>) -> Self {
+        self.allowed_contracts.extend(contracts);
+        self
+    }
+
+    /// Allows every contract, for entries keyed by a wasm hash that replace
+    /// the code of all the contracts deployed with it.
+    pub fn allow_any(mut self) -> Self {
+        self.any_contract = true;
+        self
+    }
+
+    /// Whether the entry, keyed by `key`, may run as `contract_id`.
+    pub fn allows(&self, key: &Hash, contract_id: &Hash) -> bool {
+        self.any_contract || key == contract_id || self.allowed_contracts.contains(contract_id)
+    }
+}
+
+impl From<Arc<[u8]>> for ReplacementEntry {
+    fn from(wasm: Arc<[u8]>) -> Self {
+        Self::new(wasm)
+    }
 }
 
+/// What building does with replacements whose code is run by a contract
+/// outside their allowlist.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    /// Keep the original code, logging the replacement and reporting it in
+    /// [`BuildReport::skipped_replacements`].
+    #[default]
+    Skip,
+
+    /// Fail building with [`RetroshadeError::ReplacementNotAllowed`].
+    Error,
+}
+
+/// A replacement left out because a contract of the state runs the code it
+/// replaces without being allowed to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedReplacement {
+    /// Key of the replacement, a contract id or a wasm hash.
+    pub key: Hash,
+    /// Hash of the code entry it would have replaced.
+    pub wasm_hash: Hash,
+    /// The contract outside the allowlist.
+    pub contract_id: Hash,
+}
+
+/// A replacement whose binary replaced a code entry of the state.
 #[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppliedReplacement {
+    /// Key of the replacement, a contract id or a wasm hash.
+    pub key: Hash,
+    /// Hash of the code entry it replaced.
+    pub wasm_hash: Hash,
+    /// Contracts of the state running the replaced code.
+    pub contract_ids: Vec<Hash>,
+}
+
+impl fmt::Display for SkippedReplacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "replacement {} of wasm {} isn't allowed for contract {}",
+            hex::encode(self.key.0),
+            hex::encode(self.wasm_hash.0),
+            stellar_strkey::Contract(self.contract_id.0)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PackedEventEntry {
-    pub name: String,
+    pub name: SharedStr,
     pub value: FromScVal,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RetroshadeExportPretty {
-    pub contract_id: String,
-    pub target: String,
+    pub contract_id: SharedStr,
+    pub target: SharedStr,
     pub event: Vec<PackedEventEntry>,
+    pub context: TxContext,
+    /// Strkey of the invoking account.
+    pub source_account: String,
+    /// Position of the retroshade in the emitted order.
+    pub emit_index: u32,
+    /// Canonical XDR of the emitted event object, hex in the serialized
+    /// formats so that decoded exports keep their idempotency key.
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    pub event_object_xdr: Vec<u8>,
 }
 
-#[derive(Clone, Debug)]
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    hex::decode(encoded).map_err(serde::de::Error::custom)
+}
+
+impl RetroshadeExportPretty {
+    /// Key identifying this export across re-executions of the same
+    /// transaction, so that writers can make inserts retry-safe.
+    pub fn idempotency_key(&self, tx_hash: &Hash) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(tx_hash.0);
+        // note: variable-length components are length-prefixed so they can't
+        // bleed into each other.
+        for component in [self.contract_id.as_bytes(), self.target.as_bytes()] {
+            hasher.update((component.len() as u32).to_be_bytes());
+            hasher.update(component);
+        }
+        hasher.update(self.emit_index.to_be_bytes());
+        hasher.update(&self.event_object_xdr);
+
+        hasher.finalize().into()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RetroshadeExecutionResultPretty {
+    pub call_succeeded: bool,
     pub retroshades: Vec<RetroshadeExportPretty>,
+    /// Retroshades that couldn't be packed, by their index in the emitted order.
+    #[serde(
+        serialize_with = "serialize_conversion_errors",
+        deserialize_with = "deserialize_conversion_errors"
+    )]
+    pub conversion_errors: Vec<(usize, RetroshadeError)>,
+    /// Number of byte-identical retroshades dropped when dedup is enabled.
+    pub duplicates_removed: usize,
+    /// How the retroshades differ from their contract spec with
+    /// [`SpecValidation::Warn`], by their index in the emitted order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spec_warnings: Vec<(usize, SpecMismatch)>,
     pub diagnostic: Vec<DiagnosticEvent>,
+    pub invoke_result: Result<ScVal, String>,
+    pub ledger_changes: Vec<EntryChange>,
+    pub timings: ExecutionTimings,
+    pub state: StateSummary,
+    /// Whether retroshades or diagnostics were dropped over the
+    /// [`SandboxLimits`].
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+fn serialize_conversion_errors<S: Serializer>(
+    errors: &[(usize, RetroshadeError)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(errors.iter().map(|(idx, error)| (idx, error.to_string())))
+}
+
+fn deserialize_conversion_errors<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(usize, RetroshadeError)>, D::Error> {
+    let errors = Vec::<(usize, String)>::deserialize(deserializer)?;
+    Ok(errors
+        .into_iter()
+        .map(|(idx, message)| (idx, RetroshadeError::Decoded(message)))
+        .collect())
+}
+
+impl RetroshadeExecutionResultPretty {
+    pub fn matches_onchain_return(&self, meta: &TransactionMetaV3) -> bool {
+        matches_onchain_return(&self.invoke_result, meta)
+    }
+
+    pub fn compare_with_meta(&self, meta: &TransactionMetaV3) -> Vec<Mismatch> {
+        changes::compare_with_meta(&self.ledger_changes, meta)
+    }
+}
+
+fn context_columns(context: &TxContext, interner: &Interner) -> Vec<PackedEventEntry> {
+    vec![
+        PackedEventEntry {
+            name: interner.intern("tx_hash"),
+            value: FromScVal {
+                dbtype: Type::TEXT,
+                kind: TypeKind::Text(hex::encode(context.tx_hash.0)),
+            },
+        },
+        PackedEventEntry {
+            name: interner.intern("ledger_seq"),
+            value: FromScVal::from_scval(ScVal::U32(context.ledger_seq), &mut 0),
+        },
+        PackedEventEntry {
+            name: interner.intern("closed_at"),
+            value: FromScVal::from_scval(ScVal::U64(context.closed_at), &mut 0),
+        },
+        PackedEventEntry {
+            name: interner.intern("op_index"),
+            value: FromScVal::from_scval(ScVal::U32(context.op_index), &mut 0),
+        },
+    ]
 }
 
 /// The ideal flow would be:
@@ -120,62 +969,592 @@ impl RetroshadesExecution {
             auth_entries: vec![],
             resources: None,
             source_account: None,
+            muxed_source_account: None,
             ledger_info,
             force_remove: vec![],
+            timings: ExecutionTimings::default(),
+            state_summary: StateSummary::default(),
+            on_call_failure: OnCallFailure::default(),
+            long_target_policy: LongTargetPolicy::default(),
+            max_target_len: MAX_TARGET_LEN,
+            tx_hash: None,
+            tx_context: None,
+            context_columns: false,
+            source_account_column: false,
+            fail_fast: false,
+            emit_index_column: false,
+            dedup: false,
+            idempotency_key_column: false,
+            schema_hash_column: false,
+            conversion_options: ConversionOptions::default(),
+            sandbox_limits: SandboxLimits::default(),
+            resource_validation: ResourceValidation::default(),
+            staleness_guard: StalenessGuard::default(),
+            replacement_policy: ReplacementPolicy::default(),
+            spec_column_types: false,
+            spec_validation: SpecValidation::default(),
+            interner: Interner::new(),
+            max_state_bytes: None,
+            ledger_changes: true,
+            swapped_binaries: HashMap::new(),
+            encoded_inputs: OnceLock::new(),
+            #[cfg(any(test, feature = "bench-internals"))]
+            encodings: AtomicUsize::new(0),
         }
     }
 
+    /// Also add [`RetroshadeExportPretty::idempotency_key`] as a hex
+    /// `idempotency_key` event column.
+    pub fn set_idempotency_key_column(&mut self, idempotency_key_column: bool) {
+        self.idempotency_key_column = idempotency_key_column;
+    }
+
+    /// Also add [`RetroshadeExportPretty::schema_hash`] as a hex
+    /// `schema_hash` event column. The hash covers every other column,
+    /// including the ones added by packing, and matches the method's on the
+    /// packed export.
+    pub fn set_schema_hash_column(&mut self, schema_hash_column: bool) {
+        self.schema_hash_column = schema_hash_column;
+    }
+
+    /// How packing converts values, e.g. rendering addresses as hex with
+    /// [`AddressFormat::Hex`]. The format applies to the exports'
+    /// `contract_id` too.
+    pub fn set_conversion_options(&mut self, conversion_options: ConversionOptions) {
+        self.conversion_options = conversion_options;
+    }
+
+    /// Caps what executions may use or produce, see [`SandboxLimits`].
+    pub fn set_sandbox_limits(&mut self, sandbox_limits: SandboxLimits) {
+        self.sandbox_limits = sandbox_limits;
+    }
+
+    /// Check the transaction's declared resources against the built state
+    /// before executing, see [`ResourceValidation`].
+    pub fn set_resource_validation(&mut self, resource_validation: ResourceValidation) {
+        self.resource_validation = resource_validation;
+        // note: inflating changes the encoded resources.
+        self.encoded_inputs = OnceLock::new();
+    }
+
+    /// Check that the snapshot entries aren't newer than the transaction
+    /// when building, see [`StalenessGuard`].
+    pub fn set_staleness_guard(&mut self, staleness_guard: StalenessGuard) {
+        self.staleness_guard = staleness_guard;
+    }
+
+    /// Whether building skips or fails on replacements whose code a contract
+    /// outside their allowlist runs, see [`ReplacementPolicy`].
+    pub fn set_replacement_policy(&mut self, replacement_policy: ReplacementPolicy) {
+        self.replacement_policy = replacement_policy;
+    }
+
+    /// Type the columns of void values and empty vectors from the
+    /// [`spec`] of the emitting contract's code, e.g. a `None` of an
+    /// `Option<Address>` field is a `TEXT` null and an empty `Vec<i128>` a
+    /// `NUMERIC[]`. Events matching no struct of the spec are packed as
+    /// usual.
+    pub fn set_spec_column_types(&mut self, spec_column_types: bool) {
+        self.spec_column_types = spec_column_types;
+    }
+
+    /// Check events against the struct of the emitting contract's [`spec`]
+    /// they share the most fields with. Contracts without a spec aren't
+    /// checked.
+    pub fn set_spec_validation(&mut self, spec_validation: SpecValidation) {
+        self.spec_validation = spec_validation;
+    }
+
+    /// Leave the ledger changes out of execution results, sparing their
+    /// decoding when only the retroshades are wanted. On by default, as
+    /// [`RetroshadeExecutionResult::compare_with_meta`] needs them.
+    pub fn set_ledger_changes(&mut self, ledger_changes: bool) {
+        self.ledger_changes = ledger_changes;
+    }
+
+    /// Fail builds whose serialized pre-execution state is over `limit` bytes
+    /// with [`RetroshadeError::StateTooLarge`], dropping the state. Checked as
+    /// the footprint is fetched, and on the final state. Bounds the memory
+    /// held by parallel batches hitting pathological footprints.
+    pub fn set_max_state_bytes(&mut self, limit: usize) {
+        self.max_state_bytes = Some(limit);
+    }
+
+    /// How many times the built state was encoded for the host, once per
+    /// build unless something re-encodes it.
+    #[cfg(feature = "bench-internals")]
+    pub fn encodings(&self) -> usize {
+        self.encodings.load(Ordering::Relaxed)
+    }
+
+    /// Shares the column names, contract ids and targets of packed exports
+    /// with other executions using `interner`, e.g. those of a ledger.
+    pub fn set_interner(&mut self, interner: Interner) {
+        self.interner = interner;
+    }
+
+    /// Drop retroshades that are byte-identical (same contract, target and
+    /// event object) to one emitted earlier in the same execution. Has no
+    /// effect with the emit index column, where no two rows are identical.
+    pub fn set_dedup(&mut self, dedup: bool) {
+        self.dedup = dedup;
+    }
+
+    /// Also add the emit index as an `emit_index` event column.
+    pub fn set_emit_index_column(&mut self, emit_index_column: bool) {
+        self.emit_index_column = emit_index_column;
+    }
+
+    /// By default retroshades that can't be packed are reported in
+    /// `conversion_errors`; with fail-fast the first one errors the packing.
+    pub fn set_fail_fast(&mut self, fail_fast: bool) {
+        self.fail_fast = fail_fast;
+    }
+
+    /// Overrides the context attached to packed exports. By default it's
+    /// derived from the ledger info and the envelope's hash.
+    pub fn set_tx_context(&mut self, tx_context: TxContext) {
+        self.tx_context = Some(tx_context);
+    }
+
+    /// Also add the context as event columns named after [`CONTEXT_COLUMNS`].
+    pub fn set_context_columns(&mut self, context_columns: bool) {
+        self.context_columns = context_columns;
+    }
+
+    /// Also add the source account as a `source_account` event column.
+    pub fn set_source_account_column(&mut self, source_account_column: bool) {
+        self.source_account_column = source_account_column;
+    }
+
+    /// Strkey of the operation's source account, `M...` if it's muxed.
+    pub fn source_account_strkey(&self) -> Option<String> {
+        let strkey = match self.muxed_source_account.as_ref()? {
+            MuxedAccount::Ed25519(key) => stellar_strkey::ed25519::PublicKey(key.0).to_string(),
+            MuxedAccount::MuxedEd25519(muxed) => stellar_strkey::ed25519::MuxedAccount {
+                ed25519: muxed.ed25519.0,
+                id: muxed.id,
+            }
+            .to_string(),
+        };
+
+        Some(strkey)
+    }
+
+    fn reserved_columns(&self) -> Vec<&'static str> {
+        let mut reserved = Vec::new();
+        if self.context_columns {
+            reserved.extend(CONTEXT_COLUMNS);
+        }
+        if self.source_account_column {
+            reserved.push(SOURCE_ACCOUNT_COLUMN);
+        }
+        if self.emit_index_column {
+            reserved.push(EMIT_INDEX_COLUMN);
+        }
+        if self.idempotency_key_column {
+            reserved.push(IDEMPOTENCY_KEY_COLUMN);
+        }
+        if self.schema_hash_column {
+            reserved.push(SCHEMA_HASH_COLUMN);
+        }
+
+        reserved
+    }
+
+    /// The contract id of exports, in the configured address format.
+    fn contract_id_text(&self, contract_id: &Hash) -> String {
+        match self.conversion_options.address_format {
+            AddressFormat::Hex => hex::encode(contract_id.0),
+            AddressFormat::StrKey | AddressFormat::Both => {
+                stellar_strkey::Contract(contract_id.0).to_string()
+            }
+        }
+    }
+
+    pub fn tx_context(&self) -> TxContext {
+        self.tx_context.clone().unwrap_or_else(|| TxContext {
+            tx_hash: self.tx_hash.clone().unwrap_or(Hash([0; 32])),
+            ledger_seq: self.ledger_info.sequence_number,
+            closed_at: self.ledger_info.timestamp,
+            op_index: 0,
+        })
+    }
+
+    /// Defaults to [`MAX_TARGET_LEN`]. Lower it when the writer prefixes the
+    /// target to build table names.
+    pub fn set_max_target_len(&mut self, max_target_len: usize) {
+        self.max_target_len = max_target_len;
+    }
+
+    pub fn set_long_target_policy(&mut self, long_target_policy: LongTargetPolicy) {
+        self.long_target_policy = long_target_policy;
+    }
+
+    pub fn set_on_call_failure(&mut self, on_call_failure: OnCallFailure) {
+        self.on_call_failure = on_call_failure;
+    }
+
+    /// Shorthand for [`OnCallFailure::Error`] when set, and
+    /// [`OnCallFailure::ReturnPartial`] otherwise.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.on_call_failure = if strict {
+            OnCallFailure::Error
+        } else {
+            OnCallFailure::ReturnPartial
+        };
+    }
+
+    /// Resolves the transaction's footprint against `snapshot_source` and
+    /// resets it to its pre-execution state. The source is only borrowed, so
+    /// batch processors can serve every transaction of a ledger from one.
     pub fn build_from_envelope_and_meta(
         &mut self,
-        snapshot_source: Box<dyn SnapshotSource>,
+        snapshot_source: &dyn SnapshotSource,
+        tx_envelope: TransactionV1Envelope,
+        tx_meta: TransactionMeta,
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<bool, RetroshadeError> {
+        let snapshot_source = LabeledRef::new("snapshot", snapshot_source);
+        let report = self.build(&snapshot_source, tx_envelope, tx_meta, mercury_contracts)?;
+
+        Ok(report.binaries_replaced)
+    }
+
+    /// Same as [`Self::build_from_envelope_and_meta`] but with borrowed
+    /// binaries, which are copied into shared ones on every call.
+    #[deprecated(note = "pass the binaries as `ReplacementEntry` to share them across executions")]
+    pub fn build_from_envelope_and_meta_borrowed(
+        &mut self,
+        snapshot_source: &dyn SnapshotSource,
         tx_envelope: TransactionV1Envelope,
         tx_meta: TransactionMeta,
         mercury_contracts: HashMap<Hash, &[u8]>,
     ) -> Result<bool, RetroshadeError> {
-        self.build_current_state(snapshot_source, tx_envelope)?;
-        self.state_reset_to_pre_execution(tx_meta)?;
+        let mercury_contracts = mercury_contracts
+            .into_iter()
+            .map(|(hash, binary)| (hash, ReplacementEntry::new(binary)))
+            .collect();
+
+        self.build_from_envelope_and_meta(snapshot_source, tx_envelope, tx_meta, mercury_contracts)
+    }
+
+    /// Same as [`Self::build_from_envelope_and_meta`] but also reports which source
+    /// served each footprint key, useful to catch stale entries when chaining snapshots.
+    pub fn build_from_envelope_and_meta_with_report(
+        &mut self,
+        snapshot_source: &dyn SnapshotSourceExt,
+        tx_envelope: TransactionV1Envelope,
+        tx_meta: TransactionMeta,
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<BuildReport, RetroshadeError> {
+        self.build(snapshot_source, tx_envelope, tx_meta, mercury_contracts)
+    }
+
+    /// Builds the state from scratch, a failed build leaves none.
+    fn build(
+        &mut self,
+        snapshot_source: &dyn SnapshotSourceExt,
+        tx_envelope: TransactionV1Envelope,
+        tx_meta: TransactionMeta,
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<BuildReport, RetroshadeError> {
+        self.clear_build();
+        let report = self.build_state(snapshot_source, tx_envelope, tx_meta, mercury_contracts);
+        if report.is_err() {
+            self.clear_build();
+        }
+
+        report
+    }
+
+    /// Drops everything a previous build left.
+    fn clear_build(&mut self) {
+        self.target_pre_execution_state.clear();
+        self.force_remove.clear();
+        self.host_function = None;
+        self.auth_entries.clear();
+        self.resources = None;
+        self.source_account = None;
+        self.muxed_source_account = None;
+        self.tx_hash = None;
+        self.timings = ExecutionTimings::default();
+        self.state_summary = StateSummary::default();
+        self.swapped_binaries.clear();
+        self.encoded_inputs = OnceLock::new();
+    }
+
+    fn build_state(
+        &mut self,
+        snapshot_source: &dyn SnapshotSourceExt,
+        tx_envelope: TransactionV1Envelope,
+        tx_meta: TransactionMeta,
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<BuildReport, RetroshadeError> {
+        // note: the host would fail obscurely on the first execution.
+        let info = version();
+        if !info.supports(self.ledger_info.protocol_version) {
+            return Err(RetroshadeError::UnsupportedProtocol {
+                got: self.ledger_info.protocol_version,
+                supported: info.supported_protocols(),
+            });
+        }
+
+        let start = Instant::now();
+        let footprint = self.build_current_state(snapshot_source, tx_envelope)?;
+        self.timings.build = start.elapsed();
 
-        self.replace_binaries(mercury_contracts)
+        let stale_entries = match self.staleness_guard {
+            StalenessGuard::Off => vec![],
+            StalenessGuard::Warn => {
+                let stale_entries = self.stale_entries(&footprint, &tx_meta);
+                for stale in &stale_entries {
+                    log::warn!("{}", stale);
+                }
+                stale_entries
+            }
+            StalenessGuard::Error => {
+                if let Some(stale) = self.stale_entries(&footprint, &tx_meta).into_iter().next() {
+                    return Err(RetroshadeError::StaleSnapshotEntry(stale));
+                }
+                vec![]
+            }
+        };
+
+        let start = Instant::now();
+        let entries_reset = self.state_reset_to_pre_execution(tx_meta)?;
+        let (applied_replacements, skipped_replacements) =
+            self.replace_binaries(mercury_contracts)?;
+        let binaries_replaced_count = applied_replacements.len();
+        self.timings.reset = start.elapsed();
+
+        // note: encoding now rather than on the first execution, the size is
+        // read off the encoded inputs the executions reuse.
+        let state_bytes = self.encoded_inputs()?.state_bytes();
+        if let Some(limit) = self.max_state_bytes.filter(|limit| state_bytes > *limit) {
+            return Err(RetroshadeError::StateTooLarge {
+                bytes: state_bytes,
+                limit,
+            });
+        }
+
+        self.state_summary = StateSummary {
+            state_changed: entries_reset > 0,
+            entries_reset,
+            binaries_replaced: binaries_replaced_count > 0,
+            binaries_replaced_count,
+        };
+
+        Ok(BuildReport {
+            binaries_replaced: binaries_replaced_count > 0,
+            footprint,
+            state_bytes,
+            stale_entries,
+            applied_replacements,
+            skipped_replacements,
+        })
     }
 
+    /// Same as [`Self::build_from_envelope_and_meta`] but for thread-safe snapshot sources.
+    pub fn build_from_envelope_and_meta_shared(
+        &mut self,
+        snapshot_source: Arc<dyn SnapshotSource + Send + Sync>,
+        tx_envelope: TransactionV1Envelope,
+        tx_meta: TransactionMeta,
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<bool, RetroshadeError> {
+        self.build_from_envelope_and_meta(
+            &SharedSnapshot::new(snapshot_source),
+            tx_envelope,
+            tx_meta,
+            mercury_contracts,
+        )
+    }
+
+    /// Executes with a random PRNG seed, see [`Self::retroshade_seeded`].
+    #[cfg(any(test, feature = "rand"))]
     pub fn retroshade(&self) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.retroshade_seeded(random_seed())
+    }
+
+    /// Executes with `seed` for the host's PRNG, so that contracts using it
+    /// behave the same across re-executions.
+    pub fn retroshade_seeded(
+        &self,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.retroshade_with_module_cache(None, seed)
+    }
+
+    /// Executes with the contracts in `module_cache` already parsed, see
+    /// [`engine::RetroshadeEngine`].
+    pub(crate) fn retroshade_with_module_cache(
+        &self,
+        module_cache: Option<ModuleCache>,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.execute_enforcing(module_cache, None, seed)
+    }
+
+    fn execute_enforcing(
+        &self,
+        module_cache: Option<ModuleCache>,
+        stream: Option<&mut dyn ResultStream>,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        let inputs = self.encoded_inputs()?;
+        if self.resource_validation == ResourceValidation::Error {
+            self.check_declared_resources(inputs)?;
+        }
+
+        let start = Instant::now();
         let svm_execution = execute_svm(
             true,
+            inputs,
+            &self.ledger_info,
+            &seed,
+            module_cache,
+            self.sandbox_limits.max_memory_bytes,
+        )
+        .map_err(|failure| self.host_failure(failure));
+
+        self.execution_result(svm_execution, start.elapsed(), stream)
+    }
+
+    /// Same as [`Self::retroshade`] but handing the diagnostics and ledger
+    /// changes to `stream` as they are converted, leaving them empty in the
+    /// result. The diagnostics of a failed call are still returned with
+    /// [`RetroshadeError::ContractCallFailed`] in strict mode.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn retroshade_streaming(
+        &self,
+        stream: &mut dyn ResultStream,
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.retroshade_streaming_seeded(stream, random_seed())
+    }
+
+    /// Same as [`Self::retroshade_streaming`] with `seed` for the host's PRNG.
+    pub fn retroshade_streaming_seeded(
+        &self,
+        stream: &mut dyn ResultStream,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.execute_enforcing(None, Some(stream), seed)
+    }
+
+    /// Executes and packs, streaming the diagnostics and ledger changes, see
+    /// [`Self::retroshade_streaming`].
+    #[cfg(any(test, feature = "rand"))]
+    pub fn retroshade_packed_streaming(
+        &self,
+        stream: &mut dyn ResultStream,
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        self.pack_result(self.retroshade_streaming(stream)?)
+    }
+
+    /// The XDR the host is fed, only encoded again after the state changed.
+    fn encoded_inputs(&self) -> Result<&EncodedInputs, RetroshadeError> {
+        if let Some(inputs) = self.encoded_inputs.get() {
+            return Ok(inputs);
+        }
+
+        let resources = self
+            .resources
+            .as_ref()
+            .ok_or(RetroshadeError::MissingContext)?;
+        let mut inputs = EncodedInputs::encode(
             self.host_function
                 .as_ref()
                 .ok_or(RetroshadeError::MissingContext)?,
-            self.resources
-                .as_ref()
-                .ok_or(RetroshadeError::MissingContext)?,
+            resources,
             self.source_account
                 .as_ref()
                 .ok_or(RetroshadeError::MissingContext)?,
-            self.auth_entries.clone(),
-            &self.ledger_info,
-            self.target_pre_execution_state.clone(),
-            &rand::random::<[u8; 32]>(),
+            &self.auth_entries,
+            &self.target_pre_execution_state,
         );
 
-        match svm_execution {
-            Ok(result) => Ok(RetroshadeExecutionResult {
-                retroshades: result.retroshades,
-                diagnostic: result.diagnostic_events,
-            }),
-            Err(host_error) => Err(RetroshadeError::SVMHost(host_error)),
+        let needed = u32::try_from(inputs.entry_bytes()).unwrap_or(u32::MAX);
+        if self.resource_validation == ResourceValidation::Inflate
+            && needed > resources.disk_read_bytes
+        {
+            inputs.set_resources(&SorobanResources {
+                disk_read_bytes: needed,
+                ..resources.clone()
+            });
         }
+        #[cfg(any(test, feature = "bench-internals"))]
+        self.encodings.fetch_add(1, Ordering::Relaxed);
+
+        Ok(self.encoded_inputs.get_or_init(|| inputs))
     }
 
+    #[cfg(any(test, feature = "rand"))]
     pub fn retroshade_recording(
         &self,
         ledger_snapshot: Rc<dyn SnapshotSource>,
     ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.retroshade_recording_seeded(ledger_snapshot, random_seed())
+    }
+
+    /// Same as [`Self::retroshade_recording`] with `seed` for the host's PRNG.
+    pub fn retroshade_recording_seeded(
+        &self,
+        ledger_snapshot: Rc<dyn SnapshotSource>,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        let start = Instant::now();
+        let svm_execution = self.execute_recording(ledger_snapshot, seed);
+
+        self.execution_result(svm_execution, start.elapsed(), None)
+    }
+
+    /// Runs the host function in recording mode only and reports the resources
+    /// it needed next to the ones the envelope declared. Replaced binaries are
+    /// used, so comparing runs with and without them gives the cost of the
+    /// Mercury instrumentation.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn estimate_resources(
+        &self,
+        ledger_snapshot: Rc<dyn SnapshotSource>,
+    ) -> Result<ResourceEstimate, RetroshadeError> {
+        self.estimate_resources_seeded(ledger_snapshot, random_seed())
+    }
+
+    /// Same as [`Self::estimate_resources`] with `seed` for the host's PRNG.
+    pub fn estimate_resources_seeded(
+        &self,
+        ledger_snapshot: Rc<dyn SnapshotSource>,
+        seed: [u8; 32],
+    ) -> Result<ResourceEstimate, RetroshadeError> {
+        let result = self.execute_recording(ledger_snapshot, seed)?;
+
+        Ok(ResourceEstimate {
+            recorded: result
+                .recorded_resources
+                .ok_or(RetroshadeError::MissingContext)?,
+            declared: self
+                .resources
+                .clone()
+                .ok_or(RetroshadeError::MissingContext)?,
+            cpu_instructions: result.budget.get_cpu_insns_consumed()?,
+            memory_bytes: result.budget.get_mem_bytes_consumed()?,
+            call_succeeded: result.invoke_result.is_ok(),
+        })
+    }
+
+    fn execute_recording(
+        &self,
+        ledger_snapshot: Rc<dyn SnapshotSource>,
+        seed: [u8; 32],
+    ) -> Result<InvokeHostFunctionHelperResult, RetroshadeError> {
         let internal_snapshot = InternalSnapshot::new(
             ledger_snapshot,
             self.target_pre_execution_state.clone(),
             self.force_remove.clone(),
         );
 
-        let svm_execution = execute_svm_in_recording_mode(
+        let execution = execute_svm_in_recording_mode(
             true,
             self.host_function
                 .as_ref()
@@ -184,85 +1563,713 @@ impl RetroshadesExecution {
                 .as_ref()
                 .ok_or(RetroshadeError::MissingContext)?,
             self.ledger_info.clone(),
-            rand::random::<[u8; 32]>(),
+            seed,
             Rc::new(internal_snapshot),
-        );
+            self.sandbox_limits.max_memory_bytes,
+        )
+        .map_err(|failure| self.host_failure(failure))?;
+
+        Ok(execution)
+    }
 
-        match svm_execution {
-            Ok(result) => Ok(RetroshadeExecutionResult {
-                retroshades: result.retroshades,
-                diagnostic: result.diagnostic_events,
-            }),
-            Err(host_error) => Err(RetroshadeError::SVMHost(host_error)),
+    /// Fails if the declared disk read bytes don't cover the code entries,
+    /// or the whole state.
+    fn check_declared_resources(&self, inputs: &EncodedInputs) -> Result<(), RetroshadeError> {
+        let declared = self
+            .resources
+            .as_ref()
+            .ok_or(RetroshadeError::MissingContext)?
+            .disk_read_bytes;
+
+        for (needed, dimension) in [
+            (inputs.code_bytes(), ResourceDimension::CodeBytes),
+            (inputs.entry_bytes(), ResourceDimension::DiskReadBytes),
+        ] {
+            if needed > declared as usize {
+                return Err(RetroshadeError::InsufficientDeclaredResources {
+                    needed,
+                    declared,
+                    dimension,
+                });
+            }
         }
+
+        Ok(())
     }
 
+    /// The error of a failed host invocation, [`RetroshadeError::SandboxLimit`]
+    /// if it ran out of sandbox memory.
+    fn host_failure(&self, failure: InvokeHostFunctionFailure) -> RetroshadeError {
+        let limit = self.sandbox_limits.max_memory_bytes;
+        if failure.memory_bytes > limit {
+            return RetroshadeError::SandboxLimit {
+                memory_bytes: failure.memory_bytes,
+                limit,
+            };
+        }
+
+        failure.into()
+    }
+
+    fn execution_result(
+        &self,
+        svm_execution: Result<InvokeHostFunctionHelperResult, RetroshadeError>,
+        execute: Duration,
+        stream: Option<&mut dyn ResultStream>,
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        #[cfg(feature = "metrics")]
+        telemetry::record_execution(&svm_execution, execute);
+        let result = svm_execution?;
+
+        // note: going over the memory limit fails the invocation, not the
+        // host, so it's told apart from contract failures here.
+        let limits = self.sandbox_limits;
+        let memory_bytes = result.budget.get_mem_bytes_consumed()?;
+        if memory_bytes > limits.max_memory_bytes {
+            return Err(RetroshadeError::SandboxLimit {
+                memory_bytes,
+                limit: limits.max_memory_bytes,
+            });
+        }
+
+        // note: the host charges retroshades and events to the memory limit,
+        // which bounds them before they get here. Truncating bounds the
+        // packing and export work.
+        let mut retroshades = result.retroshades;
+        let mut diagnostic_events = result.diagnostic_events;
+        let truncated = retroshades.len() > limits.max_retroshades
+            || diagnostic_events.len() > limits.max_output_events;
+        retroshades.truncate(limits.max_retroshades);
+        diagnostic_events.truncate(limits.max_output_events);
+
+        if result.invoke_result.is_err() {
+            match self.on_call_failure {
+                OnCallFailure::ReturnPartial => {}
+                OnCallFailure::Error => {
+                    return Err(RetroshadeError::ContractCallFailed {
+                        diagnostics: diagnostic_events,
+                    })
+                }
+                OnCallFailure::ReturnEmpty => retroshades.clear(),
+            }
+        }
+        #[cfg(feature = "metrics")]
+        for retroshade in &retroshades {
+            telemetry::record_retroshade(retroshade.contract_id.0);
+        }
+
+        let changes = if self.ledger_changes {
+            result.ledger_changes
+        } else {
+            vec![]
+        };
+        let (diagnostic, ledger_changes) = match stream {
+            Some(stream) => {
+                for event in diagnostic_events {
+                    stream.diagnostic(event);
+                }
+                for change in changes {
+                    stream.ledger_change(change.into());
+                }
+                (vec![], vec![])
+            }
+            None => (
+                diagnostic_events,
+                changes.into_iter().map(|c| c.into()).collect(),
+            ),
+        };
+
+        Ok(RetroshadeExecutionResult {
+            retroshades,
+            diagnostic,
+            invoke_result: result.invoke_result.map_err(|error| error.to_string()),
+            ledger_changes,
+            timings: ExecutionTimings {
+                execute,
+                ..self.timings
+            },
+            state: self.state_summary,
+            truncated,
+        })
+    }
+
+    /// Same as [`Self::retroshade_recording`] but for thread-safe snapshot sources.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn retroshade_recording_shared(
+        &self,
+        ledger_snapshot: Arc<dyn SnapshotSource + Send + Sync>,
+    ) -> Result<RetroshadeExecutionResult, RetroshadeError> {
+        self.retroshade_recording(Rc::new(SharedSnapshot::new(ledger_snapshot)))
+    }
+
+    #[cfg(any(test, feature = "rand"))]
     pub fn retroshade_packed_recording(
         &self,
         ledger_snapshot: Rc<dyn SnapshotSource>,
     ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
         let retroshade_exec = self.retroshade_recording(ledger_snapshot)?;
-        self.retroshade_prepare_for_db(retroshade_exec)
+        self.pack_result(retroshade_exec)
     }
 
+    #[cfg(any(test, feature = "rand"))]
+    pub fn retroshade_packed_recording_shared(
+        &self,
+        ledger_snapshot: Arc<dyn SnapshotSource + Send + Sync>,
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        self.retroshade_packed_recording(Rc::new(SharedSnapshot::new(ledger_snapshot)))
+    }
+
+    /// Executes and packs, see [`Self::pack_result`].
+    #[cfg(any(test, feature = "rand"))]
     pub fn retroshade_packed(&self) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
-        let retroshade_exec = self.retroshade()?;
-        self.retroshade_prepare_for_db(retroshade_exec)
+        self.pack_result(self.retroshade()?)
+    }
+
+    /// Same as [`Self::retroshade_packed`] with `seed` for the host's PRNG.
+    pub fn retroshade_packed_seeded(
+        &self,
+        seed: [u8; 32],
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        self.pack_result(self.retroshade_seeded(seed)?)
     }
 
-    /// Perfect for exporting to SQL databases.
-    fn retroshade_prepare_for_db(
+    /// Executes with the original binaries and with the Mercury ones, see
+    /// [`Self::retroshade_compare_seeded`].
+    #[cfg(any(test, feature = "rand"))]
+    pub fn retroshade_compare(&mut self) -> Result<Comparison, RetroshadeError> {
+        self.retroshade_compare_seeded(random_seed())
+    }
+
+    /// Executes the built state with the Mercury binaries, then with the
+    /// binaries they replaced, both with `seed`, and diffs the results.
+    /// Catches replacements changing what the contracts do before they're
+    /// rolled out.
+    pub fn retroshade_compare_seeded(
+        &mut self,
+        seed: [u8; 32],
+    ) -> Result<Comparison, RetroshadeError> {
+        let replaced = self.retroshade_seeded(seed)?;
+
+        self.swap_binaries();
+        let original = self.retroshade_seeded(seed);
+        self.swap_binaries();
+        let original = original?;
+
+        let diff = ExecutionDiff::new(&original, &replaced);
+        Ok(Comparison {
+            original,
+            replaced,
+            diff,
+        })
+    }
+
+    /// Packs the retroshades of an execution result for exporting, e.g. to
+    /// SQL databases. Doesn't execute anything, so callers wanting both the
+    /// raw and the packed retroshades can execute once and pack that result.
+    pub fn pack_result(
         &self,
         retroshade_exec: RetroshadeExecutionResult,
     ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
-        if let Some(first) = retroshade_exec.diagnostic.first() {
-            if !first.in_successful_contract_call {
-                return Err(RetroshadeError::NonSuccessfulContractCall(
-                    retroshade_exec.diagnostic,
-                ));
+        self.pack_result_interned(retroshade_exec, &self.interner)
+    }
+
+    /// Same as [`Self::pack_result`] but interning names with `interner`.
+    pub(crate) fn pack_result_interned(
+        &self,
+        mut retroshade_exec: RetroshadeExecutionResult,
+        interner: &Interner,
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        let start = Instant::now();
+
+        let context = self.tx_context();
+        let source_account = self
+            .source_account_strkey()
+            .ok_or(RetroshadeError::MissingContext)?;
+        let (exports, duplicates_removed) =
+            self.dedup_exports(std::mem::take(&mut retroshade_exec.retroshades))?;
+        let specs = self.contract_specs();
+
+        let packed = exports.into_iter().map(|(idx, retroshade)| {
+            self.pack_checked(idx, retroshade, &context, &source_account, &specs, interner)
+        });
+        let (retroshades, conversion_errors, spec_warnings) = self.collect_packed(packed)?;
+
+        Ok(Self::packed_result(
+            retroshade_exec,
+            retroshades,
+            conversion_errors,
+            spec_warnings,
+            duplicates_removed,
+            start.elapsed(),
+        ))
+    }
+
+    /// Same as [`Self::pack_result`] but converting the exports concurrently,
+    /// for transactions emitting hundreds of retroshades. The packed exports
+    /// and conversion errors are in the same order as the sequential ones.
+    #[cfg(feature = "parallel")]
+    pub fn pack_result_parallel(
+        &self,
+        mut retroshade_exec: RetroshadeExecutionResult,
+    ) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+        use rayon::prelude::*;
+
+        let start = Instant::now();
+
+        let context = self.tx_context();
+        let source_account = self
+            .source_account_strkey()
+            .ok_or(RetroshadeError::MissingContext)?;
+        let (exports, duplicates_removed) =
+            self.dedup_exports(std::mem::take(&mut retroshade_exec.retroshades))?;
+        let specs = self.contract_specs();
+
+        let packed: Vec<_> = exports
+            .into_par_iter()
+            .map(|(idx, retroshade)| {
+                self.pack_checked(
+                    idx,
+                    retroshade,
+                    &context,
+                    &source_account,
+                    &specs,
+                    &self.interner,
+                )
+            })
+            .collect();
+        let (retroshades, conversion_errors, spec_warnings) = self.collect_packed(packed)?;
+
+        Ok(Self::packed_result(
+            retroshade_exec,
+            retroshades,
+            conversion_errors,
+            spec_warnings,
+            duplicates_removed,
+            start.elapsed(),
+        ))
+    }
+
+    /// Specs of the contracts in the pre-execution state by contract id,
+    /// read from the code they execute, replaced binaries included. Empty
+    /// unless spec column types or spec validation are on.
+    fn contract_specs(&self) -> HashMap<[u8; 32], ContractSpec> {
+        let mut specs = HashMap::new();
+        if !self.spec_column_types && self.spec_validation == SpecValidation::Off {
+            return specs;
+        }
+
+        let code: HashMap<&Hash, &[u8]> = self
+            .target_pre_execution_state
+            .iter()
+            .filter_map(|keyed| match &keyed.entry.data {
+                LedgerEntryData::ContractCode(code) => Some((&code.hash, keyed.code()?)),
+                _ => None,
+            })
+            .collect();
+
+        for keyed in &self.target_pre_execution_state {
+            let LedgerEntryData::ContractData(data) = &keyed.entry.data else {
+                continue;
+            };
+            let (ScAddress::Contract(contract_id), ScVal::ContractInstance(instance)) =
+                (&data.contract, &data.val)
+            else {
+                continue;
+            };
+            let ContractExecutable::Wasm(wasm_hash) = &instance.executable else {
+                continue;
+            };
+            let contract_id: Hash = contract_id.clone().into();
+
+            // note: a spec that can't be read only loses the typing, the
+            // values are still packed from what they hold.
+            if let Some(spec) = code
+                .get(wasm_hash)
+                .and_then(|wasm| ContractSpec::from_wasm(wasm).ok())
+            {
+                specs.insert(contract_id.0, spec);
+            }
+        }
+
+        specs
+    }
+
+    /// Validates the export against the spec of its contract if enabled,
+    /// then packs it. Mismatches are returned alongside the packed export
+    /// with [`SpecValidation::Warn`].
+    fn pack_checked(
+        &self,
+        idx: usize,
+        retroshade: RetroshadeExport,
+        context: &TxContext,
+        source_account: &str,
+        specs: &HashMap<[u8; 32], ContractSpec>,
+        interner: &Interner,
+    ) -> PackedExport {
+        let spec = specs.get(&retroshade.contract_id.0);
+        let mismatches = match (self.spec_validation, spec, &retroshade.event_object) {
+            (SpecValidation::Off, _, _) => vec![],
+            (_, Some(spec), ScVal::Map(Some(event))) => spec.validate(event),
+            _ => vec![],
+        };
+        if self.spec_validation == SpecValidation::Error && !mismatches.is_empty() {
+            let error = RetroshadeError::EventSpecMismatch {
+                contract_id: self.contract_id_text(&retroshade.contract_id),
+                mismatches,
+            };
+            return (idx, Err(error), vec![]);
+        }
+
+        let packed = self.pack_export(
+            retroshade,
+            idx as u32,
+            context,
+            source_account,
+            spec.filter(|_| self.spec_column_types),
+            interner,
+        );
+        (idx, packed, mismatches)
+    }
+
+    /// Indexes the exports by emission order, dropping duplicates when
+    /// deduplication is on. Returns how many were dropped.
+    fn dedup_exports(
+        &self,
+        retroshades: Vec<RetroshadeExport>,
+    ) -> Result<(Vec<(usize, RetroshadeExport)>, usize), RetroshadeError> {
+        let mut exports = Vec::with_capacity(retroshades.len());
+        let mut seen = HashSet::new();
+        let mut duplicates_removed = 0;
+
+        for (idx, retroshade) in retroshades.into_iter().enumerate() {
+            if self.dedup && !self.emit_index_column && !seen.insert(export_digest(&retroshade)?) {
+                duplicates_removed += 1;
+                continue;
             }
+            exports.push((idx, retroshade));
         }
 
+        Ok((exports, duplicates_removed))
+    }
+
+    /// Splits the converted exports from the failed ones and gathers the spec
+    /// warnings, or returns the first failure with fail fast.
+    fn collect_packed(
+        &self,
+        packed: impl IntoIterator<Item = PackedExport>,
+    ) -> Result<PackedExports, RetroshadeError> {
         let mut pretty_retroshades = Vec::new();
+        let mut conversion_errors = Vec::new();
+        let mut spec_warnings = Vec::new();
 
-        for retroshade in retroshade_exec.retroshades {
-            let mut packed_event_entries = Vec::new();
+        for (idx, packed, mismatches) in packed {
+            spec_warnings.extend(mismatches.into_iter().map(|mismatch| (idx, mismatch)));
+            #[cfg(feature = "metrics")]
+            if packed.is_err() {
+                telemetry::record_conversion_failure();
+            }
+            match packed {
+                Ok(pretty) => pretty_retroshades.push(pretty),
+                Err(error) if self.fail_fast => return Err(error),
+                Err(error) => conversion_errors.push((idx, error)),
+            }
+        }
 
-            let map_entry = if let ScVal::Map(Some(map)) = retroshade.event_object {
-                map
-            } else {
-                return Err(RetroshadeError::MalformedRetroshadeEvent);
-            };
+        Ok((pretty_retroshades, conversion_errors, spec_warnings))
+    }
 
-            for key_value in map_entry.0.to_vec() {
-                let packed_entry = PackedEventEntry {
-                    name: if let ScVal::Symbol(symbol) = key_value.key {
-                        symbol.to_string()
-                    } else {
-                        return Err(RetroshadeError::MalformedRetroshadeEvent);
+    fn packed_result(
+        retroshade_exec: RetroshadeExecutionResult,
+        retroshades: Vec<RetroshadeExportPretty>,
+        conversion_errors: Vec<(usize, RetroshadeError)>,
+        spec_warnings: Vec<(usize, SpecMismatch)>,
+        duplicates_removed: usize,
+        convert: Duration,
+    ) -> RetroshadeExecutionResultPretty {
+        RetroshadeExecutionResultPretty {
+            call_succeeded: retroshade_exec.invoke_result.is_ok(),
+            retroshades,
+            conversion_errors,
+            duplicates_removed,
+            spec_warnings,
+            diagnostic: retroshade_exec.diagnostic,
+            invoke_result: retroshade_exec.invoke_result,
+            ledger_changes: retroshade_exec.ledger_changes,
+            timings: ExecutionTimings {
+                convert,
+                ..retroshade_exec.timings
+            },
+            state: retroshade_exec.state,
+            truncated: retroshade_exec.truncated,
+        }
+    }
+
+    fn pack_export(
+        &self,
+        retroshade: RetroshadeExport,
+        emit_index: u32,
+        context: &TxContext,
+        source_account: &str,
+        spec: Option<&ContractSpec>,
+        interner: &Interner,
+    ) -> Result<RetroshadeExportPretty, RetroshadeError> {
+        let contract_id = self.contract_id_text(&retroshade.contract_id);
+        let malformed = |reason, value: &ScVal| RetroshadeError::MalformedRetroshadeEvent {
+            contract_id: contract_id.clone(),
+            reason,
+            value_debug: format!("{:?}", value),
+        };
+
+        let target = match &retroshade.target {
+            ScVal::Symbol(symbol) => symbol.to_string(),
+            other => {
+                return Err(malformed(
+                    MalformedReason::TargetNotSymbol {
+                        found: other.discriminant(),
                     },
-                    value: FromScVal::from_scval(key_value.val, &mut 0),
-                };
+                    other,
+                ))
+            }
+        };
+        let target = if target.len() <= self.max_target_len {
+            interner.intern(&target)
+        } else {
+            match self.long_target_policy {
+                LongTargetPolicy::Error => {
+                    return Err(malformed(
+                        MalformedReason::TargetTooLong {
+                            len: target.len(),
+                            max: self.max_target_len,
+                        },
+                        &retroshade.target,
+                    ))
+                }
+                LongTargetPolicy::TruncateWithHash => {
+                    interner.intern(&truncate_with_hash(&target, self.max_target_len))
+                }
+            }
+        };
+
+        let map_entry = match &retroshade.event_object {
+            ScVal::Map(Some(map)) => map,
+            other => return Err(malformed(MalformedReason::EventNotMap, other)),
+        };
+
+        let spec_fields = spec
+            .and_then(|spec| {
+                let names: Vec<String> = map_entry
+                    .0
+                    .iter()
+                    .filter_map(|key_value| match &key_value.key {
+                        ScVal::Symbol(symbol) => Some(symbol.to_string()),
+                        _ => None,
+                    })
+                    .collect();
+                let name = spec.struct_with_fields(names.iter().map(String::as_str))?;
+                Some(spec.spec_for_struct(&name))
+            })
+            .unwrap_or_default();
+
+        let reserved_columns = self.reserved_columns();
+        let mut packed_event_entries = Vec::new();
+
+        for key_value in map_entry.0.iter() {
+            let name = match &key_value.key {
+                ScVal::Symbol(symbol) => symbol.to_string(),
+                other => return Err(malformed(MalformedReason::FieldNameNotSymbol, other)),
+            };
+            let field = spec_fields.iter().find(|(field, _)| *field == name);
+            let convert = |options: &ConversionOptions| match spec.zip(field) {
+                Some((spec, (_, type_def))) => {
+                    spec.from_scval_with(key_value.val.clone(), type_def, options)
+                }
+                None => FromScVal::from_scval_with(key_value.val.clone(), &mut 0, options),
+            };
+            let packed_entry = PackedEventEntry {
+                name: interner.intern(&name),
+                value: convert(&self.conversion_options),
+            };
 
-                packed_event_entries.push(packed_entry);
+            if reserved_columns.contains(&packed_entry.name.as_str()) {
+                return Err(RetroshadeError::ReservedColumn(
+                    packed_entry.name.to_string(),
+                ));
+            }
+
+            packed_event_entries.push(packed_entry);
+
+            if self.conversion_options.address_format == AddressFormat::Both
+                && conversion::contains_address(&key_value.val)
+            {
+                packed_event_entries.push(PackedEventEntry {
+                    name: interner.intern(&format!("{}_hex", name)),
+                    value: convert(&self.conversion_options.hex()),
+                });
             }
+        }
 
-            let pretty = RetroshadeExportPretty {
-                contract_id: stellar_strkey::Contract(retroshade.contract_id.0).to_string(),
-                target: if let ScVal::Symbol(symbol) = retroshade.target {
-                    symbol.to_string()
-                } else {
-                    return Err(RetroshadeError::MalformedRetroshadeEvent);
+        if self.context_columns {
+            packed_event_entries.extend(context_columns(context, interner));
+        }
+        if self.source_account_column {
+            packed_event_entries.push(PackedEventEntry {
+                name: interner.intern(SOURCE_ACCOUNT_COLUMN),
+                value: FromScVal {
+                    dbtype: Type::TEXT,
+                    kind: TypeKind::Text(source_account.to_string()),
                 },
-                event: packed_event_entries,
-            };
+            });
+        }
+        if self.emit_index_column {
+            packed_event_entries.push(PackedEventEntry {
+                name: interner.intern(EMIT_INDEX_COLUMN),
+                value: FromScVal::from_scval(ScVal::U32(emit_index), &mut 0),
+            });
+        }
+
+        let mut pretty = RetroshadeExportPretty {
+            contract_id: interner.intern(&contract_id),
+            target,
+            event: packed_event_entries,
+            context: context.clone(),
+            source_account: source_account.to_string(),
+            emit_index,
+            event_object_xdr: retroshade
+                .event_object
+                .to_xdr(Limits::none())
+                .map_err(|_| RetroshadeError::MalformedXdr)?,
+        };
 
-            pretty_retroshades.push(pretty)
+        if self.idempotency_key_column {
+            let key = pretty.idempotency_key(&context.tx_hash);
+            pretty.event.push(PackedEventEntry {
+                name: interner.intern(IDEMPOTENCY_KEY_COLUMN),
+                value: FromScVal {
+                    dbtype: Type::TEXT,
+                    kind: TypeKind::Text(hex::encode(key)),
+                },
+            });
         }
 
-        Ok(RetroshadeExecutionResultPretty {
-            retroshades: pretty_retroshades,
-            diagnostic: retroshade_exec.diagnostic,
-        })
+        if self.schema_hash_column {
+            let hash = pretty.schema_hash();
+            pretty.event.push(PackedEventEntry {
+                name: interner.intern(SCHEMA_HASH_COLUMN),
+                value: FromScVal {
+                    dbtype: Type::TEXT,
+                    kind: TypeKind::Text(hex::encode(hash)),
+                },
+            });
+        }
+
+        Ok(pretty)
     }
 }
+
+/// Settings of [`process_transaction`], the defaults being those of a new
+/// [`RetroshadesExecution`]. Anything else is set on an execution built
+/// step by step.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessOptions {
+    /// Seed of the host's PRNG, the transaction's hash when `None` so that
+    /// re-executions agree.
+    pub seed: Option<[u8; 32]>,
+    pub on_call_failure: OnCallFailure,
+    pub sandbox_limits: SandboxLimits,
+    pub replacement_policy: ReplacementPolicy,
+    pub staleness_guard: StalenessGuard,
+    pub conversion_options: ConversionOptions,
+}
+
+/// Re-executes a transaction with the Mercury binaries and packs its
+/// retroshades: building the state, executing and packing in one call.
+///
+/// ```ignore
+/// let result = retroshade::process_transaction(
+///     &snapshot, envelope, meta, ledger_info, mercury_contracts, Default::default(),
+/// )?;
+/// ```
+pub fn process_transaction(
+    snapshot_source: &dyn SnapshotSource,
+    tx_envelope: TransactionV1Envelope,
+    tx_meta: TransactionMeta,
+    ledger_info: LedgerInfo,
+    mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    options: ProcessOptions,
+) -> Result<RetroshadeExecutionResultPretty, RetroshadeError> {
+    let mut retroshades = RetroshadesExecution::new(ledger_info);
+    retroshades.set_on_call_failure(options.on_call_failure);
+    retroshades.set_sandbox_limits(options.sandbox_limits);
+    retroshades.set_replacement_policy(options.replacement_policy);
+    retroshades.set_staleness_guard(options.staleness_guard);
+    retroshades.set_conversion_options(options.conversion_options);
+
+    retroshades.build_from_envelope_and_meta(
+        snapshot_source,
+        tx_envelope,
+        tx_meta,
+        mercury_contracts,
+    )?;
+
+    let seed = options
+        .seed
+        .unwrap_or_else(|| retroshades.tx_context().tx_hash.0);
+    retroshades.retroshade_packed_seeded(seed)
+}
+
+/// Shortens the target to at most `max_len` bytes, ending it with `_` and the
+/// first 8 hex characters of the full name's SHA-256. Targets that fit are
+/// returned as is, limits leaving no room for the suffix keep only the
+/// hash's first `max_len` characters.
+pub(crate) fn truncate_with_hash(target: &str, max_len: usize) -> String {
+    if target.len() <= max_len {
+        return target.to_string();
+    }
+
+    let hash = hex::encode(Sha256::digest(target.as_bytes()));
+    if max_len < 9 {
+        return hash[..max_len].to_string();
+    }
+
+    // note: table names aren't symbols and may hold any character.
+    let mut keep = max_len - 9;
+    while !target.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    format!("{}_{}", &target[..keep], &hash[..8])
+}
+
+/// Seed of the host's PRNG for executions that aren't given one.
+#[cfg(any(test, feature = "rand"))]
+pub(crate) fn random_seed() -> [u8; 32] {
+    rand::random()
+}
+
+/// An export by emission index, packed or not, with its spec mismatches.
+type PackedExport = (
+    usize,
+    Result<RetroshadeExportPretty, RetroshadeError>,
+    Vec<SpecMismatch>,
+);
+
+/// Packed exports, the conversion errors of the others and the spec warnings,
+/// by emission index.
+type PackedExports = (
+    Vec<RetroshadeExportPretty>,
+    Vec<(usize, RetroshadeError)>,
+    Vec<(usize, SpecMismatch)>,
+);
+
+/// Digest identifying a retroshade by its contract, target and event object.
+fn export_digest(retroshade: &RetroshadeExport) -> Result<[u8; 32], RetroshadeError> {
+    let mut hasher = Sha256::new();
+    hasher.update(retroshade.contract_id.0);
+    for val in [&retroshade.target, &retroshade.event_object] {
+        hasher.update(
+            val.to_xdr(Limits::none())
+                .map_err(|_| RetroshadeError::MalformedXdr)?,
+        );
+    }
+
+    Ok(hasher.finalize().into())
+}
@@ -0,0 +1,43 @@
+//! MessagePack encoding of execution results for transport between services.
+//! Encodings start with [`FORMAT_VERSION`], bumped whenever the serialized
+//! shape of the results changes.
+
+use serde::Serialize;
+
+use crate::{RetroshadeError, RetroshadeExecutionResult, RetroshadeExecutionResultPretty};
+
+pub const FORMAT_VERSION: u8 = 2;
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, RetroshadeError> {
+    let mut encoded = vec![FORMAT_VERSION];
+    rmp_serde::encode::write_named(&mut encoded, value)
+        .map_err(|e| RetroshadeError::Export(e.to_string()))?;
+
+    Ok(encoded)
+}
+
+impl RetroshadeExecutionResultPretty {
+    /// Conversion errors only keep their message, see [`RetroshadeError::Decoded`].
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, RetroshadeError> {
+        encode(self)
+    }
+
+    pub fn from_msgpack(encoded: &[u8]) -> Result<Self, RetroshadeError> {
+        match encoded.split_first() {
+            Some((&FORMAT_VERSION, payload)) => {
+                rmp_serde::from_slice(payload).map_err(|e| RetroshadeError::Export(e.to_string()))
+            }
+            Some((version, _)) => Err(RetroshadeError::Export(format!(
+                "unsupported msgpack format version {}",
+                version
+            ))),
+            None => Err(RetroshadeError::Export("empty msgpack payload".to_string())),
+        }
+    }
+}
+
+impl RetroshadeExecutionResult {
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, RetroshadeError> {
+        encode(self)
+    }
+}
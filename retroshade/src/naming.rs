@@ -0,0 +1,72 @@
+//! Table naming strategies for packed exports. The target alone collides when
+//! different contracts emit to the same target, so callers building DDL and
+//! inserts pick a strategy and use it for both.
+
+use std::fmt;
+
+use crate::{truncate_with_hash, RetroshadeExportPretty, MAX_TARGET_LEN};
+
+/// Length of the contract id prefix used by [`TableNaming::ContractPrefixed`].
+const CONTRACT_PREFIX_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum TableNaming {
+    /// The target is the table name.
+    #[default]
+    TargetOnly,
+
+    /// The target prefixed with the start of the contract id, e.g. `caaaaaaa_swaps`.
+    ContractPrefixed,
+
+    /// The target in a schema named after the contract id.
+    SchemaPerContract,
+
+    Custom(fn(&RetroshadeExportPretty) -> String),
+}
+
+/// Where an export is stored. Both parts are valid unquoted identifiers as long
+/// as the target is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableName {
+    pub schema: Option<String>,
+    pub table: String,
+}
+
+impl fmt::Display for TableName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.schema {
+            Some(schema) => write!(f, "{}.{}", schema, self.table),
+            None => write!(f, "{}", self.table),
+        }
+    }
+}
+
+impl TableNaming {
+    /// Identifiers longer than [`MAX_TARGET_LEN`] are truncated with a stable
+    /// hash suffix.
+    pub fn table_name(&self, export: &RetroshadeExportPretty) -> TableName {
+        let contract_id = export.contract_id.to_lowercase();
+        let (schema, table) = match self {
+            TableNaming::TargetOnly => (None, export.target.to_string()),
+            TableNaming::ContractPrefixed => (
+                None,
+                format!("{}_{}", &contract_id[..CONTRACT_PREFIX_LEN], export.target),
+            ),
+            TableNaming::SchemaPerContract => (Some(contract_id), export.target.to_string()),
+            TableNaming::Custom(name) => (None, name(export)),
+        };
+
+        TableName {
+            schema: schema.map(|schema| identifier(&schema)),
+            table: identifier(&table),
+        }
+    }
+}
+
+fn identifier(name: &str) -> String {
+    if name.len() <= MAX_TARGET_LEN {
+        name.to_string()
+    } else {
+        truncate_with_hash(name, MAX_TARGET_LEN)
+    }
+}
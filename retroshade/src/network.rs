@@ -0,0 +1,118 @@
+//! Well-known networks, and the ledger settings to fall back to when the
+//! source of a re-execution doesn't provide them.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const PUBNET_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+pub const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+pub const FUTURENET_PASSPHRASE: &str = "Test SDF Future Network ; October 2022";
+pub const STANDALONE_PASSPHRASE: &str = "Standalone Network ; February 2017";
+
+/// Network id the host expects in [`soroban_env_host::LedgerInfo`], i.e. the
+/// hash of the passphrase. Auth signatures are over it, so a wrong one fails
+/// them.
+pub fn network_id(passphrase: &str) -> [u8; 32] {
+    Sha256::digest(passphrase.as_bytes()).into()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Pubnet,
+    Testnet,
+    Futurenet,
+    Standalone,
+}
+
+/// Ledger settings that aren't part of the transaction or its ledger header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetworkDefaults {
+    pub base_reserve: u32,
+    pub min_temp_entry_ttl: u32,
+    pub min_persistent_entry_ttl: u32,
+    pub max_entry_ttl: u32,
+}
+
+impl Network {
+    pub const ALL: [Network; 4] = [
+        Network::Pubnet,
+        Network::Testnet,
+        Network::Futurenet,
+        Network::Standalone,
+    ];
+
+    pub fn passphrase(self) -> &'static str {
+        match self {
+            Network::Pubnet => PUBNET_PASSPHRASE,
+            Network::Testnet => TESTNET_PASSPHRASE,
+            Network::Futurenet => FUTURENET_PASSPHRASE,
+            Network::Standalone => STANDALONE_PASSPHRASE,
+        }
+    }
+
+    pub fn network_id(self) -> [u8; 32] {
+        network_id(self.passphrase())
+    }
+
+    pub fn from_passphrase(passphrase: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|network| network.passphrase() == passphrase)
+    }
+
+    pub fn from_network_id(id: &[u8; 32]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|network| &network.network_id() == id)
+    }
+
+    /// The public networks share the state archival settings they launched
+    /// Soroban with, the standalone ones match the quickstart image.
+    pub fn defaults(self) -> NetworkDefaults {
+        match self {
+            Network::Pubnet | Network::Testnet | Network::Futurenet => NetworkDefaults {
+                base_reserve: 5_000_000,
+                min_temp_entry_ttl: 17280,
+                min_persistent_entry_ttl: 2073600,
+                max_entry_ttl: 3110400,
+            },
+            Network::Standalone => NetworkDefaults {
+                base_reserve: 100_000_000,
+                min_temp_entry_ttl: 16,
+                min_persistent_entry_ttl: 120960,
+                max_entry_ttl: 3110400,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Network::Pubnet => "pubnet",
+            Network::Testnet => "testnet",
+            Network::Futurenet => "futurenet",
+            Network::Standalone => "standalone",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|network| network.to_string() == value)
+            .ok_or_else(|| {
+                format!(
+                    "unknown network {value}, expected pubnet, testnet, futurenet or standalone"
+                )
+            })
+    }
+}
@@ -0,0 +1,96 @@
+//! Tagging packed exports with the owner of the contract that emitted them,
+//! for integrators indexing the retroshades of many tenants.
+//!
+//! Owners are registered by contract id with an id of the caller's choosing,
+//! then [`Owners::tag`] resolves the owner of each export of a result.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use soroban_env_host::xdr::Hash;
+
+use crate::{RetroshadeError, RetroshadeExportPretty};
+
+/// What tagging does with exports of contracts that have no registered
+/// owner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnregisteredContracts {
+    /// Keep the export without an owner.
+    #[default]
+    Keep,
+
+    /// Leave the export out of the tagged ones.
+    Drop,
+
+    /// Fail tagging with [`RetroshadeError::UnregisteredContract`].
+    Error,
+}
+
+/// An export with the owner of its contract.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OwnedExport<O> {
+    /// `None` only for unregistered contracts with
+    /// [`UnregisteredContracts::Keep`].
+    pub owner: Option<O>,
+    pub export: RetroshadeExportPretty,
+}
+
+/// Owners of contracts, `O` being whatever identifies a tenant.
+#[derive(Clone, Debug)]
+pub struct Owners<O> {
+    by_contract: HashMap<String, O>,
+    unregistered: UnregisteredContracts,
+}
+
+impl<O: Clone> Owners<O> {
+    pub fn new(owners: HashMap<Hash, O>) -> Self {
+        // note: exports carry the hex of their contract id with
+        // `AddressFormat::Hex`, so both renderings are keys.
+        let mut by_contract = HashMap::new();
+        for (contract_id, owner) in owners {
+            by_contract.insert(hex::encode(contract_id.0), owner.clone());
+            by_contract.insert(stellar_strkey::Contract(contract_id.0).to_string(), owner);
+        }
+
+        Self {
+            by_contract,
+            unregistered: UnregisteredContracts::default(),
+        }
+    }
+
+    pub fn set_unregistered(&mut self, unregistered: UnregisteredContracts) {
+        self.unregistered = unregistered;
+    }
+
+    /// Owner of the contract with strkey or hex `contract_id`.
+    pub fn owner_of(&self, contract_id: &str) -> Option<&O> {
+        self.by_contract.get(contract_id)
+    }
+
+    /// Tags `exports` with their owners, in the same order.
+    pub fn tag(
+        &self,
+        exports: Vec<RetroshadeExportPretty>,
+    ) -> Result<Vec<OwnedExport<O>>, RetroshadeError> {
+        let mut tagged = Vec::with_capacity(exports.len());
+
+        for export in exports {
+            let owner = self.owner_of(&export.contract_id).cloned();
+            if owner.is_none() {
+                match self.unregistered {
+                    UnregisteredContracts::Keep => {}
+                    UnregisteredContracts::Drop => continue,
+                    UnregisteredContracts::Error => {
+                        return Err(RetroshadeError::UnregisteredContract(
+                            export.contract_id.to_string(),
+                        ))
+                    }
+                }
+            }
+
+            tagged.push(OwnedExport { owner, export });
+        }
+
+        Ok(tagged)
+    }
+}
@@ -0,0 +1,337 @@
+//! Minimal soroban-rpc client, enough to re-execute a transaction from its hash
+//! alone: fetching it, the ledger it was applied in and the entries it reads.
+//!
+//! The entries are served as of the RPC's latest ledger, the execution resets
+//! the ones the transaction wrote from its meta like with any other source.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use soroban_env_host::{
+    storage::{EntryWithLiveUntil, SnapshotSource},
+    xdr::{
+        ConfigSettingEntry, ConfigSettingId, Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt,
+        LedgerHeaderHistoryEntry, LedgerKey, LedgerKeyConfigSetting, Limits, ReadXdr, ScErrorCode,
+        ScErrorType, TransactionEnvelope, TransactionMeta, WriteXdr,
+    },
+    HostError, LedgerInfo,
+};
+
+use crate::{network::Network, snapshot::SnapshotSourceExt, RetroshadeError};
+
+/// Base reserve used when the ledger header isn't available anymore and the
+/// network isn't a known one. It has been the same on pubnet and testnet
+/// since protocol 10.
+pub const DEFAULT_BASE_RESERVE: u32 = 5_000_000;
+
+#[derive(Clone)]
+pub struct RpcClient {
+    agent: ureq::Agent,
+    url: String,
+}
+
+/// A transaction as returned by `getTransaction`.
+#[derive(Clone, Debug)]
+pub struct RpcTransaction {
+    /// Sequence of the ledger the transaction was applied in.
+    pub ledger: u32,
+    /// Close time of that ledger.
+    pub created_at: u64,
+    pub envelope: TransactionEnvelope,
+    pub meta: TransactionMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetTransactionResponse {
+    status: String,
+    ledger: Option<u32>,
+    created_at: Option<Value>,
+    envelope_xdr: Option<String>,
+    result_meta_xdr: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetLedgersResponse {
+    ledgers: Vec<RpcLedger>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcLedger {
+    sequence: u32,
+    header_xdr: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetLatestLedgerResponse {
+    protocol_version: u32,
+}
+
+#[derive(Deserialize)]
+struct GetNetworkResponse {
+    passphrase: String,
+}
+
+#[derive(Deserialize)]
+struct GetLedgerEntriesResponse {
+    entries: Option<Vec<RpcLedgerEntry>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcLedgerEntry {
+    xdr: String,
+    last_modified_ledger_seq: u32,
+    live_until_ledger_seq: Option<u32>,
+}
+
+fn rpc_error(message: impl ToString) -> RetroshadeError {
+    RetroshadeError::Rpc(message.to_string())
+}
+
+fn decode<T: ReadXdr>(xdr: &str) -> Result<T, RetroshadeError> {
+    T::from_xdr_base64(xdr, Limits::none()).map_err(rpc_error)
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            url: url.into(),
+        }
+    }
+
+    fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RetroshadeError> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(body)
+            .map_err(|e| rpc_error(format!("{}: {}", method, e)))?
+            .into_json()
+            .map_err(|e| rpc_error(format!("{}: {}", method, e)))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(rpc_error(format!("{}: {}", method, error)));
+        }
+
+        serde_json::from_value(response["result"].take())
+            .map_err(|e| rpc_error(format!("{}: {}", method, e)))
+    }
+
+    pub fn get_network_passphrase(&self) -> Result<String, RetroshadeError> {
+        let network: GetNetworkResponse = self.request("getNetwork", json!({}))?;
+        Ok(network.passphrase)
+    }
+
+    pub fn get_transaction(&self, hash: &Hash) -> Result<RpcTransaction, RetroshadeError> {
+        let hash = hex::encode(hash.0);
+        let response: GetTransactionResponse =
+            self.request("getTransaction", json!({ "hash": hash }))?;
+
+        if response.status == "NOT_FOUND" {
+            return Err(rpc_error(format!(
+                "transaction {} not found, it may be outside of the RPC's retention window",
+                hash
+            )));
+        }
+
+        let missing = |field: &str| rpc_error(format!("getTransaction: missing {}", field));
+        // note: older RPC versions return the close time as a string.
+        let created_at = match response.created_at.ok_or_else(|| missing("createdAt"))? {
+            Value::Number(number) => number.as_u64(),
+            Value::String(string) => string.parse().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| rpc_error("getTransaction: invalid createdAt"))?;
+
+        Ok(RpcTransaction {
+            ledger: response.ledger.ok_or_else(|| missing("ledger"))?,
+            created_at,
+            envelope: decode(
+                &response
+                    .envelope_xdr
+                    .ok_or_else(|| missing("envelopeXdr"))?,
+            )?,
+            meta: decode(
+                &response
+                    .result_meta_xdr
+                    .ok_or_else(|| missing("resultMetaXdr"))?,
+            )?,
+        })
+    }
+
+    /// Entries and their live until ledger, in no particular order. Entries
+    /// that don't exist are left out.
+    pub fn get_ledger_entries(
+        &self,
+        keys: &[LedgerKey],
+    ) -> Result<Vec<(LedgerEntry, Option<u32>)>, RetroshadeError> {
+        let keys = keys
+            .iter()
+            .map(|key| key.to_xdr_base64(Limits::none()).map_err(rpc_error))
+            .collect::<Result<Vec<_>, _>>()?;
+        let response: GetLedgerEntriesResponse =
+            self.request("getLedgerEntries", json!({ "keys": keys }))?;
+
+        response
+            .entries
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| {
+                let data: LedgerEntryData = decode(&entry.xdr)?;
+                Ok((
+                    LedgerEntry {
+                        last_modified_ledger_seq: entry.last_modified_ledger_seq,
+                        data,
+                        ext: LedgerEntryExt::V0,
+                    },
+                    entry.live_until_ledger_seq,
+                ))
+            })
+            .collect()
+    }
+
+    /// Ledger info matching the ledger `transaction` was applied in.
+    ///
+    /// The header comes from `getLedgers`. RPCs that don't support it or
+    /// don't retain the ledger anymore fall back to `getLatestLedger`'s
+    /// protocol version, the transaction's close time and the network's
+    /// default base reserve.
+    pub fn ledger_info(
+        &self,
+        transaction: &RpcTransaction,
+        network_id: [u8; 32],
+    ) -> Result<LedgerInfo, RetroshadeError> {
+        let (protocol_version, timestamp, base_reserve) = match self.get_ledger(transaction.ledger)
+        {
+            Ok(header) => (
+                header.header.ledger_version,
+                header.header.scp_value.close_time.0,
+                header.header.base_reserve,
+            ),
+            Err(error) => {
+                log::warn!("falling back to getLatestLedger: {}", error);
+                let latest: GetLatestLedgerResponse = self.request("getLatestLedger", json!({}))?;
+                (
+                    latest.protocol_version,
+                    transaction.created_at,
+                    Network::from_network_id(&network_id).map_or(DEFAULT_BASE_RESERVE, |network| {
+                        network.defaults().base_reserve
+                    }),
+                )
+            }
+        };
+
+        let archival_key = LedgerKey::ConfigSetting(LedgerKeyConfigSetting {
+            config_setting_id: ConfigSettingId::StateArchival,
+        });
+        let archival = self
+            .get_ledger_entries(&[archival_key])?
+            .into_iter()
+            .find_map(|(entry, _)| match entry.data {
+                LedgerEntryData::ConfigSetting(ConfigSettingEntry::StateArchival(settings)) => {
+                    Some(settings)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| rpc_error("state archival settings not found"))?;
+
+        Ok(LedgerInfo {
+            protocol_version,
+            sequence_number: transaction.ledger,
+            timestamp,
+            network_id,
+            base_reserve,
+            min_temp_entry_ttl: archival.min_temporary_ttl,
+            min_persistent_entry_ttl: archival.min_persistent_ttl,
+            max_entry_ttl: archival.max_entry_ttl,
+        })
+    }
+
+    fn get_ledger(&self, sequence: u32) -> Result<LedgerHeaderHistoryEntry, RetroshadeError> {
+        let response: GetLedgersResponse = self.request(
+            "getLedgers",
+            json!({ "startLedger": sequence, "pagination": { "limit": 1 } }),
+        )?;
+
+        let ledger = response
+            .ledgers
+            .into_iter()
+            .find(|ledger| ledger.sequence == sequence)
+            .ok_or_else(|| rpc_error(format!("ledger {} not retained", sequence)))?;
+
+        decode(&ledger.header_xdr)
+    }
+}
+
+/// Snapshot source fetching entries with `getLedgerEntries`. Entries are
+/// cached so that each key is only fetched once.
+pub struct RpcSnapshot {
+    client: RpcClient,
+    cache: RefCell<HashMap<LedgerKey, Option<EntryWithLiveUntil>>>,
+}
+
+impl RpcSnapshot {
+    pub fn new(client: RpcClient) -> Self {
+        Self {
+            client,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The entries fetched so far, e.g. to freeze them into a fixture.
+    pub fn entries(&self) -> Vec<(LedgerEntry, Option<u32>)> {
+        self.cache
+            .borrow()
+            .values()
+            .flatten()
+            .map(|(entry, live_until)| (entry.as_ref().clone(), *live_until))
+            .collect()
+    }
+}
+
+impl SnapshotSource for RpcSnapshot {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        if let Some(cached) = self.cache.borrow().get(key.as_ref()) {
+            return Ok(cached.clone());
+        }
+
+        let entry = self
+            .client
+            .get_ledger_entries(std::slice::from_ref(key.as_ref()))
+            .map_err(|error| {
+                log::error!("rpc snapshot: {}", error);
+                HostError::from((ScErrorType::Storage, ScErrorCode::InternalError))
+            })?
+            .pop()
+            .map(|(entry, live_until)| (Rc::new(entry), live_until));
+
+        self.cache
+            .borrow_mut()
+            .insert(key.as_ref().clone(), entry.clone());
+        Ok(entry)
+    }
+}
+
+impl SnapshotSourceExt for RpcSnapshot {
+    fn label(&self) -> String {
+        "rpc".to_string()
+    }
+}
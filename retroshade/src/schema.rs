@@ -0,0 +1,142 @@
+//! JSON Schema (draft-07) documents describing the `fields` object of the
+//! JSON exports of a target, see [`crate::export::jsonl`], and fingerprints
+//! of the columns of an export.
+
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use stellar_strkey::Strkey;
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    RetroshadeExportPretty, SCHEMA_HASH_COLUMN,
+};
+
+const DRAFT_07: &str = "http://json-schema.org/draft-07/schema#";
+
+impl RetroshadeExportPretty {
+    /// Schema of this export's fields. Combine the schemas of several rows of
+    /// the same target with [`widen`].
+    pub fn json_schema(&self) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for entry in &self.event {
+            properties.insert(entry.name.to_string(), value_schema(&entry.value));
+            required.push(Value::String(entry.name.to_string()));
+        }
+
+        json!({
+            "$schema": DRAFT_07,
+            "title": self.target,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+
+    /// Fingerprint of this export's columns: SHA-256 over their sorted names
+    /// and types, so exports of the same shape share it whatever their
+    /// values. Void values are typed as text unless packed with
+    /// [`RetroshadesExecution::set_spec_column_types`](crate::RetroshadesExecution::set_spec_column_types),
+    /// so a `None` changes the fingerprint of an optional field otherwise.
+    ///
+    /// The [`SCHEMA_HASH_COLUMN`] isn't covered, exports packed with it hash
+    /// the same as without and as its value.
+    pub fn schema_hash(&self) -> [u8; 32] {
+        let mut columns: Vec<(&str, &str)> = self
+            .event
+            .iter()
+            .filter(|entry| entry.name != SCHEMA_HASH_COLUMN)
+            .map(|entry| (entry.name.as_str(), entry.value.dbtype.name()))
+            .collect();
+        columns.sort();
+
+        let mut hasher = Sha256::new();
+        // note: length-prefixed like the idempotency key.
+        for (name, dbtype) in columns {
+            for component in [name, dbtype] {
+                hasher.update((component.len() as u32).to_be_bytes());
+                hasher.update(component);
+            }
+        }
+
+        hasher.finalize().into()
+    }
+}
+
+fn value_schema(value: &FromScVal) -> Value {
+    match &value.kind {
+        TypeKind::GenericArray(items) => {
+            let items = items
+                .iter()
+                .map(value_schema)
+                .reduce(|widened, item| widen_value(&widened, &item))
+                .unwrap_or_else(|| json!({}));
+            json!({ "type": "array", "items": items })
+        }
+        TypeKind::Text(_) if value.dbtype == postgres_types::Type::BYTEA => {
+            json!({ "type": "string", "pattern": "^([0-9a-f]{2})*$" })
+        }
+        TypeKind::Text(text) if is_address(text) => {
+            json!({ "type": "string", "pattern": "^[GC][A-Z2-7]{55}$" })
+        }
+        TypeKind::Text(_) => json!({ "type": "string" }),
+        TypeKind::Boolean(_) => json!({ "type": "boolean" }),
+        TypeKind::Void => json!({ "type": "null" }),
+        // note: numerics are strings in the JSON exports to keep their precision.
+        TypeKind::Numeric(_) => json!({ "type": "string", "pattern": "^-?[0-9]+$" }),
+    }
+}
+
+fn is_address(text: &str) -> bool {
+    matches!(
+        Strkey::from_string(text),
+        Ok(Strkey::PublicKeyEd25519(_) | Strkey::Contract(_))
+    )
+}
+
+/// Widens `schema` so that it also accepts the rows described by `other`:
+/// properties with different schemas become an `anyOf` and only properties
+/// present in both stay required.
+pub fn widen(schema: &Value, other: &Value) -> Value {
+    let mut widened = schema.clone();
+
+    let other_properties = other["properties"].as_object().cloned().unwrap_or_default();
+    if let Some(properties) = widened["properties"].as_object_mut() {
+        for (name, other_property) in other_properties {
+            let property = match properties.get(&name) {
+                Some(property) => widen_value(property, &other_property),
+                None => other_property,
+            };
+            properties.insert(name, property);
+        }
+    }
+
+    let other_required = other["required"].as_array().cloned().unwrap_or_default();
+    if let Some(required) = widened["required"].as_array_mut() {
+        required.retain(|name| other_required.contains(name));
+    }
+
+    widened
+}
+
+fn widen_value(schema: &Value, other: &Value) -> Value {
+    if schema == other {
+        return schema.clone();
+    }
+
+    let mut any_of = alternatives(schema);
+    for alternative in alternatives(other) {
+        if !any_of.contains(&alternative) {
+            any_of.push(alternative);
+        }
+    }
+
+    json!({ "anyOf": any_of })
+}
+
+fn alternatives(schema: &Value) -> Vec<Value> {
+    match schema["anyOf"].as_array() {
+        Some(any_of) => any_of.clone(),
+        None => vec![schema.clone()],
+    }
+}
@@ -0,0 +1,56 @@
+//! Delivery of packed exports, decoupled from their execution.
+
+pub mod dedup;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+use std::io::Write;
+
+use crate::{
+    export::jsonl::JsonlSink, RetroshadeError, RetroshadeExecutionResultPretty,
+    RetroshadeExportPretty, TxContext,
+};
+
+/// Sinks report failures as [`RetroshadeError::Export`].
+pub type SinkError = RetroshadeError;
+
+pub trait RetroshadeSink {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), SinkError>;
+
+    fn flush(&mut self) -> Result<(), SinkError>;
+}
+
+/// Delivers all the packed retroshades of an execution, then flushes the sink.
+pub fn deliver_all(
+    sink: &mut dyn RetroshadeSink,
+    result: &RetroshadeExecutionResultPretty,
+) -> Result<(), SinkError> {
+    for export in &result.retroshades {
+        let delivered = sink.deliver(export, &export.context);
+        #[cfg(feature = "metrics")]
+        crate::telemetry::record_delivery(delivered.is_ok());
+        delivered?;
+    }
+
+    sink.flush()
+}
+
+impl<W: Write> RetroshadeSink for JsonlSink<W> {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), SinkError> {
+        self.write_with_context(export, ctx)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        JsonlSink::flush(self)
+    }
+}
@@ -0,0 +1,319 @@
+//! De-duplication of exports across transactions, keyed by
+//! [`RetroshadeExportPretty::idempotency_key`].
+//!
+//! Replays that crash and restart re-execute the transactions they were
+//! processing, sinks that can't roll back would write their exports twice.
+//! A [`DedupStore`] remembers the keys of the last delivered exports, within
+//! a bounded [`DedupWindow`], so the re-executed ones are dropped. Keys are
+//! only recorded once their export was delivered, exports whose delivery
+//! failed are delivered again on retry.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{RetroshadeError, RetroshadeExecutionResultPretty, RetroshadeExportPretty, TxContext};
+
+use super::{RetroshadeSink, SinkError};
+
+pub trait DedupStore {
+    /// Whether `key` is within the window.
+    fn contains(&self, key: &[u8; 32]) -> bool;
+
+    /// Records `key` as delivered, or as seen again if it's in the window.
+    fn record(&mut self, key: &[u8; 32]);
+
+    /// Persists the keys recorded so far, for stores outliving the process.
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/// Which key leaves a full window to make room for a new one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Eviction {
+    /// The first recorded.
+    #[default]
+    Fifo,
+
+    /// The least recently seen, keys of exports that keep being replayed
+    /// stay in the window.
+    Lru,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DedupWindow {
+    /// Keys remembered, usually a few ledgers worth of exports.
+    pub capacity: usize,
+    pub eviction: Eviction,
+}
+
+impl Default for DedupWindow {
+    fn default() -> Self {
+        Self {
+            capacity: 100_000,
+            eviction: Eviction::default(),
+        }
+    }
+}
+
+/// Keys of the window held in memory, lost on restart.
+#[derive(Clone, Debug)]
+pub struct MemoryDedupStore {
+    window: DedupWindow,
+    /// Latest use of each key.
+    keys: HashMap<[u8; 32], u64>,
+    /// Uses in order, those superseded by a later use of their key are
+    /// skipped when evicting.
+    uses: VecDeque<([u8; 32], u64)>,
+    clock: u64,
+}
+
+impl MemoryDedupStore {
+    pub fn new(window: DedupWindow) -> Self {
+        Self {
+            window,
+            keys: HashMap::new(),
+            uses: VecDeque::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Keys in the window, from the next to be evicted.
+    pub fn keys(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.uses
+            .iter()
+            .filter(|(key, used)| self.keys.get(key) == Some(used))
+            .map(|(key, _)| key)
+    }
+
+    fn push(&mut self, key: [u8; 32]) {
+        self.clock += 1;
+        self.keys.insert(key, self.clock);
+        self.uses.push_back((key, self.clock));
+
+        while self.keys.len() > self.window.capacity {
+            let Some((key, used)) = self.uses.pop_front() else {
+                break;
+            };
+            if self.keys.get(&key) == Some(&used) {
+                self.keys.remove(&key);
+            }
+        }
+
+        // note: with LRU every hit adds a use, drop the superseded ones
+        // before they outnumber the keys.
+        if self.uses.len() > 2 * self.window.capacity.max(1) {
+            let keys = &self.keys;
+            self.uses.retain(|(key, used)| keys.get(key) == Some(used));
+        }
+    }
+}
+
+impl DedupStore for MemoryDedupStore {
+    fn contains(&self, key: &[u8; 32]) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    fn record(&mut self, key: &[u8; 32]) {
+        if !self.contains(key) || self.window.eviction == Eviction::Lru {
+            self.push(*key);
+        }
+    }
+}
+
+/// Window persisted to a file of 32-byte keys, for the keys to survive
+/// restarts. The file is compacted to the window when opened, and when it
+/// holds twice as many keys.
+///
+/// Recorded keys are appended on [`DedupStore::flush`], after the exports
+/// they stand for were flushed. With [`Eviction::Lru`] the recency of the
+/// keys isn't persisted, restarts evict in recorded order.
+#[derive(Debug)]
+pub struct FileDedupStore {
+    path: PathBuf,
+    keys: MemoryDedupStore,
+    pending: Vec<[u8; 32]>,
+    /// Keys in the file, evicted ones included.
+    file_keys: usize,
+}
+
+impl FileDedupStore {
+    /// Opens the window stored at `path`, creating the file if needed.
+    pub fn open(path: impl AsRef<Path>, window: DedupWindow) -> Result<Self, SinkError> {
+        let path = path.as_ref().to_path_buf();
+        let mut keys = MemoryDedupStore::new(window);
+
+        let mut contents = Vec::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                file.read_to_end(&mut contents).map_err(file_error(&path))?;
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(file_error(&path)(error)),
+        }
+        // note: a crash mid-write leaves a partial key, dropped here.
+        let recorded = contents.len() / 32;
+        for key in contents.chunks_exact(32) {
+            keys.record(key.try_into().unwrap());
+        }
+
+        let mut store = Self {
+            path,
+            keys,
+            pending: vec![],
+            file_keys: recorded,
+        };
+        if recorded > store.keys.len() || contents.len() % 32 != 0 {
+            store.compact()?;
+        }
+
+        Ok(store)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Rewrites the file with the keys of the window.
+    fn compact(&mut self) -> Result<(), SinkError> {
+        let temp = self.path.with_extension("tmp");
+        let contents: Vec<u8> = self.keys.keys().flatten().copied().collect();
+        fs::write(&temp, contents).map_err(file_error(&temp))?;
+        fs::rename(&temp, &self.path).map_err(file_error(&self.path))?;
+        self.pending.clear();
+        self.file_keys = self.keys.len();
+
+        Ok(())
+    }
+}
+
+impl DedupStore for FileDedupStore {
+    fn contains(&self, key: &[u8; 32]) -> bool {
+        self.keys.contains(key)
+    }
+
+    fn record(&mut self, key: &[u8; 32]) {
+        if !self.keys.contains(key) {
+            self.pending.push(*key);
+        }
+        self.keys.record(key);
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        if self.file_keys + self.pending.len() > 2 * self.keys.window.capacity {
+            return self.compact();
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(file_error(&self.path))?;
+        file.write_all(&self.pending.concat())
+            .and_then(|_| file.sync_data())
+            .map_err(file_error(&self.path))?;
+        self.file_keys += self.pending.len();
+        self.pending.clear();
+
+        Ok(())
+    }
+}
+
+fn file_error(path: &Path) -> impl Fn(std::io::Error) -> RetroshadeError + '_ {
+    move |error| RetroshadeError::Export(format!("dedup store {}: {}", path.display(), error))
+}
+
+/// Drops the exports of `result` already in `store`, returning how many
+/// were dropped. The others are only recorded by [`record_delivered`], once
+/// they were output.
+pub fn retain_unseen(
+    result: &mut RetroshadeExecutionResultPretty,
+    store: &mut dyn DedupStore,
+) -> usize {
+    let before = result.retroshades.len();
+    result.retroshades.retain(|export| {
+        let key = export.idempotency_key(&export.context.tx_hash);
+        if store.contains(&key) {
+            store.record(&key);
+            return false;
+        }
+        true
+    });
+
+    before - result.retroshades.len()
+}
+
+/// Records the exports of `result` in `store`, after they were delivered.
+pub fn record_delivered(result: &RetroshadeExecutionResultPretty, store: &mut dyn DedupStore) {
+    for export in &result.retroshades {
+        store.record(&export.idempotency_key(&export.context.tx_hash));
+    }
+}
+
+/// Sink delivering only the exports not yet in its store. Keys are recorded
+/// once the inner sink delivered their export, and persisted when flushing,
+/// once the inner sink flushed.
+pub struct DedupSink<S, D> {
+    sink: S,
+    store: D,
+    /// Exports dropped as already delivered.
+    pub skipped: usize,
+}
+
+impl<S: RetroshadeSink, D: DedupStore> DedupSink<S, D> {
+    pub fn new(sink: S, store: D) -> Self {
+        Self {
+            sink,
+            store,
+            skipped: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> (S, D) {
+        (self.sink, self.store)
+    }
+}
+
+impl<S: RetroshadeSink, D: DedupStore> RetroshadeSink for DedupSink<S, D> {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), SinkError> {
+        let key = export.idempotency_key(&ctx.tx_hash);
+        if self.store.contains(&key) {
+            self.store.record(&key);
+            self.skipped += 1;
+            return Ok(());
+        }
+
+        self.sink.deliver(export, ctx)?;
+        self.store.record(&key);
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.sink.flush()?;
+        self.store.flush()
+    }
+}
@@ -0,0 +1,226 @@
+//! HTTP POST sink. Exports are posted as the JSON Lines objects of
+//! [`crate::export::jsonl`], either one per request or batched as an array.
+//!
+//! Requests are sent from a background thread fed through a bounded queue:
+//! when the endpoint is slower than the executor and the queue stays full
+//! for [`HttpSinkConfig::enqueue_timeout`], [`RetroshadeSink::deliver`]
+//! errors. The exports it couldn't queue are kept and queued first by the
+//! next `deliver` or `flush`.
+
+use std::{
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{export::jsonl::JsonlSink, RetroshadeError, RetroshadeExportPretty, TxContext};
+
+use super::{RetroshadeSink, SinkError};
+
+#[derive(Clone, Debug)]
+pub struct HttpSinkConfig {
+    pub url: String,
+    /// Extra headers sent with every request, e.g. an authorization token.
+    pub headers: Vec<(String, String)>,
+    /// Exports per request. With 1 the body is a single object, otherwise an array.
+    pub batch_size: usize,
+    /// Requests waiting to be sent before `deliver` starts waiting.
+    pub queue_capacity: usize,
+    /// How long `deliver` and `flush` wait for room in a full queue before
+    /// failing.
+    pub enqueue_timeout: Duration,
+    /// Attempts after the first one for requests failing with a 5xx.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled at every attempt.
+    pub initial_backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl HttpSinkConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: vec![],
+            batch_size: 1,
+            queue_capacity: 1024,
+            enqueue_timeout: Duration::from_secs(1),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+enum Message {
+    Post(String),
+    /// Acknowledged once every request queued before it was sent, with the
+    /// errors of the failed ones.
+    Flush(SyncSender<Vec<String>>),
+}
+
+pub struct HttpSink {
+    batch_size: usize,
+    enqueue_timeout: Duration,
+    /// Exports not queued yet, only removed once their request is.
+    pending: Vec<String>,
+    queue: Option<SyncSender<Message>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl HttpSink {
+    pub fn new(config: HttpSinkConfig) -> Self {
+        let (queue, messages) = mpsc::sync_channel(config.queue_capacity);
+        let batch_size = config.batch_size.max(1);
+        let enqueue_timeout = config.enqueue_timeout;
+        let worker = thread::spawn(move || post_worker(config, messages));
+
+        Self {
+            batch_size,
+            enqueue_timeout,
+            pending: Vec::new(),
+            queue: Some(queue),
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues the full batches of `pending`, and the partial one too with
+    /// `partial`.
+    fn enqueue_pending(&mut self, partial: bool) -> Result<(), SinkError> {
+        while !self.pending.is_empty() && (partial || self.pending.len() >= self.batch_size) {
+            let count = self.pending.len().min(self.batch_size);
+            let body = if self.batch_size == 1 {
+                self.pending[0].clone()
+            } else {
+                format!("[{}]", self.pending[..count].join(","))
+            };
+
+            self.enqueue(Message::Post(body))?;
+            self.pending.drain(..count);
+        }
+
+        Ok(())
+    }
+
+    /// Sends `message`, waiting up to the enqueue timeout while the queue is
+    /// full.
+    fn enqueue(&self, mut message: Message) -> Result<(), SinkError> {
+        let deadline = Instant::now() + self.enqueue_timeout;
+
+        loop {
+            match self.queue().try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(_)) if Instant::now() >= deadline => {
+                    return Err(RetroshadeError::Export(
+                        "http sink queue is full".to_string(),
+                    ))
+                }
+                Err(TrySendError::Full(unsent)) => {
+                    message = unsent;
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(TrySendError::Disconnected(_)) => return Err(worker_stopped()),
+            }
+        }
+    }
+
+    fn queue(&self) -> &SyncSender<Message> {
+        self.queue.as_ref().expect("queue is only taken on drop")
+    }
+}
+
+impl RetroshadeSink for HttpSink {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), SinkError> {
+        let mut jsonl = JsonlSink::new(Vec::new());
+        jsonl.write_with_context(export, ctx)?;
+        let mut line = jsonl.into_inner();
+        line.pop();
+        self.pending
+            .push(String::from_utf8(line).expect("serde_json writes utf-8"));
+
+        self.enqueue_pending(false)
+    }
+
+    /// Sends the partial batch and waits for the queue to drain.
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.enqueue_pending(true)?;
+
+        let (ack, acked) = mpsc::sync_channel(1);
+        self.queue()
+            .send(Message::Flush(ack))
+            .map_err(|_| worker_stopped())?;
+        let errors = acked.recv().map_err(|_| worker_stopped())?;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RetroshadeError::Export(errors.join("; ")))
+        }
+    }
+}
+
+impl Drop for HttpSink {
+    fn drop(&mut self) {
+        // note: closing the queue stops the worker once it's drained.
+        self.queue.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn post_worker(config: HttpSinkConfig, messages: Receiver<Message>) {
+    let agent = ureq::AgentBuilder::new().timeout(config.timeout).build();
+    let mut errors = Vec::new();
+
+    for message in messages {
+        match message {
+            Message::Post(body) => {
+                if let Err(error) = post_with_retries(&agent, &config, &body) {
+                    log::error!("http sink: {}", error);
+                    errors.push(error);
+                }
+            }
+            Message::Flush(ack) => {
+                let _ = ack.send(std::mem::take(&mut errors));
+            }
+        }
+    }
+}
+
+fn post_with_retries(
+    agent: &ureq::Agent,
+    config: &HttpSinkConfig,
+    body: &str,
+) -> Result<(), String> {
+    let mut backoff = config.initial_backoff;
+    let mut attempt = 0;
+
+    loop {
+        let mut request = agent
+            .post(&config.url)
+            .set("Content-Type", "application/json");
+        for (name, value) in &config.headers {
+            request = request.set(name, value);
+        }
+
+        match request.send_string(body) {
+            Ok(_) => return Ok(()),
+            Err(ureq::Error::Status(status, _))
+                if status >= 500 && attempt < config.max_retries =>
+            {
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+}
+
+fn worker_stopped() -> RetroshadeError {
+    RetroshadeError::Export("http sink worker stopped".to_string())
+}
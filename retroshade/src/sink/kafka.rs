@@ -0,0 +1,86 @@
+//! Kafka producer sink. Payloads are the JSON Lines objects of
+//! [`crate::export::jsonl`], keyed by the export's idempotency key.
+
+use std::time::Duration;
+
+use rdkafka::{
+    message::{Header, OwnedHeaders},
+    producer::{BaseProducer, BaseRecord, Producer},
+    ClientConfig,
+};
+
+use crate::{export::jsonl::JsonlSink, RetroshadeError, RetroshadeExportPretty, TxContext};
+
+use super::{RetroshadeSink, SinkError};
+
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub enum Topics {
+    /// One topic per target, named `{prefix}{target}`.
+    PerTarget { prefix: String },
+
+    /// A single topic, with the target in a `target` header.
+    Single(String),
+}
+
+pub struct KafkaSink {
+    producer: BaseProducer,
+    topics: Topics,
+}
+
+impl KafkaSink {
+    pub fn new(brokers: &str, topics: Topics) -> Result<Self, SinkError> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()
+            .map_err(kafka_error)?;
+
+        Ok(Self { producer, topics })
+    }
+}
+
+impl RetroshadeSink for KafkaSink {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), SinkError> {
+        let mut jsonl = JsonlSink::new(Vec::new());
+        jsonl.write_with_context(export, ctx)?;
+        let mut payload = jsonl.into_inner();
+        // note: drop the line terminator, each message is a single object.
+        payload.pop();
+
+        let key = export.idempotency_key(&ctx.tx_hash);
+        let (topic, headers) = match &self.topics {
+            Topics::PerTarget { prefix } => (format!("{}{}", prefix, export.target), None),
+            Topics::Single(topic) => (
+                topic.clone(),
+                Some(OwnedHeaders::new().insert(Header {
+                    key: "target",
+                    value: Some(export.target.as_str()),
+                })),
+            ),
+        };
+
+        let mut record = BaseRecord::to(&topic).payload(&payload).key(&key[..]);
+        if let Some(headers) = headers {
+            record = record.headers(headers);
+        }
+
+        self.producer
+            .send(record)
+            .map_err(|(error, _)| kafka_error(error))?;
+        self.producer.poll(Duration::ZERO);
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.producer.flush(FLUSH_TIMEOUT).map_err(kafka_error)
+    }
+}
+
+fn kafka_error(error: rdkafka::error::KafkaError) -> RetroshadeError {
+    RetroshadeError::Export(error.to_string())
+}
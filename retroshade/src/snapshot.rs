@@ -1,107 +1,313 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc, sync::Arc};
 
 use soroban_env_host::{
-    storage::SnapshotSource,
+    budget::Budget,
+    e2e_invoke::ledger_entry_to_ledger_key,
+    storage::{EntryWithLiveUntil, SnapshotSource},
     xdr::{
         LedgerEntry, LedgerEntryData, LedgerKey, LedgerKeyAccount, LedgerKeyContractCode,
         LedgerKeyContractData, LedgerKeyTrustLine,
     },
+    HostError,
 };
 
-pub struct InternalSnapshot {
-    inner_source: Rc<dyn SnapshotSource>,
-    target_pre_execution_state: Vec<(LedgerEntry, Option<u32>)>,
-    force_remove: Vec<LedgerEntry>,
+use crate::state::KeyedEntry;
+
+/// Snapshot sources that can report where the entries they serve come from.
+pub trait SnapshotSourceExt: SnapshotSource {
+    fn label(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
+    /// Like [`SnapshotSource::get`], also returning the label of the source that served the
+    /// entry. Sources chaining others should override this to report the inner label.
+    fn get_labeled(
+        &self,
+        key: &Rc<LedgerKey>,
+    ) -> Result<Option<(EntryWithLiveUntil, String)>, HostError> {
+        Ok(self.get(key)?.map(|entry| (entry, self.label())))
+    }
 }
 
-impl InternalSnapshot {
-    pub(crate) fn new(
-        inner_source: Rc<dyn SnapshotSource>,
-        target_pre_execution_state: Vec<(LedgerEntry, Option<u32>)>,
-        force_remove: Vec<LedgerEntry>,
+/// Attaches a fixed label to any snapshot source.
+pub struct LabeledSnapshot<S: SnapshotSource + ?Sized> {
+    label: String,
+    inner_source: Box<S>,
+}
+
+impl<S: SnapshotSource + ?Sized> LabeledSnapshot<S> {
+    pub fn new(label: impl Into<String>, inner_source: Box<S>) -> Self {
+        Self {
+            label: label.into(),
+            inner_source,
+        }
+    }
+}
+
+impl<S: SnapshotSource + ?Sized> SnapshotSource for LabeledSnapshot<S> {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        self.inner_source.get(key)
+    }
+}
+
+impl<S: SnapshotSource + ?Sized> SnapshotSourceExt for LabeledSnapshot<S> {
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+}
+
+/// Borrowing counterpart of [`LabeledSnapshot`], so that a build can label a
+/// source its caller keeps using afterwards.
+pub(crate) struct LabeledRef<'a> {
+    label: &'static str,
+    inner_source: &'a dyn SnapshotSource,
+}
+
+impl<'a> LabeledRef<'a> {
+    pub(crate) fn new(label: &'static str, inner_source: &'a dyn SnapshotSource) -> Self {
+        Self {
+            label,
+            inner_source,
+        }
+    }
+}
+
+impl SnapshotSource for LabeledRef<'_> {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        self.inner_source.get(key)
+    }
+}
+
+impl SnapshotSourceExt for LabeledRef<'_> {
+    fn label(&self) -> String {
+        self.label.to_string()
+    }
+}
+
+/// In-memory snapshot source holding a fixed set of entries, e.g. loaded from a fixture.
+#[derive(Clone, Default)]
+pub struct MemorySnapshot {
+    entries: HashMap<LedgerKey, EntryWithLiveUntil>,
+}
+
+impl MemorySnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_entries(entries: Vec<(LedgerEntry, Option<u32>)>) -> Result<Self, HostError> {
+        let mut snapshot = Self::new();
+        for (entry, live_until) in entries {
+            snapshot.insert(entry, live_until)?;
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Takes the entries already keyed, e.g. decoded by a caller without
+    /// filesystem access such as a browser tool.
+    pub fn from_map(entries: HashMap<LedgerKey, (LedgerEntry, Option<u32>)>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|(key, (entry, live_until))| (key, (Rc::new(entry), live_until)))
+            .collect();
+
+        Self { entries }
+    }
+
+    pub fn insert(&mut self, entry: LedgerEntry, live_until: Option<u32>) -> Result<(), HostError> {
+        let key = ledger_entry_to_ledger_key(&entry, &Budget::default())?;
+        self.entries.insert(key, (Rc::new(entry), live_until));
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> Vec<(LedgerEntry, Option<u32>)> {
+        self.entries
+            .values()
+            .map(|(entry, live_until)| (entry.as_ref().clone(), *live_until))
+            .collect()
+    }
+}
+
+impl SnapshotSource for MemorySnapshot {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        Ok(self.entries.get(key.as_ref()).cloned())
+    }
+}
+
+impl SnapshotSourceExt for MemorySnapshot {}
+
+/// Adapts a thread-safe snapshot source to the `Rc`/`Box` based interfaces the host expects.
+pub struct SharedSnapshot<
+    S: SnapshotSource + Send + Sync + ?Sized = dyn SnapshotSource + Send + Sync,
+> {
+    inner_source: Arc<S>,
+}
+
+impl<S: SnapshotSource + Send + Sync + ?Sized> SharedSnapshot<S> {
+    pub fn new(inner_source: Arc<S>) -> Self {
+        Self { inner_source }
+    }
+}
+
+impl<S: SnapshotSource + Send + Sync + ?Sized> SnapshotSource for SharedSnapshot<S> {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        self.inner_source.get(key)
+    }
+}
+
+impl<S: SnapshotSourceExt + Send + Sync + ?Sized> SnapshotSourceExt for SharedSnapshot<S> {
+    fn label(&self) -> String {
+        self.inner_source.label()
+    }
+
+    fn get_labeled(
+        &self,
+        key: &Rc<LedgerKey>,
+    ) -> Result<Option<(EntryWithLiveUntil, String)>, HostError> {
+        self.inner_source.get_labeled(key)
+    }
+}
+
+// note: sources erased to a plain `SnapshotSource` can't report their own
+// label, the wrapper reports its type.
+impl SnapshotSourceExt for SharedSnapshot {}
+
+/// Layers explicit overrides on top of an inner snapshot source. An override of
+/// `Some(entry)` is served instead of the inner entry, `None` forces the key to be
+/// reported as missing.
+pub struct OverlaySnapshot<S: SnapshotSource + ?Sized = dyn SnapshotSource> {
+    inner_source: Rc<S>,
+    overrides: HashMap<LedgerKey, Option<EntryWithLiveUntil>>,
+}
+
+impl<S: SnapshotSource + ?Sized> OverlaySnapshot<S> {
+    pub fn new(inner_source: Rc<S>) -> Self {
+        Self {
+            inner_source,
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn with_overrides(
+        inner_source: Rc<S>,
+        overrides: HashMap<LedgerKey, Option<EntryWithLiveUntil>>,
     ) -> Self {
         Self {
             inner_source,
-            target_pre_execution_state,
-            force_remove,
+            overrides,
         }
     }
+
+    pub fn set_override(&mut self, key: LedgerKey, entry: EntryWithLiveUntil) {
+        self.overrides.insert(key, Some(entry));
+    }
+
+    pub fn force_remove(&mut self, key: LedgerKey) {
+        self.overrides.insert(key, None);
+    }
+
+    /// Drops all overrides, falling back to the inner source for every key.
+    pub fn clear(&mut self) {
+        self.overrides.clear();
+    }
 }
 
-impl SnapshotSource for InternalSnapshot {
-    fn get(
+impl<S: SnapshotSource + ?Sized> SnapshotSource for OverlaySnapshot<S> {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        match self.overrides.get(key.as_ref()) {
+            Some(overridden) => Ok(overridden.clone()),
+            None => self.inner_source.get(key),
+        }
+    }
+}
+
+/// Overridden entries are labeled `overlay`, the others with the label the
+/// inner source reports.
+impl<S: SnapshotSourceExt + ?Sized> SnapshotSourceExt for OverlaySnapshot<S> {
+    fn label(&self) -> String {
+        "overlay".to_string()
+    }
+
+    fn get_labeled(
         &self,
-        key: &Rc<soroban_env_host::xdr::LedgerKey>,
-    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
-    {
-        if let Some((entry, lifetime)) =
-            self.target_pre_execution_state.iter().find(|(entry, _)| {
-                let entry_key = match &entry.data {
-                    LedgerEntryData::Account(account) => LedgerKey::Account(LedgerKeyAccount {
-                        account_id: account.account_id.clone(),
-                    }),
-                    LedgerEntryData::ContractCode(code) => {
-                        LedgerKey::ContractCode(LedgerKeyContractCode {
-                            hash: code.hash.clone(),
-                        })
-                    }
-                    LedgerEntryData::ContractData(data) => {
-                        LedgerKey::ContractData(LedgerKeyContractData {
-                            contract: data.contract.clone(),
-                            key: data.key.clone(),
-                            durability: data.durability,
-                        })
-                    }
-                    LedgerEntryData::Trustline(trustline) => {
-                        LedgerKey::Trustline(LedgerKeyTrustLine {
-                            asset: trustline.asset.clone(),
-                            account_id: trustline.account_id.clone(),
-                        })
-                    }
-                    _ => return false,
-                };
-                key.as_ref() == &entry_key
-            })
-        {
-            return Ok(Some((Rc::new(entry.clone()), *lifetime)));
+        key: &Rc<LedgerKey>,
+    ) -> Result<Option<(EntryWithLiveUntil, String)>, HostError> {
+        match self.overrides.get(key.as_ref()) {
+            Some(overridden) => Ok(overridden.clone().map(|entry| (entry, self.label()))),
+            None => self.inner_source.get_labeled(key),
+        }
+    }
+}
+
+impl SnapshotSourceExt for OverlaySnapshot {
+    fn label(&self) -> String {
+        "overlay".to_string()
+    }
+}
+
+/// Key of the entry types retroshades tracks in the pre-execution state.
+pub(crate) fn entry_key(entry: &LedgerEntry) -> Option<LedgerKey> {
+    let key = match &entry.data {
+        LedgerEntryData::Account(account) => LedgerKey::Account(LedgerKeyAccount {
+            account_id: account.account_id.clone(),
+        }),
+        LedgerEntryData::ContractCode(code) => LedgerKey::ContractCode(LedgerKeyContractCode {
+            hash: code.hash.clone(),
+        }),
+        LedgerEntryData::ContractData(data) => LedgerKey::ContractData(LedgerKeyContractData {
+            contract: data.contract.clone(),
+            key: data.key.clone(),
+            durability: data.durability,
+        }),
+        LedgerEntryData::Trustline(trustline) => LedgerKey::Trustline(LedgerKeyTrustLine {
+            asset: trustline.asset.clone(),
+            account_id: trustline.account_id.clone(),
+        }),
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+pub struct InternalSnapshot {
+    overlay: OverlaySnapshot,
+}
+
+impl InternalSnapshot {
+    pub(crate) fn new(
+        inner_source: Rc<dyn SnapshotSource>,
+        target_pre_execution_state: Vec<KeyedEntry>,
+        force_remove: Vec<LedgerEntry>,
+    ) -> Self {
+        let mut overrides = HashMap::new();
+
+        // note: pre-execution entries take precedence over forced removals, and the first
+        // occurrence of a key wins.
+        for mut keyed in target_pre_execution_state {
+            if let Some(key) = keyed.key.take() {
+                let live_until = keyed.live_until;
+                overrides
+                    .entry(key.key)
+                    .or_insert_with(|| Some((Rc::new(keyed.into_fed_entry()), live_until)));
+            }
         }
 
-        if self
-            .force_remove
-            .iter()
-            .find(|entry| {
-                let entry_key = match &entry.data {
-                    LedgerEntryData::Account(account) => LedgerKey::Account(LedgerKeyAccount {
-                        account_id: account.account_id.clone(),
-                    }),
-                    LedgerEntryData::ContractCode(code) => {
-                        LedgerKey::ContractCode(LedgerKeyContractCode {
-                            hash: code.hash.clone(),
-                        })
-                    }
-                    LedgerEntryData::ContractData(data) => {
-                        LedgerKey::ContractData(LedgerKeyContractData {
-                            contract: data.contract.clone(),
-                            key: data.key.clone(),
-                            durability: data.durability,
-                        })
-                    }
-                    LedgerEntryData::Trustline(trustline) => {
-                        LedgerKey::Trustline(LedgerKeyTrustLine {
-                            asset: trustline.asset.clone(),
-                            account_id: trustline.account_id.clone(),
-                        })
-                    }
-                    _ => return false,
-                };
-
-                key.as_ref() == &entry_key
-            })
-            .is_some()
-        {
-            return Ok(None);
+        for entry in force_remove {
+            if let Some(key) = entry_key(&entry) {
+                overrides.entry(key).or_insert(None);
+            }
         }
 
-        self.inner_source.get(key)
+        Self {
+            overlay: OverlaySnapshot::with_overrides(inner_source, overrides),
+        }
+    }
+}
+
+impl SnapshotSource for InternalSnapshot {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        self.overlay.get(key)
     }
 }
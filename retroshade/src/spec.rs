@@ -0,0 +1,611 @@
+//! Contract specs embedded in the `contractspecv0` custom section of the
+//! wasm, describing the structs Mercury contracts emit as retroshades.
+//!
+//! Packing infers column types from the values it sees, so a `None` or an
+//! empty vector says nothing about its column. With
+//! [`RetroshadesExecution::set_spec_column_types`](crate::RetroshadesExecution::set_spec_column_types)
+//! those columns are typed from the field's spec type instead, see
+//! [`column_type`].
+//!
+//! The spec also catches events that drifted from the struct they're
+//! declared as, e.g. after an upgrade renamed a field, see
+//! [`ContractSpec::validate`] and
+//! [`RetroshadesExecution::set_spec_validation`](crate::RetroshadesExecution::set_spec_validation).
+//!
+//! Tables can be created from the spec too, before the contract emits
+//! anything, with [`table_ddl_for`].
+
+use std::{collections::HashSet, fmt, io::Cursor};
+
+use postgres_types::Type;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use soroban_env_host::xdr::{
+    Hash, Limited, Limits, ReadXdr, ScMap, ScSpecEntry, ScSpecType, ScSpecTypeDef,
+    ScSpecUdtStructV0, ScSpecUdtUnionCaseV0, ScVal, ScValType,
+};
+use wasmparser::{Parser, Payload};
+
+use crate::{
+    conversion::{ConversionOptions, FromScVal, TypeKind},
+    export::sql,
+    naming::TableNaming,
+    PackedEventEntry, RetroshadeError, RetroshadeExportPretty, TxContext,
+};
+
+/// Name of the custom section the SDK writes the spec to.
+pub const SPEC_SECTION: &str = "contractspecv0";
+
+/// Deepest nesting of the contract's types converted through the spec, deeper
+/// values are converted as they would be without it.
+const MAX_UDT_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractSpec {
+    entries: Vec<ScSpecEntry>,
+}
+
+impl ContractSpec {
+    /// Decodes the spec entries of `wasm`. Wasms without a spec section have
+    /// an empty spec.
+    pub fn from_wasm(wasm: &[u8]) -> Result<Self, RetroshadeError> {
+        let mut entries = Vec::new();
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload.map_err(|error| RetroshadeError::Spec(error.to_string()))?;
+            let Payload::CustomSection(section) = payload else {
+                continue;
+            };
+            if section.name() != SPEC_SECTION {
+                continue;
+            }
+
+            let mut reader = Limited::new(Cursor::new(section.data()), Limits::none());
+            for entry in ScSpecEntry::read_xdr_iter(&mut reader) {
+                entries.push(entry.map_err(|error| RetroshadeError::Spec(error.to_string()))?);
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[ScSpecEntry] {
+        &self.entries
+    }
+
+    /// Fields of the struct `name` with their spec types, empty if the spec
+    /// has no such struct.
+    pub fn spec_for_struct(&self, name: &str) -> Vec<(String, ScSpecTypeDef)> {
+        self.structs()
+            .find(|udt| udt.name.to_utf8_string_lossy() == name)
+            .map(struct_fields)
+            .unwrap_or_default()
+    }
+
+    /// Columns of the struct `name` sorted by name, the order packing emits
+    /// them in since contracts emit structs as maps. Empty if the spec has
+    /// no such struct.
+    pub fn columns_for_struct(&self, name: &str) -> Vec<(String, ColumnType)> {
+        let mut columns: Vec<(String, ColumnType)> = self
+            .spec_for_struct(name)
+            .into_iter()
+            .map(|(field, type_def)| (field, column_type(&type_def)))
+            .collect();
+        columns.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        columns
+    }
+
+    /// Name of the struct whose fields are exactly `fields`. Emitted events
+    /// are maps that don't carry the name of the struct they were built
+    /// from, so this is how packing finds it.
+    pub fn struct_with_fields<'a>(
+        &self,
+        fields: impl IntoIterator<Item = &'a str>,
+    ) -> Option<String> {
+        let fields: HashSet<&str> = fields.into_iter().collect();
+
+        self.structs()
+            .find(|udt| {
+                let names: Vec<String> = udt
+                    .fields
+                    .iter()
+                    .map(|field| field.name.to_utf8_string_lossy())
+                    .collect();
+                names.len() == fields.len() && names.iter().all(|name| fields.contains(&**name))
+            })
+            .map(|udt| udt.name.to_utf8_string_lossy())
+    }
+
+    /// Checks `event` against the struct of the spec sharing the most fields
+    /// with it: every field present, none extra and each value of its
+    /// declared type.
+    pub fn validate(&self, event: &ScMap) -> Vec<SpecMismatch> {
+        let event: Vec<(String, &ScVal)> = event
+            .0
+            .iter()
+            .filter_map(|entry| match &entry.key {
+                ScVal::Symbol(symbol) => Some((symbol.to_string(), &entry.val)),
+                _ => None,
+            })
+            .collect();
+
+        let closest = self
+            .structs()
+            .map(|udt| {
+                let shared = udt
+                    .fields
+                    .iter()
+                    .filter(|field| {
+                        let name = field.name.to_utf8_string_lossy();
+                        event.iter().any(|(field, _)| *field == name)
+                    })
+                    .count();
+                (shared, udt)
+            })
+            .filter(|(shared, _)| *shared > 0)
+            // note: ties go to the struct declared first.
+            .fold(
+                None,
+                |closest: Option<(usize, _)>, candidate| match closest {
+                    Some(closest) if closest.0 >= candidate.0 => Some(closest),
+                    _ => Some(candidate),
+                },
+            );
+        let Some((_, udt)) = closest else {
+            return vec![SpecMismatch::NoMatchingStruct];
+        };
+
+        let structure = udt.name.to_utf8_string_lossy();
+        let fields = struct_fields(udt);
+        let mut mismatches = Vec::new();
+
+        for (field, type_def) in &fields {
+            match event.iter().find(|(name, _)| name == field) {
+                None => mismatches.push(SpecMismatch::MissingField {
+                    structure: structure.clone(),
+                    field: field.clone(),
+                }),
+                Some((_, value)) if !is_compatible(type_def, value) => {
+                    mismatches.push(SpecMismatch::TypeMismatch {
+                        structure: structure.clone(),
+                        field: field.clone(),
+                        expected: type_def.discriminant(),
+                        found: value.discriminant(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, _) in &event {
+            if !fields.iter().any(|(field, _)| field == name) {
+                mismatches.push(SpecMismatch::ExtraField {
+                    structure: structure.clone(),
+                    field: name.clone(),
+                });
+            }
+        }
+
+        mismatches
+    }
+
+    /// Converts `value` like [`FromScVal::from_scval`], except for the values
+    /// it can't type: void takes the field's column type and empty vectors
+    /// become empty arrays when the field is a vector of scalars.
+    ///
+    /// Values of the contract's own types are text: enums their case name,
+    /// followed by the JSON array of the payload for tuple cases, e.g.
+    /// `Filled(["1250"])`, and structs the JSON object of their fields.
+    /// Vectors of them are text arrays.
+    pub fn from_scval(&self, value: ScVal, type_def: &ScSpecTypeDef) -> FromScVal {
+        self.from_scval_with(value, type_def, &ConversionOptions::default())
+    }
+
+    /// Same as [`Self::from_scval`] with `options`.
+    pub fn from_scval_with(
+        &self,
+        value: ScVal,
+        type_def: &ScSpecTypeDef,
+        options: &ConversionOptions,
+    ) -> FromScVal {
+        let column = column_type(type_def);
+
+        match (type_def, value) {
+            (_, ScVal::Void) => FromScVal {
+                dbtype: column.dbtype,
+                kind: TypeKind::Void,
+            },
+            (_, ScVal::Vec(Some(items))) if items.is_empty() && is_array(&column.dbtype) => {
+                FromScVal {
+                    dbtype: column.dbtype,
+                    kind: TypeKind::GenericArray(vec![]),
+                }
+            }
+            (ScSpecTypeDef::Option(option), value) => {
+                self.from_scval_with(value, &option.value_type, options)
+            }
+            (ScSpecTypeDef::Vec(vec), ScVal::Vec(Some(items)))
+                if matches!(*vec.element_type, ScSpecTypeDef::Udt(_)) =>
+            {
+                let packed: Vec<FromScVal> = items
+                    .iter()
+                    .map(|item| self.from_scval_with(item.clone(), &vec.element_type, options))
+                    .collect();
+                if packed
+                    .iter()
+                    .all(|item| matches!(item.kind, TypeKind::Text(_)))
+                {
+                    FromScVal {
+                        dbtype: Type::TEXT_ARRAY,
+                        kind: TypeKind::GenericArray(packed),
+                    }
+                } else {
+                    FromScVal::from_scval_with(ScVal::Vec(Some(items)), &mut 0, options)
+                }
+            }
+            (ScSpecTypeDef::Udt(udt), value) => {
+                match self.udt_text(&udt.name.to_utf8_string_lossy(), &value, options) {
+                    Some(text) => FromScVal {
+                        dbtype: Type::TEXT,
+                        kind: TypeKind::Text(text),
+                    },
+                    None => FromScVal::from_scval_with(value, &mut 0, options),
+                }
+            }
+            (_, value) => FromScVal::from_scval_with(value, &mut 0, options),
+        }
+    }
+
+    fn udt_text(&self, name: &str, value: &ScVal, options: &ConversionOptions) -> Option<String> {
+        match (self.udt(name)?, value) {
+            (ScSpecEntry::UdtUnionV0(_), ScVal::Vec(Some(items))) if items.len() > 1 => {
+                let ScVal::Symbol(case) = &items[0] else {
+                    return None;
+                };
+                let Some(Value::Object(json)) = self.udt_json(name, value, 0, options) else {
+                    return None;
+                };
+                Some(format!("{}({})", case, json.get(&case.to_string())?))
+            }
+            _ => match self.udt_json(name, value, 0, options)? {
+                Value::String(text) => Some(text),
+                json => Some(json.to_string()),
+            },
+        }
+    }
+
+    fn udt(&self, name: &str) -> Option<&ScSpecEntry> {
+        self.entries.iter().find(|entry| {
+            let udt_name = match entry {
+                ScSpecEntry::UdtStructV0(udt) => &udt.name,
+                ScSpecEntry::UdtUnionV0(udt) => &udt.name,
+                ScSpecEntry::UdtEnumV0(udt) => &udt.name,
+                _ => return false,
+            };
+            udt_name.to_utf8_string_lossy() == name
+        })
+    }
+
+    /// JSON of a value of `type_def`, with the contract's types resolved
+    /// through the spec: structs are objects, enum cases their name or, for
+    /// tuple cases, an object of the name to the payload array.
+    fn to_json(
+        &self,
+        value: &ScVal,
+        type_def: &ScSpecTypeDef,
+        depth: usize,
+        options: &ConversionOptions,
+    ) -> Value {
+        let packed = || FromScVal::from_scval_with(value.clone(), &mut 0, options).to_json();
+        if depth > MAX_UDT_DEPTH {
+            return packed();
+        }
+
+        match (type_def, value) {
+            (_, ScVal::Void) => Value::Null,
+            (ScSpecTypeDef::Option(option), value) => {
+                self.to_json(value, &option.value_type, depth + 1, options)
+            }
+            (ScSpecTypeDef::Vec(vec), ScVal::Vec(Some(items))) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.to_json(item, &vec.element_type, depth + 1, options))
+                    .collect(),
+            ),
+            (ScSpecTypeDef::Udt(udt), value) => self
+                .udt_json(&udt.name.to_utf8_string_lossy(), value, depth + 1, options)
+                .unwrap_or_else(packed),
+            _ => packed(),
+        }
+    }
+
+    fn udt_json(
+        &self,
+        name: &str,
+        value: &ScVal,
+        depth: usize,
+        options: &ConversionOptions,
+    ) -> Option<Value> {
+        let json = match (self.udt(name)?, value) {
+            (ScSpecEntry::UdtStructV0(udt), ScVal::Map(Some(map))) => {
+                let mut object = serde_json::Map::new();
+                for entry in map.iter() {
+                    let ScVal::Symbol(key) = &entry.key else {
+                        return None;
+                    };
+                    let key = key.to_string();
+                    let field = udt
+                        .fields
+                        .iter()
+                        .find(|field| field.name.to_utf8_string_lossy() == key)?;
+                    object.insert(key, self.to_json(&entry.val, &field.type_, depth, options));
+                }
+                Value::Object(object)
+            }
+            // note: tuple structs are vectors of their fields.
+            (ScSpecEntry::UdtStructV0(udt), ScVal::Vec(Some(items))) => Value::Array(
+                udt.fields
+                    .iter()
+                    .zip(items.iter())
+                    .map(|(field, item)| self.to_json(item, &field.type_, depth, options))
+                    .collect(),
+            ),
+            (ScSpecEntry::UdtUnionV0(udt), ScVal::Vec(Some(items))) => {
+                let (ScVal::Symbol(case), payload) = items.split_first()? else {
+                    return None;
+                };
+                let case = case.to_string();
+                match udt
+                    .cases
+                    .iter()
+                    .find(|udt_case| union_case_name(udt_case) == case)?
+                {
+                    ScSpecUdtUnionCaseV0::VoidV0(_) => Value::String(case),
+                    ScSpecUdtUnionCaseV0::TupleV0(tuple) => {
+                        let payload = tuple
+                            .type_
+                            .iter()
+                            .zip(payload)
+                            .map(|(type_def, item)| self.to_json(item, type_def, depth, options))
+                            .collect();
+                        let mut object = serde_json::Map::new();
+                        object.insert(case, Value::Array(payload));
+                        Value::Object(object)
+                    }
+                }
+            }
+            (ScSpecEntry::UdtEnumV0(udt), ScVal::U32(value)) => Value::String(
+                udt.cases
+                    .iter()
+                    .find(|case| case.value == *value)?
+                    .name
+                    .to_utf8_string_lossy(),
+            ),
+            _ => return None,
+        };
+
+        Some(json)
+    }
+
+    fn structs(&self) -> impl Iterator<Item = &ScSpecUdtStructV0> {
+        self.entries.iter().filter_map(|entry| match entry {
+            ScSpecEntry::UdtStructV0(udt) => Some(udt),
+            _ => None,
+        })
+    }
+}
+
+fn union_case_name(case: &ScSpecUdtUnionCaseV0) -> String {
+    match case {
+        ScSpecUdtUnionCaseV0::VoidV0(case) => case.name.to_utf8_string_lossy(),
+        ScSpecUdtUnionCaseV0::TupleV0(case) => case.name.to_utf8_string_lossy(),
+    }
+}
+
+fn struct_fields(udt: &ScSpecUdtStructV0) -> Vec<(String, ScSpecTypeDef)> {
+    udt.fields
+        .iter()
+        .map(|field| (field.name.to_utf8_string_lossy(), field.type_.clone()))
+        .collect()
+}
+
+/// Column a spec type maps to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnType {
+    pub dbtype: Type,
+    /// Whether the field is an `Option`, i.e. can hold void.
+    pub nullable: bool,
+}
+
+/// `CREATE TABLE` of the exports `contract_id` emits to `target` as the
+/// struct `struct_name` of the spec of `wasm`, named with `naming`. Unlike
+/// [`sql::create_table`] it doesn't need an export, and fields that aren't
+/// options are `NOT NULL`.
+pub fn table_ddl_for(
+    wasm: &[u8],
+    struct_name: &str,
+    contract_id: &str,
+    target: &str,
+    naming: TableNaming,
+) -> Result<String, RetroshadeError> {
+    let columns = ContractSpec::from_wasm(wasm)?.columns_for_struct(struct_name);
+    if columns.is_empty() {
+        return Err(RetroshadeError::Spec(format!(
+            "the spec has no struct {}",
+            struct_name
+        )));
+    }
+
+    // note: naming strategies work on exports, so they get one with the
+    // columns of the struct and nothing emitted yet.
+    let template = RetroshadeExportPretty {
+        contract_id: contract_id.into(),
+        target: target.into(),
+        event: columns
+            .iter()
+            .map(|(name, column)| PackedEventEntry {
+                name: name.as_str().into(),
+                value: FromScVal {
+                    dbtype: column.dbtype.clone(),
+                    kind: TypeKind::Void,
+                },
+            })
+            .collect(),
+        context: TxContext {
+            tx_hash: Hash([0; 32]),
+            ledger_seq: 0,
+            closed_at: 0,
+            op_index: 0,
+        },
+        source_account: String::new(),
+        emit_index: 0,
+        event_object_xdr: vec![],
+    };
+
+    Ok(sql::create_table_for_columns(
+        &naming.table_name(&template),
+        &columns,
+    ))
+}
+
+/// Column type of a field, matching the types [`FromScVal::from_scval`]
+/// infers from the values. Options are nullable columns of their inner type.
+pub fn column_type(type_def: &ScSpecTypeDef) -> ColumnType {
+    let (dbtype, nullable) = match type_def {
+        ScSpecTypeDef::Option(option) => (column_type(&option.value_type).dbtype, true),
+        ScSpecTypeDef::Vec(vec) => {
+            let dbtype = match (&*vec.element_type, column_type(&vec.element_type).dbtype) {
+                // note: vectors whose elements can differ in type are packed
+                // as JSON.
+                (ScSpecTypeDef::Option(_) | ScSpecTypeDef::Void | ScSpecTypeDef::Val, _) => {
+                    Type::TEXT
+                }
+                (_, Type::BOOL) => Type::BOOL_ARRAY,
+                (_, Type::NUMERIC) => Type::NUMERIC_ARRAY,
+                // note: elements that aren't booleans or numerics are packed as
+                // text, nested vectors as JSON text.
+                _ => Type::TEXT_ARRAY,
+            };
+            (dbtype, false)
+        }
+        other => (scalar_type(other), false),
+    };
+
+    ColumnType { dbtype, nullable }
+}
+
+fn scalar_type(type_def: &ScSpecTypeDef) -> Type {
+    match type_def {
+        ScSpecTypeDef::Bool => Type::BOOL,
+        ScSpecTypeDef::U32
+        | ScSpecTypeDef::I32
+        | ScSpecTypeDef::U64
+        | ScSpecTypeDef::I64
+        | ScSpecTypeDef::U128
+        | ScSpecTypeDef::I128
+        | ScSpecTypeDef::U256
+        | ScSpecTypeDef::I256
+        | ScSpecTypeDef::Timepoint
+        | ScSpecTypeDef::Duration => Type::NUMERIC,
+        ScSpecTypeDef::Bytes | ScSpecTypeDef::BytesN(_) => Type::BYTEA,
+        _ => Type::TEXT,
+    }
+}
+
+fn is_array(dbtype: &Type) -> bool {
+    matches!(
+        *dbtype,
+        Type::BOOL_ARRAY | Type::NUMERIC_ARRAY | Type::TEXT_ARRAY
+    )
+}
+
+/// How an emitted event differs from the struct it's declared as.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpecMismatch {
+    /// The spec has no struct sharing a field with the event.
+    NoMatchingStruct,
+    MissingField {
+        structure: String,
+        field: String,
+    },
+    ExtraField {
+        structure: String,
+        field: String,
+    },
+    TypeMismatch {
+        structure: String,
+        field: String,
+        expected: ScSpecType,
+        found: ScValType,
+    },
+}
+
+impl fmt::Display for SpecMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecMismatch::NoMatchingStruct => write!(f, "no struct of the spec matches the event"),
+            SpecMismatch::MissingField { structure, field } => {
+                write!(f, "{} field {} is missing", structure, field)
+            }
+            SpecMismatch::ExtraField { structure, field } => {
+                write!(f, "{} has no field {}", structure, field)
+            }
+            SpecMismatch::TypeMismatch {
+                structure,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} field {} is declared {} but holds {}",
+                structure,
+                field,
+                expected.name(),
+                found.name()
+            ),
+        }
+    }
+}
+
+/// Whether `value` can be a value of `type_def`. Types the event columns
+/// don't distinguish, such as user-defined types and tuples, accept the
+/// values they could be encoded as.
+fn is_compatible(type_def: &ScSpecTypeDef, value: &ScVal) -> bool {
+    match (type_def, value) {
+        (ScSpecTypeDef::Option(option), value) => {
+            matches!(value, ScVal::Void) || is_compatible(&option.value_type, value)
+        }
+        (ScSpecTypeDef::Vec(vec), ScVal::Vec(Some(items))) => items
+            .iter()
+            .all(|item| is_compatible(&vec.element_type, item)),
+        (ScSpecTypeDef::BytesN(bytes_n), ScVal::Bytes(bytes)) => bytes.len() == bytes_n.n as usize,
+        (ScSpecTypeDef::Map(_), ScVal::Map(Some(_))) => true,
+        (ScSpecTypeDef::Tuple(_), ScVal::Vec(Some(_))) => true,
+        (ScSpecTypeDef::Udt(_), ScVal::Map(_) | ScVal::Vec(_) | ScVal::U32(_)) => true,
+        (ScSpecTypeDef::Val, _) => true,
+        (type_def, value) => scalar_matches(type_def, value),
+    }
+}
+
+fn scalar_matches(type_def: &ScSpecTypeDef, value: &ScVal) -> bool {
+    matches!(
+        (type_def, value),
+        (ScSpecTypeDef::Bool, ScVal::Bool(_))
+            | (ScSpecTypeDef::Void, ScVal::Void)
+            | (ScSpecTypeDef::Error, ScVal::Error(_))
+            | (ScSpecTypeDef::U32, ScVal::U32(_))
+            | (ScSpecTypeDef::I32, ScVal::I32(_))
+            | (ScSpecTypeDef::U64, ScVal::U64(_))
+            | (ScSpecTypeDef::I64, ScVal::I64(_))
+            | (ScSpecTypeDef::Timepoint, ScVal::Timepoint(_))
+            | (ScSpecTypeDef::Duration, ScVal::Duration(_))
+            | (ScSpecTypeDef::U128, ScVal::U128(_))
+            | (ScSpecTypeDef::I128, ScVal::I128(_))
+            | (ScSpecTypeDef::U256, ScVal::U256(_))
+            | (ScSpecTypeDef::I256, ScVal::I256(_))
+            | (ScSpecTypeDef::Bytes, ScVal::Bytes(_))
+            | (ScSpecTypeDef::String, ScVal::String(_))
+            | (ScSpecTypeDef::Symbol, ScVal::Symbol(_))
+            | (ScSpecTypeDef::Address, ScVal::Address(_))
+    )
+}
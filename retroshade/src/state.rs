@@ -1,15 +1,155 @@
-use std::{collections::HashMap, rc::Rc, u32};
-
-use soroban_env_host::{
-    storage::SnapshotSource,
-    xdr::{
-        AccountId, ContractExecutable, Hash, LedgerEntry, LedgerEntryChange, LedgerEntryData,
-        MuxedAccount, Operation, OperationBody, OperationMeta, OperationMetaV2, PublicKey,
-        ScAddress, ScVal, TransactionExt, TransactionMeta, TransactionV1Envelope,
-    },
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    sync::{Arc, OnceLock},
+    u32,
 };
 
-use crate::{RetroshadeError, RetroshadesExecution};
+use sha2::{Digest, Sha256};
+use soroban_env_host::xdr::{
+    AccountId, BytesM, ContractCodeEntry, ContractExecutable, Hash, LedgerEntry, LedgerEntryChange,
+    LedgerEntryData, LedgerKey, Limits, MuxedAccount, Operation, OperationBody, OperationMeta,
+    OperationMetaV2, PublicKey, ScAddress, ScVal, Transaction, TransactionExt, TransactionMeta,
+    TransactionSignaturePayload, TransactionSignaturePayloadTaggedTransaction,
+    TransactionV1Envelope, WriteXdr,
+};
+
+use crate::{
+    snapshot::{entry_key, SnapshotSourceExt},
+    AppliedReplacement, FootprintProvenance, ReplacementEntry, ReplacementPolicy, RetroshadeError,
+    RetroshadesExecution, SkippedReplacement, StaleSnapshotEntry,
+};
+
+/// An entry of the pre-execution state with its key, derived once when the
+/// entry is added rather than by every execution feeding it to the host.
+#[derive(Clone, Debug)]
+pub(crate) struct KeyedEntry {
+    pub entry: LedgerEntry,
+    pub live_until: Option<u32>,
+    /// `None` for the entry types retroshades doesn't track.
+    pub key: Option<EntryKey>,
+    /// Binary a code entry runs instead of its code, shared with the
+    /// replacement rather than copied into the entry.
+    pub replacement: Option<Arc<[u8]>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct EntryKey {
+    pub key: LedgerKey,
+    /// SHA-256 of the key's XDR, which TTL entries are keyed by.
+    pub hash: [u8; 32],
+}
+
+impl EntryKey {
+    fn new(key: LedgerKey) -> Self {
+        let hash = Sha256::digest(key.to_xdr(Limits::none()).unwrap()).into();
+        Self { key, hash }
+    }
+}
+
+/// Serialized size of a TTL entry, a key hash and a ledger.
+const TTL_ENTRY_BYTES: usize = 32 + 4;
+
+impl KeyedEntry {
+    pub(crate) fn new(entry: LedgerEntry, live_until: Option<u32>) -> Self {
+        let key = entry_key(&entry).map(EntryKey::new);
+        Self {
+            entry,
+            live_until,
+            key,
+            replacement: None,
+        }
+    }
+
+    /// Code the entry runs, if it's a code entry.
+    pub(crate) fn code(&self) -> Option<&[u8]> {
+        match &self.entry.data {
+            LedgerEntryData::ContractCode(code) => {
+                Some(self.replacement.as_deref().unwrap_or(code.code.as_slice()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The entry fed to the host, copying the replacement into code entries.
+    pub(crate) fn fed_entry(&self) -> Cow<'_, LedgerEntry> {
+        match &self.replacement {
+            Some(_) => Cow::Owned(self.clone().into_fed_entry()),
+            None => Cow::Borrowed(&self.entry),
+        }
+    }
+
+    pub(crate) fn into_fed_entry(self) -> LedgerEntry {
+        let mut entry = self.entry;
+        if let (LedgerEntryData::ContractCode(code), Some(wasm)) =
+            (&mut entry.data, self.replacement)
+        {
+            code.code = wasm.to_vec().try_into().unwrap();
+        }
+
+        entry
+    }
+
+    /// XDR of the entry fed to the host. Replacements are written in place
+    /// of the code rather than copied into the entry first.
+    pub(crate) fn to_xdr(&self) -> Vec<u8> {
+        let (LedgerEntryData::ContractCode(code), Some(wasm)) =
+            (&self.entry.data, &self.replacement)
+        else {
+            return self.entry.to_xdr(Limits::none()).unwrap();
+        };
+
+        let without_code = LedgerEntry {
+            last_modified_ledger_seq: self.entry.last_modified_ledger_seq,
+            data: LedgerEntryData::ContractCode(ContractCodeEntry {
+                ext: code.ext.clone(),
+                hash: code.hash.clone(),
+                code: BytesM::default(),
+            }),
+            ext: self.entry.ext.clone(),
+        }
+        .to_xdr(Limits::none())
+        .unwrap();
+        // note: the code is the last field of the code entry, its length is
+        // followed by the entry's extension only.
+        let ext_len = self.entry.ext.to_xdr(Limits::none()).unwrap().len();
+        let code_at = without_code.len() - ext_len - 4;
+        let padding = (4 - wasm.len() % 4) % 4;
+
+        let mut encoded = Vec::with_capacity(without_code.len() + wasm.len() + padding);
+        encoded.extend_from_slice(&without_code[..code_at]);
+        encoded.extend_from_slice(&(wasm.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(wasm);
+        encoded.resize(encoded.len() + padding, 0);
+        encoded.extend_from_slice(&without_code[code_at + 4..]);
+
+        encoded
+    }
+
+    /// Serialized size of the entry and of its TTL entry, as the encoded
+    /// inputs count them.
+    pub(crate) fn state_bytes(&self) -> usize {
+        let ttl_bytes = match (&self.entry.data, &self.key) {
+            (LedgerEntryData::ContractData(_) | LedgerEntryData::ContractCode(_), Some(_)) => {
+                TTL_ENTRY_BYTES
+            }
+            _ => 0,
+        };
+
+        self.to_xdr().len() + ttl_bytes
+    }
+
+    /// Same as [`Self::new`] for entries fetched by `key`.
+    fn with_key(entry: LedgerEntry, live_until: Option<u32>, key: LedgerKey) -> Self {
+        Self {
+            entry,
+            live_until,
+            key: Some(EntryKey::new(key)),
+            replacement: None,
+        }
+    }
+}
 
 pub enum MetaOperation {
     V1(OperationMeta),
@@ -19,11 +159,13 @@ pub enum MetaOperation {
 impl RetroshadesExecution {
     /// Builds the current state for the requested entries and
     /// sets the resources, auth entries, host function and source account.
+    /// Returns which source served each footprint key.
     pub(crate) fn build_current_state(
         &mut self,
-        snapshot_source: Box<dyn SnapshotSource>,
+        snapshot_source: &dyn SnapshotSourceExt,
         envelope: TransactionV1Envelope,
-    ) -> Result<(), RetroshadeError> {
+    ) -> Result<Vec<FootprintProvenance>, RetroshadeError> {
+        self.tx_hash = Some(self.transaction_hash(&envelope.tx)?);
         let tx_source = envelope.tx.source_account;
 
         let resources = match envelope.tx.ext {
@@ -52,6 +194,7 @@ impl RetroshadesExecution {
                     }
                 };
                 self.source_account = Some(id);
+                self.muxed_source_account = Some(muxed_source.clone());
             } else {
                 return Err(RetroshadeError::NotSorobanTx);
             }
@@ -65,25 +208,119 @@ impl RetroshadesExecution {
         ]
         .concat();
 
+        let mut provenance = Vec::new();
+        let mut state_bytes = 0;
         for key in full_footprint {
+            #[cfg(feature = "metrics")]
+            let start = crate::Instant::now();
             let entry = snapshot_source
-                .get(&Rc::new(key.clone()))
-                .map_err(RetroshadeError::SVMHost)?;
-
-            if let Some(entry) = entry {
-                self.target_pre_execution_state
-                    .push((entry.0.as_ref().clone(), entry.1))
+                .get_labeled(&Rc::new(key.clone()))
+                .map_err(RetroshadeError::from)?;
+            #[cfg(feature = "metrics")]
+            crate::telemetry::record_snapshot_fetch(start.elapsed());
+
+            if let Some((entry, source)) = entry {
+                let keyed = KeyedEntry::with_key(entry.0.as_ref().clone(), entry.1, key.clone());
+                // note: stops fetching pathological footprints early, the
+                // built state is checked again once replacements changed it.
+                if let Some(limit) = self.max_state_bytes {
+                    state_bytes += keyed.state_bytes();
+                    if state_bytes > limit {
+                        return Err(RetroshadeError::StateTooLarge {
+                            bytes: state_bytes,
+                            limit,
+                        });
+                    }
+                }
+                self.target_pre_execution_state.push(keyed);
+                provenance.push(FootprintProvenance {
+                    key,
+                    source,
+                    last_modified_ledger_seq: Some(entry.0.last_modified_ledger_seq),
+                    live_until: entry.1,
+                });
+            } else {
+                provenance.push(FootprintProvenance {
+                    key,
+                    source: snapshot_source.label(),
+                    last_modified_ledger_seq: None,
+                    live_until: None,
+                });
             }
         }
 
-        Ok(())
+        Ok(provenance)
     }
 
+    /// Footprint entries the snapshot has as modified at or after the
+    /// transaction's ledger, without the meta resetting them. The state then
+    /// holds changes of later transactions.
+    pub(crate) fn stale_entries(
+        &self,
+        footprint: &[FootprintProvenance],
+        tx_meta: &TransactionMeta,
+    ) -> Vec<StaleSnapshotEntry> {
+        let changes: Vec<LedgerEntryChange> = match tx_meta {
+            TransactionMeta::V3(v3) => v3
+                .operations
+                .iter()
+                .flat_map(|op| op.changes.0.iter().cloned())
+                .collect(),
+            TransactionMeta::V4(v4) => v4
+                .operations
+                .iter()
+                .flat_map(|op| op.changes.0.iter().cloned())
+                .collect(),
+            _ => vec![],
+        };
+        // note: the transaction itself modified entries it created or
+        // restored, on top of those it has the state of.
+        let reset: HashSet<LedgerKey> = changes
+            .iter()
+            .filter_map(|change| match change {
+                LedgerEntryChange::State(entry)
+                | LedgerEntryChange::Created(entry)
+                | LedgerEntryChange::Restored(entry) => entry_key(entry),
+                LedgerEntryChange::Updated(_) | LedgerEntryChange::Removed(_) => None,
+            })
+            .collect();
+
+        let ledger_seq = self.ledger_info.sequence_number;
+        footprint
+            .iter()
+            .filter_map(|provenance| {
+                let last_modified_ledger_seq = provenance.last_modified_ledger_seq?;
+                (last_modified_ledger_seq >= ledger_seq && !reset.contains(&provenance.key)).then(
+                    || StaleSnapshotEntry {
+                        key: provenance.key.clone(),
+                        last_modified_ledger_seq,
+                        ledger_seq,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Hash of the transaction on the network described by the ledger info.
+    fn transaction_hash(&self, tx: &Transaction) -> Result<Hash, RetroshadeError> {
+        let payload = TransactionSignaturePayload {
+            network_id: Hash(self.ledger_info.network_id),
+            tagged_transaction: TransactionSignaturePayloadTaggedTransaction::Tx(tx.clone()),
+        };
+        let payload = payload
+            .to_xdr(Limits::none())
+            .map_err(|_| RetroshadeError::MalformedXdr)?;
+
+        Ok(Hash(Sha256::digest(payload).into()))
+    }
+
+    /// Resets the state to how it was before the transaction was applied.
+    /// Returns the number of entries that were reset.
     pub(crate) fn state_reset_to_pre_execution(
         &mut self,
         tx_meta: TransactionMeta,
-    ) -> Result<bool, RetroshadeError> {
-        let mut changed = false;
+    ) -> Result<usize, RetroshadeError> {
+        let mut changed = 0;
 
         let ops: Vec<MetaOperation> = match tx_meta {
             TransactionMeta::V3(v3) => v3
@@ -110,53 +347,141 @@ impl RetroshadesExecution {
         Ok(changed)
     }
 
+    /// Replaces the code of Mercury-deployed contracts. Keys are either contract
+    /// ids or the hash of the wasm to replace. Returns the replacements that
+    /// replaced a code entry and the ones left out by their allowlist.
+    ///
+    /// The binaries are shared with the code entries they replace rather than
+    /// copied, callers replaying many transactions share them across
+    /// executions.
     pub(crate) fn replace_binaries(
         &mut self,
-        mercury_contracts: HashMap<Hash, &[u8]>,
-    ) -> Result<bool, RetroshadeError> {
-        let mut replaced = false;
-
-        let binaries_mutation = {
-            let mut binaries_mutation = HashMap::new();
-
-            for entry in self.target_pre_execution_state.iter() {
-                if let LedgerEntryData::ContractData(data) = &entry.0.data {
-                    let contract_hash = match &data.contract {
-                        ScAddress::Contract(hash) => hash,
-                        _ => return Err(RetroshadeError::MalformedXdr),
-                    };
-                    let hash = contract_hash.clone().into();
-                    if let Some(new_code) = mercury_contracts.get(&hash) {
-                        if let ScVal::LedgerKeyContractInstance = data.key {
-                            if let ScVal::ContractInstance(instance) = &data.val {
-                                if let ContractExecutable::Wasm(wasm) = &instance.executable {
-                                    binaries_mutation.insert(wasm.clone(), new_code);
-                                }
-                            };
+        mercury_contracts: HashMap<Hash, ReplacementEntry>,
+    ) -> Result<(Vec<AppliedReplacement>, Vec<SkippedReplacement>), RetroshadeError> {
+        self.swapped_binaries.clear();
+
+        // note: code entries are shared, every contract running one is
+        // checked against the allowlist and not only the replaced one.
+        let mut executables: HashMap<Hash, Vec<Hash>> = HashMap::new();
+        for entry in self.target_pre_execution_state.iter() {
+            if let LedgerEntryData::ContractData(data) = &entry.entry.data {
+                let contract_hash = match &data.contract {
+                    ScAddress::Contract(hash) => hash,
+                    _ => return Err(RetroshadeError::MalformedXdr),
+                };
+                if let ScVal::LedgerKeyContractInstance = data.key {
+                    if let ScVal::ContractInstance(instance) = &data.val {
+                        if let ContractExecutable::Wasm(wasm) = &instance.executable {
+                            executables
+                                .entry(wasm.clone())
+                                .or_default()
+                                .push(contract_hash.clone().into());
                         }
-                    }
+                    };
                 }
             }
+        }
 
-            binaries_mutation
-        };
+        let mut binaries_mutation = HashMap::new();
+        let mut applied = vec![];
+        let mut skipped = vec![];
+        for entry in self.target_pre_execution_state.iter() {
+            if let LedgerEntryData::ContractCode(code_entry) = &entry.entry.data {
+                let contracts = executables
+                    .get(&code_entry.hash)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                // note: entries keyed by the contracts take precedence over
+                // the one keyed by the wasm hash.
+                let mut replacements: Vec<(&Hash, &ReplacementEntry)> = contracts
+                    .iter()
+                    .filter_map(|contract_id| mercury_contracts.get_key_value(contract_id))
+                    .collect();
+                if replacements.is_empty() {
+                    replacements.extend(mercury_contracts.get_key_value(&code_entry.hash));
+                }
+                let Some(&(key, replacement)) = replacements.first() else {
+                    continue;
+                };
+                if replacements
+                    .iter()
+                    .any(|(_, other)| other.wasm != replacement.wasm)
+                {
+                    return Err(RetroshadeError::ConflictingReplacements {
+                        wasm_hash: code_entry.hash.clone(),
+                        keys: replacements.iter().map(|(key, _)| (*key).clone()).collect(),
+                    });
+                }
+
+                let denied: Vec<SkippedReplacement> = contracts
+                    .iter()
+                    .filter(|contract_id| {
+                        !replacements
+                            .iter()
+                            .any(|(key, replacement)| replacement.allows(key, contract_id))
+                    })
+                    .map(|contract_id| SkippedReplacement {
+                        key: key.clone(),
+                        wasm_hash: code_entry.hash.clone(),
+                        contract_id: contract_id.clone(),
+                    })
+                    .collect();
+                if denied.is_empty() {
+                    binaries_mutation.insert(code_entry.hash.clone(), &replacement.wasm);
+                    applied.push(AppliedReplacement {
+                        key: key.clone(),
+                        wasm_hash: code_entry.hash.clone(),
+                        contract_ids: contracts.to_vec(),
+                    });
+                }
+                skipped.extend(denied);
+            }
+        }
+
+        match self.replacement_policy {
+            ReplacementPolicy::Skip => {
+                for skipped in &skipped {
+                    log::warn!("{}", skipped);
+                }
+            }
+            ReplacementPolicy::Error => {
+                if let Some(skipped) = skipped.first() {
+                    return Err(RetroshadeError::ReplacementNotAllowed(skipped.clone()));
+                }
+            }
+        }
 
         for entry in self.target_pre_execution_state.iter_mut() {
-            if let LedgerEntryData::ContractCode(code_entry) = &mut entry.0.data {
+            if let LedgerEntryData::ContractCode(code_entry) = &entry.entry.data {
                 if let Some(new_code) = binaries_mutation.get(&code_entry.hash) {
-                    replaced = true;
-                    code_entry.code = new_code.to_vec().try_into().unwrap();
+                    entry.replacement = Some(Arc::clone(new_code));
                 }
             }
         }
 
-        Ok(replaced)
+        Ok((applied, skipped))
+    }
+
+    /// Takes the replacements out of the replaced entries, which run the
+    /// binaries they replaced, calling it again puts them back.
+    pub(crate) fn swap_binaries(&mut self) {
+        for entry in self.target_pre_execution_state.iter_mut() {
+            if let LedgerEntryData::ContractCode(code_entry) = &entry.entry.data {
+                match entry.replacement.take() {
+                    Some(wasm) => {
+                        self.swapped_binaries.insert(code_entry.hash.clone(), wasm);
+                    }
+                    None => entry.replacement = self.swapped_binaries.remove(&code_entry.hash),
+                }
+            }
+        }
+        self.encoded_inputs = OnceLock::new();
     }
 
     fn process_operation(
         &mut self,
         op: &MetaOperation,
-        changed: &mut bool,
+        changed: &mut usize,
     ) -> Result<(), RetroshadeError> {
         let mut current_state = None;
 
@@ -196,75 +521,61 @@ impl RetroshadesExecution {
 
     fn add_entry(&mut self, entry: &LedgerEntry) {
         self.target_pre_execution_state
-            .push((entry.clone(), Some(u32::MAX)));
+            .push(KeyedEntry::new(entry.clone(), Some(u32::MAX)));
     }
 
-    fn remove_entry(&mut self, current_state_entry: &LedgerEntry, changed: &mut bool) {
-        // note: should only be one entry but we do this for consistency.
-        let mut to_delete = Vec::new();
-        let mut to_delete_force = Vec::new();
-
-        let len_here = self.target_pre_execution_state.len();
-
-        for (idx, entry) in self.target_pre_execution_state.iter().enumerate() {
-            match &entry.0.data {
-                LedgerEntryData::ContractCode(_) => {
-                    // this should not happen in general. We ignore for wasm uploads.
-                }
-                LedgerEntryData::ContractData(data) => {
-                    if let LedgerEntryData::ContractData(pre_data) = &current_state_entry.data {
-                        if data.contract == pre_data.contract && data.key == pre_data.key {
-                            to_delete.push(idx);
-                            to_delete_force.push(entry.0.clone());
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        for force_remove in to_delete_force {
-            self.force_remove.push(force_remove);
+    /// Drops the contract data entry with `current_state_entry`'s key from
+    /// the pre-execution state, leaving the host to report it as missing.
+    /// Other entry types are kept, e.g. code entries of wasm uploads.
+    fn remove_entry(&mut self, current_state_entry: &LedgerEntry, changed: &mut usize) {
+        if !matches!(current_state_entry.data, LedgerEntryData::ContractData(_)) {
+            return;
         }
+        let Some(key) = entry_key(current_state_entry) else {
+            return;
+        };
 
-        let mut shift = 0;
-        for idx in to_delete {
-            let target_idx_adjusted = idx - shift;
-
-            if self.target_pre_execution_state.len() > target_idx_adjusted {
-                self.target_pre_execution_state.remove(target_idx_adjusted);
-                *changed = true;
-                shift += 1;
-            } else {
-                log::error!("Unknown retorshades state error: Previous len before processing {}, idx: {}, current len: {}", len_here, idx, self.target_pre_execution_state.len())
+        // note: should only be one entry, but removing by key in a single pass
+        // stays correct if the state ever holds the same key twice.
+        let force_remove = &mut self.force_remove;
+        self.target_pre_execution_state.retain(|keyed| {
+            let matches = keyed
+                .key
+                .as_ref()
+                .is_some_and(|entry_key| entry_key.key == key);
+            if matches {
+                force_remove.push(keyed.entry.clone());
+                *changed += 1;
             }
-        }
+
+            !matches
+        });
     }
 
-    fn update_entries(&mut self, pre_execution: &LedgerEntry, changed: &mut bool) {
+    fn update_entries(&mut self, pre_execution: &LedgerEntry, changed: &mut usize) {
         for entry in self.target_pre_execution_state.iter_mut() {
-            match &entry.0.data {
+            match &entry.entry.data {
                 LedgerEntryData::ContractCode(code) => {
                     if let LedgerEntryData::ContractCode(pre_code) = &pre_execution.data {
                         if pre_code.hash == code.hash {
-                            *entry = (pre_execution.clone(), entry.1);
-                            *changed = true;
+                            entry.entry = pre_execution.clone();
+                            *changed += 1;
                         }
                     }
                 }
                 LedgerEntryData::ContractData(data) => {
                     if let LedgerEntryData::ContractData(pre_data) = &pre_execution.data {
                         if data.contract == pre_data.contract && data.key == pre_data.key {
-                            *entry = (pre_execution.clone(), entry.1);
-                            *changed = true;
+                            entry.entry = pre_execution.clone();
+                            *changed += 1;
                         }
                     }
                 }
                 LedgerEntryData::Trustline(data) => {
                     if let LedgerEntryData::Trustline(pre_data) = &pre_execution.data {
                         if data.asset == pre_data.asset && data.account_id == pre_data.account_id {
-                            *entry = (pre_execution.clone(), entry.1);
-                            *changed = true;
+                            entry.entry = pre_execution.clone();
+                            *changed += 1;
                         }
                     }
                 }
@@ -272,8 +583,8 @@ impl RetroshadesExecution {
                 LedgerEntryData::Account(data) => {
                     if let LedgerEntryData::Account(pre_data) = &pre_execution.data {
                         if data.account_id == pre_data.account_id {
-                            *entry = (pre_execution.clone(), entry.1);
-                            *changed = true;
+                            entry.entry = pre_execution.clone();
+                            *changed += 1;
                         }
                     }
                 }
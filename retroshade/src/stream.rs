@@ -0,0 +1,31 @@
+//! Streaming of the bulky parts of an execution result.
+//!
+//! Diagnostics and ledger changes of big transactions can add up to several
+//! MB that callers often serialize and drop right away. A [`ResultStream`]
+//! receives them one at a time instead, see
+//! [`crate::RetroshadesExecution::retroshade_streaming`], so they are never
+//! collected into the result.
+
+use soroban_env_host::xdr::DiagnosticEvent;
+
+use crate::changes::EntryChange;
+
+pub trait ResultStream {
+    fn diagnostic(&mut self, event: DiagnosticEvent);
+    fn ledger_change(&mut self, change: EntryChange);
+}
+
+/// A pair of callbacks, for diagnostics and ledger changes respectively.
+impl<D, L> ResultStream for (D, L)
+where
+    D: FnMut(DiagnosticEvent),
+    L: FnMut(EntryChange),
+{
+    fn diagnostic(&mut self, event: DiagnosticEvent) {
+        (self.0)(event)
+    }
+
+    fn ledger_change(&mut self, change: EntryChange) {
+        (self.1)(change)
+    }
+}
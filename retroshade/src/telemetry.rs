@@ -0,0 +1,100 @@
+//! Metrics emitted through the [`metrics`](::metrics) facade, recorded by
+//! whichever exporter the application installs.
+//!
+//! | Name | Kind | Labels | |
+//! |------|------|--------|-|
+//! | `retroshade_executions_total` | counter | `outcome` | Executions by outcome: `success`, `call_failed` when the invocation failed, `error` when the host couldn't run it. |
+//! | `retroshade_execution_duration_seconds` | histogram | | Time spent running the host function. |
+//! | `retroshade_budget_cpu_instructions` | histogram | | CPU instructions the execution consumed. |
+//! | `retroshade_budget_memory_bytes` | histogram | | Memory the execution consumed. |
+//! | `retroshade_retroshades_emitted_total` | counter | `contract` | Retroshades an execution returned, by contract strkey. |
+//! | `retroshade_snapshot_fetch_duration_seconds` | histogram | | Time the snapshot source took to serve one footprint key. |
+//! | `retroshade_conversion_failures_total` | counter | | Retroshades that couldn't be packed. |
+//! | `retroshade_sink_deliveries_total` | counter | `outcome` | Exports handed to a sink by [`deliver_all`](crate::sink::deliver_all), `delivered` or `failed`. |
+//!
+//! Call [`describe`] once the recorder is installed for exporters to show
+//! the descriptions and units.
+
+use std::time::Duration;
+
+use ::metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+
+use crate::{internal::InvokeHostFunctionHelperResult, RetroshadeError};
+
+pub const EXECUTIONS: &str = "retroshade_executions_total";
+pub const EXECUTION_DURATION: &str = "retroshade_execution_duration_seconds";
+pub const BUDGET_CPU: &str = "retroshade_budget_cpu_instructions";
+pub const BUDGET_MEMORY: &str = "retroshade_budget_memory_bytes";
+pub const RETROSHADES_EMITTED: &str = "retroshade_retroshades_emitted_total";
+pub const SNAPSHOT_FETCH_DURATION: &str = "retroshade_snapshot_fetch_duration_seconds";
+pub const CONVERSION_FAILURES: &str = "retroshade_conversion_failures_total";
+pub const SINK_DELIVERIES: &str = "retroshade_sink_deliveries_total";
+
+/// Registers the descriptions and units of the metrics with the installed
+/// recorder.
+pub fn describe() {
+    describe_counter!(EXECUTIONS, "Retroshade executions by outcome.");
+    describe_histogram!(
+        EXECUTION_DURATION,
+        Unit::Seconds,
+        "Time spent running the host function."
+    );
+    describe_histogram!(
+        BUDGET_CPU,
+        Unit::Count,
+        "CPU instructions consumed by an execution."
+    );
+    describe_histogram!(
+        BUDGET_MEMORY,
+        Unit::Bytes,
+        "Memory consumed by an execution."
+    );
+    describe_counter!(RETROSHADES_EMITTED, "Retroshades returned, by contract.");
+    describe_histogram!(
+        SNAPSHOT_FETCH_DURATION,
+        Unit::Seconds,
+        "Time the snapshot source took to serve a footprint key."
+    );
+    describe_counter!(CONVERSION_FAILURES, "Retroshades that couldn't be packed.");
+    describe_counter!(SINK_DELIVERIES, "Exports handed to a sink, by outcome.");
+}
+
+pub(crate) fn record_execution(
+    execution: &Result<InvokeHostFunctionHelperResult, RetroshadeError>,
+    duration: Duration,
+) {
+    let outcome = match execution {
+        Ok(result) if result.invoke_result.is_ok() => "success",
+        Ok(_) => "call_failed",
+        Err(_) => "error",
+    };
+    counter!(EXECUTIONS, "outcome" => outcome).increment(1);
+    histogram!(EXECUTION_DURATION).record(duration.as_secs_f64());
+
+    if let Ok(result) = execution {
+        if let Ok(cpu) = result.budget.get_cpu_insns_consumed() {
+            histogram!(BUDGET_CPU).record(cpu as f64);
+        }
+        if let Ok(memory) = result.budget.get_mem_bytes_consumed() {
+            histogram!(BUDGET_MEMORY).record(memory as f64);
+        }
+    }
+}
+
+pub(crate) fn record_retroshade(contract_id: [u8; 32]) {
+    let contract = stellar_strkey::Contract(contract_id).to_string();
+    counter!(RETROSHADES_EMITTED, "contract" => contract).increment(1);
+}
+
+pub(crate) fn record_snapshot_fetch(duration: Duration) {
+    histogram!(SNAPSHOT_FETCH_DURATION).record(duration.as_secs_f64());
+}
+
+pub(crate) fn record_conversion_failure() {
+    counter!(CONVERSION_FAILURES).increment(1);
+}
+
+pub(crate) fn record_delivery(delivered: bool) {
+    let outcome = if delivered { "delivered" } else { "failed" };
+    counter!(SINK_DELIVERIES, "outcome" => outcome).increment(1);
+}
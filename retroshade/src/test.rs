@@ -1,2 +1,48 @@
+mod address_format;
+mod all_types;
+mod cache;
+mod changes;
+mod compare;
+mod compat;
+mod contract_event;
+mod conversion;
+mod decode;
+mod dedup;
+mod deployer;
+mod diagnostics;
+#[cfg(feature = "diesel")]
+mod diesel;
+mod dump;
+mod e2e;
+mod engine;
+mod estimate;
+mod examples;
+mod export;
+mod failure;
+mod fixture;
+mod ledger_meta;
+mod ledger_snapshot;
+mod msgpack;
+mod naming;
+mod network;
+mod overlay;
+mod owners;
+mod packing;
+mod replacement;
+mod resources;
+mod sandbox;
+mod schema;
+mod seed;
+mod shared;
 mod simple;
+mod sink;
+mod spec;
+#[cfg(feature = "sqlx")]
+mod sqlx;
+mod staleness;
+mod state;
 mod storage;
+mod stream;
+#[cfg(feature = "metrics")]
+mod telemetry;
+mod testutils;
@@ -0,0 +1,200 @@
+//! Addresses are rendered the same way wherever they're packed: the
+//! contract id, address fields, arrays and the JSON of containers.
+
+use soroban_env_host::{
+    xdr::{
+        AccountId, ContractId, Hash, MuxedAccount, PublicKey, ScAddress, ScMap, ScMapEntry,
+        ScString, ScVal, Uint256,
+    },
+    zephyr::RetroshadeExport,
+};
+
+use crate::{
+    conversion::{address_text, AddressFormat, ConversionOptions, FromScVal, TypeKind},
+    RetroshadeExportPretty,
+};
+
+use super::packing::{built_execution, execution_result, export, symbol};
+
+fn account() -> ScAddress {
+    ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([3; 32]))))
+}
+
+fn contract() -> ScAddress {
+    ScAddress::Contract(ContractId(Hash([6; 32])))
+}
+
+fn holdings() -> RetroshadeExport {
+    let mut export = export(
+        symbol("holdings"),
+        vec![
+            (
+                symbol("balances"),
+                ScVal::Map(Some(ScMap(
+                    vec![ScMapEntry {
+                        key: ScVal::Address(account()),
+                        val: ScVal::U32(10),
+                    }]
+                    .try_into()
+                    .unwrap(),
+                ))),
+            ),
+            (
+                symbol("holders"),
+                ScVal::Vec(Some(
+                    vec![ScVal::Address(account()), ScVal::Address(contract())]
+                        .try_into()
+                        .unwrap(),
+                )),
+            ),
+            (symbol("owner"), ScVal::Address(contract())),
+            (symbol("supply"), ScVal::U32(10)),
+        ],
+    );
+    export.contract_id = Hash([5; 32]);
+
+    export
+}
+
+fn pack(address_format: AddressFormat) -> RetroshadeExportPretty {
+    let mut execution = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    execution.set_conversion_options(ConversionOptions { address_format });
+
+    let mut packed = execution
+        .pack_result(execution_result(vec![holdings()]))
+        .unwrap()
+        .retroshades;
+    assert_eq!(packed.len(), 1);
+
+    packed.remove(0)
+}
+
+fn field<'a>(export: &'a RetroshadeExportPretty, name: &str) -> Option<&'a FromScVal> {
+    export
+        .event
+        .iter()
+        .find(|entry| entry.name == name)
+        .map(|entry| &entry.value)
+}
+
+fn texts(value: &FromScVal) -> Vec<String> {
+    match &value.kind {
+        TypeKind::Text(text) => vec![text.clone()],
+        TypeKind::GenericArray(items) => items.iter().flat_map(texts).collect(),
+        other => panic!("not text: {:?}", other),
+    }
+}
+
+/// The addresses rendered in `format`, in the order [`rendered`] finds them.
+fn addresses(format: AddressFormat) -> [String; 3] {
+    [
+        ScAddress::Contract(ContractId(Hash([5; 32]))),
+        account(),
+        contract(),
+    ]
+    .map(|address| address_text(&address, format))
+}
+
+/// The contract id, the holder, the owner and the key of the balances JSON.
+fn rendered(export: &RetroshadeExportPretty, suffix: &str) -> [String; 4] {
+    let holders = texts(field(export, &format!("holders{}", suffix)).unwrap());
+    let owner = texts(field(export, &format!("owner{}", suffix)).unwrap());
+    let balances = texts(field(export, &format!("balances{}", suffix)).unwrap());
+
+    assert_eq!(holders.len(), 2);
+    assert_eq!(holders[1], owner[0]);
+    [
+        export.contract_id.to_string(),
+        holders[0].clone(),
+        owner[0].clone(),
+        balances[0].clone(),
+    ]
+}
+
+fn assert_rendered(export: &RetroshadeExportPretty, suffix: &str, format: AddressFormat) {
+    let [contract_id, account, contract] = addresses(format);
+    let [packed_contract_id, holder, owner, balances] = rendered(export, suffix);
+
+    if suffix.is_empty() {
+        assert_eq!(packed_contract_id, contract_id);
+    }
+    assert_eq!(holder, account);
+    assert_eq!(owner, contract);
+    assert!(
+        balances.contains(&format!("\"{}\"", account)),
+        "{} doesn't hold {}",
+        balances,
+        account
+    );
+}
+
+#[test]
+fn strkey_by_default() {
+    let export = pack(AddressFormat::default());
+
+    assert_rendered(&export, "", AddressFormat::StrKey);
+    assert!(field(&export, "owner_hex").is_none());
+}
+
+#[test]
+fn hex_everywhere() {
+    let export = pack(AddressFormat::Hex);
+
+    assert_eq!(export.contract_id, hex::encode([5; 32]));
+    assert_rendered(&export, "", AddressFormat::Hex);
+    assert!(field(&export, "owner_hex").is_none());
+}
+
+#[test]
+fn both_adds_hex_columns() {
+    let export = pack(AddressFormat::Both);
+
+    assert_rendered(&export, "", AddressFormat::StrKey);
+    assert_rendered(&export, "_hex", AddressFormat::Hex);
+
+    // note: only columns holding addresses get a sibling.
+    assert!(field(&export, "supply_hex").is_none());
+    let mut names: Vec<&str> = export
+        .event
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "balances",
+            "balances_hex",
+            "holders",
+            "holders_hex",
+            "owner",
+            "owner_hex",
+            "supply"
+        ]
+    );
+}
+
+#[test]
+fn strings_holding_strkeys_are_kept() {
+    let strkey = address_text(&account(), AddressFormat::StrKey);
+    // note: a string that happens to be a strkey, e.g. a memo.
+    let value = ScVal::Map(Some(ScMap(
+        vec![ScMapEntry {
+            key: ScVal::Address(account()),
+            val: ScVal::String(ScString(strkey.as_str().try_into().unwrap())),
+        }]
+        .try_into()
+        .unwrap(),
+    )));
+
+    let converted = FromScVal::from_scval_with(value, &mut 0, &ConversionOptions::default().hex());
+
+    assert_eq!(
+        converted.kind,
+        TypeKind::Text(format!(
+            r#"[{{"key":{{"address":"{}"}},"val":{{"string":"{}"}}}}]"#,
+            hex::encode([3; 32]),
+            strkey
+        ))
+    );
+}
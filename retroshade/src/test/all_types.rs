@@ -0,0 +1,268 @@
+//! The all_types example emits a field of every type a retroshade can have,
+//! this checks how each of them is packed. Changes to the conversion should
+//! show up here first.
+
+use std::collections::HashMap;
+
+use postgres_types::Type;
+use soroban_env_host::xdr::{
+    AccountId, ExtensionPoint, Hash, LedgerEntryChanges, OperationMeta, PublicKey, ScAddress,
+    ScMap, ScMapEntry, ScSymbol, ScVal, ScVec, SorobanTransactionMeta, SorobanTransactionMetaExt,
+    TransactionMeta, TransactionMetaV3, Uint256,
+};
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+    ReplacementEntry, RetroshadeExportPretty, RetroshadesExecution,
+};
+
+use super::examples::{example_wasm, example_wasm_with_features, fixture_wasm};
+
+/// Meta of a call that doesn't change anything, like those of the all_types
+/// contract.
+pub fn unchanged_meta() -> TransactionMeta {
+    TransactionMeta::V3(TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value: ScVal::Void,
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(vec![].try_into().unwrap()),
+        }]
+        .try_into()
+        .unwrap(),
+    })
+}
+
+fn account() -> ScAddress {
+    ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([3; 32]))))
+}
+
+/// The export of the prebuilt all_types contract.
+fn all_types_export() -> RetroshadeExportPretty {
+    all_types_export_of(
+        &fixture_wasm("all_types"),
+        fixture_wasm("all_types-mercury"),
+    )
+}
+
+/// The export of the all_types contract deployed as `wasm` and replaced
+/// with `binary`.
+fn all_types_export_of(wasm: &[u8], binary: Vec<u8>) -> RetroshadeExportPretty {
+    let snapshot = MockSnapshot::with_contract(Hash([0; 32]), wasm, ScMap::default());
+    let envelope = EnvelopeBuilder::new()
+        .function("emit")
+        .arg(ScVal::Address(account()))
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let mut mercury_contracts = HashMap::new();
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(binary));
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    let replaced = retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, unchanged_meta(), mercury_contracts)
+        .unwrap();
+    assert!(replaced);
+
+    let result = retroshades.retroshade_packed().unwrap();
+    assert!(result.call_succeeded);
+    assert_eq!(result.retroshades.len(), 1);
+
+    result.retroshades.into_iter().next().unwrap()
+}
+
+fn field<'a>(export: &'a RetroshadeExportPretty, name: &str) -> &'a FromScVal {
+    &export
+        .event
+        .iter()
+        .find(|entry| entry.name == name)
+        .unwrap_or_else(|| panic!("no {} field", name))
+        .value
+}
+
+fn scalar(dbtype: Type, kind: TypeKind) -> FromScVal {
+    FromScVal { dbtype, kind }
+}
+
+fn numeric(n: &str) -> FromScVal {
+    scalar(Type::NUMERIC, TypeKind::Numeric(n.to_string()))
+}
+
+fn text(s: &str) -> FromScVal {
+    scalar(Type::TEXT, TypeKind::Text(s.to_string()))
+}
+
+fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+fn u32_vec(items: &[u32]) -> ScVec {
+    ScVec(
+        items
+            .iter()
+            .map(|item| ScVal::U32(*item))
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn scalars() {
+    let export = all_types_export();
+    assert_eq!(export.target, "alltypes");
+
+    let account = stellar_strkey::ed25519::PublicKey([3; 32]).to_string();
+    for (name, expected) in [
+        ("flag", scalar(Type::BOOL, TypeKind::Boolean(true))),
+        ("small", numeric("7")),
+        ("signed", numeric("-7")),
+        ("big", numeric(&u64::MAX.to_string())),
+        ("negative", numeric(&i64::MIN.to_string())),
+        ("unsigned_128", numeric(&u128::MAX.to_string())),
+        ("signed_128", numeric(&i128::MIN.to_string())),
+        (
+            "unsigned_256",
+            numeric(
+                "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+            ),
+        ),
+        (
+            "signed_256",
+            numeric(
+                "-57896044618658097711785492504343953926634992332820282019728792003956564819968",
+            ),
+        ),
+        ("duration", numeric("3600")),
+        ("timepoint", numeric("1700000000")),
+        (
+            "bytes",
+            scalar(Type::BYTEA, TypeKind::Text("deadbeef".to_string())),
+        ),
+        (
+            "hash",
+            scalar(Type::BYTEA, TypeKind::Text(hex::encode([7; 32]))),
+        ),
+        ("text", text("retroshade")),
+        ("symbol", text("mercury")),
+        ("address", text(&account)),
+        ("some", numeric("5")),
+        // note: without spec column types a none says nothing about its
+        // column, so it's text.
+        ("none", scalar(Type::TEXT, TypeKind::Void)),
+    ] {
+        assert_eq!(field(&export, name), &expected, "{} field", name);
+    }
+}
+
+#[test]
+fn containers() {
+    let export = all_types_export();
+
+    let account = stellar_strkey::ed25519::PublicKey([3; 32]).to_string();
+    let contract = stellar_strkey::Contract([0; 32]).to_string();
+    assert_eq!(
+        field(&export, "addresses"),
+        &scalar(
+            Type::TEXT_ARRAY,
+            TypeKind::GenericArray(vec![text(&account), text(&contract)])
+        )
+    );
+
+    // note: vectors nested past the maximum recursion depth are packed as
+    // the JSON of their XDR.
+    let nested = field(&export, "nested");
+    assert_eq!(nested.dbtype, Type::TEXT_ARRAY);
+    let TypeKind::GenericArray(items) = &nested.kind else {
+        panic!("nested isn't an array: {:?}", nested)
+    };
+    let items: Vec<Option<ScVec>> = items
+        .iter()
+        .map(|item| match &item.kind {
+            TypeKind::Text(json) => serde_json::from_str(json).unwrap(),
+            other => panic!("nested item isn't text: {:?}", other),
+        })
+        .collect();
+    assert_eq!(items, vec![Some(u32_vec(&[1, 2])), Some(u32_vec(&[3]))]);
+
+    let map = field(&export, "map");
+    assert_eq!(map.dbtype, Type::TEXT);
+    let TypeKind::Text(json) = &map.kind else {
+        panic!("map isn't text: {:?}", map)
+    };
+    let map: Option<ScMap> = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        map,
+        Some(ScMap(
+            vec![
+                ScMapEntry {
+                    key: symbol("a"),
+                    val: ScVal::U32(1),
+                },
+                ScMapEntry {
+                    key: symbol("b"),
+                    val: ScVal::U32(2),
+                },
+            ]
+            .try_into()
+            .unwrap()
+        ))
+    );
+}
+
+#[test]
+fn every_field_is_packed() {
+    let export = all_types_export();
+
+    let mut names: Vec<&str> = export
+        .event
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "address",
+            "addresses",
+            "big",
+            "bytes",
+            "duration",
+            "flag",
+            "hash",
+            "map",
+            "negative",
+            "nested",
+            "none",
+            "signed",
+            "signed_128",
+            "signed_256",
+            "small",
+            "some",
+            "symbol",
+            "text",
+            "timepoint",
+            "unsigned_128",
+            "unsigned_256",
+        ]
+    );
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn prebuilt_contract_matches_the_example() {
+    let example = all_types_export_of(
+        &example_wasm("all_types", "soroban_all_types"),
+        example_wasm_with_features("all_types", "soroban_all_types", &["mercury"]),
+    );
+
+    assert_eq!(all_types_export().event, example.event);
+}
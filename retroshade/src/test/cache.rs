@@ -0,0 +1,147 @@
+use std::{cell::Cell, rc::Rc};
+
+use crate::{
+    cache::ReadThroughSnapshot,
+    snapshot::{LabeledSnapshot, SnapshotSourceExt},
+};
+use soroban_env_host::{
+    storage::SnapshotSource,
+    xdr::{
+        ContractCodeEntry, Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey,
+        LedgerKeyContractCode,
+    },
+};
+
+/// Serves a code entry for the zero hash and reports every other key as missing,
+/// counting how many times it was queried.
+pub struct CountingSnapshot {
+    calls: Rc<Cell<usize>>,
+}
+
+impl SnapshotSource for CountingSnapshot {
+    fn get(
+        &self,
+        key: &std::rc::Rc<soroban_env_host::xdr::LedgerKey>,
+    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
+    {
+        self.calls.set(self.calls.get() + 1);
+
+        match key.as_ref() {
+            LedgerKey::ContractCode(code) if code.hash == Hash([0; 32]) => Ok(Some((
+                Rc::new(LedgerEntry {
+                    last_modified_ledger_seq: 0,
+                    ext: LedgerEntryExt::V0,
+                    data: LedgerEntryData::ContractCode(ContractCodeEntry {
+                        ext: soroban_env_host::xdr::ContractCodeEntryExt::V0,
+                        hash: Hash([0; 32]),
+                        code: vec![].try_into().unwrap(),
+                    }),
+                }),
+                Some(10000),
+            ))),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn code_key(byte: u8) -> Rc<LedgerKey> {
+    Rc::new(LedgerKey::ContractCode(LedgerKeyContractCode {
+        hash: Hash([byte; 32]),
+    }))
+}
+
+fn counting_cache(
+    max_cached_misses: usize,
+) -> (ReadThroughSnapshot<CountingSnapshot>, Rc<Cell<usize>>) {
+    let calls = Rc::new(Cell::new(0));
+    let cache = ReadThroughSnapshot::with_max_cached_misses(
+        Rc::new(CountingSnapshot {
+            calls: calls.clone(),
+        }),
+        max_cached_misses,
+    );
+
+    (cache, calls)
+}
+
+#[test]
+fn caches_hits_and_misses() {
+    let (cache, calls) = counting_cache(16);
+
+    assert!(cache.get(&code_key(0)).unwrap().is_some());
+    assert!(cache.get(&code_key(0)).unwrap().is_some());
+    assert!(cache.get(&code_key(1)).unwrap().is_none());
+    assert!(cache.get(&code_key(1)).unwrap().is_none());
+
+    assert_eq!(calls.get(), 2);
+    assert_eq!(cache.cached_hits(), 1);
+    assert_eq!(cache.cached_misses(), 1);
+}
+
+#[test]
+fn invalidation() {
+    let (cache, calls) = counting_cache(16);
+
+    cache.get(&code_key(0)).unwrap();
+    cache.get(&code_key(1)).unwrap();
+
+    cache.invalidate(&code_key(1));
+    cache.get(&code_key(0)).unwrap();
+    cache.get(&code_key(1)).unwrap();
+    assert_eq!(calls.get(), 3);
+
+    cache.invalidate_all();
+    cache.get(&code_key(0)).unwrap();
+    cache.get(&code_key(1)).unwrap();
+    assert_eq!(calls.get(), 5);
+}
+
+#[test]
+fn new_ledger_drops_cache() {
+    let (cache, calls) = counting_cache(16);
+
+    cache.begin_ledger(1000);
+    cache.get(&code_key(1)).unwrap();
+    cache.begin_ledger(1000);
+    cache.get(&code_key(1)).unwrap();
+    assert_eq!(calls.get(), 1);
+
+    cache.begin_ledger(1001);
+    assert_eq!(cache.cached_misses(), 0);
+    cache.get(&code_key(1)).unwrap();
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn misses_are_capped() {
+    let (cache, calls) = counting_cache(2);
+
+    for byte in 1..=4 {
+        assert!(cache.get(&code_key(byte)).unwrap().is_none());
+    }
+    assert_eq!(cache.cached_misses(), 2);
+
+    // the first two misses are served from the cache, the others go through.
+    for byte in 1..=4 {
+        cache.get(&code_key(byte)).unwrap();
+    }
+    assert_eq!(calls.get(), 6);
+}
+
+#[test]
+fn cached_entries_keep_their_label() {
+    let calls = Rc::new(Cell::new(0));
+    let counting = CountingSnapshot {
+        calls: calls.clone(),
+    };
+    let cache = ReadThroughSnapshot::new(Rc::new(LabeledSnapshot::new(
+        "counting",
+        Box::new(counting),
+    )));
+
+    for _ in 0..2 {
+        let (_, label) = cache.get_labeled(&code_key(0)).unwrap().unwrap();
+        assert_eq!(label, "counting");
+    }
+    assert_eq!(calls.get(), 1);
+}
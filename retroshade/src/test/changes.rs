@@ -0,0 +1,134 @@
+//! [`RetroshadeExecutionResult::compare_with_meta`] both ways: entries the
+//! chain changed that the re-execution didn't, and the other way around.
+
+use soroban_env_host::xdr::{
+    ContractDataDurability, ContractDataEntry, ExtensionPoint, Hash, LedgerEntry,
+    LedgerEntryChange, LedgerEntryChanges, LedgerEntryData, LedgerEntryExt, LedgerKey,
+    LedgerKeyContractData, OperationMeta, ScAddress, ScVal, TransactionMetaV3,
+};
+
+use crate::{
+    changes::{EntryChange, Mismatch},
+    RetroshadeExecutionResult,
+};
+
+use super::packing::{execution_result, symbol};
+
+fn data_key(name: &str) -> LedgerKey {
+    LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::Contract(Hash([0; 32]).into()),
+        key: symbol(name),
+        durability: ContractDataDurability::Persistent,
+    })
+}
+
+fn data_entry(name: &str, value: u32) -> LedgerEntry {
+    LedgerEntry {
+        last_modified_ledger_seq: 1000,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            key: symbol(name),
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::U32(value),
+        }),
+        ext: LedgerEntryExt::V0,
+    }
+}
+
+/// A write of `value` under `name` over an entry of `old_entry_size_bytes`,
+/// `None` deleting it.
+fn write(name: &str, old_entry_size_bytes: u32, value: Option<u32>) -> EntryChange {
+    EntryChange {
+        read_only: false,
+        key: data_key(name),
+        old_entry_size_bytes,
+        new_value: value.map(|value| data_entry(name, value)),
+        ttl_change: None,
+    }
+}
+
+fn meta(changes: Vec<LedgerEntryChange>) -> TransactionMetaV3 {
+    TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(changes.try_into().unwrap()),
+        }]
+        .try_into()
+        .unwrap(),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: None,
+    }
+}
+
+fn reexecuted(ledger_changes: Vec<EntryChange>) -> RetroshadeExecutionResult {
+    let mut result = execution_result(vec![]);
+    result.ledger_changes = ledger_changes;
+    result
+}
+
+#[test]
+fn same_changes_both_ways() {
+    let result = reexecuted(vec![
+        write("created", 0, Some(1)),
+        write("updated", 48, Some(2)),
+        write("removed", 48, None),
+    ]);
+    let meta = meta(vec![
+        LedgerEntryChange::Created(data_entry("created", 1)),
+        LedgerEntryChange::State(data_entry("updated", 1)),
+        LedgerEntryChange::Updated(data_entry("updated", 2)),
+        LedgerEntryChange::State(data_entry("removed", 1)),
+        LedgerEntryChange::Removed(data_key("removed")),
+    ]);
+
+    assert!(result.compare_with_meta(&meta).is_empty());
+}
+
+#[test]
+fn changed_on_chain_only() {
+    let result = reexecuted(vec![]);
+    let meta = meta(vec![LedgerEntryChange::Created(data_entry("created", 1))]);
+
+    assert_eq!(
+        result.compare_with_meta(&meta),
+        vec![Mismatch::NotWritten {
+            key: data_key("created")
+        }]
+    );
+}
+
+#[test]
+fn changed_by_the_reexecution_only() {
+    let result = reexecuted(vec![
+        write("created", 0, Some(1)),
+        write("removed", 48, None),
+    ]);
+
+    assert_eq!(
+        result.compare_with_meta(&meta(vec![])),
+        vec![
+            Mismatch::NotOnChain {
+                key: data_key("created"),
+                reexecuted: Some(data_entry("created", 1)),
+            },
+            Mismatch::NotOnChain {
+                key: data_key("removed"),
+                reexecuted: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn untouched_entries_are_not_mismatches() {
+    // note: the host reports read-write entries left as they were, and
+    // missing ones staying missing, as written.
+    let result = reexecuted(vec![
+        write("updated", 48, Some(1)),
+        write("missing", 0, None),
+    ]);
+
+    assert!(result.compare_with_meta(&meta(vec![])).is_empty());
+}
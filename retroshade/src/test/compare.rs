@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{
+    ContractDataDurability, ContractDataEntry, ExtensionPoint, Hash, LedgerEntry, LedgerEntryData,
+    LedgerEntryExt, LedgerKey, LedgerKeyContractData, ScAddress, ScMap, ScVal,
+};
+
+use crate::{
+    changes::EntryChange,
+    compare::ExecutionDiff,
+    testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+    ReplacementEntry, RetroshadeExecutionResult, RetroshadesExecution,
+};
+
+use super::{
+    all_types::unchanged_meta,
+    examples::{example_wasm, example_wasm_with_features},
+    packing::{execution_result, export, symbol},
+};
+
+fn data_key(name: &str) -> LedgerKey {
+    LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::Contract(Hash([0; 32]).into()),
+        key: symbol(name),
+        durability: ContractDataDurability::Persistent,
+    })
+}
+
+/// A write of `value` under `name`, `None` deleting it.
+fn write(name: &str, value: Option<u32>, last_modified_ledger_seq: u32) -> EntryChange {
+    EntryChange {
+        read_only: false,
+        key: data_key(name),
+        old_entry_size_bytes: 0,
+        new_value: value.map(|value| LedgerEntry {
+            last_modified_ledger_seq,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
+                ext: ExtensionPoint::V0,
+                contract: ScAddress::Contract(Hash([0; 32]).into()),
+                key: symbol(name),
+                durability: ContractDataDurability::Persistent,
+                val: ScVal::U32(value),
+            }),
+            ext: LedgerEntryExt::V0,
+        }),
+        ttl_change: None,
+    }
+}
+
+fn with_changes(
+    mut result: RetroshadeExecutionResult,
+    ledger_changes: Vec<EntryChange>,
+) -> RetroshadeExecutionResult {
+    result.ledger_changes = ledger_changes;
+    result
+}
+
+#[test]
+fn identical_results_have_no_diff() {
+    let result = with_changes(
+        execution_result(vec![export(symbol("swaps"), vec![])]),
+        vec![write("tvl", Some(1), 1000)],
+    );
+
+    assert!(ExecutionDiff::new(&result, &result).is_empty());
+}
+
+#[test]
+fn added_retroshades_keep_the_semantics() {
+    let original = execution_result(vec![]);
+    let replaced = execution_result(vec![
+        export(symbol("swaps"), vec![(symbol("amount"), ScVal::U32(1))]),
+        export(symbol("swaps"), vec![(symbol("amount"), ScVal::U32(1))]),
+    ]);
+
+    let diff = ExecutionDiff::new(&original, &replaced);
+    assert!(diff.same_semantics());
+    assert!(!diff.is_empty());
+    assert!(diff.only_original.is_empty());
+    // note: identical retroshades each count.
+    assert_eq!(diff.only_replaced.len(), 2);
+}
+
+#[test]
+fn differing_outcomes() {
+    let mut original = with_changes(
+        execution_result(vec![]),
+        vec![
+            write("tvl", Some(1), 1000),
+            write("owner", Some(7), 1000),
+            write("fee", None, 1000),
+        ],
+    );
+    let replaced = with_changes(
+        execution_result(vec![]),
+        vec![
+            // note: the last modified ledger isn't an outcome.
+            write("tvl", Some(1), 1001),
+            write("owner", Some(8), 1000),
+            write("paused", Some(1), 1000),
+        ],
+    );
+    original.invoke_result = Err("contract panicked".to_string());
+
+    let diff = ExecutionDiff::new(&original, &replaced);
+    assert!(!diff.same_semantics());
+
+    let invoke_result = diff.invoke_result.unwrap();
+    assert_eq!(invoke_result.original, original.invoke_result);
+    assert_eq!(invoke_result.replaced, Ok(ScVal::Void));
+
+    let keys: Vec<LedgerKey> = diff
+        .ledger_changes
+        .iter()
+        .map(|change| change.key.clone())
+        .collect();
+    assert_eq!(
+        keys,
+        vec![data_key("owner"), data_key("fee"), data_key("paused")]
+    );
+    assert!(diff.ledger_changes[1].original.is_none());
+    assert!(diff.ledger_changes[1].replaced.is_none());
+}
+
+#[test]
+fn diff_serializes() {
+    let diff = ExecutionDiff::new(
+        &execution_result(vec![]),
+        &with_changes(execution_result(vec![]), vec![write("tvl", Some(1), 1000)]),
+    );
+
+    let json = serde_json::to_value(&diff).unwrap();
+    assert!(json["invoke_result"].is_null());
+    assert_eq!(json["ledger_changes"].as_array().unwrap().len(), 1);
+}
+
+fn all_types_execution(original: &[u8], replaced: &[u8]) -> RetroshadesExecution {
+    let snapshot = MockSnapshot::with_contract(Hash([0; 32]), original, ScMap::default());
+    let envelope = EnvelopeBuilder::new()
+        .function("emit")
+        .arg(ScVal::Address(ScAddress::Contract(Hash([0; 32]).into())))
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let mut mercury_contracts = HashMap::new();
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(replaced));
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    let replaced = retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, unchanged_meta(), mercury_contracts)
+        .unwrap();
+    assert!(replaced);
+
+    retroshades
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn instrumented_binary_only_adds_retroshades() {
+    let mut retroshades = all_types_execution(
+        &example_wasm("all_types", "soroban_all_types"),
+        &example_wasm_with_features("all_types", "soroban_all_types", &["mercury"]),
+    );
+
+    let comparison = retroshades.retroshade_compare_seeded([5; 32]).unwrap();
+    assert!(comparison.original.retroshades.is_empty());
+    assert_eq!(comparison.replaced.retroshades.len(), 1);
+    assert!(comparison.diff.same_semantics());
+    assert_eq!(comparison.diff.only_replaced.len(), 1);
+
+    // note: the replaced binaries are back in place.
+    assert_eq!(
+        retroshades
+            .retroshade_seeded([5; 32])
+            .unwrap()
+            .retroshades
+            .len(),
+        1
+    );
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn other_binary_changes_the_semantics() {
+    let mut retroshades = all_types_execution(
+        &example_wasm("all_types", "soroban_all_types"),
+        &example_wasm("hello_world", "soroban_hello_world_contract"),
+    );
+
+    let comparison = retroshades.retroshade_compare_seeded([5; 32]).unwrap();
+    assert!(comparison.original.invoke_result.is_ok());
+    assert!(comparison.replaced.invoke_result.is_err());
+    assert!(!comparison.diff.same_semantics());
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::{
+    compat::{MAX_PROTOCOL, MIN_PROTOCOL},
+    testutils::{ledger_info_protocol, Fixture, DEFAULT_PROTOCOL},
+    version, RetroshadeError, RetroshadesExecution,
+};
+
+use super::fixture::MAINNET_FIXTURE;
+
+#[test]
+fn version_reports_the_supported_protocols() {
+    let info = version();
+
+    assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+    assert!(!info.env_host_version.is_empty());
+    assert_eq!(info.supported_protocols(), MIN_PROTOCOL..=MAX_PROTOCOL);
+    // the protocols the tests execute on.
+    assert!(info.supports(DEFAULT_PROTOCOL));
+    assert!(info.supports(25));
+}
+
+#[test]
+fn build_rejects_unsupported_protocols() {
+    let fixture = Fixture::load(MAINNET_FIXTURE);
+
+    for protocol in [0, MIN_PROTOCOL - 1, MAX_PROTOCOL + 1] {
+        let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(protocol));
+        let built = retroshades.build_from_envelope_and_meta(
+            &fixture.snapshot,
+            fixture.envelope.clone(),
+            fixture.meta.clone(),
+            HashMap::new(),
+        );
+
+        match built {
+            Err(RetroshadeError::UnsupportedProtocol { got, supported }) => {
+                assert_eq!(got, protocol);
+                assert_eq!(supported, MIN_PROTOCOL..=MAX_PROTOCOL);
+            }
+            other => panic!("expected UnsupportedProtocol, got {:?}", other),
+        }
+    }
+}
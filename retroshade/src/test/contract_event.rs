@@ -0,0 +1,124 @@
+use soroban_env_host::{
+    xdr::{
+        ContractEvent, ContractEventBody, ContractEventType, ContractEventV0, ExtensionPoint, Hash,
+        MuxedAccount, ScVal, ScValType, Uint256,
+    },
+    zephyr::RetroshadeExport,
+};
+
+use crate::{contract_event::RetroshadeExportExt, MalformedReason, RetroshadeError};
+
+use super::packing::{built_execution, execution_result, export, symbol};
+
+fn swap() -> RetroshadeExport {
+    let mut export = export(
+        symbol("swaps"),
+        vec![
+            (symbol("amount"), ScVal::U32(2)),
+            (symbol("pool"), symbol("xlm_usdc")),
+        ],
+    );
+    export.contract_id = Hash([4; 32]);
+
+    export
+}
+
+fn body(event: &ContractEvent) -> &ContractEventV0 {
+    let ContractEventBody::V0(body) = &event.body;
+    body
+}
+
+fn malformed_reason(event: &ContractEvent) -> MalformedReason {
+    match RetroshadeExport::from_contract_event(event) {
+        Err(RetroshadeError::MalformedRetroshadeEvent { reason, .. }) => reason,
+        other => panic!("expected a malformed event, got {:?}", other),
+    }
+}
+
+#[test]
+fn synthesized_event_shape() {
+    let event = swap().to_contract_event();
+
+    assert_eq!(event.ext, ExtensionPoint::V0);
+    assert_eq!(event.type_, ContractEventType::Contract);
+    assert_eq!(event.contract_id, Some(Hash([4; 32]).into()));
+    assert_eq!(body(&event).topics.to_vec(), vec![symbol("swaps")]);
+    assert_eq!(body(&event).data, swap().event_object);
+}
+
+#[test]
+fn round_trip() {
+    let event = swap().to_contract_event();
+    let export = RetroshadeExport::from_contract_event(&event).unwrap();
+
+    assert_eq!(export.contract_id, swap().contract_id);
+    assert_eq!(export.target, swap().target);
+    assert_eq!(export.event_object, swap().event_object);
+    assert_eq!(export.to_contract_event(), event);
+}
+
+#[test]
+fn round_trip_packs_the_same() {
+    let execution = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    let round_tripped = RetroshadeExport::from_contract_event(&swap().to_contract_event()).unwrap();
+
+    assert_eq!(
+        execution
+            .pack_result(execution_result(vec![round_tripped]))
+            .unwrap()
+            .retroshades,
+        execution
+            .pack_result(execution_result(vec![swap()]))
+            .unwrap()
+            .retroshades
+    );
+}
+
+#[test]
+fn rejects_events_of_other_types() {
+    let mut event = swap().to_contract_event();
+    event.type_ = ContractEventType::Diagnostic;
+    assert_eq!(malformed_reason(&event), MalformedReason::NotContractEvent);
+
+    let mut event = swap().to_contract_event();
+    event.contract_id = None;
+    assert_eq!(malformed_reason(&event), MalformedReason::NotContractEvent);
+}
+
+#[test]
+fn rejects_other_topics() {
+    let with_topics = |topics: Vec<ScVal>| {
+        let mut event = swap().to_contract_event();
+        event.body = ContractEventBody::V0(ContractEventV0 {
+            topics: topics.try_into().unwrap(),
+            data: swap().event_object,
+        });
+        event
+    };
+
+    assert_eq!(
+        malformed_reason(&with_topics(vec![])),
+        MalformedReason::TopicCount { count: 0 }
+    );
+    assert_eq!(
+        malformed_reason(&with_topics(vec![symbol("transfer"), symbol("swaps")])),
+        MalformedReason::TopicCount { count: 2 }
+    );
+    assert_eq!(
+        malformed_reason(&with_topics(vec![ScVal::U32(7)])),
+        MalformedReason::TargetNotSymbol {
+            found: ScValType::U32
+        }
+    );
+}
+
+#[test]
+fn rejects_data_that_isnt_a_map() {
+    let mut retroshade = swap();
+    retroshade.event_object = ScVal::U32(2);
+
+    assert_eq!(
+        malformed_reason(&retroshade.to_contract_event()),
+        MalformedReason::EventNotMap
+    );
+}
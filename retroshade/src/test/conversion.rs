@@ -0,0 +1,89 @@
+use bytes::BytesMut;
+use num_bigint::BigInt;
+use postgres_types::{ToSql, Type};
+use proptest::prelude::*;
+use soroban_env_host::xdr::{Int128Parts, ScVal};
+
+use crate::{conversion::FromScVal, testutils::arb_scval};
+
+/// The integer a numeric value holds, computed independently of the
+/// conversion.
+fn expected_numeric(value: &ScVal) -> Option<BigInt> {
+    let numeric = match value {
+        ScVal::U32(n) => BigInt::from(*n),
+        ScVal::I32(n) => BigInt::from(*n),
+        ScVal::U64(n) => BigInt::from(*n),
+        ScVal::I64(n) => BigInt::from(*n),
+        ScVal::Timepoint(t) => BigInt::from(t.0),
+        ScVal::Duration(d) => BigInt::from(d.0),
+        ScVal::U128(parts) => BigInt::from(((parts.hi as u128) << 64) | parts.lo as u128),
+        ScVal::I128(parts) => BigInt::from(((parts.hi as i128) << 64) | parts.lo as i128),
+        ScVal::U256(parts) => BigInt::from_bytes_be(
+            num_bigint::Sign::Plus,
+            &[parts.hi_hi, parts.hi_lo, parts.lo_hi, parts.lo_lo]
+                .map(u64::to_be_bytes)
+                .concat(),
+        ),
+        ScVal::I256(parts) => BigInt::from_signed_bytes_be(
+            &[
+                parts.hi_hi.to_be_bytes(),
+                parts.hi_lo.to_be_bytes(),
+                parts.lo_hi.to_be_bytes(),
+                parts.lo_lo.to_be_bytes(),
+            ]
+            .concat(),
+        ),
+        _ => return None,
+    };
+
+    Some(numeric)
+}
+
+proptest! {
+    #[test]
+    fn declared_type_is_writable(value in arb_scval(3, 4)) {
+        let converted = FromScVal::from_scval(value, &mut 0);
+
+        prop_assert!(<FromScVal as ToSql>::accepts(&converted.dbtype));
+        let written = converted.to_sql_checked(&converted.dbtype, &mut BytesMut::new());
+        prop_assert!(
+            written.is_ok(),
+            "{:?} can't be written as {}: {:?}",
+            converted,
+            converted.dbtype,
+            written
+        );
+    }
+
+    #[test]
+    fn numerics_roundtrip_through_json(value in arb_scval(0, 0)) {
+        if let Some(expected) = expected_numeric(&value) {
+            let json = FromScVal::from_scval(value, &mut 0).to_json();
+
+            prop_assert_eq!(json, serde_json::Value::String(expected.to_string()));
+        }
+    }
+}
+
+#[test]
+fn numeric_binary_encoding() {
+    let encode = |value: ScVal| {
+        let mut out = BytesMut::new();
+        FromScVal::from_scval(value, &mut 0)
+            .to_sql_checked(&Type::NUMERIC, &mut out)
+            .unwrap();
+        out.to_vec()
+    };
+
+    // 1 digit of weight 1, negative, trailing zero digit dropped.
+    assert_eq!(
+        encode(ScVal::I64(-10000)),
+        vec![0, 1, 0, 1, 0x40, 0, 0, 0, 0, 1]
+    );
+    assert_eq!(encode(ScVal::U32(0)), vec![0, 0, 0, 0, 0, 0, 0, 0]);
+    // 12345 is 1 2345.
+    assert_eq!(
+        encode(ScVal::I128(Int128Parts { hi: 0, lo: 12345 })),
+        vec![0, 2, 0, 1, 0, 0, 0, 0, 0, 1, 0x09, 0x29]
+    );
+}
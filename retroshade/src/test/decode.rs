@@ -0,0 +1,316 @@
+use soroban_env_host::xdr::{
+    AccountId, Int128Parts, MuxedAccount, PublicKey, ScAddress, ScBytes, ScSpecTypeBytesN,
+    ScSpecTypeDef, ScVal, ScVec, Uint256,
+};
+
+use crate::{
+    conversion::TypeKind,
+    decode::{Address, FromTypedEvent, TypedDecoder, TypedEvent, TypedValue},
+    RetroshadeError, RetroshadeExportPretty,
+};
+
+use super::{
+    examples::fixture_wasm,
+    packing::{built_execution, execution_result, export, symbol, test_context},
+    spec::{option, udt_struct, vec_of, wasm_with_spec},
+};
+
+/// The event of the deposit example.
+#[derive(Debug, PartialEq, Eq)]
+struct DepositEvent {
+    previous_tvl: i128,
+    now_tvl: i128,
+    from: Address,
+    amount: i128,
+    ledger: u32,
+    timestamp: u64,
+}
+
+impl FromTypedEvent for DepositEvent {
+    fn from_typed_event(mut event: TypedEvent) -> Result<Self, RetroshadeError> {
+        Ok(Self {
+            previous_tvl: event.take("previous_tvl")?,
+            now_tvl: event.take("now_tvl")?,
+            from: event.take("from")?,
+            amount: event.take("amount")?,
+            ledger: event.take("ledger")?,
+            timestamp: event.take("timestamp")?,
+        })
+    }
+}
+
+fn deposit_decoder() -> TypedDecoder {
+    let spec = wasm_with_spec(&[udt_struct(
+        "DepositEvent",
+        &[
+            ("previous_tvl", ScSpecTypeDef::I128),
+            ("now_tvl", ScSpecTypeDef::I128),
+            ("from", ScSpecTypeDef::Address),
+            ("amount", ScSpecTypeDef::I128),
+            ("ledger", ScSpecTypeDef::U32),
+            ("timestamp", ScSpecTypeDef::U64),
+        ],
+    )]);
+
+    TypedDecoder::from_wasm(&spec, "DepositEvent").unwrap()
+}
+
+fn i128_val(value: i128) -> ScVal {
+    ScVal::I128(Int128Parts {
+        hi: (value >> 64) as i64,
+        lo: value as u64,
+    })
+}
+
+fn depositor() -> ScVal {
+    ScVal::Address(ScAddress::Account(AccountId(
+        PublicKey::PublicKeyTypeEd25519(Uint256([3; 32])),
+    )))
+}
+
+/// Packs a deposit event moving the TVL from `previous_tvl` by `amount`.
+fn packed_deposit(
+    previous_tvl: i128,
+    amount: i128,
+    context_columns: bool,
+) -> RetroshadeExportPretty {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_tx_context(test_context());
+    retroshades.set_context_columns(context_columns);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("mydeposit"),
+            vec![
+                (symbol("amount"), i128_val(amount)),
+                (symbol("from"), depositor()),
+                (symbol("ledger"), ScVal::U32(1000)),
+                (symbol("now_tvl"), i128_val(previous_tvl + amount)),
+                (symbol("previous_tvl"), i128_val(previous_tvl)),
+                (symbol("timestamp"), ScVal::U64(200)),
+            ],
+        )]))
+        .unwrap();
+
+    packed.retroshades[0].clone()
+}
+
+#[test]
+fn deposit_event_decodes() {
+    let decoded: DepositEvent = deposit_decoder()
+        .decode_as(&packed_deposit(1000, 250, false))
+        .unwrap();
+
+    assert_eq!(
+        decoded,
+        DepositEvent {
+            previous_tvl: 1000,
+            now_tvl: 1250,
+            from: Address(stellar_strkey::ed25519::PublicKey([3; 32]).to_string()),
+            amount: 250,
+            ledger: 1000,
+            timestamp: 200,
+        }
+    );
+}
+
+#[test]
+fn deposit_event_keeps_the_full_i128_range() {
+    let decoded: DepositEvent = deposit_decoder()
+        .decode_as(&packed_deposit(i128::MIN + 5, -5, false))
+        .unwrap();
+
+    assert_eq!(decoded.previous_tvl, i128::MIN + 5);
+    assert_eq!(decoded.amount, -5);
+    assert_eq!(decoded.now_tvl, i128::MIN);
+}
+
+#[test]
+fn deposit_event_ignores_context_columns() {
+    let decoder = deposit_decoder();
+    let event = decoder.decode(&packed_deposit(1000, 250, true)).unwrap();
+
+    assert_eq!(event.structure(), "DepositEvent");
+    let fields: Vec<&str> = event
+        .fields()
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            "previous_tvl",
+            "now_tvl",
+            "from",
+            "amount",
+            "ledger",
+            "timestamp"
+        ]
+    );
+    assert_eq!(event.get("ledger"), Some(&TypedValue::U32(1000)));
+    assert_eq!(event.get("tx_hash"), None);
+}
+
+#[test]
+fn deposit_event_decodes_with_the_example_spec() {
+    let wasm = fixture_wasm("deposit_spec");
+    let decoder = TypedDecoder::from_wasm(&wasm, "DepositEvent").unwrap();
+
+    let decoded: DepositEvent = decoder
+        .decode_as(&packed_deposit(1000, 250, false))
+        .unwrap();
+    assert_eq!(decoded.now_tvl, 1250);
+}
+
+#[test]
+fn unknown_struct() {
+    let spec = wasm_with_spec(&[]);
+    assert!(matches!(
+        TypedDecoder::from_wasm(&spec, "DepositEvent"),
+        Err(RetroshadeError::Spec(_))
+    ));
+}
+
+#[test]
+fn missing_field() {
+    let mut export = packed_deposit(1000, 250, false);
+    export.event.retain(|entry| entry.name != "ledger");
+
+    let error = deposit_decoder().decode(&export).unwrap_err();
+    assert!(matches!(
+        error,
+        RetroshadeError::TypedDecode { ref field, .. } if field == "ledger"
+    ));
+}
+
+#[test]
+fn field_of_another_type() {
+    let mut export = packed_deposit(1000, 250, false);
+    let from = export
+        .event
+        .iter()
+        .position(|entry| entry.name == "from")
+        .unwrap();
+    let ledger = export
+        .event
+        .iter()
+        .position(|entry| entry.name == "ledger")
+        .unwrap();
+    export.event[ledger].value = export.event[from].value.clone();
+
+    let error = deposit_decoder().decode(&export).unwrap_err();
+    assert!(matches!(
+        error,
+        RetroshadeError::TypedDecode { ref field, .. } if field == "ledger"
+    ));
+}
+
+#[test]
+fn field_out_of_range() {
+    let mut export = packed_deposit(1000, 250, false);
+    let ledger = export
+        .event
+        .iter_mut()
+        .find(|entry| entry.name == "ledger")
+        .unwrap();
+    ledger.value.kind = TypeKind::Numeric((u32::MAX as u64 + 1).to_string());
+
+    let error = deposit_decoder().decode(&export).unwrap_err();
+    assert!(matches!(
+        error,
+        RetroshadeError::TypedDecode { ref field, .. } if field == "ledger"
+    ));
+}
+
+#[test]
+fn struct_field_of_another_type() {
+    let mut event = deposit_decoder()
+        .decode(&packed_deposit(1000, 250, false))
+        .unwrap();
+
+    assert!(matches!(
+        event.take::<u64>("ledger"),
+        Err(RetroshadeError::TypedDecode { ref field, .. }) if field == "ledger"
+    ));
+    assert!(matches!(
+        event.take::<i128>("unknown"),
+        Err(RetroshadeError::TypedDecode { .. })
+    ));
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PayoutEvent {
+    memo: Option<String>,
+    referrer: Option<Address>,
+    amounts: Vec<i128>,
+    refunds: Vec<u64>,
+    hash: Vec<u8>,
+    proofs: Vec<Vec<u8>>,
+}
+
+impl FromTypedEvent for PayoutEvent {
+    fn from_typed_event(mut event: TypedEvent) -> Result<Self, RetroshadeError> {
+        Ok(Self {
+            memo: event.take("memo")?,
+            referrer: event.take("referrer")?,
+            amounts: event.take("amounts")?,
+            refunds: event.take("refunds")?,
+            hash: event.take("hash")?,
+            proofs: event.take("proofs")?,
+        })
+    }
+}
+
+fn bytes(bytes: &[u8]) -> ScVal {
+    ScVal::Bytes(ScBytes(bytes.to_vec().try_into().unwrap()))
+}
+
+fn sc_vec(items: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(items.try_into().unwrap())))
+}
+
+#[test]
+fn options_vectors_and_bytes() {
+    let spec = wasm_with_spec(&[udt_struct(
+        "PayoutEvent",
+        &[
+            ("memo", option(ScSpecTypeDef::String)),
+            ("referrer", option(ScSpecTypeDef::Address)),
+            ("amounts", vec_of(ScSpecTypeDef::I128)),
+            ("refunds", vec_of(ScSpecTypeDef::U64)),
+            ("hash", ScSpecTypeDef::BytesN(ScSpecTypeBytesN { n: 4 })),
+            ("proofs", vec_of(ScSpecTypeDef::Bytes)),
+        ],
+    )]);
+    let decoder = TypedDecoder::from_wasm(&spec, "PayoutEvent").unwrap();
+
+    // note: memo is left out of the event entirely, refunds is an empty
+    // vector which is packed as JSON.
+    let packed = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])))
+        .pack_result(execution_result(vec![export(
+            symbol("payout"),
+            vec![
+                (symbol("amounts"), sc_vec(vec![i128_val(-1), i128_val(2)])),
+                (symbol("hash"), bytes(&[1, 2, 3, 4])),
+                (symbol("proofs"), sc_vec(vec![bytes(&[5]), bytes(&[])])),
+                (symbol("referrer"), depositor()),
+                (symbol("refunds"), sc_vec(vec![])),
+            ],
+        )]))
+        .unwrap();
+
+    let decoded: PayoutEvent = decoder.decode_as(&packed.retroshades[0]).unwrap();
+    assert_eq!(
+        decoded,
+        PayoutEvent {
+            memo: None,
+            referrer: Some(Address(
+                stellar_strkey::ed25519::PublicKey([3; 32]).to_string()
+            )),
+            amounts: vec![-1, 2],
+            refunds: vec![],
+            hash: vec![1, 2, 3, 4],
+            proofs: vec![vec![5], vec![]],
+        }
+    );
+}
@@ -0,0 +1,245 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    export::jsonl::JsonlSink,
+    sink::{
+        dedup::{
+            record_delivered, retain_unseen, DedupSink, DedupStore, DedupWindow, Eviction,
+            FileDedupStore, MemoryDedupStore,
+        },
+        deliver_all, RetroshadeSink, SinkError,
+    },
+    RetroshadeError, RetroshadeExportPretty, TxContext,
+};
+
+use super::sink::packed_result;
+
+fn key(byte: u8) -> [u8; 32] {
+    [byte; 32]
+}
+
+/// Whether `key` was in the window, recording it.
+fn seen(store: &mut impl DedupStore, key: &[u8; 32]) -> bool {
+    let seen = store.contains(key);
+    store.record(key);
+    seen
+}
+
+fn window(capacity: usize, eviction: Eviction) -> DedupWindow {
+    DedupWindow { capacity, eviction }
+}
+
+/// A store file of its own per test, removed if left by a previous run.
+fn store_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "retroshade-dedup-{name}-{}.bin",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn records_unseen_keys() {
+    let mut store = MemoryDedupStore::new(DedupWindow::default());
+
+    assert!(!seen(&mut store, &key(1)));
+    assert!(seen(&mut store, &key(1)));
+    assert!(!seen(&mut store, &key(2)));
+    assert_eq!(store.len(), 2);
+}
+
+#[test]
+fn fifo_evicts_the_first_recorded() {
+    let mut store = MemoryDedupStore::new(window(2, Eviction::Fifo));
+    seen(&mut store, &key(1));
+    seen(&mut store, &key(2));
+    // note: hits don't keep a key in the window.
+    assert!(seen(&mut store, &key(1)));
+    seen(&mut store, &key(3));
+
+    assert_eq!(store.len(), 2);
+    assert!(seen(&mut store, &key(2)));
+    assert!(!seen(&mut store, &key(1)));
+}
+
+#[test]
+fn lru_evicts_the_least_recently_seen() {
+    let mut store = MemoryDedupStore::new(window(2, Eviction::Lru));
+    seen(&mut store, &key(1));
+    seen(&mut store, &key(2));
+    assert!(seen(&mut store, &key(1)));
+    seen(&mut store, &key(3));
+
+    assert!(seen(&mut store, &key(1)));
+    assert!(seen(&mut store, &key(3)));
+    assert!(!seen(&mut store, &key(2)));
+}
+
+#[test]
+fn lru_hits_stay_bounded() {
+    let mut store = MemoryDedupStore::new(window(2, Eviction::Lru));
+    seen(&mut store, &key(1));
+    seen(&mut store, &key(2));
+    for _ in 0..100 {
+        assert!(seen(&mut store, &key(1)));
+        assert!(seen(&mut store, &key(2)));
+    }
+
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.keys().collect::<Vec<_>>(), vec![&key(1), &key(2)]);
+}
+
+#[test]
+fn flushed_keys_survive_restarts() {
+    let path = store_path("restart");
+    let mut store = FileDedupStore::open(&path, DedupWindow::default()).unwrap();
+    seen(&mut store, &key(1));
+    seen(&mut store, &key(2));
+    store.flush().unwrap();
+    // note: recorded but not flushed, its export may not have been output.
+    seen(&mut store, &key(3));
+    drop(store);
+
+    let mut store = FileDedupStore::open(&path, DedupWindow::default()).unwrap();
+    assert_eq!(store.len(), 2);
+    assert!(seen(&mut store, &key(1)));
+    assert!(seen(&mut store, &key(2)));
+    assert!(!seen(&mut store, &key(3)));
+}
+
+#[test]
+fn restarts_keep_the_window() {
+    let path = store_path("window");
+    let mut store = FileDedupStore::open(&path, window(3, Eviction::Fifo)).unwrap();
+    for byte in 1..=5 {
+        seen(&mut store, &key(byte));
+    }
+    store.flush().unwrap();
+    drop(store);
+
+    let mut store = FileDedupStore::open(&path, window(2, Eviction::Fifo)).unwrap();
+    // note: compacted to the smaller window when opened.
+    assert_eq!(fs::metadata(&path).unwrap().len(), 2 * 32);
+    assert!(seen(&mut store, &key(5)));
+    assert!(seen(&mut store, &key(4)));
+    assert!(!seen(&mut store, &key(3)));
+}
+
+#[test]
+fn file_is_compacted_as_it_grows() {
+    let path = store_path("compaction");
+    let mut store = FileDedupStore::open(&path, window(2, Eviction::Fifo)).unwrap();
+    for byte in 1..=10 {
+        seen(&mut store, &key(byte));
+        store.flush().unwrap();
+        assert!(fs::metadata(&path).unwrap().len() <= 4 * 32);
+    }
+    drop(store);
+
+    let mut store = FileDedupStore::open(&path, window(2, Eviction::Fifo)).unwrap();
+    assert!(seen(&mut store, &key(10)));
+    assert!(seen(&mut store, &key(9)));
+    assert!(!seen(&mut store, &key(8)));
+}
+
+#[test]
+fn partial_keys_are_dropped() {
+    let path = store_path("partial");
+    let mut contents = key(1).to_vec();
+    contents.extend_from_slice(&key(2)[..10]);
+    fs::write(&path, contents).unwrap();
+
+    let mut store = FileDedupStore::open(&path, DedupWindow::default()).unwrap();
+    assert_eq!(store.len(), 1);
+    assert_eq!(fs::metadata(&path).unwrap().len(), 32);
+    assert!(seen(&mut store, &key(1)));
+    assert!(!seen(&mut store, &key(2)));
+}
+
+#[test]
+fn replays_after_a_restart_are_not_written_twice() {
+    let path = store_path("sink");
+    let result = packed_result();
+
+    let store = FileDedupStore::open(&path, DedupWindow::default()).unwrap();
+    let mut sink = DedupSink::new(JsonlSink::new(Vec::new()), store);
+    deliver_all(&mut sink, &result).unwrap();
+    let (written, _) = sink.into_inner();
+    let jsonl = String::from_utf8(written.into_inner()).unwrap();
+    assert_eq!(jsonl.lines().count(), 2);
+
+    let store = FileDedupStore::open(&path, DedupWindow::default()).unwrap();
+    let mut sink = DedupSink::new(JsonlSink::new(Vec::new()), store);
+    deliver_all(&mut sink, &result).unwrap();
+    assert_eq!(sink.skipped, 2);
+    let (written, _) = sink.into_inner();
+    assert!(written.into_inner().is_empty());
+}
+
+#[test]
+fn retains_the_unseen_exports() {
+    let mut result = packed_result();
+    let mut store = MemoryDedupStore::new(DedupWindow::default());
+    let first = &result.retroshades[0];
+    store.record(&first.idempotency_key(&first.context.tx_hash));
+
+    assert_eq!(retain_unseen(&mut result, &mut store), 1);
+    assert_eq!(result.retroshades.len(), 1);
+    assert_eq!(result.retroshades[0].target.to_string(), "deposit");
+
+    // note: not recorded until delivered.
+    let mut retried = packed_result();
+    assert_eq!(retain_unseen(&mut retried, &mut store), 1);
+
+    record_delivered(&result, &mut store);
+    assert_eq!(retain_unseen(&mut retried, &mut store), 1);
+    assert!(retried.retroshades.is_empty());
+}
+
+/// Fails every delivery while `failing` is set.
+#[derive(Default)]
+struct FlakySink {
+    failing: bool,
+    delivered: Vec<String>,
+}
+
+impl RetroshadeSink for FlakySink {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        _ctx: &TxContext,
+    ) -> Result<(), SinkError> {
+        if self.failing {
+            return Err(RetroshadeError::Export("unavailable".to_string()));
+        }
+        self.delivered.push(export.target.to_string());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn failed_deliveries_are_retried() {
+    let result = packed_result();
+    let sink = FlakySink {
+        failing: true,
+        ..Default::default()
+    };
+    let mut sink = DedupSink::new(sink, MemoryDedupStore::new(DedupWindow::default()));
+    assert!(deliver_all(&mut sink, &result).is_err());
+
+    let (mut flaky, store) = sink.into_inner();
+    assert!(store.is_empty());
+    flaky.failing = false;
+    let mut sink = DedupSink::new(flaky, store);
+    deliver_all(&mut sink, &result).unwrap();
+    assert_eq!(sink.skipped, 0);
+
+    let (flaky, store) = sink.into_inner();
+    assert_eq!(flaky.delivered, vec!["swap", "deposit"]);
+    assert_eq!(store.len(), 2);
+}
@@ -1,160 +1,63 @@
 use std::collections::HashMap;
 
-use crate::RetroshadesExecution;
-use soroban_env_host::{
-    storage::SnapshotSource,
-    xdr::{
-        ContractCodeEntry, ContractDataEntry, ContractExecutable, ExtensionPoint, Hash,
-        HostFunction, InvokeContractArgs, InvokeHostFunctionOp, LedgerEntry, LedgerEntryChanges,
-        LedgerEntryData, LedgerEntryExt, LedgerFootprint, LedgerKey, LedgerKeyContractCode,
-        LedgerKeyContractData, MuxedAccount, Operation, OperationBody, OperationMeta, ScAddress,
-        ScContractInstance, ScMap, ScSymbol, ScVal, ScVec, SequenceNumber, SorobanResources,
-        SorobanTransactionMeta, Transaction, TransactionMetaV3, TransactionV1Envelope, Uint256,
+use crate::{
+    testutils::{
+        assert_golden, assert_retroshade, code_key, instance_key, ledger_info_protocol,
+        EnvelopeBuilder, MockSnapshot,
     },
-    LedgerInfo,
+    ReplacementEntry, RetroshadesExecution,
 };
+use soroban_env_host::xdr::{Hash, ScMap};
 
-use std::rc::Rc;
-
-pub struct TestDynamicSnapshot {}
-
-impl SnapshotSource for TestDynamicSnapshot {
-    fn get(
-        &self,
-        key: &std::rc::Rc<soroban_env_host::xdr::LedgerKey>,
-    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
-    {
-        let entry = match key.as_ref() {
-            LedgerKey::ContractCode(_) => LedgerEntry {
-                last_modified_ledger_seq: 0,
-                ext: LedgerEntryExt::V0,
-                data: LedgerEntryData::ContractCode(ContractCodeEntry {
-                    ext: soroban_env_host::xdr::ContractCodeEntryExt::V0,
-                    hash: Hash([0;32]),
-                    code: std::fs::read("/Users/tdep/projects/retroshade/examples/vec/target/wasm32-unknown-unknown/release/soroban_hello_world_contract.wasm").unwrap().try_into().unwrap()
-                })
-            },
-            LedgerKey::ContractData(_) => LedgerEntry { last_modified_ledger_seq: 0, data: LedgerEntryData::ContractData(ContractDataEntry {
-                ext: ExtensionPoint::V0,
-                contract: ScAddress::Contract(Hash([0;32])),
-                durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
-                key: soroban_env_host::xdr::ScVal::LedgerKeyContractInstance,
-                val: ScVal::ContractInstance(ScContractInstance {
-                    executable: ContractExecutable::Wasm(Hash([0; 32])),
-                    storage: Some(ScMap(vec![].try_into().unwrap()))
-                })
-            }), ext: LedgerEntryExt::V0 },
-            _ => panic!()
-        };
-
-        Ok(Some((Rc::new(entry), Some(10000))))
-    }
-}
+use super::{examples::example_wasm, simple::hello_world_meta};
 
 #[test]
+#[ignore = "builds the example contracts"]
 fn simple() {
-    let mut retroshades = RetroshadesExecution::new(LedgerInfo {
-        protocol_version: 22,
-        sequence_number: 1000,
-        timestamp: 200,
-        network_id: [0; 32],
-        base_reserve: 1,
-        min_temp_entry_ttl: 300,
-        min_persistent_entry_ttl: 400,
-        max_entry_ttl: 500000,
-    });
-
-    let snapshot_source = TestDynamicSnapshot {};
-
-    let envelope = TransactionV1Envelope {
-        signatures: vec![].try_into().unwrap(),
-        tx: Transaction {
-            source_account: MuxedAccount::Ed25519(Uint256([0; 32])),
-            fee: 0,
-            seq_num: SequenceNumber(1),
-            cond: soroban_env_host::xdr::Preconditions::None,
-            memo: soroban_env_host::xdr::Memo::None,
-            ext: soroban_env_host::xdr::TransactionExt::V1(
-                soroban_env_host::xdr::SorobanTransactionData {
-                    ext: ExtensionPoint::V0,
-                    resources: SorobanResources {
-                        footprint: LedgerFootprint {
-                            read_only: vec![
-                                LedgerKey::ContractCode(LedgerKeyContractCode {
-                                    hash: Hash([0; 32]),
-                                }),
-                                LedgerKey::ContractData(LedgerKeyContractData {
-                                    contract: ScAddress::Contract(Hash([0; 32])),
-                                    key: ScVal::LedgerKeyContractInstance,
-                                    durability:
-                                        soroban_env_host::xdr::ContractDataDurability::Persistent,
-                                }),
-                            ]
-                            .try_into()
-                            .unwrap(),
-                            read_write: vec![].try_into().unwrap(),
-                        },
-                        instructions: 1000000,
-                        read_bytes: 10000,
-                        write_bytes: 0,
-                    },
-                    resource_fee: 10000000,
-                },
-            ),
-            operations: vec![Operation {
-                source_account: None,
-                body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
-                    host_function: HostFunction::InvokeContract(InvokeContractArgs {
-                        contract_address: ScAddress::Contract(Hash([0; 32])),
-                        function_name: ScSymbol("t".try_into().unwrap()),
-                        args: vec![].try_into().unwrap(),
-                    }),
-                    auth: vec![].try_into().unwrap(),
-                }),
-            }]
-            .try_into()
-            .unwrap(),
-        },
-    };
-
-    let meta = TransactionMetaV3 {
-        ext: ExtensionPoint::V0,
-        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
-        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
-        soroban_meta: Some(SorobanTransactionMeta {
-            ext: soroban_env_host::xdr::SorobanTransactionMetaExt::V0,
-            events: vec![].try_into().unwrap(),
-            return_value: ScVal::Vec(Some(ScVec(
-                vec![
-                    ScVal::Symbol(ScSymbol("hello".try_into().unwrap())),
-                    ScVal::Symbol(ScSymbol("tdep".try_into().unwrap())),
-                ]
-                .try_into()
-                .unwrap(),
-            ))),
-            diagnostic_events: vec![].try_into().unwrap(),
-        }),
-        operations: vec![OperationMeta {
-            changes: LedgerEntryChanges(vec![].try_into().unwrap()), // the hello world contract doesn't change anything
-        }]
-        .try_into()
-        .unwrap(),
-    };
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+
+    // the deployed contract's `t` panics without its storage, the export can
+    // only come from the replacement.
+    let snapshot_source = MockSnapshot::with_contract(
+        Hash([0; 32]),
+        &example_wasm("storage", "soroban_hello_world_contract"),
+        ScMap::default(),
+    );
+
+    let envelope = EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
 
     let mut mercury_contracts = HashMap::new();
-    let binary = std::fs::read("/Users/tdep/projects/retroshade/examples/hello_world/target/wasm32-unknown-unknown/release/soroban_hello_world_contract_retroshade.wasm").unwrap();
-    mercury_contracts.insert(Hash([0; 32]), binary.as_slice());
+    let binary = example_wasm("hello_world", "soroban_hello_world_contract");
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(binary));
 
     let replaced = retroshades
-        .build_from_envelope_and_meta(Box::new(snapshot_source), envelope, meta, mercury_contracts)
+        .build_from_envelope_and_meta(
+            &snapshot_source,
+            envelope,
+            hello_world_meta(),
+            mercury_contracts,
+        )
         .unwrap();
 
-    assert_eq!(replaced, true);
+    assert!(replaced);
 
-    let retroshades = retroshades.retroshade().unwrap();
+    let raw = retroshades.retroshade().unwrap();
+    assert_golden("deployer_retroshades", &raw.retroshades);
 
-    assert_eq!(
-        r#"[{"contract_id":"0000000000000000000000000000000000000000000000000000000000000000","target":{"symbol":"test"},"event_object":{"map":[{"key":{"symbol":"test"},"val":{"address":"CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4"}}]}}]"#,
-        serde_json::to_string(&retroshades.retroshades).unwrap()
-    )
+    let retroshades = retroshades.retroshade_packed().unwrap();
+
+    assert_eq!(retroshades.retroshades.len(), 1);
+    assert_retroshade!(
+        retroshades.retroshades[0],
+        target: "test1",
+        contract: "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4",
+        fields: {
+            "amount" => numeric "990",
+            "test" => text "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4",
+        },
+    );
 }
@@ -0,0 +1,43 @@
+use std::fs;
+
+use crate::{
+    diagnostics::{self, DisplayDiagnostics},
+    testutils::assert_golden,
+};
+use soroban_env_host::xdr::{DiagnosticEvent, Limits, ReadXdr};
+
+/// Diagnostics the host emitted for the call of `trapping_execution` in
+/// test/failure.rs, base64 XDR.
+const TRAPPING_CALL: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/diagnostics/trapping_call.json"
+);
+
+pub fn trapping_call_trace() -> Vec<DiagnosticEvent> {
+    let encoded: Vec<String> =
+        serde_json::from_str(&fs::read_to_string(TRAPPING_CALL).unwrap()).unwrap();
+
+    encoded
+        .iter()
+        .map(|event| DiagnosticEvent::from_xdr_base64(event, Limits::none()).unwrap())
+        .collect()
+}
+
+#[test]
+fn format_trapping_call_trace() {
+    assert_golden(
+        "trapping_call_diagnostics",
+        &diagnostics::format(&trapping_call_trace()),
+    );
+}
+
+#[test]
+fn display_numbers_lines() {
+    let trace = trapping_call_trace();
+    let displayed = DisplayDiagnostics(&trace[1..2]).to_string();
+
+    assert_eq!(
+        displayed,
+        "0: [failed] Diagnostic event from host: topics [error, {\"wasm_vm\":\"invalid_action\"}], data Error(WasmVm, InvalidAction)\n"
+    );
+}
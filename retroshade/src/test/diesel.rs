@@ -0,0 +1,43 @@
+use crate::conversion::diesel::insert_query;
+use diesel::{debug_query, pg::Pg};
+use postgres_types::Type;
+use soroban_env_host::xdr::{Int128Parts, MuxedAccount, ScMap, ScMapEntry, ScVal, ScVec, Uint256};
+
+use super::packing::{built_execution, execution_result, export, symbol};
+
+#[test]
+fn dynamic_insert() {
+    let packed = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])))
+        .pack_result(execution_result(vec![export(
+            symbol("test"),
+            vec![
+                (symbol("amount"), ScVal::I128(Int128Parts { hi: 0, lo: 2 })),
+                (symbol("flag"), ScVal::Bool(true)),
+                (
+                    symbol("ids"),
+                    ScVal::Vec(Some(ScVec(vec![ScVal::U32(1)].try_into().unwrap()))),
+                ),
+                (
+                    symbol("meta"),
+                    ScVal::Map(Some(ScMap(
+                        vec![ScMapEntry {
+                            key: symbol("fee"),
+                            val: ScVal::U32(3),
+                        }]
+                        .try_into()
+                        .unwrap(),
+                    ))),
+                ),
+            ],
+        )]))
+        .unwrap();
+
+    let export = &packed.retroshades[0];
+    // note: the map is JSON text, bound as text.
+    assert_eq!(export.event[3].value.dbtype, Type::TEXT);
+
+    let query = insert_query("test", export);
+    assert!(debug_query::<Pg, _>(&query).to_string().starts_with(
+        r#"INSERT INTO test ("amount", "flag", "ids", "meta") VALUES ($1, $2, $3, $4)"#
+    ));
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::{
+    dump::EntryOrigin, fixture, testutils::ledger_info_protocol, ReplacementEntry,
+    RetroshadesExecution,
+};
+use soroban_env_host::xdr::{LedgerEntryData, LedgerKey, TransactionEnvelope, TransactionExt};
+
+const MAINNET_FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/mainnet_first_retroshade.json"
+);
+
+// note: the smallest valid module, the code is only compared.
+const EMPTY_WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+#[test]
+fn state_dump_from_fixture() {
+    let (snapshot, envelope, meta) = fixture::load(MAINNET_FIXTURE).unwrap();
+    let TransactionEnvelope::Tx(envelope) = envelope else {
+        panic!("fixture envelope is not a v1 envelope")
+    };
+    let TransactionExt::V1(soroban) = &envelope.tx.ext else {
+        panic!("fixture envelope is not a soroban tx")
+    };
+    let code_hash = soroban
+        .resources
+        .footprint
+        .read_only
+        .iter()
+        .find_map(|key| match key {
+            LedgerKey::ContractCode(code) => Some(code.hash.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    let entries = retroshades
+        .build_with_state_dump(
+            &snapshot,
+            envelope,
+            meta,
+            HashMap::from([(code_hash, ReplacementEntry::new(EMPTY_WASM).allow_any())]),
+        )
+        .unwrap();
+
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        assert!(entry.snapshot.is_some());
+        match entry.key {
+            LedgerKey::ContractCode(_) => {
+                assert_eq!(entry.origin, EntryOrigin::Replaced);
+                let Some(LedgerEntryData::ContractCode(code)) = entry.fed.map(|fed| fed.data)
+                else {
+                    panic!("fed entry is not contract code")
+                };
+                assert_eq!(code.code.as_slice(), EMPTY_WASM);
+            }
+            _ => assert_eq!(
+                entry.origin == EntryOrigin::Unchanged,
+                entry.snapshot == entry.fed
+            ),
+        }
+    }
+}
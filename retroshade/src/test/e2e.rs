@@ -0,0 +1,290 @@
+//! End-to-end runs of the example contracts, from building their wasm to the
+//! packed retroshades. Building every example takes a while, so these are
+//! ignored by default: run them with `cargo test e2e -- --ignored`.
+//!
+//! The deployed code is always built without the emitting feature, as it
+//! would be on chain, and replaced with the one emitting retroshades.
+
+use std::collections::HashMap;
+
+use postgres_types::Type;
+use soroban_env_host::xdr::{
+    AccountId, ContractDataDurability, ContractDataEntry, ContractExecutable, ExtensionPoint, Hash,
+    Int128Parts, LedgerEntry, LedgerEntryChange, LedgerEntryChanges, LedgerEntryData,
+    LedgerEntryExt, OperationMeta, PublicKey, ScAddress, ScContractInstance, ScMap, ScMapEntry,
+    ScVal, SorobanTransactionMeta, SorobanTransactionMetaExt, TransactionMeta, TransactionMetaV3,
+    Uint256,
+};
+use wasmparser::{Parser, Payload};
+
+use crate::{
+    testutils::{
+        assert_retroshade, code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot,
+    },
+    ReplacementEntry, RetroshadesExecution,
+};
+
+use super::examples::{example_wasm, example_wasm_with_features};
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn examples_build() {
+    for (example, crate_name, features) in [
+        ("abusive", "soroban_abusive", &[][..]),
+        ("abusive", "soroban_abusive", &["mercury"][..]),
+        ("all_types", "soroban_all_types", &[][..]),
+        ("all_types", "soroban_all_types", &["mercury"][..]),
+        ("deposit", "soroban_deposit", &[][..]),
+        ("deposit", "soroban_deposit", &["mercury"][..]),
+        ("orders", "soroban_orders", &[][..]),
+        ("orders", "soroban_orders", &["mercury"][..]),
+        ("hello_world", "soroban_hello_world_contract", &[][..]),
+        ("storage", "soroban_hello_world_contract", &[][..]),
+        (
+            "soroban-insurance-factory",
+            "soroban_hello_world_contract",
+            &[][..],
+        ),
+    ] {
+        let wasm = example_wasm_with_features(example, crate_name, features);
+        assert!(
+            wasm.starts_with(WASM_MAGIC),
+            "the {} example didn't build to wasm",
+            example
+        );
+    }
+}
+
+/// Modules the wasm imports from, and what it exports.
+fn imports_and_exports(wasm: &[u8]) -> (Vec<String>, Vec<String>) {
+    let mut modules = Vec::new();
+    let mut exports = Vec::new();
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.unwrap() {
+            Payload::ImportSection(imports) => {
+                for import in imports {
+                    modules.push(import.unwrap().module.to_string());
+                }
+            }
+            Payload::ExportSection(section) => {
+                for export in section {
+                    exports.push(export.unwrap().name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    exports.sort();
+
+    (modules, exports)
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn emitting_is_a_no_op_without_mercury() {
+    for (example, crate_name) in [("deposit", "soroban_deposit"), ("orders", "soroban_orders")] {
+        let (modules, exports) = imports_and_exports(&example_wasm(example, crate_name));
+        let (mercury_modules, mercury_exports) = imports_and_exports(&example_wasm_with_features(
+            example,
+            crate_name,
+            &["mercury"],
+        ));
+
+        assert!(
+            !modules.iter().any(|module| module == "x"),
+            "the deployed {} example imports from Mercury",
+            example
+        );
+        assert!(mercury_modules.iter().any(|module| module == "x"));
+        assert_eq!(exports, mercury_exports, "{} exports", example);
+    }
+}
+
+/// Instance storage of the deposit contract, holding its TVL under `0`.
+fn deposit_instance(tvl: u64) -> LedgerEntry {
+    LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(Hash([0; 32])),
+                storage: Some(tvl_storage(tvl)),
+            }),
+        }),
+        ext: LedgerEntryExt::V0,
+    }
+}
+
+fn tvl_storage(tvl: u64) -> ScMap {
+    ScMap(
+        vec![ScMapEntry {
+            key: ScVal::I32(0),
+            val: ScVal::I128(Int128Parts { hi: 0, lo: tvl }),
+        }]
+        .try_into()
+        .unwrap(),
+    )
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn deposit_pipeline() {
+    let depositor =
+        ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([3; 32]))));
+
+    // the state after a deposit of 250 brought the TVL from 1000 to 1250.
+    let snapshot = MockSnapshot::with_contract(
+        Hash([0; 32]),
+        &example_wasm("deposit", "soroban_deposit"),
+        tvl_storage(1250),
+    );
+    let envelope = EnvelopeBuilder::new()
+        .function("deposit")
+        .arg(ScVal::Address(depositor))
+        .arg(ScVal::I128(Int128Parts { hi: 0, lo: 250 }))
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_write_key(instance_key(Hash([0; 32])))
+        .build();
+    let meta = TransactionMeta::V3(TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value: ScVal::Void,
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(
+                vec![
+                    LedgerEntryChange::State(deposit_instance(1000)),
+                    LedgerEntryChange::Updated(deposit_instance(1250)),
+                ]
+                .try_into()
+                .unwrap(),
+            ),
+        }]
+        .try_into()
+        .unwrap(),
+    });
+
+    let binary = example_wasm_with_features("deposit", "soroban_deposit", &["mercury"]);
+    let mut mercury_contracts = HashMap::new();
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(binary));
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    let replaced = retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, mercury_contracts)
+        .unwrap();
+    assert!(replaced);
+
+    let result = retroshades.retroshade_packed().unwrap();
+    assert!(result.call_succeeded);
+    assert_eq!(result.retroshades.len(), 1);
+
+    let retroshade = &result.retroshades[0];
+    assert_retroshade!(
+        retroshade,
+        target: "mydeposit",
+        contract: &stellar_strkey::Contract([0; 32]).to_string(),
+        fields: {
+            "from" => text &stellar_strkey::ed25519::PublicKey([3; 32]).to_string(),
+            "amount" => numeric "250",
+            "previous_tvl" => numeric "1000",
+            "now_tvl" => numeric "1250",
+            "ledger" => numeric "1000",
+            "timestamp" => numeric "200",
+        },
+    );
+
+    for entry in &retroshade.event {
+        let expected = if entry.name == "from" {
+            Type::TEXT
+        } else {
+            Type::NUMERIC
+        };
+        assert_eq!(entry.value.dbtype, expected, "{} column type", entry.name);
+    }
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn orders_pipeline_resolves_the_contract_types() {
+    let maker = ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([3; 32]))));
+
+    // the state after filling 250 of an empty book, the orders contract keeps
+    // what's filled under `0` like the deposit one keeps its TVL.
+    let snapshot = MockSnapshot::with_contract(
+        Hash([0; 32]),
+        &example_wasm("orders", "soroban_orders"),
+        tvl_storage(250),
+    );
+    let envelope = EnvelopeBuilder::new()
+        .function("fill")
+        .arg(ScVal::Address(maker))
+        .arg(ScVal::U32(1))
+        .arg(ScVal::I128(Int128Parts { hi: 0, lo: 250 }))
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_write_key(instance_key(Hash([0; 32])))
+        .build();
+    let meta = TransactionMeta::V3(TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value: ScVal::Void,
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(
+                vec![
+                    LedgerEntryChange::State(deposit_instance(0)),
+                    LedgerEntryChange::Updated(deposit_instance(250)),
+                ]
+                .try_into()
+                .unwrap(),
+            ),
+        }]
+        .try_into()
+        .unwrap(),
+    });
+
+    let binary = example_wasm_with_features("orders", "soroban_orders", &["mercury"]);
+    let mut mercury_contracts = HashMap::new();
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(binary));
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades.set_spec_column_types(true);
+    let replaced = retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, mercury_contracts)
+        .unwrap();
+    assert!(replaced);
+
+    let result = retroshades.retroshade_packed().unwrap();
+    assert!(result.call_succeeded);
+    assert_eq!(result.retroshades.len(), 1);
+
+    let maker = stellar_strkey::ed25519::PublicKey([3; 32]).to_string();
+    let fill = format!(r#"{{"amount":"250","maker":"{}"}}"#, maker);
+    let retroshade = &result.retroshades[0];
+    assert_retroshade!(
+        retroshade,
+        target: "orders",
+        contract: &stellar_strkey::Contract([0; 32]).to_string(),
+        fields: {
+            "side" => text "Sell",
+            "status" => text r#"Filled(["250"])"#,
+            "fill" => text &fill,
+            "ledger" => numeric "1000",
+        },
+    );
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{Hash, LedgerEntryData};
+
+use crate::{engine::RetroshadeEngine, ReplacementEntry, RetroshadesExecution};
+
+use super::fixture::{mainnet_execution, mainnet_execution_with};
+
+#[test]
+fn engine_matches_standalone_execution() {
+    let retroshades = mainnet_execution();
+    let engine = RetroshadeEngine::new().unwrap();
+
+    let expected = retroshades.retroshade_packed().unwrap();
+    // the second run goes through the already parsed modules.
+    for _ in 0..2 {
+        let result = engine.execute_packed(&retroshades).unwrap();
+        assert_eq!(result.call_succeeded, expected.call_succeeded);
+        assert_eq!(result.retroshades, expected.retroshades);
+        assert_eq!(result.invoke_result, expected.invoke_result);
+    }
+}
+
+#[test]
+fn engine_shares_names_across_executions() {
+    let engine = RetroshadeEngine::new().unwrap();
+    let first = engine.execute_packed(&mainnet_execution()).unwrap();
+    let second = engine.execute_packed(&mainnet_execution()).unwrap();
+    assert!(!first.retroshades.is_empty());
+
+    let (first, second) = (&first.retroshades[0], &second.retroshades[0]);
+    assert_eq!(first.target.as_ptr(), second.target.as_ptr());
+    assert_eq!(first.contract_id.as_ptr(), second.contract_id.as_ptr());
+    assert!(!engine.interner().is_empty());
+}
+
+/// The fixture's contract, emitting its field as `best` rather than `test`.
+fn renamed_field_wasm(retroshades: &RetroshadesExecution) -> Vec<u8> {
+    let mut wasm = retroshades
+        .target_pre_execution_state
+        .iter()
+        .find_map(|keyed| match &keyed.entry.data {
+            LedgerEntryData::ContractCode(code) => Some(code.code.to_vec()),
+            _ => None,
+        })
+        .unwrap();
+    let data = b"\x0b\x0ctest";
+    let at = wasm
+        .windows(data.len())
+        .position(|window| window == data)
+        .expect("the field name isn't in the data section");
+    wasm[at + 2] = b'b';
+
+    wasm
+}
+
+#[test]
+fn replacements_dont_run_cached_modules() {
+    let engine = RetroshadeEngine::new().unwrap();
+    let deployed = mainnet_execution();
+    engine.execute_packed(&deployed).unwrap();
+
+    let contract_id = Hash(
+        hex::decode("7d6a35c814d8361032f93e030b6c2379ba03bb84f8e9efbd2eb55b316ea26d65")
+            .unwrap()
+            .try_into()
+            .unwrap(),
+    );
+    let replaced = mainnet_execution_with(HashMap::from([(
+        contract_id,
+        ReplacementEntry::new(renamed_field_wasm(&deployed)),
+    )]));
+    let expected = replaced.retroshade_packed().unwrap();
+    assert_eq!(expected.retroshades[0].event[0].name.to_string(), "best");
+
+    let result = engine.execute_packed(&replaced).unwrap();
+    assert_eq!(result.retroshades, expected.retroshades);
+
+    // the deployed code is still cached for the executions not replacing it.
+    let result = engine.execute_packed(&deployed).unwrap();
+    assert_eq!(result.retroshades[0].event[0].name.to_string(), "test");
+}
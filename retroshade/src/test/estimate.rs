@@ -0,0 +1,74 @@
+use crate::estimate::{ResourceEstimate, Shortfall};
+use soroban_env_host::xdr::{
+    Hash, LedgerFootprint, LedgerKey, LedgerKeyContractCode, SorobanResources,
+};
+
+fn code_key(byte: u8) -> LedgerKey {
+    LedgerKey::ContractCode(LedgerKeyContractCode {
+        hash: Hash([byte; 32]),
+    })
+}
+
+fn resources(
+    instructions: u32,
+    read_only: Vec<LedgerKey>,
+    read_write: Vec<LedgerKey>,
+) -> SorobanResources {
+    SorobanResources {
+        footprint: LedgerFootprint {
+            read_only: read_only.try_into().unwrap(),
+            read_write: read_write.try_into().unwrap(),
+        },
+        instructions,
+        disk_read_bytes: 1000,
+        write_bytes: 100,
+    }
+}
+
+fn estimate(recorded: SorobanResources, declared: SorobanResources) -> ResourceEstimate {
+    ResourceEstimate {
+        recorded,
+        declared,
+        cpu_instructions: 0,
+        memory_bytes: 0,
+        call_succeeded: true,
+    }
+}
+
+#[test]
+fn covered_by_declared() {
+    // note: a key declared read-write covers a read-only access.
+    let estimate = estimate(
+        resources(900, vec![code_key(0)], vec![]),
+        resources(1000, vec![], vec![code_key(0)]),
+    );
+
+    assert!(estimate.sufficient());
+}
+
+#[test]
+fn shortfalls() {
+    let estimate = estimate(
+        resources(1200, vec![code_key(1)], vec![code_key(0)]),
+        resources(1000, vec![code_key(0)], vec![]),
+    );
+
+    assert_eq!(
+        estimate.shortfalls(),
+        vec![
+            Shortfall::Instructions {
+                declared: 1000,
+                recorded: 1200
+            },
+            Shortfall::Footprint {
+                key: code_key(1),
+                read_write: false
+            },
+            Shortfall::Footprint {
+                key: code_key(0),
+                read_write: true
+            },
+        ]
+    );
+    assert!(!estimate.sufficient());
+}
@@ -0,0 +1,78 @@
+//! Wasms of the contracts in `../examples`, built on demand into each
+//! example's own `target/`, and prebuilt contracts for the tests to run
+//! without building them.
+
+use std::{collections::HashSet, env, path::PathBuf, process::Command, sync::Mutex};
+
+/// Examples already built by this test run.
+static BUILT: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Reads `fixtures/wasm/{name}.wasm`:
+///
+/// - `all_types` and `all_types-mercury`, the all_types example without and
+///   with the `mercury` feature, assembled by hand.
+/// - `deposit_spec`, a module holding the spec of the deposit example.
+/// - `void`, a contract whose `t` function returns void.
+///
+/// Ignored tests check that the all_types and deposit ones still match
+/// the examples.
+pub fn fixture_wasm(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/wasm")
+        .join(format!("{}.wasm", name));
+    std::fs::read(&path).unwrap_or_else(|error| panic!("can't read {}: {}", path.display(), error))
+}
+
+/// Reads the wasm of `example`, (re)building it once per test run. Needs the
+/// `wasm32-unknown-unknown` target installed.
+pub fn example_wasm(example: &str, crate_name: &str) -> Vec<u8> {
+    example_wasm_with_features(example, crate_name, &[])
+}
+
+/// Same as [`example_wasm`], building with the default features and
+/// `features`. Each feature set gets its own target dir so that builds don't
+/// overwrite each other's wasm.
+pub fn example_wasm_with_features(example: &str, crate_name: &str, features: &[&str]) -> Vec<u8> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../examples")
+        .join(example);
+    let target_dir = if features.is_empty() {
+        dir.join("target")
+    } else {
+        dir.join("target")
+            .join(format!("features-{}", features.join("-")))
+    };
+
+    {
+        let mut built = BUILT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if built
+            .get_or_insert_with(HashSet::new)
+            .insert(format!("{}{:?}", example, features))
+        {
+            // note: the target dir is explicit so that a CARGO_TARGET_DIR meant
+            // for this crate doesn't redirect the example's build.
+            let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+                .args(["build", "--release", "--target", "wasm32-unknown-unknown"])
+                .args(features.iter().flat_map(|feature| ["--features", feature]))
+                .arg("--target-dir")
+                .arg(&target_dir)
+                .current_dir(&dir)
+                .status()
+                .expect("failed to run cargo");
+            assert!(
+                status.success(),
+                "building the {} example failed, is the wasm32-unknown-unknown target installed?",
+                example
+            );
+        }
+    }
+
+    std::fs::read(
+        target_dir
+            .join("wasm32-unknown-unknown/release")
+            .join(format!("{}.wasm", crate_name)),
+    )
+    .unwrap()
+}
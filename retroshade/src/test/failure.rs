@@ -0,0 +1,156 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    testutils::{ledger_info_protocol, EnvelopeBuilder, Fixture, MockSnapshot},
+    OnCallFailure, RetroshadeError, RetroshadesExecution,
+};
+use soroban_env_host::{
+    storage::{EntryWithLiveUntil, SnapshotSource},
+    xdr::{
+        ContractEventBody, DiagnosticEvent, Hash, HostFunction, LedgerKey, OperationBody,
+        ScErrorCode, ScErrorType, ScMap, ScSymbol, ScVal,
+    },
+    HostError,
+};
+
+use super::{
+    diagnostics::trapping_call_trace, examples::example_wasm, fixture::MAINNET_FIXTURE,
+    simple::hello_world_meta,
+};
+
+/// Builds an execution from the mainnet fixture that invokes a function the
+/// contract doesn't export, so that the call traps.
+fn trapping_execution() -> RetroshadesExecution {
+    let Fixture {
+        snapshot,
+        mut envelope,
+        meta,
+        ..
+    } = Fixture::load(MAINNET_FIXTURE);
+
+    let mut operations = envelope.tx.operations.to_vec();
+    if let OperationBody::InvokeHostFunction(op) = &mut operations[0].body {
+        if let HostFunction::InvokeContract(args) = &mut op.host_function {
+            args.function_name = ScSymbol("missing".try_into().unwrap());
+        }
+    }
+    envelope.tx.operations = operations.try_into().unwrap();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+
+    retroshades
+}
+
+#[test]
+fn trapping_call_is_flagged() {
+    let retroshades = trapping_execution();
+
+    let result = retroshades.retroshade_packed().unwrap();
+    assert!(!result.call_succeeded);
+    assert!(result.invoke_result.is_err());
+}
+
+#[test]
+fn trapping_call_errors_in_strict_mode() {
+    let mut retroshades = trapping_execution();
+    retroshades.set_strict(true);
+
+    match retroshades.retroshade_packed() {
+        Err(RetroshadeError::ContractCallFailed { diagnostics }) => {
+            assert_eq!(diagnostics, trapping_call_trace())
+        }
+        other => panic!("expected the call to fail, got {:?}", other),
+    }
+}
+
+#[test]
+fn trapping_call_errors_before_packing() {
+    let mut retroshades = trapping_execution();
+    retroshades.set_on_call_failure(OnCallFailure::Error);
+
+    assert!(matches!(
+        retroshades.retroshade(),
+        Err(RetroshadeError::ContractCallFailed { .. })
+    ));
+}
+
+#[test]
+fn trapping_call_returns_no_retroshades() {
+    let mut retroshades = trapping_execution();
+    retroshades.set_on_call_failure(OnCallFailure::ReturnEmpty);
+
+    let result = retroshades.retroshade_packed().unwrap();
+    assert!(!result.call_succeeded);
+    assert!(result.retroshades.is_empty());
+    assert!(result.conversion_errors.is_empty());
+}
+
+/// Fails every read of a key after its `reads`-th one.
+struct FailingRereads {
+    inner: MockSnapshot,
+    reads: usize,
+    counts: RefCell<HashMap<LedgerKey, usize>>,
+}
+
+impl SnapshotSource for FailingRereads {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        let mut counts = self.counts.borrow_mut();
+        let count = counts.entry(key.as_ref().clone()).or_default();
+        *count += 1;
+        if *count > self.reads {
+            return Err((ScErrorType::Storage, ScErrorCode::InternalError).into());
+        }
+
+        self.inner.get(key)
+    }
+}
+
+fn topic(event: &DiagnosticEvent, idx: usize) -> Option<&ScVal> {
+    let ContractEventBody::V0(body) = &event.event.body;
+    body.topics.get(idx)
+}
+
+#[test]
+fn host_failure_keeps_the_diagnostics() {
+    let wasm = example_wasm("hello_world", "soroban_hello_world_contract");
+    let snapshot = MockSnapshot::with_contract(Hash([0; 32]), &wasm, ScMap::default());
+    // note: no footprint, the recording host reads the entries from the
+    // snapshot rather than from the pre-execution state.
+    let envelope = EnvelopeBuilder::new().function("t").build();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, hello_world_meta(), HashMap::new())
+        .unwrap();
+
+    // note: the recording host reads each entry during the call and when
+    // building the footprint, then once more for the ledger changes, after
+    // the call's diagnostics were collected. Failing that read fails the
+    // host rather than the call.
+    let failing = FailingRereads {
+        inner: snapshot,
+        reads: 2,
+        counts: RefCell::default(),
+    };
+    let error = retroshades
+        .retroshade_recording(Rc::new(failing))
+        .unwrap_err();
+
+    let RetroshadeError::SVMHost { error, diagnostics } = error else {
+        panic!("expected a host failure, got {:?}", error)
+    };
+    assert!(error.error.is_type(ScErrorType::Storage));
+
+    let fn_call = ScVal::Symbol(ScSymbol("fn_call".try_into().unwrap()));
+    let fn_return = ScVal::Symbol(ScSymbol("fn_return".try_into().unwrap()));
+    let t = ScVal::Symbol(ScSymbol("t".try_into().unwrap()));
+    assert_eq!(topic(&diagnostics[0], 0), Some(&fn_call));
+    assert_eq!(topic(&diagnostics[0], 2), Some(&t));
+    // note: the call itself completed before the failure.
+    assert!(diagnostics
+        .iter()
+        .any(|event| topic(event, 0) == Some(&fn_return) && topic(event, 1) == Some(&t)));
+}
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, rc::Rc, sync::atomic::Ordering, time::Duration};
+
+#[cfg(feature = "http")]
+use crate::testutils::{rpc_url, RpcCase};
+use crate::{
+    fixture,
+    testutils::{ledger_info_protocol, Fixture},
+    ReplacementEntry, RetroshadeError, RetroshadesExecution,
+};
+use soroban_env_host::{
+    budget::Budget,
+    e2e_invoke::ledger_entry_to_ledger_key,
+    storage::SnapshotSource,
+    xdr::{
+        ExtensionPoint, Hash, LedgerEntryChanges, ScVal, SorobanTransactionMeta, TransactionMetaV3,
+    },
+    LedgerInfo,
+};
+
+pub const MAINNET_FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/mainnet_first_retroshade.json"
+);
+
+/// An execution built from the mainnet fixture.
+pub fn mainnet_execution() -> RetroshadesExecution {
+    mainnet_execution_with(HashMap::new())
+}
+
+/// Same as [`mainnet_execution`] with binaries replaced.
+pub fn mainnet_execution_with(
+    mercury_contracts: HashMap<Hash, ReplacementEntry>,
+) -> RetroshadesExecution {
+    let fixture = Fixture::load(MAINNET_FIXTURE);
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(
+            &fixture.snapshot,
+            fixture.envelope,
+            fixture.meta,
+            mercury_contracts,
+        )
+        .unwrap();
+
+    retroshades
+}
+
+#[test]
+fn dump_and_load_roundtrip() {
+    let (snapshot, envelope, meta) = fixture::load(MAINNET_FIXTURE).unwrap();
+    let entries = snapshot.entries();
+    assert_eq!(entries.len(), 2);
+
+    let path = std::env::temp_dir().join("retroshade_fixture_roundtrip.json");
+    fixture::dump(&path, &entries, &envelope, &meta).unwrap();
+    let (reloaded, reloaded_envelope, reloaded_meta) = fixture::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(envelope, reloaded_envelope);
+    assert_eq!(meta, reloaded_meta);
+
+    for (entry, live_until) in entries {
+        let key = ledger_entry_to_ledger_key(&entry, &Budget::default()).unwrap();
+        let (reloaded_entry, reloaded_live_until) = reloaded.get(&Rc::new(key)).unwrap().unwrap();
+
+        assert_eq!(&entry, reloaded_entry.as_ref());
+        assert_eq!(live_until, reloaded_live_until);
+    }
+}
+
+#[test]
+fn build_report_from_fixture() {
+    let fixture = Fixture::load(MAINNET_FIXTURE);
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    let report = retroshades
+        .build_from_envelope_and_meta_with_report(
+            &fixture.snapshot,
+            fixture.envelope,
+            fixture.meta,
+            HashMap::new(),
+        )
+        .unwrap();
+
+    assert!(!report.binaries_replaced);
+    assert_eq!(report.footprint.len(), 2);
+    for provenance in report.footprint {
+        assert!(provenance.source.ends_with("MemorySnapshot"));
+        assert!(provenance.last_modified_ledger_seq.is_some());
+        assert!(provenance.live_until.is_some());
+    }
+}
+
+#[test]
+fn state_over_the_limit_fails_the_build() {
+    let fixture = Fixture::load(MAINNET_FIXTURE);
+    let build = |limit: Option<usize>| {
+        let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+        if let Some(limit) = limit {
+            retroshades.set_max_state_bytes(limit);
+        }
+        retroshades.build_from_envelope_and_meta_with_report(
+            &fixture.snapshot,
+            fixture.envelope.clone(),
+            fixture.meta.clone(),
+            HashMap::new(),
+        )
+    };
+
+    let state_bytes = build(None).unwrap().state_bytes;
+    // the two entries and their 36 bytes TTL entries.
+    assert!(state_bytes > 2 * 36);
+
+    assert_eq!(build(Some(state_bytes)).unwrap().state_bytes, state_bytes);
+    match build(Some(state_bytes - 1)) {
+        Err(RetroshadeError::StateTooLarge { bytes, limit }) => {
+            assert_eq!(bytes, state_bytes);
+            assert_eq!(limit, state_bytes - 1);
+        }
+        other => panic!("expected StateTooLarge, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn invoke_result_matches_onchain_return() {
+    let retroshades = mainnet_execution();
+    let result = retroshades.retroshade().unwrap();
+    let return_value = result.invoke_result.clone().unwrap();
+
+    let onchain_meta = |return_value: ScVal| TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        operations: vec![].try_into().unwrap(),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: soroban_env_host::xdr::SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value,
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+    };
+
+    assert!(result.matches_onchain_return(&onchain_meta(return_value)));
+    assert!(!result.matches_onchain_return(&onchain_meta(ScVal::Bool(true))));
+}
+
+#[test]
+fn packed_execution_timings() {
+    let retroshades = mainnet_execution();
+    let timings = retroshades.retroshade_packed().unwrap().timings;
+
+    assert!(timings.build > Duration::ZERO);
+    assert!(timings.reset > Duration::ZERO);
+    assert!(timings.execute > Duration::ZERO);
+    assert!(timings.convert > Duration::ZERO);
+}
+
+#[test]
+fn repeated_executions_encode_once() {
+    let fixture = Fixture::load(MAINNET_FIXTURE);
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(
+            &fixture.snapshot,
+            fixture.envelope.clone(),
+            fixture.meta.clone(),
+            HashMap::new(),
+        )
+        .unwrap();
+
+    let first = retroshades.retroshade().unwrap();
+    let second = retroshades.retroshade().unwrap();
+    retroshades.retroshade_packed().unwrap();
+    assert_eq!(retroshades.encodings.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        serde_json::to_value(&first.retroshades).unwrap(),
+        serde_json::to_value(&second.retroshades).unwrap()
+    );
+
+    // building again changes the state, so it's encoded again.
+    retroshades
+        .build_from_envelope_and_meta(
+            &fixture.snapshot,
+            fixture.envelope,
+            fixture.meta,
+            HashMap::new(),
+        )
+        .unwrap();
+    retroshades.retroshade().unwrap();
+    assert_eq!(retroshades.encodings.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn packing_an_execution_matches_retroshade_packed() {
+    let retroshades = mainnet_execution();
+
+    let result = retroshades.retroshade().unwrap();
+    let raw_count = result.retroshades.len();
+    let packed = retroshades.pack_result(result).unwrap();
+    assert_eq!(packed.retroshades.len(), raw_count);
+
+    let executed_and_packed = retroshades.retroshade_packed().unwrap();
+    assert_eq!(packed.call_succeeded, executed_and_packed.call_succeeded);
+    assert_eq!(packed.retroshades, executed_and_packed.retroshades);
+    assert_eq!(packed.invoke_result, executed_and_packed.invoke_result);
+}
+
+#[test]
+fn ledger_changes_can_be_left_out() {
+    let mut retroshades = mainnet_execution();
+
+    let with_changes = retroshades.retroshade().unwrap();
+    assert!(!with_changes.ledger_changes.is_empty());
+
+    retroshades.set_ledger_changes(false);
+    let without_changes = retroshades.retroshade().unwrap();
+    assert!(without_changes.ledger_changes.is_empty());
+    assert_eq!(without_changes.invoke_result, with_changes.invoke_result);
+    assert_eq!(
+        serde_json::to_value(&without_changes.retroshades).unwrap(),
+        serde_json::to_value(&with_changes.retroshades).unwrap()
+    );
+}
+
+#[test]
+fn mainnet_replays_as_expected() {
+    let fixture = Fixture::load("fixtures/mainnet_first_retroshade.json");
+    assert_eq!(fixture.ledger_info.protocol_version, 25);
+
+    let result = fixture.assert_replay();
+    assert!(result.call_succeeded);
+}
+
+#[test]
+fn dump_with_ledger_info_roundtrip() {
+    let (snapshot, envelope, meta) = fixture::load(MAINNET_FIXTURE).unwrap();
+    let ledger_info = LedgerInfo {
+        protocol_version: 23,
+        sequence_number: 57_000_000,
+        timestamp: 1_750_000_000,
+        network_id: [7; 32],
+        base_reserve: 5_000_000,
+        min_temp_entry_ttl: 17_280,
+        min_persistent_entry_ttl: 2_073_600,
+        max_entry_ttl: 3_110_400,
+    };
+
+    let path = std::env::temp_dir().join("retroshade_fixture_ledger_info.json");
+    fixture::dump_with_ledger_info(&path, &snapshot.entries(), &envelope, &meta, &ledger_info)
+        .unwrap();
+    let reloaded = fixture::load_ledger_info(&path).unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(reloaded.protocol_version, ledger_info.protocol_version);
+    assert_eq!(reloaded.sequence_number, ledger_info.sequence_number);
+    assert_eq!(reloaded.timestamp, ledger_info.timestamp);
+    assert_eq!(reloaded.network_id, ledger_info.network_id);
+    assert_eq!(reloaded.max_entry_ttl, ledger_info.max_entry_ttl);
+}
+
+/// Replays `RETROSHADE_RPC_TX` live, then offline from the fixture it's
+/// recorded into.
+#[cfg(feature = "http")]
+#[test]
+fn rpc_case_records_replayable_fixture() {
+    let Some(rpc_url) = rpc_url() else {
+        return;
+    };
+    let tx_hash = std::env::var("RETROSHADE_RPC_TX")
+        .ok()
+        .and_then(|hash| hex::decode(hash).ok())
+        .and_then(|hash| hash.try_into().ok())
+        .map(Hash)
+        .expect("RETROSHADE_RPC_TX should be the hex hash of a transaction");
+
+    let case = RpcCase::fetch(&rpc_url, &tx_hash);
+    let live = case.replay(HashMap::new());
+
+    let path = std::env::temp_dir().join("retroshade_rpc_case.json");
+    case.record_fixture(&path);
+    let offline = Fixture::load(&path).replay();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(offline.call_succeeded, live.call_succeeded);
+    assert_eq!(
+        serde_json::to_value(&offline.retroshades).unwrap(),
+        serde_json::to_value(&live.retroshades).unwrap()
+    );
+}
@@ -0,0 +1,67 @@
+use std::fs::File;
+
+use crate::{
+    fixture,
+    ledger_meta::{ledger_header, read_frame, transactions, write_frame, LedgerMetaReader},
+    network::Network,
+    RetroshadeError,
+};
+
+const LEDGERS_FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/ledger_meta/ledgers_100_101.xdr"
+);
+
+const MAINNET_FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/mainnet_first_retroshade.json"
+);
+
+#[test]
+fn reads_fixture_ledgers() {
+    let ledgers: Vec<_> = LedgerMetaReader::new(File::open(LEDGERS_FIXTURE).unwrap())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let sequences: Vec<u32> = ledgers
+        .iter()
+        .map(|ledger| ledger_header(ledger).ledger_seq)
+        .collect();
+    assert_eq!(sequences, vec![100, 101]);
+
+    // note: the fixture wraps the mainnet fixture's transaction.
+    let (_, envelope, meta) = fixture::load(MAINNET_FIXTURE).unwrap();
+    let txs = transactions(&ledgers[0], Network::Pubnet.network_id()).unwrap();
+    assert_eq!(txs, vec![(envelope, meta)]);
+    assert!(transactions(&ledgers[1], Network::Pubnet.network_id())
+        .unwrap()
+        .is_empty());
+
+    // note: on another network the envelope hashes differently.
+    assert!(matches!(
+        transactions(&ledgers[0], Network::Testnet.network_id()),
+        Err(RetroshadeError::LedgerMetaFile(_))
+    ));
+}
+
+#[test]
+fn frames_roundtrip() {
+    let mut stream = Vec::new();
+    write_frame(&mut stream, b"first").unwrap();
+    write_frame(&mut stream, b"").unwrap();
+
+    // note: a record split in two fragments, only the last one is marked.
+    stream.extend(3u32.to_be_bytes());
+    stream.extend(b"sec");
+    stream.extend((0x8000_0000u32 | 3).to_be_bytes());
+    stream.extend(b"ond");
+
+    let mut reader = stream.as_slice();
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"first");
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"");
+    assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"second");
+    assert!(read_frame(&mut reader).unwrap().is_none());
+
+    let truncated = [0x80, 0, 0, 10, 1, 2];
+    assert!(read_frame(&mut truncated.as_slice()).is_err());
+}
@@ -0,0 +1,44 @@
+use std::rc::Rc;
+
+use crate::ledger_snapshot::LedgerSnapshotFileSource;
+use soroban_env_host::{
+    storage::SnapshotSource,
+    xdr::{Hash, LedgerKey, LedgerKeyContractCode},
+};
+
+#[test]
+fn open_cli_snapshot() {
+    let snapshot = LedgerSnapshotFileSource::open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/ledger_snapshot.json"
+    ))
+    .unwrap();
+
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot.sequence_number(), 1470900);
+    assert_eq!(snapshot.timestamp(), 1718000000);
+
+    let ledger_info = snapshot.ledger_info();
+    assert_eq!(ledger_info.protocol_version, 25);
+    assert_eq!(ledger_info.max_entry_ttl, 6312000);
+
+    let code_hash: [u8; 32] =
+        hex::decode("5bf30f4ebf6e399a0f6cf8c7d134f2e6741ab78455aa6bcb20e3dc01261ea5e3")
+            .unwrap()
+            .try_into()
+            .unwrap();
+    let (_, live_until) = snapshot
+        .get(&Rc::new(LedgerKey::ContractCode(LedgerKeyContractCode {
+            hash: Hash(code_hash),
+        })))
+        .unwrap()
+        .unwrap();
+    assert_eq!(live_until, Some(3544484));
+
+    assert!(snapshot
+        .get(&Rc::new(LedgerKey::ContractCode(LedgerKeyContractCode {
+            hash: Hash([0; 32]),
+        })))
+        .unwrap()
+        .is_none());
+}
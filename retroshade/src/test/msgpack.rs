@@ -0,0 +1,75 @@
+use crate::{msgpack::FORMAT_VERSION, RetroshadeError, RetroshadeExecutionResultPretty};
+use soroban_env_host::xdr::{
+    ContractEvent, ContractEventBody, ContractEventType, ContractEventV0, DiagnosticEvent,
+    ExtensionPoint, MuxedAccount, ScVal, ScVec, Uint256,
+};
+
+use super::packing::{built_execution, execution_result, export, symbol, test_context};
+
+fn packed_result() -> RetroshadeExecutionResultPretty {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_tx_context(test_context());
+
+    let mut result = execution_result(vec![
+        export(
+            symbol("test"),
+            vec![
+                (
+                    symbol("ids"),
+                    ScVal::Vec(Some(ScVec(
+                        vec![ScVal::U32(1), ScVal::U32(2)].try_into().unwrap(),
+                    ))),
+                ),
+                (symbol("memo"), ScVal::Void),
+            ],
+        ),
+        export(ScVal::U32(7), vec![]),
+    ]);
+    result.diagnostic = vec![DiagnosticEvent {
+        in_successful_contract_call: true,
+        event: ContractEvent {
+            ext: ExtensionPoint::V0,
+            contract_id: None,
+            type_: ContractEventType::Diagnostic,
+            body: ContractEventBody::V0(ContractEventV0 {
+                topics: vec![symbol("log")].try_into().unwrap(),
+                data: ScVal::U32(1),
+            }),
+        },
+    }];
+
+    retroshades.pack_result(result).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let result = packed_result();
+    let encoded = result.to_msgpack().unwrap();
+    assert_eq!(encoded[0], FORMAT_VERSION);
+
+    let decoded = RetroshadeExecutionResultPretty::from_msgpack(&encoded).unwrap();
+    assert_eq!(decoded.retroshades, result.retroshades);
+    for (decoded, export) in decoded.retroshades.iter().zip(&result.retroshades) {
+        assert!(!export.event_object_xdr.is_empty());
+        assert_eq!(
+            decoded.idempotency_key(&decoded.context.tx_hash),
+            export.idempotency_key(&export.context.tx_hash)
+        );
+    }
+    assert_eq!(decoded.diagnostic, result.diagnostic);
+    assert_eq!(decoded.invoke_result, result.invoke_result);
+    assert_eq!(decoded.timings, result.timings);
+
+    let (idx, error) = &decoded.conversion_errors[0];
+    assert_eq!(*idx, 1);
+    assert!(matches!(error, RetroshadeError::Decoded(_)));
+    assert_eq!(error.to_string(), result.conversion_errors[0].1.to_string());
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut encoded = packed_result().to_msgpack().unwrap();
+    encoded[0] = FORMAT_VERSION + 1;
+
+    assert!(RetroshadeExecutionResultPretty::from_msgpack(&encoded).is_err());
+}
@@ -0,0 +1,135 @@
+use crate::{
+    naming::{TableName, TableNaming},
+    truncate_with_hash, RetroshadeExportPretty, TxContext,
+};
+use soroban_env_host::xdr::{Hash, MuxedAccount, ScVal, Uint256};
+
+use super::packing::{built_execution, execution_result, export, symbol};
+
+fn packed_export(contract_byte: u8, target: &str) -> RetroshadeExportPretty {
+    RetroshadeExportPretty {
+        contract_id: stellar_strkey::Contract([contract_byte; 32])
+            .to_string()
+            .into(),
+        target: target.into(),
+        event: vec![],
+        context: TxContext {
+            tx_hash: Hash([0; 32]),
+            ledger_seq: 1000,
+            closed_at: 200,
+            op_index: 0,
+        },
+        source_account: stellar_strkey::ed25519::PublicKey([0; 32]).to_string(),
+        emit_index: 0,
+        event_object_xdr: vec![],
+    }
+}
+
+fn custom(export: &RetroshadeExportPretty) -> String {
+    format!("zephyr_{}", export.target)
+}
+
+#[test]
+fn strategies() {
+    let export = packed_export(0, "swaps");
+
+    assert_eq!(
+        TableNaming::TargetOnly.table_name(&export).to_string(),
+        "swaps"
+    );
+    assert_eq!(
+        TableNaming::ContractPrefixed
+            .table_name(&export)
+            .to_string(),
+        "caaaaaaa_swaps"
+    );
+    assert_eq!(
+        TableNaming::SchemaPerContract.table_name(&export),
+        TableName {
+            schema: Some(export.contract_id.to_lowercase()),
+            table: "swaps".to_string(),
+        }
+    );
+    assert_eq!(
+        TableNaming::Custom(custom).table_name(&export).to_string(),
+        "zephyr_swaps"
+    );
+}
+
+#[test]
+fn same_target_different_contracts() {
+    let first = TableNaming::ContractPrefixed.table_name(&packed_export(0, "swaps"));
+    let second = TableNaming::ContractPrefixed.table_name(&packed_export(1, "swaps"));
+
+    assert_ne!(first, second);
+}
+
+#[test]
+fn long_identifiers_are_truncated() {
+    fn long(export: &RetroshadeExportPretty) -> String {
+        export.target.repeat(20)
+    }
+
+    let name = TableNaming::Custom(long).table_name(&packed_export(0, "swaps"));
+
+    assert_eq!(name.table.len(), 63);
+    assert_eq!(
+        name,
+        TableNaming::Custom(long).table_name(&packed_export(0, "swaps"))
+    );
+}
+
+#[test]
+fn names_that_fit_are_kept() {
+    assert_eq!(truncate_with_hash("swaps", 5), "swaps");
+    assert_eq!(truncate_with_hash("swaps", 63), "swaps");
+    assert_eq!(truncate_with_hash("", 0), "");
+}
+
+#[test]
+fn limits_shorter_than_the_suffix() {
+    // note: the first characters of the name's SHA-256, `ee0b46be...`.
+    assert_eq!(truncate_with_hash("swaps", 4), "ee0b");
+    assert_eq!(truncate_with_hash("swaps", 0), "");
+}
+
+#[test]
+fn limits_close_to_the_suffix() {
+    assert_eq!(truncate_with_hash("swaps_v2", 7), "5668fbb");
+
+    let truncated = truncate_with_hash("deposits_v2", 10);
+    assert_eq!(truncated.len(), 10);
+    assert!(truncated.starts_with("d_"));
+}
+
+#[test]
+fn non_ascii_names_are_cut_at_a_char_boundary() {
+    // note: two bytes per character, a 12 byte limit keeps 3 bytes.
+    let truncated = truncate_with_hash("ééééééé", 12);
+
+    assert_eq!(truncated.len(), 11);
+    assert!(truncated.starts_with("é_"));
+}
+
+#[test]
+fn custom_target_names_the_table() {
+    // note: what a `SwapEvent` given its target with
+    // `retroshade_target!(SwapEvent, "swaps_v2")` emits, the struct's name
+    // isn't part of the event.
+    let packed = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])))
+        .pack_result(execution_result(vec![export(
+            symbol("swaps_v2"),
+            vec![(symbol("amount"), ScVal::U32(2))],
+        )]))
+        .unwrap();
+    let export = &packed.retroshades[0];
+
+    assert_eq!(
+        TableNaming::TargetOnly.table_name(export).to_string(),
+        "swaps_v2"
+    );
+    assert_eq!(
+        TableNaming::ContractPrefixed.table_name(export).to_string(),
+        "caaaaaaa_swaps_v2"
+    );
+}
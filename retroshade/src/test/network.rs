@@ -0,0 +1,27 @@
+use crate::network::{network_id, Network, TESTNET_PASSPHRASE};
+
+#[test]
+fn network_ids() {
+    assert_eq!(
+        hex::encode(Network::Pubnet.network_id()),
+        "7ac33997544e3175d266bd022439b22cdb16508c01163f26e5cb2a3e1045a979"
+    );
+    assert_eq!(
+        hex::encode(network_id(TESTNET_PASSPHRASE)),
+        "cee0302d59844d32bdca915c8203dd44b33fbb7edc19051ea37abedf28ecd472"
+    );
+
+    for network in Network::ALL {
+        assert_eq!(
+            Network::from_passphrase(network.passphrase()),
+            Some(network)
+        );
+        assert_eq!(
+            Network::from_network_id(&network.network_id()),
+            Some(network)
+        );
+        assert_eq!(network.to_string().parse::<Network>(), Ok(network));
+    }
+    assert_eq!(Network::from_passphrase("custom"), None);
+    assert!("mainnet".parse::<Network>().is_err());
+}
@@ -0,0 +1,90 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::snapshot::{LabeledSnapshot, MemorySnapshot, OverlaySnapshot, SnapshotSourceExt};
+use soroban_env_host::{
+    storage::SnapshotSource,
+    xdr::{
+        ContractCodeEntry, Hash, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey,
+        LedgerKeyContractCode,
+    },
+};
+
+fn code_entry(byte: u8, last_modified_ledger_seq: u32) -> LedgerEntry {
+    LedgerEntry {
+        last_modified_ledger_seq,
+        ext: LedgerEntryExt::V0,
+        data: LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: soroban_env_host::xdr::ContractCodeEntryExt::V0,
+            hash: Hash([byte; 32]),
+            code: vec![].try_into().unwrap(),
+        }),
+    }
+}
+
+fn code_key(byte: u8) -> LedgerKey {
+    LedgerKey::ContractCode(LedgerKeyContractCode {
+        hash: Hash([byte; 32]),
+    })
+}
+
+#[test]
+fn overrides_and_forced_removals() {
+    let inner = MemorySnapshot::from_entries(vec![
+        (code_entry(0, 1), Some(100)),
+        (code_entry(1, 1), Some(100)),
+    ])
+    .unwrap();
+    let mut overlay = OverlaySnapshot::new(Rc::new(inner));
+
+    overlay.set_override(code_key(0), (Rc::new(code_entry(0, 2)), Some(200)));
+    overlay.force_remove(code_key(1));
+
+    let (entry, live_until) = overlay.get(&Rc::new(code_key(0))).unwrap().unwrap();
+    assert_eq!(entry.last_modified_ledger_seq, 2);
+    assert_eq!(live_until, Some(200));
+    assert!(overlay.get(&Rc::new(code_key(1))).unwrap().is_none());
+
+    overlay.clear();
+
+    let (entry, _) = overlay.get(&Rc::new(code_key(0))).unwrap().unwrap();
+    assert_eq!(entry.last_modified_ledger_seq, 1);
+    assert!(overlay.get(&Rc::new(code_key(1))).unwrap().is_some());
+}
+
+#[test]
+fn inner_labels_are_forwarded() {
+    let inner = MemorySnapshot::from_entries(vec![
+        (code_entry(0, 1), Some(100)),
+        (code_entry(1, 1), Some(100)),
+    ])
+    .unwrap();
+    let mut overlay =
+        OverlaySnapshot::new(Rc::new(LabeledSnapshot::new("fixture", Box::new(inner))));
+    overlay.set_override(code_key(0), (Rc::new(code_entry(0, 2)), Some(200)));
+    overlay.force_remove(code_key(1));
+
+    let (_, label) = overlay.get_labeled(&Rc::new(code_key(0))).unwrap().unwrap();
+    assert_eq!(label, "overlay");
+    assert!(overlay
+        .get_labeled(&Rc::new(code_key(1)))
+        .unwrap()
+        .is_none());
+
+    overlay.clear();
+    let (_, label) = overlay.get_labeled(&Rc::new(code_key(0))).unwrap().unwrap();
+    assert_eq!(label, "fixture");
+}
+
+#[test]
+fn memory_snapshot_from_map() {
+    let snapshot = MemorySnapshot::from_map(HashMap::from([
+        (code_key(0), (code_entry(0, 1), Some(100))),
+        (code_key(1), (code_entry(1, 2), None)),
+    ]));
+
+    let (entry, live_until) = snapshot.get(&Rc::new(code_key(1))).unwrap().unwrap();
+    assert_eq!(entry.last_modified_ledger_seq, 2);
+    assert_eq!(live_until, None);
+    assert!(snapshot.get(&Rc::new(code_key(2))).unwrap().is_none());
+    assert_eq!(snapshot.entries().len(), 2);
+}
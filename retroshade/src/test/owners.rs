@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{Hash, MuxedAccount, ScVal, Uint256};
+
+use crate::{
+    owners::{OwnedExport, Owners, UnregisteredContracts},
+    RetroshadeError, RetroshadeExportPretty,
+};
+
+use super::packing::{built_execution, execution_result, export, symbol};
+
+/// Tenant ids of the caller, anything cloneable works.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Tenant(u64);
+
+/// Exports of the contracts `[1; 32]`, `[2; 32]` and `[1; 32]` again.
+fn exports() -> Vec<RetroshadeExportPretty> {
+    let retroshades = [1, 2, 1]
+        .into_iter()
+        .map(|contract| {
+            let mut retroshade = export(symbol("swaps"), vec![(symbol("amount"), ScVal::U32(2))]);
+            retroshade.contract_id = Hash([contract; 32]);
+            retroshade
+        })
+        .collect();
+
+    built_execution(MuxedAccount::Ed25519(Uint256([0; 32])))
+        .pack_result(execution_result(retroshades))
+        .unwrap()
+        .retroshades
+}
+
+fn owners(unregistered: UnregisteredContracts) -> Owners<Tenant> {
+    let mut owners = Owners::new(HashMap::from([(Hash([1; 32]), Tenant(7))]));
+    owners.set_unregistered(unregistered);
+    owners
+}
+
+fn tenants(tagged: &[OwnedExport<Tenant>]) -> Vec<Option<Tenant>> {
+    tagged.iter().map(|owned| owned.owner.clone()).collect()
+}
+
+#[test]
+fn registered_contracts_are_tagged() {
+    let owners = owners(UnregisteredContracts::Keep);
+
+    assert_eq!(
+        owners.owner_of(&stellar_strkey::Contract([1; 32]).to_string()),
+        Some(&Tenant(7))
+    );
+    assert_eq!(
+        owners.owner_of(&stellar_strkey::Contract([2; 32]).to_string()),
+        None
+    );
+}
+
+#[test]
+fn hex_contract_ids_are_tagged() {
+    let owners = owners(UnregisteredContracts::Keep);
+
+    assert_eq!(owners.owner_of(&hex::encode([1; 32])), Some(&Tenant(7)));
+    assert_eq!(owners.owner_of(&hex::encode([2; 32])), None);
+}
+
+#[test]
+fn unregistered_are_kept_untagged() {
+    let exports = exports();
+    let tagged = owners(UnregisteredContracts::Keep)
+        .tag(exports.clone())
+        .unwrap();
+
+    assert_eq!(
+        tenants(&tagged),
+        vec![Some(Tenant(7)), None, Some(Tenant(7))]
+    );
+    let tagged: Vec<RetroshadeExportPretty> =
+        tagged.into_iter().map(|owned| owned.export).collect();
+    assert_eq!(tagged, exports);
+}
+
+#[test]
+fn unregistered_are_dropped() {
+    let exports = exports();
+    let tagged = owners(UnregisteredContracts::Drop)
+        .tag(exports.clone())
+        .unwrap();
+
+    assert_eq!(tenants(&tagged), vec![Some(Tenant(7)), Some(Tenant(7))]);
+    assert_eq!(tagged[1].export, exports[2]);
+}
+
+#[test]
+fn unregistered_are_errors() {
+    let error = owners(UnregisteredContracts::Error)
+        .tag(exports())
+        .unwrap_err();
+
+    assert!(matches!(
+        error,
+        RetroshadeError::UnregisteredContract(ref contract_id)
+            if *contract_id == stellar_strkey::Contract([2; 32]).to_string()
+    ));
+}
+
+#[test]
+fn default_keeps_unregistered() {
+    let owners = Owners::new(HashMap::<Hash, Tenant>::new());
+
+    let tagged = owners.tag(exports()).unwrap();
+    assert_eq!(tenants(&tagged), vec![None, None, None]);
+}
@@ -0,0 +1,519 @@
+//! Packing tests over hand-constructed retroshades, no contract execution involved.
+
+use std::collections::HashMap;
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    intern::Interner,
+    testutils::{ledger_info_protocol, Fixture},
+    LongTargetPolicy, MalformedReason, RetroshadeError, RetroshadeExecutionResult,
+    RetroshadeExportPretty, RetroshadesExecution, TxContext,
+};
+use soroban_env_host::{
+    xdr::{
+        Hash, Int128Parts, MuxedAccount, MuxedAccountMed25519, ScAddress, ScMap, ScMapEntry,
+        ScSymbol, ScVal, ScValType, Uint256,
+    },
+    zephyr::RetroshadeExport,
+};
+
+use super::fixture::MAINNET_FIXTURE;
+
+pub fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+pub fn export(target: ScVal, fields: Vec<(ScVal, ScVal)>) -> RetroshadeExport {
+    RetroshadeExport {
+        contract_id: Hash([0; 32]),
+        target,
+        event_object: ScVal::Map(Some(ScMap(
+            fields
+                .into_iter()
+                .map(|(key, val)| ScMapEntry { key, val })
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap(),
+        ))),
+    }
+}
+
+/// An execution built from the mainnet fixture with the given transaction source.
+pub fn built_execution(source_account: MuxedAccount) -> RetroshadesExecution {
+    let Fixture {
+        snapshot,
+        mut envelope,
+        meta,
+        ..
+    } = Fixture::load(MAINNET_FIXTURE);
+    envelope.tx.source_account = source_account;
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+
+    retroshades
+}
+
+pub fn execution_result(retroshades: Vec<RetroshadeExport>) -> RetroshadeExecutionResult {
+    RetroshadeExecutionResult {
+        retroshades,
+        diagnostic: vec![],
+        invoke_result: Ok(ScVal::Void),
+        ledger_changes: vec![],
+        timings: Default::default(),
+        state: Default::default(),
+        truncated: false,
+    }
+}
+
+pub fn test_context() -> TxContext {
+    TxContext {
+        tx_hash: Hash([1; 32]),
+        ledger_seq: 1000,
+        closed_at: 200,
+        op_index: 0,
+    }
+}
+
+#[test]
+fn context_is_attached() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_tx_context(test_context());
+    retroshades.set_context_columns(true);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("test"),
+            vec![(symbol("amount"), ScVal::U32(2))],
+        )]))
+        .unwrap();
+
+    let export = &packed.retroshades[0];
+    assert_eq!(export.context, test_context());
+
+    let columns: Vec<&str> = export
+        .event
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    assert_eq!(
+        columns,
+        vec!["amount", "tx_hash", "ledger_seq", "closed_at", "op_index"]
+    );
+}
+
+#[test]
+fn context_column_collision() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_context_columns(true);
+
+    let packed = retroshades.pack_result(execution_result(vec![export(
+        symbol("test"),
+        vec![(symbol("ledger_seq"), ScVal::U32(2))],
+    )]));
+
+    let packed = packed.unwrap();
+    assert!(packed.retroshades.is_empty());
+    assert!(matches!(
+        &packed.conversion_errors[..],
+        [(0, RetroshadeError::ReservedColumn(name))] if name == "ledger_seq"
+    ));
+}
+
+#[test]
+fn source_account_is_attached() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([7; 32])));
+    retroshades.set_source_account_column(true);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(symbol("test"), vec![])]))
+        .unwrap();
+
+    let expected = stellar_strkey::ed25519::PublicKey([7; 32]).to_string();
+    let export = &packed.retroshades[0];
+    assert_eq!(export.source_account, expected);
+    assert_eq!(export.event[0].name, "source_account");
+    assert_eq!(export.event[0].value.kind, TypeKind::Text(expected.clone()));
+}
+
+#[test]
+fn muxed_source_account() {
+    let retroshades = built_execution(MuxedAccount::MuxedEd25519(MuxedAccountMed25519 {
+        id: 5,
+        ed25519: Uint256([7; 32]),
+    }));
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(symbol("test"), vec![])]))
+        .unwrap();
+
+    assert_eq!(
+        packed.retroshades[0].source_account,
+        stellar_strkey::ed25519::MuxedAccount {
+            ed25519: [7; 32],
+            id: 5,
+        }
+        .to_string()
+    );
+}
+
+#[test]
+fn packed_json_wire_format() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_tx_context(test_context());
+
+    let mut packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("test"),
+            vec![
+                (symbol("amount"), ScVal::U32(2)),
+                (symbol("flag"), ScVal::Bool(true)),
+            ],
+        )]))
+        .unwrap();
+    packed.timings = Default::default();
+
+    assert_eq!(
+        serde_json::to_string(&packed).unwrap(),
+        concat!(
+            r#"{"call_succeeded":true,"retroshades":[{"contract_id":"CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4","#,
+            r#""target":"test","event":[{"name":"amount","value":{"dbtype":"numeric","kind":{"numeric":"2"}}},"#,
+            r#"{"name":"flag","value":{"dbtype":"bool","kind":{"boolean":true}}}],"#,
+            r#""context":{"tx_hash":"0101010101010101010101010101010101010101010101010101010101010101","ledger_seq":1000,"closed_at":200,"op_index":0},"#,
+            r#""source_account":"GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF","emit_index":0}],"#,
+            r#""conversion_errors":[],"duplicates_removed":0,"#,
+            r#""diagnostic":[],"invoke_result":{"Ok":"void"},"ledger_changes":[],"#,
+            r#""timings":{"build":{"secs":0,"nanos":0},"reset":{"secs":0,"nanos":0},"execute":{"secs":0,"nanos":0},"convert":{"secs":0,"nanos":0}},"#,
+            r#""state":{"state_changed":false,"entries_reset":0,"binaries_replaced":false,"binaries_replaced_count":0}}"#
+        )
+    );
+}
+
+fn malformed_reason(retroshade: RetroshadeExport) -> (MalformedReason, String) {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_fail_fast(true);
+
+    match retroshades.pack_result(execution_result(vec![retroshade])) {
+        Err(RetroshadeError::MalformedRetroshadeEvent {
+            contract_id,
+            reason,
+            value_debug,
+        }) => {
+            assert_eq!(
+                contract_id,
+                "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4"
+            );
+            (reason, value_debug)
+        }
+        other => panic!("expected a malformed event, got {:?}", other),
+    }
+}
+
+#[test]
+fn malformed_target() {
+    let (reason, value_debug) = malformed_reason(export(ScVal::U32(7), vec![]));
+
+    assert_eq!(
+        reason,
+        MalformedReason::TargetNotSymbol {
+            found: ScValType::U32
+        }
+    );
+    assert_eq!(value_debug, format!("{:?}", ScVal::U32(7)));
+}
+
+#[test]
+fn malformed_event_object() {
+    let mut retroshade = export(symbol("test"), vec![]);
+    retroshade.event_object = ScVal::Bool(false);
+    let (reason, value_debug) = malformed_reason(retroshade);
+
+    assert_eq!(reason, MalformedReason::EventNotMap);
+    assert_eq!(value_debug, format!("{:?}", ScVal::Bool(false)));
+}
+
+#[test]
+fn malformed_field_name() {
+    let (reason, value_debug) =
+        malformed_reason(export(symbol("test"), vec![(ScVal::I32(0), ScVal::Void)]));
+
+    assert_eq!(reason, MalformedReason::FieldNameNotSymbol);
+    assert_eq!(value_debug, format!("{:?}", ScVal::I32(0)));
+}
+
+#[test]
+fn malformed_export_does_not_drop_valid_ones() {
+    let retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+
+    // same shape as the export of the storage example.
+    let valid = export(
+        symbol("test"),
+        vec![
+            (symbol("amount"), ScVal::I128(Int128Parts { hi: 0, lo: 2 })),
+            (
+                symbol("test"),
+                ScVal::Address(ScAddress::Contract(Hash([0; 32]).into())),
+            ),
+        ],
+    );
+    let malformed = export(ScVal::U32(7), vec![]);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![
+            malformed.clone(),
+            valid.clone(),
+            malformed,
+        ]))
+        .unwrap();
+
+    assert_eq!(packed.retroshades.len(), 1);
+    assert_eq!(packed.retroshades[0].event[0].name, "amount");
+
+    let failed: Vec<usize> = packed
+        .conversion_errors
+        .iter()
+        .map(|(idx, _)| *idx)
+        .collect();
+    assert_eq!(failed, vec![0, 2]);
+}
+
+#[test]
+fn fail_fast_restores_batch_failure() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_fail_fast(true);
+
+    let packed = retroshades.pack_result(execution_result(vec![
+        export(symbol("test"), vec![]),
+        export(ScVal::U32(7), vec![]),
+    ]));
+
+    assert!(matches!(
+        packed,
+        Err(RetroshadeError::MalformedRetroshadeEvent { .. })
+    ));
+}
+
+#[test]
+fn names_are_shared_across_executions() {
+    let interner = Interner::new();
+    let swap = export(symbol("swap"), vec![(symbol("amount"), ScVal::U32(1))]);
+
+    let packed: Vec<RetroshadeExportPretty> = (0..2)
+        .flat_map(|_| {
+            let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+            retroshades.set_interner(interner.clone());
+            retroshades
+                .pack_result(execution_result(vec![swap.clone(), swap.clone()]))
+                .unwrap()
+                .retroshades
+        })
+        .collect();
+
+    assert_eq!(packed.len(), 4);
+    for export in &packed[1..] {
+        assert_eq!(export.target, "swap");
+        assert_eq!(export.target.as_ptr(), packed[0].target.as_ptr());
+        assert_eq!(export.contract_id.as_ptr(), packed[0].contract_id.as_ptr());
+        assert_eq!(
+            export.event[0].name.as_ptr(),
+            packed[0].event[0].name.as_ptr()
+        );
+    }
+    // the contract id, the target and the column name.
+    assert_eq!(interner.len(), 3);
+}
+
+#[test]
+fn dedup_drops_identical_exports() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_dedup(true);
+
+    let swap = export(symbol("swap"), vec![(symbol("amount"), ScVal::U32(1))]);
+    let other = export(symbol("swap"), vec![(symbol("amount"), ScVal::U32(2))]);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![
+            swap.clone(),
+            other,
+            swap.clone(),
+            swap,
+        ]))
+        .unwrap();
+
+    assert_eq!(packed.duplicates_removed, 2);
+    let emit_indexes: Vec<u32> = packed.retroshades.iter().map(|r| r.emit_index).collect();
+    assert_eq!(emit_indexes, vec![0, 1]);
+}
+
+#[test]
+fn dedup_keeps_exports_with_emit_index_column() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_dedup(true);
+    retroshades.set_emit_index_column(true);
+
+    let swap = export(symbol("swap"), vec![(symbol("amount"), ScVal::U32(1))]);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![swap.clone(), swap]))
+        .unwrap();
+
+    assert_eq!(packed.duplicates_removed, 0);
+    assert_eq!(packed.retroshades.len(), 2);
+    assert_eq!(packed.retroshades[1].event[1].name, "emit_index");
+    assert_eq!(
+        packed.retroshades[1].event[1].value,
+        FromScVal::from_scval(ScVal::U32(1), &mut 0)
+    );
+}
+
+#[test]
+fn idempotency_key_column() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_tx_context(test_context());
+    retroshades.set_idempotency_key_column(true);
+
+    let packed = retroshades
+        .retroshade_prepare_for_db(execution_result(vec![export(
+            symbol("swap"),
+            vec![(symbol("amount"), ScVal::U32(1))],
+        )]))
+        .unwrap();
+    let export = &packed.retroshades[0];
+
+    let column = export.event.last().unwrap();
+    assert_eq!(column.name, "idempotency_key");
+    assert_eq!(
+        column.value.kind,
+        TypeKind::Text(hex::encode(export.idempotency_key(&test_context().tx_hash)))
+    );
+}
+
+#[test]
+fn idempotency_key_changes_with_any_component() {
+    let retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("swap"),
+            vec![(symbol("amount"), ScVal::U32(1))],
+        )]))
+        .unwrap();
+    let base = packed.retroshades[0].clone();
+    let tx_hash = Hash([1; 32]);
+    let key = base.idempotency_key(&tx_hash);
+
+    assert_ne!(key, base.idempotency_key(&Hash([2; 32])));
+
+    let mut changed = base.clone();
+    changed.contract_id = stellar_strkey::Contract([1; 32]).to_string().into();
+    assert_ne!(key, changed.idempotency_key(&tx_hash));
+
+    let mut changed = base.clone();
+    changed.target = "swaps".into();
+    assert_ne!(key, changed.idempotency_key(&tx_hash));
+
+    let mut changed = base.clone();
+    changed.emit_index = 1;
+    assert_ne!(key, changed.idempotency_key(&tx_hash));
+
+    let mut changed = base;
+    changed.event_object_xdr.push(0);
+    assert_ne!(key, changed.idempotency_key(&tx_hash));
+}
+
+fn long_target(len: usize) -> ScVal {
+    ScVal::Symbol(ScSymbol("t".repeat(len).try_into().unwrap()))
+}
+
+#[test]
+fn long_target_errors_by_default() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_max_target_len(20);
+
+    let packed = retroshades.pack_result(execution_result(vec![export(long_target(30), vec![])]));
+
+    assert_eq!(
+        packed.unwrap().conversion_errors[0].1.to_string(),
+        format!(
+            "malformed retroshade event from CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4: target is 30 characters long, the limit is 20, got {:?}",
+            long_target(30)
+        )
+    );
+}
+
+#[test]
+fn long_target_is_truncated_with_hash() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_max_target_len(20);
+    retroshades.set_long_target_policy(LongTargetPolicy::TruncateWithHash);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![
+            export(long_target(30), vec![]),
+            export(long_target(31), vec![]),
+            export(long_target(20), vec![]),
+        ]))
+        .unwrap();
+
+    let targets: Vec<&str> = packed
+        .retroshades
+        .iter()
+        .map(|export| export.target.as_str())
+        .collect();
+    assert_eq!(targets[0].len(), 20);
+    assert!(targets[0].starts_with(&format!("{}_", "t".repeat(11))));
+    assert_eq!(targets[1].len(), 20);
+    assert_ne!(targets[0], targets[1]);
+    assert_eq!(targets[2], "t".repeat(20));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_packing_matches_sequential() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_emit_index_column(true);
+
+    // every 100th export has a non-symbol target, to also check the order of
+    // conversion errors.
+    let exports: Vec<RetroshadeExport> = (0..10_000u32)
+        .map(|idx| {
+            let target = if idx % 100 == 99 {
+                ScVal::U32(idx)
+            } else {
+                symbol("settlement")
+            };
+            export(target, vec![(symbol("amount"), ScVal::U32(idx))])
+        })
+        .collect();
+
+    let sequential = retroshades
+        .pack_result(execution_result(exports.clone()))
+        .unwrap();
+    let parallel = retroshades
+        .pack_result_parallel(execution_result(exports))
+        .unwrap();
+
+    assert_eq!(parallel.retroshades.len(), 9_900);
+    assert_eq!(parallel.retroshades, sequential.retroshades);
+    for (idx, export) in parallel.retroshades.iter().take(150).enumerate() {
+        let expected = if idx < 99 { idx } else { idx + 1 };
+        assert_eq!(
+            export.event[0].value,
+            FromScVal::from_scval(ScVal::U32(expected as u32), &mut 0)
+        );
+    }
+
+    let error_indices = |result: &crate::RetroshadeExecutionResultPretty| {
+        result
+            .conversion_errors
+            .iter()
+            .map(|(idx, _)| *idx)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        error_indices(&parallel),
+        (0..100).map(|n| n * 100 + 99).collect::<Vec<_>>()
+    );
+    assert_eq!(error_indices(&parallel), error_indices(&sequential));
+}
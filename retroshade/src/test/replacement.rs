@@ -0,0 +1,228 @@
+//! Replacements restricted to an allowlist of contracts, in states where
+//! several contracts run the same code entry.
+
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{Hash, ScMap};
+
+use crate::{
+    testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+    AppliedReplacement, BuildReport, ReplacementEntry, ReplacementPolicy, RetroshadeError,
+    RetroshadesExecution, SkippedReplacement,
+};
+
+use super::all_types::unchanged_meta;
+
+const WASM: &[u8] = b"\0asm\x01\0\0\0";
+const REPLACEMENT: &[u8] = b"\0asm\x01\0\0\0\0\x04\x03abc";
+
+/// Code entry both contracts are deployed with.
+const CODE_HASH: Hash = Hash([0; 32]);
+const OWNED: Hash = Hash([1; 32]);
+const OTHER: Hash = Hash([2; 32]);
+
+/// Builds a state holding the instances of `contracts`, all running the
+/// same code entry.
+fn build(
+    contracts: &[Hash],
+    key: Hash,
+    replacement: ReplacementEntry,
+    policy: ReplacementPolicy,
+) -> Result<BuildReport, RetroshadeError> {
+    build_with(contracts, HashMap::from([(key, replacement)]), policy)
+}
+
+fn build_with(
+    contracts: &[Hash],
+    replacements: HashMap<Hash, ReplacementEntry>,
+    policy: ReplacementPolicy,
+) -> Result<BuildReport, RetroshadeError> {
+    let snapshot = MockSnapshot::with_contract(OWNED, WASM, ScMap::default()).contract(
+        OTHER,
+        WASM,
+        ScMap::default(),
+    );
+    let mut envelope = EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(CODE_HASH));
+    for contract_id in contracts {
+        envelope = envelope.read_only_key(instance_key(contract_id.clone()));
+    }
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades.set_replacement_policy(policy);
+    retroshades.build_from_envelope_and_meta_with_report(
+        &snapshot,
+        envelope.build(),
+        unchanged_meta(),
+        replacements,
+    )
+}
+
+fn owned_only() -> ReplacementEntry {
+    ReplacementEntry::new(REPLACEMENT).allow([OWNED])
+}
+
+#[test]
+fn wasm_hash_keys_allow_no_contract_by_default() {
+    let report = build(
+        &[OWNED],
+        CODE_HASH,
+        ReplacementEntry::new(REPLACEMENT),
+        ReplacementPolicy::default(),
+    )
+    .unwrap();
+
+    assert!(!report.binaries_replaced);
+    assert_eq!(report.skipped_replacements[0].contract_id, OWNED);
+}
+
+#[test]
+fn wasm_hash_keys_allowing_any_contract() {
+    let report = build(
+        &[OWNED, OTHER],
+        CODE_HASH,
+        ReplacementEntry::new(REPLACEMENT).allow_any(),
+        ReplacementPolicy::Error,
+    )
+    .unwrap();
+
+    assert!(report.binaries_replaced);
+    assert!(report.skipped_replacements.is_empty());
+}
+
+#[test]
+fn shared_wasm_hash_collision_is_skipped() {
+    // note: replacing the code entry would also run the binary as `OTHER`.
+    let report = build(
+        &[OWNED, OTHER],
+        CODE_HASH,
+        owned_only(),
+        ReplacementPolicy::Skip,
+    )
+    .unwrap();
+
+    assert!(!report.binaries_replaced);
+    assert_eq!(
+        report.skipped_replacements,
+        vec![SkippedReplacement {
+            key: CODE_HASH,
+            wasm_hash: CODE_HASH,
+            contract_id: OTHER,
+        }]
+    );
+}
+
+#[test]
+fn shared_wasm_hash_collision_by_contract_id() {
+    let report = build(
+        &[OWNED, OTHER],
+        OWNED,
+        owned_only(),
+        ReplacementPolicy::Skip,
+    )
+    .unwrap();
+
+    assert!(!report.binaries_replaced);
+    assert_eq!(
+        report.skipped_replacements,
+        vec![SkippedReplacement {
+            key: OWNED,
+            wasm_hash: CODE_HASH,
+            contract_id: OTHER,
+        }]
+    );
+}
+
+#[test]
+fn colliding_contracts_outside_the_footprint_are_ignored() {
+    let report = build(&[OWNED], CODE_HASH, owned_only(), ReplacementPolicy::Error).unwrap();
+
+    assert!(report.binaries_replaced);
+    assert_eq!(
+        report.applied_replacements,
+        vec![AppliedReplacement {
+            key: CODE_HASH,
+            wasm_hash: CODE_HASH,
+            contract_ids: vec![OWNED],
+        }]
+    );
+    assert!(report.skipped_replacements.is_empty());
+}
+
+#[test]
+fn every_colliding_contract_allowed() {
+    let report = build(
+        &[OWNED, OTHER],
+        OWNED,
+        owned_only().allow([OTHER]),
+        ReplacementPolicy::Error,
+    )
+    .unwrap();
+
+    assert!(report.binaries_replaced);
+}
+
+#[test]
+fn keyed_contract_is_allowed_without_being_listed() {
+    let replacement = ReplacementEntry::new(REPLACEMENT).allow([OTHER]);
+    let report = build(&[OWNED], OWNED, replacement, ReplacementPolicy::Error).unwrap();
+
+    assert!(report.binaries_replaced);
+}
+
+#[test]
+fn strict_policy_fails_on_collisions() {
+    let error = build(
+        &[OWNED, OTHER],
+        CODE_HASH,
+        owned_only(),
+        ReplacementPolicy::Error,
+    )
+    .unwrap_err();
+
+    let RetroshadeError::ReplacementNotAllowed(skipped) = error else {
+        panic!("expected a replacement not allowed, got {:?}", error)
+    };
+    assert_eq!(skipped.contract_id, OTHER);
+    assert_eq!(skipped.wasm_hash, CODE_HASH);
+}
+
+#[test]
+fn colliding_contracts_keyed_with_the_same_binary() {
+    let report = build_with(
+        &[OWNED, OTHER],
+        HashMap::from([
+            (OWNED, ReplacementEntry::new(REPLACEMENT)),
+            (OTHER, ReplacementEntry::new(REPLACEMENT)),
+        ]),
+        ReplacementPolicy::Error,
+    )
+    .unwrap();
+
+    assert!(report.binaries_replaced);
+}
+
+#[test]
+fn colliding_contracts_keyed_with_different_binaries() {
+    let error = build_with(
+        &[OWNED, OTHER],
+        HashMap::from([
+            (OWNED, ReplacementEntry::new(REPLACEMENT)),
+            (OTHER, ReplacementEntry::new(WASM)),
+        ]),
+        ReplacementPolicy::Skip,
+    )
+    .unwrap_err();
+
+    let RetroshadeError::ConflictingReplacements {
+        wasm_hash,
+        mut keys,
+    } = error
+    else {
+        panic!("expected conflicting replacements, got {:?}", error)
+    };
+    keys.sort();
+    assert_eq!(wasm_hash, CODE_HASH);
+    assert_eq!(keys, vec![OWNED, OTHER]);
+}
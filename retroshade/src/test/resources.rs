@@ -0,0 +1,132 @@
+//! Declared resources checked against a state with an oversized replaced
+//! binary.
+
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{Hash, ScMap};
+
+use crate::{
+    testutils::{code_key, instance_key, ledger_info_protocol, EnvelopeBuilder, MockSnapshot},
+    ReplacementEntry, ResourceDimension, ResourceValidation, RetroshadeError, RetroshadesExecution,
+};
+
+use super::{all_types::unchanged_meta, examples::example_wasm};
+
+/// Bytes the replaced binary has on top of the deployed one.
+const PADDING: usize = 64 * 1024;
+
+/// `wasm` with a custom section of `len` bytes appended, which the host
+/// ignores.
+fn padded(wasm: &[u8], len: usize) -> Vec<u8> {
+    let name = b"padding";
+    let mut section = vec![name.len() as u8];
+    section.extend_from_slice(name);
+    section.resize(section.len() + len, 0);
+
+    let mut padded = wasm.to_vec();
+    padded.push(0);
+    let mut size = section.len();
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            padded.push(byte);
+            break;
+        }
+        padded.push(byte | 0x80);
+    }
+    padded.extend(section);
+
+    padded
+}
+
+/// An execution of the hello world contract replaced by a padded copy, the
+/// envelope declaring enough disk reads for the deployed binary only.
+fn oversized_execution(validation: ResourceValidation) -> RetroshadesExecution {
+    let wasm = example_wasm("hello_world", "soroban_hello_world_contract");
+    let snapshot = MockSnapshot::with_contract(Hash([0; 32]), &wasm, ScMap::default());
+    let envelope = EnvelopeBuilder::new()
+        .function("t")
+        .disk_read_bytes((wasm.len() + PADDING / 2) as u32)
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let mut mercury_contracts = HashMap::new();
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(padded(&wasm, PADDING)));
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades.set_resource_validation(validation);
+    let replaced = retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, unchanged_meta(), mercury_contracts)
+        .unwrap();
+    assert!(replaced);
+
+    retroshades
+}
+
+#[test]
+fn unchecked_by_default() {
+    let result = oversized_execution(ResourceValidation::default())
+        .retroshade()
+        .unwrap();
+
+    assert!(result.invoke_result.is_ok());
+}
+
+#[test]
+fn insufficient_code_bytes() {
+    let wasm_len = example_wasm("hello_world", "soroban_hello_world_contract").len();
+    let error = oversized_execution(ResourceValidation::Error)
+        .retroshade()
+        .unwrap_err();
+
+    let RetroshadeError::InsufficientDeclaredResources {
+        needed,
+        declared,
+        dimension,
+    } = error
+    else {
+        panic!("expected insufficient resources, got {:?}", error)
+    };
+    assert_eq!(dimension, ResourceDimension::CodeBytes);
+    assert_eq!(declared as usize, wasm_len + PADDING / 2);
+    assert!(needed > wasm_len + PADDING);
+}
+
+#[test]
+fn insufficient_state_bytes() {
+    // note: the code entry fits in the declared bytes, the instance entry
+    // on top of it doesn't.
+    let wasm = example_wasm("hello_world", "soroban_hello_world_contract");
+    let snapshot = MockSnapshot::with_contract(Hash([0; 32]), &wasm, ScMap::default());
+    let envelope = EnvelopeBuilder::new()
+        .function("t")
+        .disk_read_bytes(wasm.len() as u32 + 100)
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades.set_resource_validation(ResourceValidation::Error);
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, unchanged_meta(), HashMap::new())
+        .unwrap();
+
+    assert!(matches!(
+        retroshades.retroshade(),
+        Err(RetroshadeError::InsufficientDeclaredResources {
+            dimension: ResourceDimension::DiskReadBytes,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn inflated_resources_execute() {
+    let result = oversized_execution(ResourceValidation::Inflate)
+        .retroshade()
+        .unwrap();
+
+    assert!(result.invoke_result.is_ok());
+}
@@ -0,0 +1,196 @@
+//! Executions of the abusive example contract, and of a prebuilt fixture
+//! contract, under [`SandboxLimits`].
+
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{Hash, ScMap, ScVal};
+
+use crate::{
+    testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+    ReplacementEntry, RetroshadeError, RetroshadesExecution, SandboxLimits,
+};
+
+use super::{
+    all_types::unchanged_meta,
+    examples::{example_wasm, example_wasm_with_features, fixture_wasm},
+};
+
+/// Low enough for the abusive contract to go over, high enough for the
+/// host to instantiate it.
+const MAX_MEMORY_BYTES: u64 = 32 * 1024 * 1024;
+
+fn abusive_execution(function: &str, arg: u32, limits: SandboxLimits) -> RetroshadesExecution {
+    let snapshot = MockSnapshot::with_contract(
+        Hash([0; 32]),
+        &example_wasm("abusive", "soroban_abusive"),
+        ScMap::default(),
+    );
+    let envelope = EnvelopeBuilder::new()
+        .function(function)
+        .arg(ScVal::U32(arg))
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let binary = example_wasm_with_features("abusive", "soroban_abusive", &["mercury"]);
+    let mut mercury_contracts = HashMap::new();
+    mercury_contracts.insert(Hash([0; 32]), ReplacementEntry::new(binary));
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades.set_sandbox_limits(limits);
+    let replaced = retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, unchanged_meta(), mercury_contracts)
+        .unwrap();
+    assert!(replaced);
+
+    retroshades
+}
+
+fn void_execution(limits: SandboxLimits) -> RetroshadesExecution {
+    let wasm = fixture_wasm("void");
+    let snapshot = MockSnapshot::with_contract(Hash([0; 32]), &wasm, ScMap::default());
+    let envelope = EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_only_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades.set_sandbox_limits(limits);
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, unchanged_meta(), HashMap::new())
+        .unwrap();
+
+    retroshades
+}
+
+fn memory_limited() -> SandboxLimits {
+    SandboxLimits {
+        max_memory_bytes: MAX_MEMORY_BYTES,
+        ..Default::default()
+    }
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn unlimited_by_default() {
+    let result = abusive_execution("flood", 100, SandboxLimits::default())
+        .retroshade_packed()
+        .unwrap();
+
+    assert!(result.call_succeeded);
+    assert_eq!(result.retroshades.len(), 100);
+    assert!(!result.truncated);
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn retroshades_over_the_limit_are_truncated() {
+    let limits = SandboxLimits {
+        max_retroshades: 3,
+        ..Default::default()
+    };
+    let result = abusive_execution("flood", 100, limits)
+        .retroshade_packed()
+        .unwrap();
+
+    assert!(result.call_succeeded);
+    assert!(result.truncated);
+    // note: the first ones emitted are kept.
+    let indices: Vec<String> = result
+        .retroshades
+        .iter()
+        .map(|retroshade| retroshade.event[0].value.kind.to_string())
+        .collect();
+    assert_eq!(indices, vec!["0", "1", "2"]);
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn events_over_the_limit_are_truncated() {
+    let unlimited = abusive_execution("flood", 10, SandboxLimits::default())
+        .retroshade()
+        .unwrap();
+    assert!(unlimited.diagnostic.len() > 1);
+
+    let limits = SandboxLimits {
+        max_output_events: 1,
+        ..Default::default()
+    };
+    let result = abusive_execution("flood", 10, limits).retroshade().unwrap();
+
+    assert!(result.truncated);
+    assert_eq!(result.diagnostic, &unlimited.diagnostic[..1]);
+    assert_eq!(result.retroshades.len(), 10);
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn memory_within_the_limit() {
+    let result = abusive_execution("hoard", 4, memory_limited())
+        .retroshade()
+        .unwrap();
+
+    assert_eq!(result.invoke_result, Ok(ScVal::U32(4 * 1024)));
+    assert!(!result.truncated);
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn memory_over_the_limit_aborts() {
+    let error = abusive_execution("hoard", 1024, memory_limited())
+        .retroshade()
+        .unwrap_err();
+
+    let RetroshadeError::SandboxLimit {
+        memory_bytes,
+        limit,
+    } = error
+    else {
+        panic!("expected a sandbox limit error, got {:?}", error)
+    };
+    assert_eq!(limit, MAX_MEMORY_BYTES);
+    assert!(memory_bytes > MAX_MEMORY_BYTES);
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn linear_memory_growth_over_the_limit_fails() {
+    // note: the host refuses growth past the limit rather than charging it,
+    // the contract panics on the refusal.
+    let pages = (MAX_MEMORY_BYTES / (64 * 1024)) as u32;
+    match abusive_execution("grow", pages, memory_limited()).retroshade() {
+        Ok(result) => assert!(result.invoke_result.is_err(), "{:?}", result.invoke_result),
+        Err(error) => assert!(
+            matches!(error, RetroshadeError::SandboxLimit { .. }),
+            "{:?}",
+            error
+        ),
+    }
+
+    let result = abusive_execution("grow", 1, memory_limited())
+        .retroshade()
+        .unwrap();
+    assert!(result.invoke_result.is_ok());
+}
+
+#[test]
+fn output_events_over_the_limit_are_truncated() {
+    let unlimited = void_execution(SandboxLimits::default())
+        .retroshade()
+        .unwrap();
+    assert_eq!(unlimited.invoke_result, Ok(ScVal::Void));
+    assert!(!unlimited.truncated);
+    assert!(!unlimited.diagnostic.is_empty());
+
+    let kept = unlimited.diagnostic.len() - 1;
+    let limits = SandboxLimits {
+        max_output_events: kept,
+        ..Default::default()
+    };
+    let result = void_execution(limits).retroshade().unwrap();
+
+    assert!(result.truncated);
+    assert_eq!(result.diagnostic, &unlimited.diagnostic[..kept]);
+    assert_eq!(result.invoke_result, Ok(ScVal::Void));
+}
@@ -0,0 +1,143 @@
+use crate::{conversion::TypeKind, schema::widen, RetroshadeExportPretty, SCHEMA_HASH_COLUMN};
+use serde_json::{json, Value};
+use soroban_env_host::xdr::{
+    AccountId, Int128Parts, MuxedAccount, PublicKey, ScAddress, ScVal, Uint256,
+};
+
+use super::packing::{built_execution, execution_result, export, symbol};
+
+fn i128(lo: u64) -> ScVal {
+    ScVal::I128(Int128Parts { hi: 0, lo })
+}
+
+/// Packs events shaped like the `DepositEvent` of the deposit example.
+fn deposits(events: Vec<Vec<(ScVal, ScVal)>>) -> Vec<RetroshadeExportPretty> {
+    built_execution(MuxedAccount::Ed25519(Uint256([0; 32])))
+        .pack_result(execution_result(
+            events
+                .into_iter()
+                .map(|fields| export(symbol("mydeposit"), fields))
+                .collect(),
+        ))
+        .unwrap()
+        .retroshades
+}
+
+fn deposit_event() -> Vec<(ScVal, ScVal)> {
+    vec![
+        (symbol("amount"), i128(100)),
+        (
+            symbol("from"),
+            ScVal::Address(ScAddress::Account(AccountId(
+                PublicKey::PublicKeyTypeEd25519(Uint256([0; 32])),
+            ))),
+        ),
+        (symbol("ledger"), ScVal::U32(1000)),
+        (symbol("now_tvl"), i128(300)),
+        (symbol("previous_tvl"), i128(200)),
+        (symbol("timestamp"), ScVal::U64(200)),
+    ]
+}
+
+#[test]
+fn deposit_schema_golden() {
+    let golden: Value = serde_json::from_str(include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/schema/mydeposit.json"
+    )))
+    .unwrap();
+
+    assert_eq!(deposits(vec![deposit_event()])[0].json_schema(), golden);
+}
+
+#[test]
+fn widening() {
+    let mut without_from = deposit_event();
+    without_from.remove(1);
+    let mut void_amount = deposit_event();
+    void_amount[0].1 = ScVal::Void;
+
+    let exports = deposits(vec![deposit_event(), without_from, void_amount]);
+    let schema = exports
+        .iter()
+        .map(RetroshadeExportPretty::json_schema)
+        .reduce(|schema, other| widen(&schema, &other))
+        .unwrap();
+
+    assert_eq!(
+        schema["properties"]["amount"],
+        json!({ "anyOf": [
+            { "type": "string", "pattern": "^-?[0-9]+$" },
+            { "type": "null" },
+        ]})
+    );
+    assert_eq!(
+        schema["required"],
+        json!(["amount", "ledger", "now_tvl", "previous_tvl", "timestamp"])
+    );
+    assert!(schema["properties"]["from"].is_object());
+}
+
+#[test]
+fn schema_hash_ignores_values() {
+    let mut other_values = deposit_event();
+    other_values[0].1 = i128(5);
+    other_values[2].1 = ScVal::U32(2000);
+
+    let exports = deposits(vec![deposit_event(), other_values]);
+    assert_eq!(exports[0].schema_hash(), exports[1].schema_hash());
+}
+
+#[test]
+fn schema_hash_changes_with_the_columns() {
+    let mut added = deposit_event();
+    added.push((symbol("referrer"), ScVal::U32(1)));
+    let mut retyped = deposit_event();
+    retyped[2].1 = symbol("ledger");
+
+    let exports = deposits(vec![deposit_event(), added, retyped]);
+    assert_ne!(exports[0].schema_hash(), exports[1].schema_hash());
+    assert_ne!(exports[0].schema_hash(), exports[2].schema_hash());
+}
+
+#[test]
+fn schema_hash_column() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_schema_hash_column(true);
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("mydeposit"),
+            deposit_event(),
+        )]))
+        .unwrap();
+
+    let export = &packed.retroshades[0];
+    let (column, fields) = export.event.split_last().unwrap();
+    assert_eq!(column.name, SCHEMA_HASH_COLUMN);
+    assert_eq!(
+        column.value.kind,
+        TypeKind::Text(hex::encode(
+            deposits(vec![deposit_event()])[0].schema_hash()
+        ))
+    );
+    assert_eq!(fields.len(), deposit_event().len());
+}
+
+#[test]
+fn schema_hash_column_agrees_with_the_method() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_schema_hash_column(true);
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("mydeposit"),
+            deposit_event(),
+        )]))
+        .unwrap();
+
+    let export = &packed.retroshades[0];
+    let column = export.event.last().unwrap();
+    assert_eq!(
+        column.value.kind,
+        TypeKind::Text(hex::encode(export.schema_hash()))
+    );
+}
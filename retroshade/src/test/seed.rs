@@ -0,0 +1,70 @@
+//! The randomly seeded executions are compiled in for the tests with or
+//! without the `rand` feature, so both kinds run in every configuration.
+
+use crate::{conversion::TypeKind, engine::RetroshadeEngine, RetroshadeExecutionResultPretty};
+
+use super::fixture::mainnet_execution;
+
+const SEED: [u8; 32] = [7; 32];
+
+#[test]
+fn seeded_executions_are_reproducible() {
+    let retroshades = mainnet_execution();
+
+    let first = retroshades.retroshade_packed_seeded(SEED).unwrap();
+    let second = retroshades.retroshade_packed_seeded(SEED).unwrap();
+    assert!(first.call_succeeded);
+    assert_eq!(first.retroshades, second.retroshades);
+    assert_eq!(first.invoke_result, second.invoke_result);
+    assert_eq!(first.ledger_changes, second.ledger_changes);
+
+    let engine = RetroshadeEngine::new().unwrap();
+    let with_engine = engine.execute_packed_seeded(&retroshades, SEED).unwrap();
+    assert_eq!(with_engine.retroshades, first.retroshades);
+}
+
+#[test]
+fn random_seeds_match_seeded_executions() {
+    let retroshades = mainnet_execution();
+
+    // note: the fixture's contract doesn't draw from the PRNG, the seed
+    // doesn't change its retroshades.
+    let seeded = retroshades.retroshade_packed_seeded(SEED).unwrap();
+    let random = retroshades.retroshade_packed().unwrap();
+    assert_eq!(random.retroshades, seeded.retroshades);
+    assert_eq!(random.invoke_result, seeded.invoke_result);
+}
+
+#[test]
+fn idempotency_keys_are_stable_across_reexecutions() {
+    // note: rebuilt from the fixture each time, as a reprocessed ledger is.
+    let reexecute = || {
+        let mut retroshades = mainnet_execution();
+        retroshades.set_idempotency_key_column(true);
+        retroshades.retroshade_packed_seeded(SEED).unwrap()
+    };
+    let tx_hash = mainnet_execution().tx_context().tx_hash;
+
+    let first = reexecute();
+    let second = reexecute();
+    assert!(!first.retroshades.is_empty());
+
+    let keys = |result: &RetroshadeExecutionResultPretty| {
+        result
+            .retroshades
+            .iter()
+            .map(|export| export.idempotency_key(&tx_hash))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(first.retroshades, second.retroshades);
+    assert_eq!(keys(&first), keys(&second));
+
+    for export in &first.retroshades {
+        let column = export.event.last().unwrap();
+        assert_eq!(column.name, "idempotency_key");
+        assert_eq!(
+            column.value.kind,
+            TypeKind::Text(hex::encode(export.idempotency_key(&tx_hash)))
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{testutils::ledger_info_protocol, RetroshadesExecution};
+use soroban_env_host::{
+    storage::SnapshotSource,
+    xdr::{TransactionMeta, TransactionV1Envelope},
+    LedgerInfo,
+};
+
+pub struct EmptySnapshot {}
+
+impl SnapshotSource for EmptySnapshot {
+    fn get(
+        &self,
+        _key: &std::rc::Rc<soroban_env_host::xdr::LedgerKey>,
+    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
+    {
+        Ok(None)
+    }
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[test]
+fn shared_execution_is_send() {
+    let snapshot: Arc<dyn SnapshotSource + Send + Sync> = Arc::new(EmptySnapshot {});
+
+    let execution = move |envelope: TransactionV1Envelope, meta: TransactionMeta| {
+        let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+        retroshades.build_from_envelope_and_meta_shared(
+            snapshot.clone(),
+            envelope,
+            meta,
+            HashMap::new(),
+        )?;
+        retroshades.retroshade_packed_recording_shared(snapshot.clone())
+    };
+
+    assert_send(&execution);
+    assert_send(&RetroshadesExecution::new(LedgerInfo::default()));
+}
@@ -0,0 +1,282 @@
+use crate::{
+    export::jsonl::JsonlSink,
+    sink::{deliver_all, RetroshadeSink, SinkError},
+    RetroshadeExecutionResultPretty, RetroshadeExportPretty, TxContext,
+};
+use soroban_env_host::xdr::{Hash, MuxedAccount, ScVal, Uint256};
+
+use super::packing::{built_execution, execution_result, export, symbol, test_context};
+
+#[derive(Default)]
+struct RecordingSink {
+    delivered: Vec<(String, u32)>,
+    flushes: usize,
+}
+
+impl RetroshadeSink for RecordingSink {
+    fn deliver(
+        &mut self,
+        export: &RetroshadeExportPretty,
+        ctx: &TxContext,
+    ) -> Result<(), SinkError> {
+        assert_eq!(ctx, &export.context);
+        self.delivered
+            .push((export.target.to_string(), export.emit_index));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+pub fn packed_result() -> RetroshadeExecutionResultPretty {
+    built_execution(MuxedAccount::Ed25519(Uint256([0; 32])))
+        .pack_result(execution_result(vec![
+            export(symbol("swap"), vec![(symbol("amount"), ScVal::U32(1))]),
+            export(symbol("deposit"), vec![(symbol("amount"), ScVal::U32(2))]),
+        ]))
+        .unwrap()
+}
+
+#[test]
+fn delivers_in_emitted_order_and_flushes() {
+    let mut sink = RecordingSink::default();
+    deliver_all(&mut sink, &packed_result()).unwrap();
+
+    assert_eq!(
+        sink.delivered,
+        vec![("swap".to_string(), 0), ("deposit".to_string(), 1)]
+    );
+    assert_eq!(sink.flushes, 1);
+}
+
+#[test]
+fn jsonl_sink_behind_the_trait() {
+    let mut sink = JsonlSink::new(Vec::new());
+    deliver_all(&mut sink, &packed_result()).unwrap();
+
+    let jsonl = String::from_utf8(sink.into_inner()).unwrap();
+    assert_eq!(jsonl.lines().count(), 2);
+}
+
+/// A context other than the one the exports were packed with.
+pub fn delivery_context() -> TxContext {
+    TxContext {
+        tx_hash: Hash([2; 32]),
+        ledger_seq: 1001,
+        closed_at: 205,
+        op_index: 1,
+    }
+}
+
+#[test]
+fn jsonl_sink_uses_the_delivery_context() {
+    let mut retroshades = built_execution(MuxedAccount::Ed25519(Uint256([0; 32])));
+    retroshades.set_tx_context(test_context());
+    retroshades.set_context_columns(true);
+    let result = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("swap"),
+            vec![(symbol("amount"), ScVal::U32(1))],
+        )]))
+        .unwrap();
+
+    let mut sink = JsonlSink::new(Vec::new());
+    sink.deliver(&result.retroshades[0], &delivery_context())
+        .unwrap();
+
+    let line: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+    assert_eq!(
+        line["context"],
+        serde_json::to_value(delivery_context()).unwrap()
+    );
+    assert_eq!(line["fields"]["tx_hash"], hex::encode([2; 32]));
+    assert_eq!(line["fields"]["ledger_seq"], "1001");
+    assert_eq!(line["fields"]["amount"], "1");
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use crate::sink::{
+        deliver_all,
+        http::{HttpSink, HttpSinkConfig},
+        RetroshadeSink,
+    };
+
+    use super::{delivery_context, packed_result};
+
+    /// Serves one request per connection, answering with the given statuses
+    /// in order (200 once they run out) and recording the request bodies.
+    fn test_server(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        slow_test_server(statuses, Duration::ZERO)
+    }
+
+    /// Same as [`test_server`], waiting `delay` before answering.
+    fn slow_test_server(statuses: Vec<u16>, delay: Duration) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = bodies.clone();
+        thread::spawn(move || {
+            let mut statuses = statuses.into_iter();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8(body).unwrap());
+
+                thread::sleep(delay);
+                let status = statuses.next().unwrap_or(200);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+
+        (url, bodies)
+    }
+
+    #[test]
+    fn posts_batches() {
+        let (url, bodies) = test_server(vec![]);
+        let mut config = HttpSinkConfig::new(url);
+        config.batch_size = 2;
+
+        let mut sink = HttpSink::new(config);
+        let result = packed_result();
+        deliver_all(&mut sink, &result).unwrap();
+        sink.deliver(&result.retroshades[0], &result.retroshades[0].context)
+            .unwrap();
+        sink.flush().unwrap();
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[0].starts_with("[{\"target\":\"swap\""));
+        assert!(bodies[1].starts_with("[{\"target\":\"swap\""));
+    }
+
+    #[test]
+    fn posts_the_delivery_context() {
+        let (url, bodies) = test_server(vec![]);
+        let mut sink = HttpSink::new(HttpSinkConfig::new(url));
+        let result = packed_result();
+        sink.deliver(&result.retroshades[0], &delivery_context())
+            .unwrap();
+        sink.flush().unwrap();
+
+        let bodies = bodies.lock().unwrap();
+        let body: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(
+            body["context"],
+            serde_json::to_value(delivery_context()).unwrap()
+        );
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, bodies) = test_server(vec![503, 502]);
+        let mut config = HttpSinkConfig::new(url);
+        config.initial_backoff = Duration::from_millis(1);
+
+        let mut sink = HttpSink::new(config);
+        let result = packed_result();
+        sink.deliver(&result.retroshades[0], &result.retroshades[0].context)
+            .unwrap();
+        sink.flush().unwrap();
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 3);
+        assert!(bodies.iter().all(|body| body == &bodies[0]));
+    }
+
+    #[test]
+    fn full_queue_fails_delivery() {
+        // accepts connections but never answers.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config =
+            HttpSinkConfig::new(format!("http://{}/events", listener.local_addr().unwrap()));
+        config.queue_capacity = 1;
+        config.enqueue_timeout = Duration::ZERO;
+        config.max_retries = 0;
+        config.timeout = Duration::from_millis(200);
+
+        let mut sink = HttpSink::new(config);
+        let result = packed_result();
+        let export = &result.retroshades[0];
+        let failed = (0..5)
+            .map(|_| sink.deliver(export, &export.context))
+            .filter(Result::is_err)
+            .count();
+
+        assert!(failed > 0);
+        drop(listener);
+    }
+
+    #[test]
+    fn full_queue_keeps_the_exports() {
+        let (url, bodies) = slow_test_server(vec![], Duration::from_millis(20));
+        let mut config = HttpSinkConfig::new(url);
+        config.queue_capacity = 1;
+        config.enqueue_timeout = Duration::ZERO;
+
+        let mut sink = HttpSink::new(config);
+        let result = packed_result();
+        let mut failed = 0;
+        for emit_index in 0..10 {
+            let mut export = result.retroshades[0].clone();
+            export.emit_index = emit_index;
+            if sink.deliver(&export, &export.context).is_err() {
+                failed += 1;
+            }
+        }
+        assert!(failed > 0);
+
+        let mut attempts = 0;
+        while sink.flush().is_err() {
+            attempts += 1;
+            assert!(attempts < 100, "the queue never drained");
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let emit_indexes: Vec<u64> = bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|body| {
+                let line: serde_json::Value = serde_json::from_str(body).unwrap();
+                line["emit_index"].as_u64().unwrap()
+            })
+            .collect();
+        assert_eq!(emit_indexes, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,742 @@
+use postgres_types::Type;
+use soroban_env_host::xdr::{
+    ContractCodeEntry, ContractCodeEntryExt, ContractDataDurability, ContractDataEntry,
+    ContractExecutable, ExtensionPoint, Hash, Int128Parts, LedgerEntry, LedgerEntryData,
+    LedgerEntryExt, Limits, MuxedAccount, ScAddress, ScBytes, ScContractInstance, ScMap,
+    ScMapEntry, ScSpecEntry, ScSpecType, ScSpecTypeBytesN, ScSpecTypeDef, ScSpecTypeOption,
+    ScSpecTypeUdt, ScSpecTypeVec, ScSpecUdtEnumCaseV0, ScSpecUdtEnumV0, ScSpecUdtStructFieldV0,
+    ScSpecUdtStructV0, ScSpecUdtUnionCaseTupleV0, ScSpecUdtUnionCaseV0, ScSpecUdtUnionCaseVoidV0,
+    ScSpecUdtUnionV0, ScVal, ScValType, ScVec, Uint256, WriteXdr,
+};
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    export::sql,
+    naming::TableNaming,
+    spec::{column_type, table_ddl_for, ColumnType, ContractSpec, SpecMismatch, SPEC_SECTION},
+    state::KeyedEntry,
+    testutils::ledger_info,
+    RetroshadeError, RetroshadesExecution, SpecValidation,
+};
+
+use super::{
+    examples::{example_wasm_with_features, fixture_wasm},
+    packing::{execution_result, export, symbol},
+};
+
+pub fn udt_struct(name: &str, fields: &[(&str, ScSpecTypeDef)]) -> ScSpecEntry {
+    ScSpecEntry::UdtStructV0(ScSpecUdtStructV0 {
+        doc: Default::default(),
+        lib: Default::default(),
+        name: name.try_into().unwrap(),
+        fields: fields
+            .iter()
+            .map(|(name, type_)| ScSpecUdtStructFieldV0 {
+                doc: Default::default(),
+                name: (*name).try_into().unwrap(),
+                type_: type_.clone(),
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap(),
+    })
+}
+
+pub fn option(value_type: ScSpecTypeDef) -> ScSpecTypeDef {
+    ScSpecTypeDef::Option(Box::new(ScSpecTypeOption {
+        value_type: Box::new(value_type),
+    }))
+}
+
+pub fn vec_of(element_type: ScSpecTypeDef) -> ScSpecTypeDef {
+    ScSpecTypeDef::Vec(Box::new(ScSpecTypeVec {
+        element_type: Box::new(element_type),
+    }))
+}
+
+/// An empty module with `entries` in its spec section.
+pub fn wasm_with_spec(entries: &[ScSpecEntry]) -> Vec<u8> {
+    let mut payload = vec![SPEC_SECTION.len() as u8];
+    payload.extend(SPEC_SECTION.as_bytes());
+    for entry in entries {
+        payload.extend(entry.to_xdr(Limits::none()).unwrap());
+    }
+
+    let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+    wasm.push(0);
+    let mut len = payload.len();
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            wasm.push(byte);
+            break;
+        }
+        wasm.push(byte | 0x80);
+    }
+    wasm.extend(payload);
+
+    wasm
+}
+
+fn transfer_spec() -> Vec<ScSpecEntry> {
+    vec![udt_struct(
+        "TransferEvent",
+        &[
+            ("amounts", vec_of(ScSpecTypeDef::I128)),
+            ("fee", option(ScSpecTypeDef::I128)),
+            ("to", ScSpecTypeDef::Address),
+        ],
+    )]
+}
+
+#[test]
+fn deposit_event_spec() {
+    let wasm = fixture_wasm("deposit_spec");
+    let spec = ContractSpec::from_wasm(&wasm).unwrap();
+
+    let mut fields = spec.spec_for_struct("DepositEvent");
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        fields,
+        vec![
+            ("amount".to_string(), ScSpecTypeDef::I128),
+            ("from".to_string(), ScSpecTypeDef::Address),
+            ("ledger".to_string(), ScSpecTypeDef::U32),
+            ("now_tvl".to_string(), ScSpecTypeDef::I128),
+            ("previous_tvl".to_string(), ScSpecTypeDef::I128),
+            ("timestamp".to_string(), ScSpecTypeDef::U64),
+        ]
+    );
+    for (field, type_def) in &fields {
+        let expected = if field == "from" {
+            Type::TEXT
+        } else {
+            Type::NUMERIC
+        };
+        assert_eq!(
+            column_type(type_def).dbtype,
+            expected,
+            "{} column type",
+            field
+        );
+    }
+
+    assert_eq!(
+        spec.struct_with_fields([
+            "timestamp",
+            "ledger",
+            "amount",
+            "from",
+            "now_tvl",
+            "previous_tvl"
+        ])
+        .as_deref(),
+        Some("DepositEvent")
+    );
+}
+
+#[test]
+#[ignore = "builds the example contracts"]
+fn prebuilt_deposit_spec_matches_the_example() {
+    let wasm = example_wasm_with_features("deposit", "soroban_deposit", &["mercury"]);
+    let example = ContractSpec::from_wasm(&wasm).unwrap();
+    let prebuilt = ContractSpec::from_wasm(&fixture_wasm("deposit_spec")).unwrap();
+
+    assert_eq!(
+        prebuilt.spec_for_struct("DepositEvent"),
+        example.spec_for_struct("DepositEvent")
+    );
+}
+
+#[test]
+fn missing_struct_has_no_fields() {
+    let spec = ContractSpec::from_wasm(&wasm_with_spec(&transfer_spec())).unwrap();
+
+    assert!(spec.spec_for_struct("DepositEvent").is_empty());
+    assert_eq!(spec.spec_for_struct("TransferEvent").len(), 3);
+}
+
+#[test]
+fn wasm_without_spec_section() {
+    let spec = ContractSpec::from_wasm(b"\0asm\x01\0\0\0").unwrap();
+    assert!(spec.entries().is_empty());
+    assert!(ContractSpec::from_wasm(b"not a wasm").is_err());
+}
+
+#[test]
+fn column_types_and_nullability() {
+    assert_eq!(
+        column_type(&option(ScSpecTypeDef::Address)),
+        ColumnType {
+            dbtype: Type::TEXT,
+            nullable: true,
+        }
+    );
+    assert_eq!(
+        column_type(&vec_of(ScSpecTypeDef::I128)),
+        ColumnType {
+            dbtype: Type::NUMERIC_ARRAY,
+            nullable: false,
+        }
+    );
+    assert_eq!(column_type(&ScSpecTypeDef::Bool).dbtype, Type::BOOL);
+    assert_eq!(column_type(&ScSpecTypeDef::Bytes).dbtype, Type::BYTEA);
+    assert_eq!(
+        column_type(&vec_of(ScSpecTypeDef::Bytes)).dbtype,
+        Type::TEXT_ARRAY
+    );
+    assert_eq!(
+        column_type(&vec_of(option(ScSpecTypeDef::U32))).dbtype,
+        Type::TEXT
+    );
+}
+
+/// An execution whose contract `[0; 32]` runs `wasm`, with a source account
+/// for packing.
+fn execution_with_code(wasm: Vec<u8>) -> RetroshadesExecution {
+    let instance = LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(Hash([1; 32])),
+                storage: None,
+            }),
+        }),
+        ext: LedgerEntryExt::V0,
+    };
+    let code = LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: Hash([1; 32]),
+            code: wasm.try_into().unwrap(),
+        }),
+        ext: LedgerEntryExt::V0,
+    };
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades.target_pre_execution_state = vec![
+        KeyedEntry::new(instance, Some(100)),
+        KeyedEntry::new(code, Some(100)),
+    ];
+    retroshades.muxed_source_account = Some(MuxedAccount::Ed25519(Uint256([2; 32])));
+
+    retroshades
+}
+
+fn empty_transfer() -> Vec<(ScVal, ScVal)> {
+    vec![
+        (
+            symbol("amounts"),
+            ScVal::Vec(Some(ScVec(Default::default()))),
+        ),
+        (symbol("fee"), ScVal::Void),
+        (
+            symbol("to"),
+            ScVal::Address(ScAddress::Contract(Hash([0; 32]).into())),
+        ),
+    ]
+}
+
+#[test]
+fn spec_types_void_and_empty_columns() {
+    let mut retroshades = execution_with_code(wasm_with_spec(&transfer_spec()));
+    retroshades.set_spec_column_types(true);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("transfer"),
+            empty_transfer(),
+        )]))
+        .unwrap();
+    let event = &packed.retroshades[0].event;
+
+    assert_eq!(event[0].name, "amounts");
+    assert_eq!(event[0].value.dbtype, Type::NUMERIC_ARRAY);
+    assert_eq!(event[0].value.kind, TypeKind::GenericArray(vec![]));
+    assert_eq!(event[1].name, "fee");
+    assert_eq!(event[1].value.dbtype, Type::NUMERIC);
+    assert_eq!(event[1].value.kind, TypeKind::Void);
+    assert_eq!(event[2].value.dbtype, Type::TEXT);
+}
+
+#[test]
+fn spec_types_are_opt_in() {
+    let retroshades = execution_with_code(wasm_with_spec(&transfer_spec()));
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("transfer"),
+            empty_transfer(),
+        )]))
+        .unwrap();
+
+    let event = &packed.retroshades[0].event;
+    assert_eq!(event[0].value.dbtype, Type::TEXT);
+    assert!(matches!(event[0].value.kind, TypeKind::Text(_)));
+}
+
+#[test]
+fn events_matching_no_struct_are_inferred() {
+    let mut retroshades = execution_with_code(wasm_with_spec(&transfer_spec()));
+    retroshades.set_spec_column_types(true);
+
+    let mut fields = empty_transfer();
+    fields.push((symbol("extra"), ScVal::U32(1)));
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(symbol("transfer"), fields)]))
+        .unwrap();
+
+    assert_eq!(packed.retroshades[0].event[0].value.dbtype, Type::TEXT);
+}
+
+fn transfer(fields: Vec<(ScVal, ScVal)>) -> ScMap {
+    let ScVal::Map(Some(event)) = export(symbol("transfer"), fields).event_object else {
+        unreachable!()
+    };
+
+    event
+}
+
+fn messages(mismatches: &[SpecMismatch]) -> Vec<String> {
+    mismatches.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn matching_event_validates() {
+    let spec = ContractSpec::from_wasm(&wasm_with_spec(&transfer_spec())).unwrap();
+
+    let mut fields = empty_transfer();
+    fields[1].1 = ScVal::I128(Int128Parts { hi: 0, lo: 5 });
+    assert!(spec.validate(&transfer(empty_transfer())).is_empty());
+    assert!(spec.validate(&transfer(fields)).is_empty());
+}
+
+#[test]
+fn missing_field() {
+    let spec = ContractSpec::from_wasm(&wasm_with_spec(&transfer_spec())).unwrap();
+
+    let mut fields = empty_transfer();
+    fields.remove(1);
+    assert_eq!(
+        spec.validate(&transfer(fields)),
+        vec![SpecMismatch::MissingField {
+            structure: "TransferEvent".to_string(),
+            field: "fee".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn renamed_field_is_missing_and_extra() {
+    let spec = ContractSpec::from_wasm(&wasm_with_spec(&transfer_spec())).unwrap();
+
+    let mut fields = empty_transfer();
+    fields[2].0 = symbol("recipient");
+    assert_eq!(
+        messages(&spec.validate(&transfer(fields))),
+        vec![
+            "TransferEvent field to is missing",
+            "TransferEvent has no field recipient",
+        ]
+    );
+}
+
+#[test]
+fn type_mismatch() {
+    let spec = ContractSpec::from_wasm(&wasm_with_spec(&transfer_spec())).unwrap();
+
+    let mut fields = empty_transfer();
+    fields[0].1 = ScVal::Vec(Some(ScVec(vec![ScVal::U32(1)].try_into().unwrap())));
+    fields[1].1 = symbol("free");
+    assert_eq!(
+        spec.validate(&transfer(fields)),
+        vec![
+            SpecMismatch::TypeMismatch {
+                structure: "TransferEvent".to_string(),
+                field: "amounts".to_string(),
+                expected: ScSpecType::Vec,
+                found: ScValType::Vec,
+            },
+            SpecMismatch::TypeMismatch {
+                structure: "TransferEvent".to_string(),
+                field: "fee".to_string(),
+                expected: ScSpecType::Option,
+                found: ScValType::Symbol,
+            },
+        ]
+    );
+}
+
+#[test]
+fn unrelated_event_matches_no_struct() {
+    let spec = ContractSpec::from_wasm(&wasm_with_spec(&transfer_spec())).unwrap();
+
+    assert_eq!(
+        spec.validate(&transfer(vec![(symbol("other"), ScVal::U32(1))])),
+        vec![SpecMismatch::NoMatchingStruct]
+    );
+}
+
+#[test]
+fn validation_warns_and_still_packs() {
+    let mut retroshades = execution_with_code(wasm_with_spec(&transfer_spec()));
+    retroshades.set_spec_validation(SpecValidation::Warn);
+
+    let mut fields = empty_transfer();
+    fields.push((symbol("extra"), ScVal::U32(1)));
+    let packed = retroshades
+        .pack_result(execution_result(vec![
+            export(symbol("transfer"), empty_transfer()),
+            export(symbol("transfer"), fields),
+        ]))
+        .unwrap();
+
+    assert_eq!(packed.retroshades.len(), 2);
+    assert_eq!(
+        packed.spec_warnings,
+        vec![(
+            1,
+            SpecMismatch::ExtraField {
+                structure: "TransferEvent".to_string(),
+                field: "extra".to_string(),
+            }
+        )]
+    );
+}
+
+#[test]
+fn validation_errors_fail_the_export() {
+    let mut retroshades = execution_with_code(wasm_with_spec(&transfer_spec()));
+    retroshades.set_spec_validation(SpecValidation::Error);
+
+    let mut fields = empty_transfer();
+    fields.remove(0);
+    let packed = retroshades
+        .pack_result(execution_result(vec![
+            export(symbol("transfer"), fields),
+            export(symbol("transfer"), empty_transfer()),
+        ]))
+        .unwrap();
+
+    assert_eq!(packed.retroshades.len(), 1);
+    assert_eq!(packed.retroshades[0].emit_index, 1);
+    assert!(packed.spec_warnings.is_empty());
+    let (idx, error) = &packed.conversion_errors[0];
+    assert_eq!(*idx, 0);
+    assert!(matches!(
+        error,
+        RetroshadeError::EventSpecMismatch { mismatches, .. }
+            if mismatches == &[SpecMismatch::MissingField {
+                structure: "TransferEvent".to_string(),
+                field: "amounts".to_string(),
+            }]
+    ));
+}
+
+fn payout_spec() -> Vec<u8> {
+    wasm_with_spec(&[udt_struct(
+        "PayoutEvent",
+        &[
+            ("to", ScSpecTypeDef::Address),
+            ("memo", option(ScSpecTypeDef::String)),
+            ("amounts", vec_of(ScSpecTypeDef::I128)),
+            ("recipients", vec_of(ScSpecTypeDef::Address)),
+            ("hash", ScSpecTypeDef::BytesN(ScSpecTypeBytesN { n: 32 })),
+            ("approved", ScSpecTypeDef::Bool),
+        ],
+    )])
+}
+
+fn contract() -> String {
+    stellar_strkey::Contract([0; 32]).to_string()
+}
+
+#[test]
+fn spec_ddl_golden() {
+    let wasm = payout_spec();
+    let mut ddl = String::new();
+    for naming in [TableNaming::TargetOnly, TableNaming::SchemaPerContract] {
+        ddl += &table_ddl_for(&wasm, "PayoutEvent", &contract(), "payout", naming).unwrap();
+        ddl += "\n";
+    }
+
+    let golden = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/sql/spec_ddl.sql"
+    ))
+    .unwrap();
+    assert_eq!(ddl, golden);
+}
+
+#[test]
+fn deposit_ddl_golden() {
+    let wasm = fixture_wasm("deposit_spec");
+    let ddl = table_ddl_for(
+        &wasm,
+        "DepositEvent",
+        &contract(),
+        "mydeposit",
+        TableNaming::TargetOnly,
+    )
+    .unwrap();
+
+    let golden = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/sql/deposit_ddl.sql"
+    ))
+    .unwrap();
+    assert_eq!(ddl + "\n", golden);
+}
+
+#[test]
+fn spec_ddl_matches_the_observed_one() {
+    let address = || ScVal::Address(ScAddress::Contract(Hash([0; 32]).into()));
+    let packed = execution_with_code(payout_spec())
+        .pack_result(execution_result(vec![export(
+            symbol("payout"),
+            vec![
+                (
+                    symbol("amounts"),
+                    ScVal::Vec(Some(ScVec(vec![ScVal::I32(1)].try_into().unwrap()))),
+                ),
+                (symbol("approved"), ScVal::Bool(true)),
+                (
+                    symbol("hash"),
+                    ScVal::Bytes(ScBytes(vec![0; 32].try_into().unwrap())),
+                ),
+                (symbol("memo"), symbol("memo")),
+                (
+                    symbol("recipients"),
+                    ScVal::Vec(Some(ScVec(vec![address()].try_into().unwrap()))),
+                ),
+                (symbol("to"), address()),
+            ],
+        )]))
+        .unwrap();
+
+    let observed = sql::create_table(&packed.retroshades[0], TableNaming::TargetOnly);
+    let from_spec = table_ddl_for(
+        &payout_spec(),
+        "PayoutEvent",
+        &contract(),
+        "payout",
+        TableNaming::TargetOnly,
+    )
+    .unwrap();
+    assert_eq!(from_spec.replace(" NOT NULL", ""), observed);
+}
+
+#[test]
+fn spec_ddl_of_an_unknown_struct() {
+    assert!(matches!(
+        table_ddl_for(
+            &payout_spec(),
+            "DepositEvent",
+            &contract(),
+            "payout",
+            TableNaming::TargetOnly
+        ),
+        Err(RetroshadeError::Spec(_))
+    ));
+}
+
+fn udt(name: &str) -> ScSpecTypeDef {
+    ScSpecTypeDef::Udt(ScSpecTypeUdt {
+        name: name.try_into().unwrap(),
+    })
+}
+
+/// The types of the orders example: an integer enum, a union with a unit and
+/// a tuple case and a nested struct.
+fn orders_spec() -> Vec<u8> {
+    let side = ScSpecEntry::UdtEnumV0(ScSpecUdtEnumV0 {
+        doc: Default::default(),
+        lib: Default::default(),
+        name: "Side".try_into().unwrap(),
+        cases: [("Buy", 0), ("Sell", 1)]
+            .into_iter()
+            .map(|(name, value)| ScSpecUdtEnumCaseV0 {
+                doc: Default::default(),
+                name: name.try_into().unwrap(),
+                value,
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap(),
+    });
+    let status = ScSpecEntry::UdtUnionV0(ScSpecUdtUnionV0 {
+        doc: Default::default(),
+        lib: Default::default(),
+        name: "OrderStatus".try_into().unwrap(),
+        cases: vec![
+            ScSpecUdtUnionCaseV0::VoidV0(ScSpecUdtUnionCaseVoidV0 {
+                doc: Default::default(),
+                name: "Open".try_into().unwrap(),
+            }),
+            ScSpecUdtUnionCaseV0::TupleV0(ScSpecUdtUnionCaseTupleV0 {
+                doc: Default::default(),
+                name: "Filled".try_into().unwrap(),
+                type_: vec![ScSpecTypeDef::I128].try_into().unwrap(),
+            }),
+        ]
+        .try_into()
+        .unwrap(),
+    });
+
+    wasm_with_spec(&[
+        side,
+        status,
+        udt_struct(
+            "Fill",
+            &[
+                ("maker", ScSpecTypeDef::Address),
+                ("amount", ScSpecTypeDef::I128),
+            ],
+        ),
+        udt_struct(
+            "OrderEvent",
+            &[
+                ("side", udt("Side")),
+                ("status", udt("OrderStatus")),
+                ("fill", udt("Fill")),
+                ("history", vec_of(udt("OrderStatus"))),
+                ("previous", option(udt("Fill"))),
+            ],
+        ),
+    ])
+}
+
+fn sc_vec(items: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(items.try_into().unwrap())))
+}
+
+fn sc_map(entries: Vec<(ScVal, ScVal)>) -> ScVal {
+    ScVal::Map(Some(ScMap(
+        entries
+            .into_iter()
+            .map(|(key, val)| ScMapEntry { key, val })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap(),
+    )))
+}
+
+fn filled(amount: u64) -> ScVal {
+    sc_vec(vec![
+        symbol("Filled"),
+        ScVal::I128(Int128Parts { hi: 0, lo: amount }),
+    ])
+}
+
+fn fill() -> ScVal {
+    sc_map(vec![
+        (
+            symbol("amount"),
+            ScVal::I128(Int128Parts { hi: 0, lo: 250 }),
+        ),
+        (
+            symbol("maker"),
+            ScVal::Address(ScAddress::Contract(Hash([0; 32]).into())),
+        ),
+    ])
+}
+
+fn text(value: &str) -> TypeKind {
+    TypeKind::Text(value.to_string())
+}
+
+#[test]
+fn enums_are_their_case_name() {
+    let spec = ContractSpec::from_wasm(&orders_spec()).unwrap();
+
+    let side = spec.from_scval(ScVal::U32(1), &udt("Side"));
+    assert_eq!(side.dbtype, Type::TEXT);
+    assert_eq!(side.kind, text("Sell"));
+
+    let open = spec.from_scval(sc_vec(vec![symbol("Open")]), &udt("OrderStatus"));
+    assert_eq!(open.kind, text("Open"));
+    let filled = spec.from_scval(filled(250), &udt("OrderStatus"));
+    assert_eq!(filled.kind, text(r#"Filled(["250"])"#));
+}
+
+#[test]
+fn nested_structs_are_json_objects() {
+    let spec = ContractSpec::from_wasm(&orders_spec()).unwrap();
+
+    let expected = text(&format!(r#"{{"amount":"250","maker":"{}"}}"#, contract()));
+    assert_eq!(spec.from_scval(fill(), &udt("Fill")).kind, expected);
+    assert_eq!(spec.from_scval(fill(), &option(udt("Fill"))).kind, expected);
+    assert_eq!(
+        spec.from_scval(ScVal::Void, &option(udt("Fill"))).kind,
+        TypeKind::Void
+    );
+}
+
+#[test]
+fn unknown_cases_are_inferred() {
+    let spec = ContractSpec::from_wasm(&orders_spec()).unwrap();
+
+    let unknown = spec.from_scval(ScVal::U32(7), &udt("Side"));
+    assert_eq!(unknown.kind, TypeKind::Numeric("7".to_string()));
+    let undeclared = spec.from_scval(ScVal::U32(1), &udt("Undeclared"));
+    assert_eq!(undeclared.kind, TypeKind::Numeric("1".to_string()));
+}
+
+#[test]
+fn packed_event_resolves_the_contract_types() {
+    let mut retroshades = execution_with_code(orders_spec());
+    retroshades.set_spec_column_types(true);
+
+    let packed = retroshades
+        .pack_result(execution_result(vec![export(
+            symbol("orders"),
+            vec![
+                (symbol("fill"), fill()),
+                (
+                    symbol("history"),
+                    sc_vec(vec![sc_vec(vec![symbol("Open")]), filled(100)]),
+                ),
+                (symbol("previous"), ScVal::Void),
+                (symbol("side"), ScVal::U32(0)),
+                (symbol("status"), filled(250)),
+            ],
+        )]))
+        .unwrap();
+
+    let kinds: Vec<(&str, &TypeKind)> = packed.retroshades[0]
+        .event
+        .iter()
+        .map(|entry| (entry.name.as_str(), &entry.value.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (
+                "fill",
+                &text(&format!(r#"{{"amount":"250","maker":"{}"}}"#, contract()))
+            ),
+            (
+                "history",
+                &TypeKind::GenericArray(vec![
+                    FromScVal {
+                        dbtype: Type::TEXT,
+                        kind: text("Open"),
+                    },
+                    FromScVal {
+                        dbtype: Type::TEXT,
+                        kind: text(r#"Filled(["100"])"#),
+                    },
+                ])
+            ),
+            ("previous", &TypeKind::Void),
+            ("side", &text("Buy")),
+            ("status", &text(r#"Filled(["250"])"#)),
+        ]
+    );
+}
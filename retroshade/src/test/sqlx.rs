@@ -0,0 +1,197 @@
+use std::str::FromStr;
+
+use crate::conversion::{FromScVal, TypeKind};
+use postgres_types::Type;
+use soroban_env_host::xdr::{
+    Int128Parts, Int256Parts, ScBytes, ScSymbol, ScVal, ScVec, UInt128Parts, UInt256Parts,
+};
+use sqlx::{
+    encode::IsNull, postgres::PgArgumentBuffer, types::BigDecimal, Arguments, Encode, Execute,
+    Postgres, TypeInfo,
+};
+
+fn produced(value: ScVal) -> String {
+    let value = FromScVal::from_scval(value, &mut 0);
+    <FromScVal as Encode<Postgres>>::produces(&value)
+        .unwrap()
+        .name()
+        .to_string()
+}
+
+fn encoded(value: ScVal) -> (IsNull, Vec<u8>) {
+    let value = FromScVal::from_scval(value, &mut 0);
+    let mut buf = PgArgumentBuffer::default();
+    let is_null = value.encode_by_ref(&mut buf).unwrap();
+
+    (is_null, buf.to_vec())
+}
+
+/// What sqlx encodes `value` as, to compare with the conversion's encoding.
+fn expected<'q, T: Encode<'q, Postgres>>(value: T) -> (IsNull, Vec<u8>) {
+    let mut buf = PgArgumentBuffer::default();
+    let is_null = value.encode_by_ref(&mut buf).unwrap();
+
+    (is_null, buf.to_vec())
+}
+
+fn decimal(n: &str) -> BigDecimal {
+    BigDecimal::from_str(n).unwrap()
+}
+
+fn vec_of(items: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(items.try_into().unwrap())))
+}
+
+fn symbol(s: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(s.try_into().unwrap()))
+}
+
+#[test]
+fn binds_as_the_converted_type() {
+    assert_eq!(produced(ScVal::Bool(true)), "bool");
+    assert_eq!(
+        produced(ScVal::I128(Int128Parts { hi: 0, lo: 2 })),
+        "numeric"
+    );
+    assert_eq!(
+        produced(ScVal::Bytes(ScBytes(vec![1].try_into().unwrap()))),
+        "bytea"
+    );
+    assert_eq!(
+        produced(ScVal::Vec(Some(ScVec(
+            vec![ScVal::U32(1), ScVal::U32(2)].try_into().unwrap()
+        )))),
+        "_numeric"
+    );
+    assert_eq!(produced(ScVal::Void), "text");
+}
+
+#[test]
+fn encodes_values() {
+    assert_eq!(encoded(ScVal::Void).0, IsNull::Yes);
+    assert_eq!(encoded(ScVal::Bool(true)), (IsNull::No, vec![1]));
+    assert_eq!(
+        encoded(ScVal::Bytes(ScBytes(vec![0xab, 0xcd].try_into().unwrap()))),
+        (IsNull::No, vec![0xab, 0xcd])
+    );
+}
+
+#[test]
+fn encodes_numerics() {
+    // note: postgres numerics are ndigits, weight, sign, dscale, then the
+    // base 10000 digits, zero has no digits.
+    assert_eq!(
+        encoded(ScVal::U256(UInt256Parts {
+            hi_hi: 0,
+            hi_lo: 0,
+            lo_hi: 0,
+            lo_lo: 0,
+        })),
+        (IsNull::No, vec![0, 0, 0, 0, 0, 0, 0, 0])
+    );
+    assert_eq!(
+        encoded(ScVal::I128(Int128Parts {
+            hi: -1,
+            lo: u64::MAX,
+        })),
+        (IsNull::No, vec![0, 1, 0, 0, 0x40, 0, 0, 0, 0, 1])
+    );
+
+    assert_eq!(
+        encoded(ScVal::I128(Int128Parts {
+            hi: i64::MIN,
+            lo: 0,
+        })),
+        expected(decimal("-170141183460469231731687303715884105728"))
+    );
+    assert_eq!(
+        encoded(ScVal::U128(UInt128Parts {
+            hi: u64::MAX,
+            lo: u64::MAX,
+        })),
+        expected(decimal("340282366920938463463374607431768211455"))
+    );
+    assert_eq!(
+        encoded(ScVal::I256(Int256Parts {
+            hi_hi: i64::MIN,
+            hi_lo: 0,
+            lo_hi: 0,
+            lo_lo: 0,
+        })),
+        expected(decimal(
+            "-57896044618658097711785492504343953926634992332820282019728792003956564819968"
+        ))
+    );
+    assert_eq!(
+        encoded(ScVal::I256(Int256Parts {
+            hi_hi: i64::MAX,
+            hi_lo: u64::MAX,
+            lo_hi: u64::MAX,
+            lo_lo: u64::MAX,
+        })),
+        expected(decimal(
+            "57896044618658097711785492504343953926634992332820282019728792003956564819967"
+        ))
+    );
+    assert_eq!(
+        encoded(ScVal::U256(UInt256Parts {
+            hi_hi: u64::MAX,
+            hi_lo: u64::MAX,
+            lo_hi: u64::MAX,
+            lo_lo: u64::MAX,
+        })),
+        expected(decimal(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        ))
+    );
+}
+
+#[test]
+fn encodes_arrays() {
+    assert_eq!(
+        encoded(vec_of(vec![ScVal::Bool(true), ScVal::Bool(false)])),
+        expected(vec![true, false])
+    );
+    assert_eq!(
+        encoded(vec_of(vec![
+            ScVal::U32(1),
+            ScVal::I128(Int128Parts {
+                hi: -1,
+                lo: u64::MAX,
+            }),
+        ])),
+        expected(vec![decimal("1"), decimal("-1")])
+    );
+    assert_eq!(
+        encoded(vec_of(vec![symbol("usdc"), symbol("xlm")])),
+        expected(vec!["usdc".to_string(), "xlm".to_string()])
+    );
+}
+
+#[test]
+fn binds_in_queries() {
+    let mut query = sqlx::query("INSERT INTO swaps (amount, pair, memo) VALUES ($1, $2, $3)")
+        .bind(FromScVal::from_scval(
+            ScVal::I128(Int128Parts { hi: 0, lo: 990 }),
+            &mut 0,
+        ))
+        .bind(FromScVal::from_scval(
+            vec_of(vec![symbol("usdc"), symbol("xlm")]),
+            &mut 0,
+        ))
+        .bind(FromScVal::from_scval(ScVal::Void, &mut 0));
+
+    let arguments = query.take_arguments().unwrap().unwrap();
+    assert_eq!(arguments.len(), 3);
+}
+
+#[test]
+fn unsupported_arrays_fail_to_bind() {
+    let malformed = FromScVal {
+        dbtype: Type::TEXT,
+        kind: TypeKind::GenericArray(vec![]),
+    };
+
+    let mut query = sqlx::query("SELECT $1");
+    assert!(query.try_bind(malformed).is_err());
+}
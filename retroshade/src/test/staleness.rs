@@ -0,0 +1,168 @@
+//! Snapshots ahead of the transaction's ledger, with entries modified by
+//! "future" transactions.
+
+use std::collections::HashMap;
+
+use soroban_env_host::xdr::{
+    ExtensionPoint, Hash, LedgerEntry, LedgerEntryChange, LedgerEntryChanges, LedgerEntryData,
+    OperationMeta, ScMap, ScVal, SorobanTransactionMeta, SorobanTransactionMetaExt,
+    TransactionMeta, TransactionMetaV3,
+};
+
+use crate::{
+    testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+    BuildReport, RetroshadeError, RetroshadesExecution, StaleSnapshotEntry, StalenessGuard,
+};
+
+use super::all_types::unchanged_meta;
+
+/// Ledger of the transaction, that of [`ledger_info`].
+const LEDGER_SEQ: u32 = 1000;
+
+/// A contract whose instance was last modified in `instance_modified`.
+fn snapshot(instance_modified: u32) -> MockSnapshot {
+    let mut snapshot = MockSnapshot::new();
+    for (mut entry, _) in
+        MockSnapshot::with_contract(Hash([0; 32]), b"\0asm", ScMap::default()).entries()
+    {
+        if matches!(entry.data, LedgerEntryData::ContractData(_)) {
+            entry.last_modified_ledger_seq = instance_modified;
+        }
+        snapshot.insert(entry);
+    }
+
+    snapshot
+}
+
+fn instance(snapshot: &MockSnapshot) -> LedgerEntry {
+    snapshot
+        .entries()
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .find(|entry| matches!(entry.data, LedgerEntryData::ContractData(_)))
+        .unwrap()
+}
+
+/// Meta of a transaction that updated the instance.
+fn instance_updated_meta(snapshot: &MockSnapshot) -> TransactionMeta {
+    let updated = instance(snapshot);
+    let previous = LedgerEntry {
+        last_modified_ledger_seq: LEDGER_SEQ - 10,
+        ..updated.clone()
+    };
+
+    TransactionMeta::V3(TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMeta {
+            ext: SorobanTransactionMetaExt::V0,
+            events: vec![].try_into().unwrap(),
+            return_value: ScVal::Void,
+            diagnostic_events: vec![].try_into().unwrap(),
+        }),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(
+                vec![
+                    LedgerEntryChange::State(previous),
+                    LedgerEntryChange::Updated(updated),
+                ]
+                .try_into()
+                .unwrap(),
+            ),
+        }]
+        .try_into()
+        .unwrap(),
+    })
+}
+
+fn build(
+    snapshot: &MockSnapshot,
+    meta: TransactionMeta,
+    guard: StalenessGuard,
+) -> Result<BuildReport, RetroshadeError> {
+    let envelope = EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_write_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    retroshades.set_staleness_guard(guard);
+    retroshades.build_from_envelope_and_meta_with_report(snapshot, envelope, meta, HashMap::new())
+}
+
+#[test]
+fn unchecked_by_default() {
+    let report = build(
+        &snapshot(LEDGER_SEQ + 5),
+        unchanged_meta(),
+        StalenessGuard::default(),
+    )
+    .unwrap();
+
+    assert!(report.stale_entries.is_empty());
+}
+
+#[test]
+fn future_entries_are_reported() {
+    let report = build(
+        &snapshot(LEDGER_SEQ + 5),
+        unchanged_meta(),
+        StalenessGuard::Warn,
+    )
+    .unwrap();
+
+    assert_eq!(
+        report.stale_entries,
+        vec![StaleSnapshotEntry {
+            key: instance_key(Hash([0; 32])),
+            last_modified_ledger_seq: LEDGER_SEQ + 5,
+            ledger_seq: LEDGER_SEQ,
+        }]
+    );
+}
+
+#[test]
+fn future_entries_fail_the_strict_guard() {
+    let error = build(
+        &snapshot(LEDGER_SEQ + 5),
+        unchanged_meta(),
+        StalenessGuard::Error,
+    )
+    .unwrap_err();
+
+    let RetroshadeError::StaleSnapshotEntry(stale) = error else {
+        panic!("expected a stale entry, got {:?}", error)
+    };
+    assert_eq!(stale.key, instance_key(Hash([0; 32])));
+    assert_eq!(stale.last_modified_ledger_seq, LEDGER_SEQ + 5);
+}
+
+#[test]
+fn entries_of_the_ledger_are_stale_unless_reset() {
+    // note: another transaction of the same ledger modified it.
+    let snapshot = snapshot(LEDGER_SEQ);
+    let report = build(&snapshot, unchanged_meta(), StalenessGuard::Warn).unwrap();
+    assert_eq!(report.stale_entries.len(), 1);
+
+    let report = build(
+        &snapshot,
+        instance_updated_meta(&snapshot),
+        StalenessGuard::Error,
+    )
+    .unwrap();
+    assert!(report.stale_entries.is_empty());
+}
+
+#[test]
+fn older_entries_are_not_stale() {
+    let report = build(
+        &snapshot(LEDGER_SEQ - 1),
+        unchanged_meta(),
+        StalenessGuard::Error,
+    )
+    .unwrap();
+
+    assert!(report.stale_entries.is_empty());
+}
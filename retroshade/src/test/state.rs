@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    state::KeyedEntry,
+    testutils::{ledger_info, EnvelopeBuilder, MockSnapshot},
+    RetroshadeError, RetroshadesExecution,
+};
+use soroban_env_host::{
+    xdr::{
+        ContractCodeEntry, ContractCodeEntryExt, ContractDataDurability, ContractDataEntry,
+        ExtensionPoint, Hash, LedgerEntry, LedgerEntryChange, LedgerEntryChanges, LedgerEntryData,
+        LedgerEntryExt, LedgerKey, LedgerKeyContractData, Limits, OperationMeta, ScAddress, ScVal,
+        TransactionMeta, TransactionMetaV3, WriteXdr,
+    },
+    LedgerInfo,
+};
+
+fn data_entry(key: u32) -> LedgerEntry {
+    LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            key: ScVal::U32(key),
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::U64(key as u64),
+        }),
+        ext: LedgerEntryExt::V0,
+    }
+}
+
+fn data_key(key: u32) -> LedgerKey {
+    LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::Contract(Hash([0; 32]).into()),
+        key: ScVal::U32(key),
+        durability: ContractDataDurability::Persistent,
+    })
+}
+
+/// Meta of a transaction that created the data entries with `keys`.
+fn created_meta(keys: &[u32]) -> TransactionMeta {
+    let changes: Vec<_> = keys
+        .iter()
+        .map(|key| LedgerEntryChange::Created(data_entry(*key)))
+        .collect();
+
+    TransactionMeta::V3(TransactionMetaV3 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        operations: vec![OperationMeta {
+            changes: LedgerEntryChanges(changes.try_into().unwrap()),
+        }]
+        .try_into()
+        .unwrap(),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: None,
+    })
+}
+
+/// Resets a state holding the data entries 0 to 4 with `created`, returning
+/// the keys left in order, the changed count and the forced removals count.
+fn reset(created: &[u32]) -> (Vec<u32>, usize, usize) {
+    let mut retroshades = RetroshadesExecution::new(LedgerInfo::default());
+    retroshades.target_pre_execution_state = (0..5)
+        .map(|key| KeyedEntry::new(data_entry(key), Some(100)))
+        .collect();
+
+    let changed = retroshades
+        .state_reset_to_pre_execution(created_meta(created))
+        .unwrap();
+    let left = retroshades
+        .target_pre_execution_state
+        .iter()
+        .map(|keyed| match &keyed.entry.data {
+            LedgerEntryData::ContractData(ContractDataEntry {
+                key: ScVal::U32(key),
+                ..
+            }) => *key,
+            _ => panic!("unexpected entry in the state"),
+        })
+        .collect();
+
+    (left, changed, retroshades.force_remove.len())
+}
+
+#[test]
+fn removes_the_first_entry() {
+    assert_eq!(reset(&[0]), (vec![1, 2, 3, 4], 1, 1));
+}
+
+#[test]
+fn removes_a_middle_entry() {
+    assert_eq!(reset(&[2]), (vec![0, 1, 3, 4], 1, 1));
+}
+
+#[test]
+fn removes_the_last_entry() {
+    assert_eq!(reset(&[4]), (vec![0, 1, 2, 3], 1, 1));
+}
+
+#[test]
+fn removes_multiple_entries() {
+    assert_eq!(reset(&[4, 0, 2]), (vec![1, 3], 3, 3));
+}
+
+#[test]
+fn ignores_entries_not_in_the_state() {
+    assert_eq!(reset(&[7]), (vec![0, 1, 2, 3, 4], 0, 0));
+}
+
+/// Builds a state of the data entries 0 to 4, the transaction creating 2.
+fn build(retroshades: &mut RetroshadesExecution) -> Result<(), RetroshadeError> {
+    let mut snapshot = MockSnapshot::new();
+    let mut envelope = EnvelopeBuilder::new().function("t");
+    for key in 0..5 {
+        snapshot.insert(data_entry(key));
+        envelope = envelope.read_write_key(data_key(key));
+    }
+
+    retroshades.build_from_envelope_and_meta(
+        &snapshot,
+        envelope.build(),
+        created_meta(&[2]),
+        HashMap::new(),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn building_twice_starts_over() {
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    build(&mut retroshades).unwrap();
+    let state_bytes = retroshades.encoded_inputs().unwrap().state_bytes();
+    build(&mut retroshades).unwrap();
+
+    assert_eq!(retroshades.target_pre_execution_state.len(), 4);
+    assert_eq!(retroshades.force_remove.len(), 1);
+    assert_eq!(
+        retroshades.encoded_inputs().unwrap().state_bytes(),
+        state_bytes
+    );
+}
+
+#[test]
+fn failed_builds_leave_no_state() {
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    build(&mut retroshades).unwrap();
+    retroshades.set_max_state_bytes(0);
+
+    assert!(matches!(
+        build(&mut retroshades),
+        Err(RetroshadeError::StateTooLarge { .. })
+    ));
+    assert!(retroshades.target_pre_execution_state.is_empty());
+    assert!(retroshades.force_remove.is_empty());
+    assert!(retroshades.encoded_inputs.get().is_none());
+}
+
+#[test]
+fn large_states_fail_while_fetched() {
+    let mut retroshades = RetroshadesExecution::new(ledger_info());
+    build(&mut retroshades).unwrap();
+    // note: 4 entries of the same size are left once the created one is reset.
+    let entry_bytes = retroshades.encoded_inputs().unwrap().state_bytes() / 4;
+    retroshades.set_max_state_bytes(entry_bytes);
+
+    let Err(RetroshadeError::StateTooLarge { bytes, limit }) = build(&mut retroshades) else {
+        panic!("expected the state to be too large")
+    };
+    assert_eq!(limit, entry_bytes);
+    assert_eq!(bytes, 2 * entry_bytes);
+}
+
+#[test]
+fn replacements_are_encoded_in_place() {
+    let code = LedgerEntry {
+        last_modified_ledger_seq: 7,
+        data: LedgerEntryData::ContractCode(ContractCodeEntry {
+            ext: ContractCodeEntryExt::V0,
+            hash: Hash([1; 32]),
+            code: vec![0xaa; 3].try_into().unwrap(),
+        }),
+        ext: LedgerEntryExt::V0,
+    };
+
+    // note: covers every padding of the code.
+    for len in 0..8 {
+        let mut keyed = KeyedEntry::new(code.clone(), Some(100));
+        keyed.replacement = Some(Arc::from(vec![0xbb; len]));
+
+        let fed = keyed.fed_entry().into_owned();
+        assert_eq!(keyed.code().unwrap(), vec![0xbb; len].as_slice());
+        assert_eq!(keyed.to_xdr(), fed.to_xdr(Limits::none()).unwrap());
+    }
+}
@@ -11,287 +11,275 @@
 use std::collections::HashMap;
 
 use crate::{
-    conversion::{FromScVal, TypeKind},
-    PackedEventEntry, RetroshadeExportPretty, RetroshadesExecution,
-};
-use postgres_types::Type;
-use soroban_env_host::{
-    storage::SnapshotSource,
-    xdr::{
-        ContractCodeEntry, ContractDataEntry, ContractExecutable, ExtensionPoint, Hash,
-        HostFunction, Int128Parts, InvokeContractArgs, InvokeHostFunctionOp, LedgerEntry,
-        LedgerEntryChange, LedgerEntryChanges, LedgerEntryData, LedgerEntryExt, LedgerFootprint,
-        LedgerKey, LedgerKeyContractCode, LedgerKeyContractData, MuxedAccount, Operation,
-        OperationBody, OperationMeta, ScAddress, ScContractInstance, ScMap, ScMapEntry, ScSymbol,
-        ScVal, ScVec, SequenceNumber, SorobanResources, SorobanTransactionDataExt,
-        SorobanTransactionMeta, Transaction, TransactionMeta, TransactionMetaV3,
-        TransactionV1Envelope, Uint256,
+    changes::Mismatch,
+    process_transaction,
+    testutils::{
+        assert_golden, assert_retroshade, code_key, instance_key, ledger_info_protocol,
+        EnvelopeBuilder, MockSnapshot,
     },
-    LedgerInfo,
+    OnCallFailure, ProcessOptions, RetroshadeError, RetroshadesExecution,
+};
+use soroban_env_host::xdr::{
+    ContractDataEntry, ContractExecutable, ExtensionPoint, Hash, Int128Parts, LedgerEntry,
+    LedgerEntryChange, LedgerEntryChanges, LedgerEntryData, LedgerEntryExt, Limits, OperationMeta,
+    OperationMetaV2, ScAddress, ScContractInstance, ScMap, ScMapEntry, ScVal, ScVec,
+    SorobanTransactionMeta, SorobanTransactionMetaExt, SorobanTransactionMetaV2, TransactionMeta,
+    TransactionMetaV3, TransactionMetaV4, TransactionV1Envelope, WriteXdr,
 };
 
-use std::rc::Rc;
-
-pub struct TestDynamicSnapshot {}
-
-impl SnapshotSource for TestDynamicSnapshot {
-    fn get(
-        &self,
-        key: &std::rc::Rc<soroban_env_host::xdr::LedgerKey>,
-    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
-    {
-        let entry = match key.as_ref() {
-            LedgerKey::ContractCode(_) => LedgerEntry {
-                last_modified_ledger_seq: 0,
-                ext: LedgerEntryExt::V0,
-                data: LedgerEntryData::ContractCode(ContractCodeEntry {
-                    ext: soroban_env_host::xdr::ContractCodeEntryExt::V0,
-                    hash: Hash([0;32]),
-                    code: std::fs::read("/Users/tdep/projects/retroshade/examples/storage/target/wasm32-unknown-unknown/release/soroban_hello_world_contract.wasm").unwrap().try_into().unwrap()
-                })
-            },
-            LedgerKey::ContractData(_) => LedgerEntry { last_modified_ledger_seq: 0, data: LedgerEntryData::ContractData(ContractDataEntry {
-                ext: ExtensionPoint::V0,
-                contract: ScAddress::Contract(Hash([0;32]).into()),
-                durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
-                key: soroban_env_host::xdr::ScVal::LedgerKeyContractInstance,
-                val: ScVal::ContractInstance(ScContractInstance {
-                    executable: ContractExecutable::Wasm(Hash([0; 32])),
-                    storage: Some(ScMap(vec![ScMapEntry {
-                        key: ScVal::I32(0),
-                        val: ScVal::I128(Int128Parts {hi: 0, lo: 2})
-                    }].try_into().unwrap()))
-                })
-            }), ext: LedgerEntryExt::V0 },
-            _ => panic!()
-        };
-
-        Ok(Some((Rc::new(entry), Some(10000))))
+use super::examples::example_wasm;
+
+/// Instance storage holding `0 -> stored`.
+fn storage(stored: u64) -> ScMap {
+    ScMap(
+        vec![ScMapEntry {
+            key: ScVal::I32(0),
+            val: ScVal::I128(Int128Parts { hi: 0, lo: stored }),
+        }]
+        .try_into()
+        .unwrap(),
+    )
+}
+
+fn instance(stored: u64) -> LedgerEntry {
+    LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data: LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([0; 32]).into()),
+            durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
+            key: ScVal::LedgerKeyContractInstance,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(Hash([0; 32])),
+                storage: Some(storage(stored)),
+            }),
+        }),
+        ext: LedgerEntryExt::V0,
     }
 }
 
-#[test]
-fn simple() {
-    let mut retroshades = RetroshadesExecution::new(LedgerInfo {
-        protocol_version: 25,
-        sequence_number: 1000,
-        timestamp: 200,
-        network_id: [0; 32],
-        base_reserve: 1,
-        min_temp_entry_ttl: 300,
-        min_persistent_entry_ttl: 400,
-        max_entry_ttl: 500000,
-    });
-
-    let snapshot_source = TestDynamicSnapshot {};
-
-    let _put_envelope = TransactionV1Envelope {
-        signatures: vec![].try_into().unwrap(),
-        tx: Transaction {
-            source_account: MuxedAccount::Ed25519(Uint256([0; 32])),
-            fee: 0,
-            seq_num: SequenceNumber(1),
-            cond: soroban_env_host::xdr::Preconditions::None,
-            memo: soroban_env_host::xdr::Memo::None,
-            ext: soroban_env_host::xdr::TransactionExt::V1(
-                soroban_env_host::xdr::SorobanTransactionData {
-                    ext: SorobanTransactionDataExt::V0,
-                    resources: SorobanResources {
-                        footprint: LedgerFootprint {
-                            read_only: vec![LedgerKey::ContractCode(LedgerKeyContractCode {
-                                hash: Hash([0; 32]),
-                            })]
-                            .try_into()
-                            .unwrap(),
-                            read_write: vec![LedgerKey::ContractData(LedgerKeyContractData {
-                                contract: ScAddress::Contract(Hash([0; 32]).into()),
-                                key: ScVal::LedgerKeyContractInstance,
-                                durability:
-                                    soroban_env_host::xdr::ContractDataDurability::Persistent,
-                            })]
-                            .try_into()
-                            .unwrap(),
-                        },
-                        instructions: 10000000,
-                        disk_read_bytes: 1000000,
-                        write_bytes: 100000,
-                    },
-                    resource_fee: 10000000,
-                },
-            ),
-            operations: vec![Operation {
-                source_account: None,
-                body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
-                    host_function: HostFunction::InvokeContract(InvokeContractArgs {
-                        contract_address: ScAddress::Contract(Hash([0; 32]).into()),
-                        function_name: ScSymbol("put".try_into().unwrap()),
-                        args: vec![].try_into().unwrap(),
-                    }),
-                    auth: vec![].try_into().unwrap(),
-                }),
-            }]
-            .try_into()
-            .unwrap(),
-        },
-    };
+/// The state after the transaction was applied.
+fn snapshot() -> MockSnapshot {
+    MockSnapshot::with_contract(
+        Hash([0; 32]),
+        &example_wasm("storage", "soroban_hello_world_contract"),
+        storage(2),
+    )
+}
 
-    let t_envelope = TransactionV1Envelope {
-        signatures: vec![].try_into().unwrap(),
-        tx: Transaction {
-            source_account: MuxedAccount::Ed25519(Uint256([0; 32])),
-            fee: 0,
-            seq_num: SequenceNumber(1),
-            cond: soroban_env_host::xdr::Preconditions::None,
-            memo: soroban_env_host::xdr::Memo::None,
-            ext: soroban_env_host::xdr::TransactionExt::V1(
-                soroban_env_host::xdr::SorobanTransactionData {
-                    ext: SorobanTransactionDataExt::V0,
-                    resources: SorobanResources {
-                        footprint: LedgerFootprint {
-                            read_only: vec![LedgerKey::ContractCode(LedgerKeyContractCode {
-                                hash: Hash([0; 32]),
-                            })]
-                            .try_into()
-                            .unwrap(),
-                            read_write: vec![LedgerKey::ContractData(LedgerKeyContractData {
-                                contract: ScAddress::Contract(Hash([0; 32]).into()),
-                                key: ScVal::LedgerKeyContractInstance,
-                                durability:
-                                    soroban_env_host::xdr::ContractDataDurability::Persistent,
-                            })]
-                            .try_into()
-                            .unwrap(),
-                        },
-                        instructions: 10000000,
-                        disk_read_bytes: 1000000,
-                        write_bytes: 100000,
-                    },
-                    resource_fee: 10000000,
-                },
-            ),
-            operations: vec![Operation {
-                source_account: None,
-                body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
-                    host_function: HostFunction::InvokeContract(InvokeContractArgs {
-                        contract_address: ScAddress::Contract(Hash([0; 32]).into()),
-                        function_name: ScSymbol("t".try_into().unwrap()),
-                        args: vec![].try_into().unwrap(),
-                    }),
-                    auth: vec![].try_into().unwrap(),
-                }),
-            }]
-            .try_into()
-            .unwrap(),
-        },
-    };
+fn t_envelope() -> TransactionV1Envelope {
+    EnvelopeBuilder::new()
+        .function("t")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_write_key(instance_key(Hash([0; 32])))
+        .build()
+}
+
+/// The transaction updated the instance from `0 -> 1` to `0 -> 2`.
+fn instance_changes() -> LedgerEntryChanges {
+    LedgerEntryChanges(
+        vec![
+            LedgerEntryChange::State(instance(1)),
+            LedgerEntryChange::Updated(instance(2)),
+        ]
+        .try_into()
+        .unwrap(),
+    )
+}
 
-    let meta = TransactionMetaV3 {
+fn v3_meta() -> TransactionMetaV3 {
+    TransactionMetaV3 {
         ext: ExtensionPoint::V0,
         tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
         tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
         soroban_meta: Some(SorobanTransactionMeta {
-            ext: soroban_env_host::xdr::SorobanTransactionMetaExt::V0,
+            ext: SorobanTransactionMetaExt::V0,
             events: vec![].try_into().unwrap(),
             return_value: ScVal::Vec(Some(ScVec(vec![].try_into().unwrap()))),
             diagnostic_events: vec![].try_into().unwrap(),
         }),
         operations: vec![OperationMeta {
-            changes: LedgerEntryChanges(
-                vec![
-                    LedgerEntryChange::State(LedgerEntry {
-                        last_modified_ledger_seq: 0,
-                        data: LedgerEntryData::ContractData(ContractDataEntry {
-                            ext: ExtensionPoint::V0,
-                            contract: ScAddress::Contract(Hash([0; 32]).into()),
-                            durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
-                            key: soroban_env_host::xdr::ScVal::LedgerKeyContractInstance,
-                            val: ScVal::ContractInstance(ScContractInstance {
-                                executable: ContractExecutable::Wasm(Hash([0; 32])),
-                                storage: Some(ScMap(
-                                    vec![ScMapEntry {
-                                        key: ScVal::I32(0),
-                                        val: ScVal::I128(Int128Parts { hi: 0, lo: 1 }),
-                                    }]
-                                    .try_into()
-                                    .unwrap(),
-                                )),
-                            }),
-                        }),
-                        ext: LedgerEntryExt::V0,
-                    }),
-                    LedgerEntryChange::Updated(LedgerEntry {
-                        last_modified_ledger_seq: 0,
-                        data: LedgerEntryData::ContractData(ContractDataEntry {
-                            ext: ExtensionPoint::V0,
-                            contract: ScAddress::Contract(Hash([0; 32]).into()),
-                            durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
-                            key: soroban_env_host::xdr::ScVal::LedgerKeyContractInstance,
-                            val: ScVal::ContractInstance(ScContractInstance {
-                                executable: ContractExecutable::Wasm(Hash([0; 32])),
-                                storage: Some(ScMap(
-                                    vec![ScMapEntry {
-                                        key: ScVal::I32(0),
-                                        val: ScVal::I128(Int128Parts { hi: 0, lo: 2 }),
-                                    }]
-                                    .try_into()
-                                    .unwrap(),
-                                )),
-                            }),
-                        }),
-                        ext: LedgerEntryExt::V0,
-                    }),
-                ]
-                .try_into()
-                .unwrap(),
-            ),
+            changes: instance_changes(),
         }]
         .try_into()
         .unwrap(),
-    };
+    }
+}
+
+#[test]
+fn simple() {
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+
+    let _put_envelope = EnvelopeBuilder::new()
+        .function("put")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_write_key(instance_key(Hash([0; 32])))
+        .build();
+
+    let meta = v3_meta();
 
     retroshades
         .build_from_envelope_and_meta(
-            Box::new(snapshot_source),
-            t_envelope,
-            TransactionMeta::V3(meta),
+            &snapshot(),
+            t_envelope(),
+            TransactionMeta::V3(meta.clone()),
             HashMap::new(),
         )
         .unwrap();
 
     let retroshades_result = retroshades.retroshade().unwrap();
 
+    assert!(retroshades_result.state.state_changed);
+    assert_eq!(retroshades_result.state.entries_reset, 1);
+    assert!(!retroshades_result.state.binaries_replaced);
+
     // println!("{:?}", retroshades_result.diagnostic);
 
+    // the re-execution writes 0 -> 2_i128 to the instance, same as on-chain.
+    assert!(retroshades_result.compare_with_meta(&meta).is_empty());
+
+    let mut diverging_meta = meta.clone();
+    let mut operations = diverging_meta.operations.to_vec();
+    let mut changes = operations[0].changes.0.to_vec();
+    changes[1] = LedgerEntryChange::Updated(instance(3));
+    operations[0].changes = LedgerEntryChanges(changes.try_into().unwrap());
+    diverging_meta.operations = operations.try_into().unwrap();
+
+    let mismatches = retroshades_result.compare_with_meta(&diverging_meta);
+    assert_eq!(mismatches.len(), 1);
+    assert!(matches!(mismatches[0], Mismatch::ValueDiffers { .. }));
+
+    assert_golden("storage_retroshades", &retroshades_result.retroshades);
+
+    let retroshades_pretty = retroshades.pack_result(retroshades_result.clone()).unwrap();
+
+    assert_eq!(retroshades_pretty.retroshades.len(), 1);
+    let retroshade = &retroshades_pretty.retroshades[0];
+    assert_retroshade!(
+        retroshade,
+        target: "test",
+        contract: "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4",
+        fields: {
+            "amount" => numeric "2",
+            "test" => text "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4",
+        },
+    );
+    assert_eq!(retroshade.context, retroshades.tx_context());
     assert_eq!(
-        "[{\"contract_id\":\"0000000000000000000000000000000000000000000000000000000000000000\",\"target\":{\"symbol\":\"test\"},\"event_object\":{\"map\":[{\"key\":{\"symbol\":\"amount\"},\"val\":{\"i128\":\"2\"}},{\"key\":{\"symbol\":\"test\"},\"val\":{\"address\":\"CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4\"}}]}}]",
-        serde_json::to_string(&retroshades_result.retroshades).unwrap()
+        retroshade.source_account,
+        retroshades.source_account_strkey().unwrap()
     );
+    assert_eq!(retroshade.emit_index, 0);
+    assert_eq!(
+        retroshade.event_object_xdr,
+        retroshades_result.retroshades[0]
+            .event_object
+            .to_xdr(Limits::none())
+            .unwrap()
+    );
+}
 
-    let retroshades_pretty = retroshades.retroshade_packed().unwrap();
+/// Protocol 23 ledgers carry v4 meta, the reset reads the changes from there.
+#[test]
+fn resets_state_from_v4_meta() {
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(23));
 
-    assert_eq!(
-        retroshades_pretty.retroshades,
-        vec![RetroshadeExportPretty {
-            contract_id: "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4".to_string(),
-            target: "test".to_string(),
-            event: vec![
-                PackedEventEntry {
-                    name: "amount".to_string(),
-                    value: FromScVal {
-                        dbtype: Type::NUMERIC,
-                        kind: TypeKind::Numeric("2".to_string())
-                    }
-                },
-                PackedEventEntry {
-                    name: "test".to_string(),
-                    value: FromScVal {
-                        dbtype: Type::TEXT,
-                        kind: TypeKind::Text(
-                            "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4".to_string()
-                        )
-                    }
-                }
-            ]
+    let meta = TransactionMetaV4 {
+        ext: ExtensionPoint::V0,
+        tx_changes_before: LedgerEntryChanges(vec![].try_into().unwrap()),
+        operations: vec![OperationMetaV2 {
+            ext: ExtensionPoint::V0,
+            changes: instance_changes(),
+            events: vec![].try_into().unwrap(),
         }]
+        .try_into()
+        .unwrap(),
+        tx_changes_after: LedgerEntryChanges(vec![].try_into().unwrap()),
+        soroban_meta: Some(SorobanTransactionMetaV2 {
+            ext: SorobanTransactionMetaExt::V0,
+            return_value: Some(ScVal::Vec(Some(ScVec(vec![].try_into().unwrap())))),
+        }),
+        events: vec![].try_into().unwrap(),
+        diagnostic_events: vec![].try_into().unwrap(),
+    };
+
+    retroshades
+        .build_from_envelope_and_meta(
+            &snapshot(),
+            t_envelope(),
+            TransactionMeta::V4(meta),
+            HashMap::new(),
+        )
+        .unwrap();
+    let result = retroshades.retroshade_packed().unwrap();
+
+    assert_eq!(result.state.entries_reset, 1);
+    assert_eq!(result.retroshades.len(), 1);
+    assert_retroshade!(
+        result.retroshades[0],
+        target: "test",
+        fields: { "amount" => numeric "2" },
     );
 }
+
+#[test]
+fn processes_in_one_call() {
+    let result = process_transaction(
+        &snapshot(),
+        t_envelope(),
+        TransactionMeta::V3(v3_meta()),
+        ledger_info_protocol(25),
+        HashMap::new(),
+        ProcessOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(result.state.entries_reset, 1);
+    assert_eq!(result.retroshades.len(), 1);
+    assert_retroshade!(
+        result.retroshades[0],
+        target: "test",
+        fields: { "amount" => numeric "2" },
+    );
+
+    // note: same as the step by step pipeline, seeded with the transaction's
+    // hash.
+    let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+    retroshades
+        .build_from_envelope_and_meta(
+            &snapshot(),
+            t_envelope(),
+            TransactionMeta::V3(v3_meta()),
+            HashMap::new(),
+        )
+        .unwrap();
+    let seeded = retroshades
+        .retroshade_packed_seeded(retroshades.tx_context().tx_hash.0)
+        .unwrap();
+    assert_eq!(result.retroshades, seeded.retroshades);
+}
+
+#[test]
+fn processing_applies_the_options() {
+    let envelope = EnvelopeBuilder::new()
+        .function("missing")
+        .read_only_key(code_key(Hash([0; 32])))
+        .read_write_key(instance_key(Hash([0; 32])))
+        .build();
+    let options = ProcessOptions {
+        on_call_failure: OnCallFailure::Error,
+        ..Default::default()
+    };
+
+    let error = process_transaction(
+        &snapshot(),
+        envelope,
+        TransactionMeta::V3(v3_meta()),
+        ledger_info_protocol(25),
+        HashMap::new(),
+        options,
+    )
+    .unwrap_err();
+
+    assert!(matches!(error, RetroshadeError::ContractCallFailed { .. }));
+}
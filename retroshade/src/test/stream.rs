@@ -0,0 +1,34 @@
+use soroban_env_host::xdr::DiagnosticEvent;
+
+use crate::changes::EntryChange;
+
+use super::fixture::mainnet_execution;
+
+#[test]
+fn streamed_payload_matches_collected() {
+    let retroshades = mainnet_execution();
+
+    let collected = retroshades.retroshade().unwrap();
+    // the invocation's function calls and returns at least.
+    assert!(collected.diagnostic.len() > 1);
+    assert!(!collected.ledger_changes.is_empty());
+
+    let mut diagnostic = Vec::new();
+    let mut ledger_changes = Vec::new();
+    let streamed = retroshades
+        .retroshade_streaming(&mut (
+            |event: DiagnosticEvent| diagnostic.push(event),
+            |change: EntryChange| ledger_changes.push(change),
+        ))
+        .unwrap();
+
+    assert!(streamed.diagnostic.is_empty());
+    assert!(streamed.ledger_changes.is_empty());
+    assert_eq!(diagnostic, collected.diagnostic);
+    assert_eq!(ledger_changes, collected.ledger_changes);
+    assert_eq!(streamed.invoke_result, collected.invoke_result);
+    assert_eq!(
+        serde_json::to_value(&streamed.retroshades).unwrap(),
+        serde_json::to_value(&collected.retroshades).unwrap()
+    );
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::{
+    export::jsonl::JsonlSink,
+    sink::deliver_all,
+    telemetry,
+    testutils::{ledger_info_protocol, Fixture},
+    RetroshadesExecution,
+};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+use super::fixture::MAINNET_FIXTURE;
+
+/// Totals by metric name, summing the counters and counting the values
+/// recorded by the histograms.
+fn totals(snapshotter: &Snapshotter) -> HashMap<String, u64> {
+    let mut totals = HashMap::new();
+    for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+        let value = match value {
+            DebugValue::Counter(value) => value,
+            DebugValue::Histogram(values) => values.len() as u64,
+            DebugValue::Gauge(_) => continue,
+        };
+        *totals.entry(key.key().name().to_string()).or_default() += value;
+    }
+
+    totals
+}
+
+#[test]
+fn pipeline_increments_the_counters() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    let (footprint, retroshades_emitted) = metrics::with_local_recorder(&recorder, || {
+        let fixture = Fixture::load(MAINNET_FIXTURE);
+
+        let mut retroshades = RetroshadesExecution::new(ledger_info_protocol(25));
+        let footprint = retroshades
+            .build_from_envelope_and_meta_with_report(
+                &fixture.snapshot,
+                fixture.envelope,
+                fixture.meta,
+                HashMap::new(),
+            )
+            .unwrap()
+            .footprint
+            .len() as u64;
+
+        let result = retroshades.retroshade_packed().unwrap();
+        deliver_all(&mut JsonlSink::new(Vec::new()), &result).unwrap();
+
+        (footprint, result.retroshades.len() as u64)
+    });
+
+    let totals = totals(&snapshotter);
+    let total = |name: &str| totals.get(name).copied().unwrap_or_default();
+    assert_eq!(total(telemetry::EXECUTIONS), 1);
+    assert_eq!(total(telemetry::EXECUTION_DURATION), 1);
+    assert_eq!(total(telemetry::BUDGET_CPU), 1);
+    assert_eq!(total(telemetry::SNAPSHOT_FETCH_DURATION), footprint);
+    assert_eq!(total(telemetry::RETROSHADES_EMITTED), retroshades_emitted);
+    assert_eq!(total(telemetry::SINK_DELIVERIES), retroshades_emitted);
+    assert_eq!(total(telemetry::CONVERSION_FAILURES), 0);
+}
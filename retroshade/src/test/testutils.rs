@@ -0,0 +1,153 @@
+use postgres_types::Type;
+use soroban_env_host::xdr::{
+    FeeBumpTransactionInnerTx, Hash, HostFunction, MuxedAccount, OperationBody, ScSymbol, ScVal,
+    TransactionEnvelope, TransactionExt, Uint256,
+};
+
+use crate::{
+    conversion::{FromScVal, TypeKind},
+    network::Network,
+    testutils::{
+        assert_retroshade, code_key, instance_key, ledger_info, ledger_info_protocol,
+        EnvelopeBuilder, ExpectedRetroshade, LedgerInfoBuilder,
+    },
+    PackedEventEntry, RetroshadeExportPretty, TxContext,
+};
+
+fn export(target: &str, event: Vec<PackedEventEntry>) -> RetroshadeExportPretty {
+    RetroshadeExportPretty {
+        contract_id: stellar_strkey::Contract([0; 32]).to_string().into(),
+        target: target.into(),
+        event,
+        context: TxContext {
+            tx_hash: Hash([0; 32]),
+            ledger_seq: 1000,
+            closed_at: 200,
+            op_index: 0,
+        },
+        source_account: stellar_strkey::ed25519::PublicKey([0; 32]).to_string(),
+        emit_index: 0,
+        event_object_xdr: vec![],
+    }
+}
+
+#[test]
+fn envelope_builder() {
+    let builder = EnvelopeBuilder::new()
+        .source([1; 32])
+        .contract(Hash([2; 32]))
+        .function("put")
+        .arg(ScVal::U32(7))
+        .read_only_key(code_key(Hash([3; 32])))
+        .read_write_key(instance_key(Hash([2; 32])))
+        .instructions(5000);
+    let envelope = builder.clone().build();
+
+    assert_eq!(
+        envelope.tx.source_account,
+        MuxedAccount::Ed25519(Uint256([1; 32]))
+    );
+    let TransactionExt::V1(soroban_data) = &envelope.tx.ext else {
+        panic!("not a soroban transaction");
+    };
+    assert_eq!(soroban_data.resources.instructions, 5000);
+    assert_eq!(
+        soroban_data.resources.footprint.read_only.to_vec(),
+        vec![code_key(Hash([3; 32]))]
+    );
+    assert_eq!(
+        soroban_data.resources.footprint.read_write.to_vec(),
+        vec![instance_key(Hash([2; 32]))]
+    );
+
+    let OperationBody::InvokeHostFunction(op) = &envelope.tx.operations[0].body else {
+        panic!("not an invocation");
+    };
+    let HostFunction::InvokeContract(invocation) = &op.host_function else {
+        panic!("not a contract call");
+    };
+    assert_eq!(
+        invocation.function_name,
+        ScSymbol("put".try_into().unwrap())
+    );
+    assert_eq!(invocation.args.to_vec(), vec![ScVal::U32(7)]);
+
+    let TransactionEnvelope::TxFeeBump(fee_bump) = builder.build_fee_bump([4; 32]) else {
+        panic!("not a fee bump");
+    };
+    assert_eq!(
+        fee_bump.tx.fee_source,
+        MuxedAccount::Ed25519(Uint256([4; 32]))
+    );
+    let FeeBumpTransactionInnerTx::Tx(inner) = fee_bump.tx.inner_tx;
+    assert_eq!(inner, envelope);
+}
+
+#[test]
+fn expected_retroshade_ignores_field_order() {
+    let retroshade = export(
+        "test",
+        vec![
+            PackedEventEntry {
+                name: "test".into(),
+                value: FromScVal {
+                    dbtype: Type::TEXT,
+                    kind: TypeKind::Text("G...".to_string()),
+                },
+            },
+            PackedEventEntry {
+                name: "amount".into(),
+                value: FromScVal {
+                    dbtype: Type::NUMERIC,
+                    kind: TypeKind::Numeric("2".to_string()),
+                },
+            },
+        ],
+    );
+
+    assert_retroshade!(
+        retroshade,
+        target: "test",
+        fields: { "amount" => numeric 2, "test" => text "G..." },
+    );
+
+    let differences = ExpectedRetroshade::new()
+        .target("other")
+        .field("amount", TypeKind::Numeric("3".to_string()))
+        .field("paused", TypeKind::Boolean(false))
+        .differences(&retroshade);
+    assert_eq!(
+        differences,
+        vec![
+            r#"target: expected "other", got "test""#.to_string(),
+            r#"amount: expected Numeric("3"), got Numeric("2")"#.to_string(),
+            "paused: missing".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn ledger_info_defaults() {
+    let standalone = ledger_info();
+    assert_eq!(standalone.protocol_version, 22);
+    assert_eq!(standalone.network_id, Network::Standalone.network_id());
+    assert_eq!(
+        standalone.min_persistent_entry_ttl,
+        Network::Standalone.defaults().min_persistent_entry_ttl
+    );
+
+    assert_eq!(ledger_info_protocol(23).protocol_version, 23);
+    assert_eq!(
+        ledger_info_protocol(23).sequence_number,
+        standalone.sequence_number
+    );
+
+    let pubnet = LedgerInfoBuilder::new()
+        .network(Network::Pubnet)
+        .sequence(5)
+        .timestamp(6)
+        .build();
+    assert_eq!(pubnet.network_id, Network::Pubnet.network_id());
+    assert_eq!(pubnet.base_reserve, 5_000_000);
+    assert_eq!((pubnet.sequence_number, pubnet.timestamp), (5, 6));
+}
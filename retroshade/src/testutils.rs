@@ -1,50 +1,915 @@
-use std::rc::Rc;
+//! Helpers to re-execute transactions against mocked state, for this crate's
+//! tests and the tests of crates embedding it. Enabled with the `testutils`
+//! feature, which only adds `proptest`; crates that don't need the
+//! standalone binary can also turn off the default `cli` feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! retroshade = { version = "0.1", default-features = false, features = ["testutils"] }
+//! ```
+//!
+//! The state is mocked in memory. Only [`Fixture`], [`assert_golden`] and,
+//! with the `http` feature, `RpcCase::record_fixture` touch files, relative
+//! paths being resolved from the crate under test like `cargo test` does.
+//!
+//! ```
+//! use std::rc::Rc;
+//!
+//! use retroshade::{
+//!     soroban_env_host::{
+//!         storage::SnapshotSource,
+//!         xdr::{Hash, ScMap, ScVal},
+//!     },
+//!     testutils::{code_key, instance_key, ledger_info, EnvelopeBuilder, MockSnapshot},
+//! };
+//!
+//! // your contract's wasm, e.g. built with `soroban contract build`.
+//! let wasm = b"\0asm\x01\0\0\0";
+//! let contract = Hash([1; 32]);
+//!
+//! let snapshot = MockSnapshot::with_contract(contract.clone(), wasm, ScMap::default());
+//! assert!(snapshot
+//!     .get(&Rc::new(instance_key(contract.clone())))
+//!     .unwrap()
+//!     .is_some());
+//!
+//! let envelope = EnvelopeBuilder::new()
+//!     .contract(contract.clone())
+//!     .function("deposit")
+//!     .arg(ScVal::U32(250))
+//!     .read_only_key(code_key(Hash([0; 32])))
+//!     .read_write_key(instance_key(contract))
+//!     .build();
+//! assert_eq!(envelope.tx.operations.len(), 1);
+//!
+//! // the snapshot, envelope and the transaction's meta are then replayed
+//! // with `RetroshadesExecution::new(ledger_info())`, and the retroshades
+//! // checked with `assert_retroshade!`.
+//! assert_eq!(ledger_info().sequence_number, 1000);
+//! ```
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use soroban_env_host::{
     storage::{EntryWithLiveUntil, SnapshotSource},
     xdr::{
-        AccountEntry, ContractCodeEntry, ContractDataEntry, ContractExecutable, ExtensionPoint,
-        Hash, HostFunction, InvokeContractArgs, InvokeHostFunctionOp, LedgerEntry,
-        LedgerEntryChanges, LedgerEntryData, LedgerEntryExt, LedgerFootprint, LedgerKey,
-        LedgerKeyContractCode, LedgerKeyContractData, Limits, MuxedAccount, Operation,
-        OperationBody, OperationMeta, PublicKey, ReadXdr, ScAddress, ScContractInstance, ScMap,
-        ScSymbol, ScVal, ScVec, SequenceNumber, SorobanResources, SorobanTransactionMeta,
-        Thresholds, Transaction, TransactionMetaV3, TransactionV1Envelope, Uint256, WriteXdr,
+        AccountId, ContractCodeEntry, ContractCodeEntryExt, ContractDataDurability,
+        ContractDataEntry, ContractExecutable, Duration, ExtensionPoint, FeeBumpTransaction,
+        FeeBumpTransactionEnvelope, FeeBumpTransactionExt, FeeBumpTransactionInnerTx, Hash,
+        HostFunction, Int128Parts, Int256Parts, InvokeContractArgs, InvokeHostFunctionOp,
+        LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerFootprint, LedgerKey,
+        LedgerKeyContractCode, LedgerKeyContractData, Memo, MuxedAccount, Operation, OperationBody,
+        Preconditions, PublicKey, ScAddress, ScBytes, ScContractInstance, ScError, ScMap,
+        ScMapEntry, ScString, ScSymbol, ScVal, ScVec, SequenceNumber, SorobanResources,
+        SorobanTransactionData, SorobanTransactionDataExt, TimePoint, Transaction,
+        TransactionEnvelope, TransactionExt, TransactionMeta, TransactionV1Envelope, UInt128Parts,
+        UInt256Parts, Uint256,
     },
-    LedgerInfo,
+    HostError, LedgerInfo,
 };
 
-pub struct TestDynamicSnapshot {}
+use proptest::{collection::vec, prelude::*};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
-impl SnapshotSource for TestDynamicSnapshot {
-    fn get(
-        &self,
-        key: &std::rc::Rc<soroban_env_host::xdr::LedgerKey>,
-    ) -> Result<Option<soroban_env_host::storage::EntryWithLiveUntil>, soroban_env_host::HostError>
-    {
-        let entry = match key.as_ref() {
-            LedgerKey::ContractCode(_) => LedgerEntry {
-                last_modified_ledger_seq: 0,
-                ext: LedgerEntryExt::V0,
-                data: LedgerEntryData::ContractCode(ContractCodeEntry {
-                    ext: soroban_env_host::xdr::ContractCodeEntryExt::V0,
-                    hash: Hash([0;32]),
-                    code: std::fs::read("/Users/tdep/projects/retroshade/examples/hello_world/target/wasm32-unknown-unknown/release/soroban_hello_world_contract.wasm").unwrap().try_into().unwrap()
-                })
-            },
-            LedgerKey::ContractData(_) => LedgerEntry { last_modified_ledger_seq: 0, data: LedgerEntryData::ContractData(ContractDataEntry {
+pub use crate::assert_retroshade;
+use crate::{
+    conversion::TypeKind,
+    fixture,
+    network::Network,
+    snapshot::{MemorySnapshot, SnapshotSourceExt},
+    ReplacementEntry, RetroshadeExecutionResultPretty, RetroshadeExportPretty,
+    RetroshadesExecution,
+};
+#[cfg(feature = "http")]
+use crate::{
+    network::network_id,
+    rpc::{RpcClient, RpcSnapshot},
+};
+
+/// Live until ledger of the mocked entries, past the sequence of any test
+/// ledger.
+pub const MOCK_LIVE_UNTIL: u32 = 10000;
+
+/// In-memory state serving mocked contracts. Keys it doesn't hold aren't
+/// found, as if they didn't exist on the ledger.
+#[derive(Clone, Default)]
+pub struct MockSnapshot {
+    entries: MemorySnapshot,
+}
+
+impl MockSnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// State holding the contract `contract_id`, running `wasm` and with
+    /// `instance_storage` as its instance storage.
+    pub fn with_contract(contract_id: Hash, wasm: &[u8], instance_storage: ScMap) -> Self {
+        Self::new().contract(contract_id, wasm, instance_storage)
+    }
+
+    /// State holding the contract `contract_id` running `wasm`, with its code
+    /// keyed by its actual hash, which is returned. The instance storage is
+    /// empty.
+    pub fn with_real_hash(contract_id: Hash, wasm: &[u8]) -> (Self, Hash) {
+        let code_hash = Hash(Sha256::digest(wasm).into());
+        let snapshot = Self::new().contract_with_code_hash(
+            contract_id,
+            code_hash.clone(),
+            wasm,
+            ScMap::default(),
+        );
+
+        (snapshot, code_hash)
+    }
+
+    /// Adds a contract to the state. Its code is keyed by the all-zero hash,
+    /// the host doesn't check it against the wasm.
+    pub fn contract(self, contract_id: Hash, wasm: &[u8], instance_storage: ScMap) -> Self {
+        self.contract_with_code_hash(contract_id, Hash([0; 32]), wasm, instance_storage)
+    }
+
+    fn contract_with_code_hash(
+        mut self,
+        contract_id: Hash,
+        code_hash: Hash,
+        wasm: &[u8],
+        instance_storage: ScMap,
+    ) -> Self {
+        self.insert(LedgerEntry {
+            last_modified_ledger_seq: 0,
+            data: LedgerEntryData::ContractCode(ContractCodeEntry {
+                ext: ContractCodeEntryExt::V0,
+                hash: code_hash.clone(),
+                code: wasm.try_into().expect("wasm too large"),
+            }),
+            ext: LedgerEntryExt::V0,
+        });
+        self.insert(LedgerEntry {
+            last_modified_ledger_seq: 0,
+            data: LedgerEntryData::ContractData(ContractDataEntry {
                 ext: ExtensionPoint::V0,
-                contract: ScAddress::Contract(Hash([0;32])),
-                durability: soroban_env_host::xdr::ContractDataDurability::Persistent,
-                key: soroban_env_host::xdr::ScVal::LedgerKeyContractInstance,
+                contract: ScAddress::Contract(contract_id.into()),
+                key: ScVal::LedgerKeyContractInstance,
+                durability: ContractDataDurability::Persistent,
                 val: ScVal::ContractInstance(ScContractInstance {
-                    executable: ContractExecutable::Wasm(Hash([0; 32])),
-                    storage: Some(ScMap(vec![].try_into().unwrap()))
-                })
-            }), ext: LedgerEntryExt::V0 },
-            _ => panic!()
+                    executable: ContractExecutable::Wasm(code_hash),
+                    storage: Some(instance_storage),
+                }),
+            }),
+            ext: LedgerEntryExt::V0,
+        });
+
+        self
+    }
+
+    /// Adds or replaces an entry, live until [`MOCK_LIVE_UNTIL`].
+    pub fn insert(&mut self, entry: LedgerEntry) {
+        self.entries
+            .insert(entry, Some(MOCK_LIVE_UNTIL))
+            .expect("entry without a ledger key");
+    }
+
+    pub fn entries(&self) -> Vec<(LedgerEntry, Option<u32>)> {
+        self.entries.entries()
+    }
+}
+
+impl SnapshotSource for MockSnapshot {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        self.entries.get(key)
+    }
+}
+
+impl SnapshotSourceExt for MockSnapshot {}
+
+/// Protocol of [`ledger_info`].
+pub const DEFAULT_PROTOCOL: u32 = 22;
+
+/// Ledger 1000 of a standalone network on protocol 22, with the settings the
+/// standalone binary defaults to.
+pub fn ledger_info() -> LedgerInfo {
+    LedgerInfoBuilder::new().build()
+}
+
+/// Same as [`ledger_info`] on another protocol.
+pub fn ledger_info_protocol(protocol_version: u32) -> LedgerInfo {
+    LedgerInfoBuilder::new().protocol(protocol_version).build()
+}
+
+/// Tweaks [`ledger_info`].
+#[derive(Clone, Debug)]
+pub struct LedgerInfoBuilder {
+    ledger_info: LedgerInfo,
+}
+
+impl Default for LedgerInfoBuilder {
+    fn default() -> Self {
+        let network = Network::Standalone;
+        let defaults = network.defaults();
+
+        Self {
+            ledger_info: LedgerInfo {
+                protocol_version: DEFAULT_PROTOCOL,
+                sequence_number: 1000,
+                timestamp: 200,
+                network_id: network.network_id(),
+                base_reserve: defaults.base_reserve,
+                min_temp_entry_ttl: defaults.min_temp_entry_ttl,
+                min_persistent_entry_ttl: defaults.min_persistent_entry_ttl,
+                max_entry_ttl: defaults.max_entry_ttl,
+            },
+        }
+    }
+}
+
+impl LedgerInfoBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn protocol(mut self, protocol_version: u32) -> Self {
+        self.ledger_info.protocol_version = protocol_version;
+        self
+    }
+
+    pub fn sequence(mut self, sequence_number: u32) -> Self {
+        self.ledger_info.sequence_number = sequence_number;
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.ledger_info.timestamp = timestamp;
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        let defaults = network.defaults();
+        self.ledger_info.network_id = network.network_id();
+        self.ledger_info.base_reserve = defaults.base_reserve;
+        self.ledger_info.min_temp_entry_ttl = defaults.min_temp_entry_ttl;
+        self.ledger_info.min_persistent_entry_ttl = defaults.min_persistent_entry_ttl;
+        self.ledger_info.max_entry_ttl = defaults.max_entry_ttl;
+        self
+    }
+
+    pub fn build(self) -> LedgerInfo {
+        self.ledger_info
+    }
+}
+
+/// Footprint key of the code with hash `hash`.
+pub fn code_key(hash: Hash) -> LedgerKey {
+    LedgerKey::ContractCode(LedgerKeyContractCode { hash })
+}
+
+/// Footprint key of the instance of the contract `contract_id`.
+pub fn instance_key(contract_id: Hash) -> LedgerKey {
+    LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::Contract(contract_id.into()),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    })
+}
+
+/// Builds an unsigned transaction invoking a contract function. Unless set,
+/// the source and the contract are all-zero and the resources are enough for
+/// the example contracts.
+#[derive(Clone, Debug)]
+pub struct EnvelopeBuilder {
+    source: [u8; 32],
+    contract: Hash,
+    function: String,
+    args: Vec<ScVal>,
+    read_only: Vec<LedgerKey>,
+    read_write: Vec<LedgerKey>,
+    instructions: u32,
+    disk_read_bytes: u32,
+    write_bytes: u32,
+    resource_fee: i64,
+}
+
+impl Default for EnvelopeBuilder {
+    fn default() -> Self {
+        Self {
+            source: [0; 32],
+            contract: Hash([0; 32]),
+            function: String::new(),
+            args: Vec::new(),
+            read_only: Vec::new(),
+            read_write: Vec::new(),
+            instructions: 10_000_000,
+            disk_read_bytes: 1_000_000,
+            write_bytes: 100_000,
+            resource_fee: 10_000_000,
+        }
+    }
+}
+
+impl EnvelopeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ed25519 key of the transaction's source account.
+    pub fn source(mut self, ed25519: [u8; 32]) -> Self {
+        self.source = ed25519;
+        self
+    }
+
+    pub fn contract(mut self, contract_id: Hash) -> Self {
+        self.contract = contract_id;
+        self
+    }
+
+    pub fn function(mut self, function: &str) -> Self {
+        self.function = function.to_string();
+        self
+    }
+
+    pub fn arg(mut self, arg: ScVal) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn read_only_key(mut self, key: LedgerKey) -> Self {
+        self.read_only.push(key);
+        self
+    }
+
+    pub fn read_write_key(mut self, key: LedgerKey) -> Self {
+        self.read_write.push(key);
+        self
+    }
+
+    pub fn instructions(mut self, instructions: u32) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    pub fn disk_read_bytes(mut self, disk_read_bytes: u32) -> Self {
+        self.disk_read_bytes = disk_read_bytes;
+        self
+    }
+
+    pub fn write_bytes(mut self, write_bytes: u32) -> Self {
+        self.write_bytes = write_bytes;
+        self
+    }
+
+    pub fn build(self) -> TransactionV1Envelope {
+        let resources = SorobanResources {
+            footprint: LedgerFootprint {
+                read_only: self.read_only.try_into().expect("footprint too large"),
+                read_write: self.read_write.try_into().expect("footprint too large"),
+            },
+            instructions: self.instructions,
+            disk_read_bytes: self.disk_read_bytes,
+            write_bytes: self.write_bytes,
         };
+        let host_function = HostFunction::InvokeContract(InvokeContractArgs {
+            contract_address: ScAddress::Contract(self.contract.into()),
+            function_name: ScSymbol(self.function.try_into().expect("function name too long")),
+            args: self.args.try_into().expect("too many arguments"),
+        });
 
-        Ok(Some((Rc::new(entry), Some(10000))))
+        TransactionV1Envelope {
+            tx: Transaction {
+                source_account: MuxedAccount::Ed25519(Uint256(self.source)),
+                fee: 0,
+                seq_num: SequenceNumber(1),
+                cond: Preconditions::None,
+                memo: Memo::None,
+                operations: vec![Operation {
+                    source_account: None,
+                    body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                        host_function,
+                        auth: Default::default(),
+                    }),
+                }]
+                .try_into()
+                .unwrap(),
+                ext: TransactionExt::V1(SorobanTransactionData {
+                    ext: SorobanTransactionDataExt::V0,
+                    resources,
+                    resource_fee: self.resource_fee,
+                }),
+            },
+            signatures: Default::default(),
+        }
     }
+
+    /// The transaction wrapped in a fee bump paid by `fee_source`.
+    pub fn build_fee_bump(self, fee_source: [u8; 32]) -> TransactionEnvelope {
+        let fee = self.resource_fee;
+
+        TransactionEnvelope::TxFeeBump(FeeBumpTransactionEnvelope {
+            tx: FeeBumpTransaction {
+                fee_source: MuxedAccount::Ed25519(Uint256(fee_source)),
+                fee,
+                inner_tx: FeeBumpTransactionInnerTx::Tx(self.build()),
+                ext: FeeBumpTransactionExt::V0,
+            },
+            signatures: Default::default(),
+        })
+    }
+}
+
+/// Set to regenerate the expectations of [`Fixture::assert_replay`].
+pub const BLESS_VAR: &str = "RETROSHADE_BLESS";
+
+/// A [`fixture`] ready to be replayed, with the retroshades it's expected to
+/// emit. These are kept next to the fixture, `foo.json` expecting
+/// `foo.expected.json`.
+pub struct Fixture {
+    pub path: PathBuf,
+    pub snapshot: MemorySnapshot,
+    pub envelope: TransactionV1Envelope,
+    pub meta: TransactionMeta,
+    /// The fixture's ledger settings, the defaults if it has none.
+    pub ledger_info: LedgerInfo,
+    pub expected_retroshades: Option<serde_json::Value>,
+}
+
+impl Fixture {
+    /// Loads a fixture, relative paths being relative to the crate under
+    /// test. Panics if it's missing or malformed.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let (snapshot, envelope, meta) = fixture::load(&path)
+            .unwrap_or_else(|error| panic!("can't load {}: {}", path.display(), error));
+        let ledger_info = fixture::load_ledger_info(&path)
+            .unwrap_or_else(|error| panic!("can't load {}: {}", path.display(), error))
+            .unwrap_or_default();
+
+        let envelope = v1_envelope(envelope, &path.display().to_string());
+
+        let expected_retroshades = fs::read_to_string(Self::expected_path(&path))
+            .ok()
+            .map(|json| serde_json::from_str(&json).expect("malformed expected retroshades"));
+
+        Self {
+            path,
+            snapshot,
+            envelope,
+            meta,
+            ledger_info,
+            expected_retroshades,
+        }
+    }
+
+    fn expected_path(path: &Path) -> PathBuf {
+        path.with_extension("expected.json")
+    }
+
+    /// Re-executes the fixture and packs its retroshades.
+    pub fn replay(&self) -> RetroshadeExecutionResultPretty {
+        let mut retroshades = RetroshadesExecution::new(self.ledger_info.clone());
+        retroshades
+            .build_from_envelope_and_meta(
+                &self.snapshot,
+                self.envelope.clone(),
+                self.meta.clone(),
+                HashMap::new(),
+            )
+            .unwrap_or_else(|error| panic!("can't build {}: {}", self.path.display(), error));
+
+        retroshades
+            .retroshade_packed()
+            .unwrap_or_else(|error| panic!("can't replay {}: {}", self.path.display(), error))
+    }
+
+    /// Replays the fixture and compares the packed retroshades with the
+    /// expected ones, panicking if there are none. These are written instead
+    /// when [`BLESS_VAR`] is set.
+    pub fn assert_replay(&self) -> RetroshadeExecutionResultPretty {
+        let result = self.replay();
+        let retroshades = serde_json::to_value(&result.retroshades).unwrap();
+
+        assert_json_file(&Self::expected_path(&self.path), &retroshades, BLESS_VAR);
+
+        result
+    }
+}
+
+/// The envelope executed, the inner one for fee bumps.
+fn v1_envelope(envelope: TransactionEnvelope, name: &str) -> TransactionV1Envelope {
+    match envelope {
+        TransactionEnvelope::Tx(envelope) => envelope,
+        TransactionEnvelope::TxFeeBump(envelope) => {
+            let FeeBumpTransactionInnerTx::Tx(inner) = envelope.tx.inner_tx;
+            inner
+        }
+        TransactionEnvelope::TxV0(_) => panic!("{} has a v0 envelope", name),
+    }
+}
+
+/// Set to the URL of a soroban-rpc to run the tests replaying live
+/// transactions, they are skipped otherwise.
+#[cfg(feature = "http")]
+pub const RPC_URL_VAR: &str = "RETROSHADE_RPC_URL";
+
+/// The RPC to replay live transactions from, `None` when [`RPC_URL_VAR`]
+/// isn't set so that the calling test can return early.
+#[cfg(feature = "http")]
+pub fn rpc_url() -> Option<String> {
+    let url = env::var(RPC_URL_VAR).ok();
+    if url.is_none() {
+        eprintln!("{} isn't set, skipping", RPC_URL_VAR);
+    }
+
+    url
+}
+
+/// A transaction fetched from a soroban-rpc with everything needed to replay
+/// it. Entries are fetched as the replay reads them, as of the RPC's latest
+/// ledger.
+#[cfg(feature = "http")]
+pub struct RpcCase {
+    pub tx_hash: Hash,
+    pub envelope: TransactionEnvelope,
+    pub meta: TransactionMeta,
+    pub ledger_info: LedgerInfo,
+    snapshot: Rc<RpcSnapshot>,
+}
+
+#[cfg(feature = "http")]
+impl RpcCase {
+    /// Fetches the transaction `tx_hash` and the settings of the ledger it
+    /// was applied in. Panics if the RPC doesn't have it.
+    pub fn fetch(rpc_url: &str, tx_hash: &Hash) -> Self {
+        let name = hex::encode(tx_hash.0);
+        let client = RpcClient::new(rpc_url);
+
+        let transaction = client
+            .get_transaction(tx_hash)
+            .unwrap_or_else(|error| panic!("can't fetch {}: {}", name, error));
+        let passphrase = client
+            .get_network_passphrase()
+            .unwrap_or_else(|error| panic!("can't fetch the network: {}", error));
+        let ledger_info = client
+            .ledger_info(&transaction, network_id(&passphrase))
+            .unwrap_or_else(|error| panic!("can't fetch ledger {}: {}", transaction.ledger, error));
+
+        Self {
+            tx_hash: tx_hash.clone(),
+            envelope: transaction.envelope,
+            meta: transaction.meta,
+            ledger_info,
+            snapshot: Rc::new(RpcSnapshot::new(client)),
+        }
+    }
+
+    /// Re-executes the transaction, replacing the contract code keyed by the
+    /// hashes of `replacements`, and packs its retroshades.
+    pub fn replay(
+        &self,
+        replacements: HashMap<Hash, ReplacementEntry>,
+    ) -> RetroshadeExecutionResultPretty {
+        let name = hex::encode(self.tx_hash.0);
+
+        let mut retroshades = RetroshadesExecution::new(self.ledger_info.clone());
+        retroshades
+            .build_from_envelope_and_meta(
+                &SharedRpcSnapshot(self.snapshot.clone()),
+                v1_envelope(self.envelope.clone(), &name),
+                self.meta.clone(),
+                replacements,
+            )
+            .unwrap_or_else(|error| panic!("can't build {}: {}", name, error));
+
+        retroshades
+            .retroshade_packed()
+            .unwrap_or_else(|error| panic!("can't replay {}: {}", name, error))
+    }
+
+    /// Freezes the transaction into a fixture at `path`, to be replayed
+    /// offline with [`Fixture`]. The entries are the ones the original code
+    /// reads, replacements aren't part of the fixture.
+    pub fn record_fixture(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        self.replay(HashMap::new());
+
+        fixture::dump_with_ledger_info(
+            path,
+            &self.snapshot.entries(),
+            &self.envelope,
+            &self.meta,
+            &self.ledger_info,
+        )
+        .unwrap_or_else(|error| panic!("can't write {}: {}", path.display(), error));
+    }
+}
+
+/// Lets the case keep the snapshot, and the entries it fetched, once
+/// boxed for the execution.
+#[cfg(feature = "http")]
+struct SharedRpcSnapshot(Rc<RpcSnapshot>);
+
+#[cfg(feature = "http")]
+impl SnapshotSource for SharedRpcSnapshot {
+    fn get(&self, key: &Rc<LedgerKey>) -> Result<Option<EntryWithLiveUntil>, HostError> {
+        self.0.get(key)
+    }
+}
+
+/// Fetches the transaction `tx_hash` from the RPC at `rpc_url` and replays it
+/// with `replacements`, see [`RpcCase`].
+#[cfg(feature = "http")]
+pub fn replay_from_rpc(
+    rpc_url: &str,
+    tx_hash: &Hash,
+    replacements: HashMap<Hash, ReplacementEntry>,
+) -> RetroshadeExecutionResultPretty {
+    RpcCase::fetch(rpc_url, tx_hash).replay(replacements)
+}
+
+/// Set to regenerate the files of [`assert_golden`].
+pub const UPDATE_GOLDEN_VAR: &str = "UPDATE_GOLDEN";
+
+/// Compares `value` as JSON with `tests/golden/<name>.json`, showing a diff
+/// if they differ and panicking if the file is missing. The file is written
+/// instead when [`UPDATE_GOLDEN_VAR`] is set.
+#[track_caller]
+pub fn assert_golden(name: &str, value: &impl Serialize) {
+    let path = Path::new("tests/golden").join(format!("{}.json", name));
+    let value = serde_json::to_value(value).expect("value isn't serializable as JSON");
+
+    assert_json_file(&path, &value, UPDATE_GOLDEN_VAR);
+}
+
+/// Compares JSON values rather than text so that formatting doesn't matter.
+#[track_caller]
+fn assert_json_file(path: &Path, actual: &serde_json::Value, update_var: &str) {
+    let actual_pretty = serde_json::to_string_pretty(actual).unwrap();
+
+    match fs::read_to_string(path) {
+        Ok(expected) if env::var_os(update_var).is_none() => {
+            let expected: serde_json::Value = serde_json::from_str(&expected)
+                .unwrap_or_else(|error| panic!("malformed {}: {}", path.display(), error));
+            if expected != *actual {
+                panic!(
+                    "{} doesn't match, rerun with {}=1 if that's expected:\n{}",
+                    path.display(),
+                    update_var,
+                    line_diff(
+                        &serde_json::to_string_pretty(&expected).unwrap(),
+                        &actual_pretty
+                    )
+                );
+            }
+        }
+        Err(error) if env::var_os(update_var).is_none() => {
+            panic!(
+                "can't read {}: {}, rerun with {}=1 to write it",
+                path.display(),
+                error,
+                update_var
+            );
+        }
+        _ => {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            fs::write(path, actual_pretty + "\n")
+                .unwrap_or_else(|error| panic!("can't write {}: {}", path.display(), error));
+        }
+    }
+}
+
+/// Lines only in `expected` are prefixed with `-`, lines only in `actual`
+/// with `+`.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of
+    // expected[i..] and actual[j..].
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            diff.push_str(&format!("  {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j < actual.len() && (i == expected.len() || common[i][j + 1] >= common[i + 1][j])
+        {
+            diff.push_str(&format!("+ {}\n", actual[j]));
+            j += 1;
+        } else {
+            diff.push_str(&format!("- {}\n", expected[i]));
+            i += 1;
+        }
+    }
+
+    diff
+}
+
+/// What a packed retroshade is expected to hold. Only the fields listed are
+/// checked, by name, so the order of the columns and columns added to the
+/// event don't matter.
+#[derive(Clone, Debug, Default)]
+pub struct ExpectedRetroshade {
+    target: Option<String>,
+    contract: Option<String>,
+    fields: Vec<(String, TypeKind)>,
+}
+
+impl ExpectedRetroshade {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Strkey of the emitting contract.
+    pub fn contract(mut self, contract: &str) -> Self {
+        self.contract = Some(contract.to_string());
+        self
+    }
+
+    pub fn field(mut self, name: &str, kind: TypeKind) -> Self {
+        self.fields.push((name.to_string(), kind));
+        self
+    }
+
+    /// How `retroshade` differs from the expectation, one line per
+    /// difference.
+    pub fn differences(&self, retroshade: &RetroshadeExportPretty) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if let Some(target) = self.target.as_ref().filter(|t| **t != retroshade.target) {
+            differences.push(format!(
+                "target: expected {:?}, got {:?}",
+                target, retroshade.target
+            ));
+        }
+        if let Some(contract) = self
+            .contract
+            .as_ref()
+            .filter(|c| **c != retroshade.contract_id)
+        {
+            differences.push(format!(
+                "contract: expected {}, got {}",
+                contract, retroshade.contract_id
+            ));
+        }
+
+        for (name, kind) in &self.fields {
+            match retroshade.event.iter().find(|entry| entry.name == *name) {
+                None => differences.push(format!("{}: missing", name)),
+                Some(entry) if entry.value.kind != *kind => differences.push(format!(
+                    "{}: expected {:?}, got {:?}",
+                    name, kind, entry.value.kind
+                )),
+                Some(_) => {}
+            }
+        }
+
+        differences
+    }
+
+    #[track_caller]
+    pub fn assert_matches(&self, retroshade: &RetroshadeExportPretty) {
+        let differences = self.differences(retroshade);
+        if differences.is_empty() {
+            return;
+        }
+
+        let fields: Vec<String> = retroshade
+            .event
+            .iter()
+            .map(|entry| format!("  {} = {:?}", entry.name, entry.value.kind))
+            .collect();
+        panic!(
+            "retroshade {} of {} doesn't match:\n  {}\nits fields are:\n{}",
+            retroshade.target,
+            retroshade.contract_id,
+            differences.join("\n  "),
+            fields.join("\n")
+        );
+    }
+}
+
+/// Asserts a packed retroshade holds the given target, contract and fields,
+/// see [`ExpectedRetroshade`]. Fields are `numeric`, `text`, `boolean` or
+/// `void`:
+///
+/// ```ignore
+/// assert_retroshade!(result.retroshades[0],
+///     target: "test",
+///     contract: "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABSC4",
+///     fields: { "amount" => numeric "2", "paused" => boolean false },
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_retroshade {
+    (@kind numeric $value:expr) => {
+        $crate::conversion::TypeKind::Numeric(::std::string::ToString::to_string(&$value))
+    };
+    (@kind text $value:expr) => {
+        $crate::conversion::TypeKind::Text(::std::string::ToString::to_string(&$value))
+    };
+    (@kind boolean $value:expr) => {
+        $crate::conversion::TypeKind::Boolean($value)
+    };
+    (@kind void) => {
+        $crate::conversion::TypeKind::Void
+    };
+    ($retroshade:expr
+        $(, target: $target:expr)?
+        $(, contract: $contract:expr)?
+        $(, fields: { $($name:literal => $kind:ident $($value:expr)?),* $(,)? })?
+        $(,)?
+    ) => {
+        $crate::testutils::ExpectedRetroshade::new()
+            $(.target($target))?
+            $(.contract($contract))?
+            $($(.field($name, $crate::assert_retroshade!(@kind $kind $($value)?)))*)?
+            .assert_matches(&$retroshade)
+    };
+}
+
+/// Any non-container value.
+fn arb_scval_leaf() -> impl Strategy<Value = ScVal> {
+    prop_oneof![
+        any::<bool>().prop_map(ScVal::Bool),
+        Just(ScVal::Void),
+        any::<u32>().prop_map(ScVal::U32),
+        any::<i32>().prop_map(ScVal::I32),
+        any::<u64>().prop_map(ScVal::U64),
+        any::<i64>().prop_map(ScVal::I64),
+        any::<u64>().prop_map(|t| ScVal::Timepoint(TimePoint(t))),
+        any::<u64>().prop_map(|d| ScVal::Duration(Duration(d))),
+        any::<(u64, u64)>().prop_map(|(hi, lo)| ScVal::U128(UInt128Parts { hi, lo })),
+        any::<(i64, u64)>().prop_map(|(hi, lo)| ScVal::I128(Int128Parts { hi, lo })),
+        any::<(u64, u64, u64, u64)>().prop_map(|(hi_hi, hi_lo, lo_hi, lo_lo)| {
+            ScVal::U256(UInt256Parts {
+                hi_hi,
+                hi_lo,
+                lo_hi,
+                lo_lo,
+            })
+        }),
+        any::<(i64, u64, u64, u64)>().prop_map(|(hi_hi, hi_lo, lo_hi, lo_lo)| {
+            ScVal::I256(Int256Parts {
+                hi_hi,
+                hi_lo,
+                lo_hi,
+                lo_lo,
+            })
+        }),
+        vec(any::<u8>(), 0..64).prop_map(|b| ScVal::Bytes(ScBytes(b.try_into().unwrap()))),
+        vec(any::<u8>(), 0..64).prop_map(|s| ScVal::String(ScString(s.try_into().unwrap()))),
+        "[a-zA-Z0-9_]{0,32}".prop_map(|s| ScVal::Symbol(ScSymbol(s.try_into().unwrap()))),
+        any::<[u8; 32]>().prop_map(|id| ScVal::Address(ScAddress::Contract(Hash(id).into()))),
+        any::<[u8; 32]>().prop_map(|key| {
+            ScVal::Address(ScAddress::Account(AccountId(
+                PublicKey::PublicKeyTypeEd25519(Uint256(key)),
+            )))
+        }),
+        any::<u32>().prop_map(|code| ScVal::Error(ScError::Contract(code))),
+    ]
+}
+
+/// Arbitrary `ScVal`s, vecs and maps nested at most `depth` levels with up
+/// to `size` elements each. Failing values shrink towards smaller and
+/// shallower ones.
+pub fn arb_scval(depth: u32, size: u32) -> impl Strategy<Value = ScVal> {
+    arb_scval_leaf().prop_recursive(depth, size.saturating_pow(depth), size, move |inner| {
+        let elements = 0..=size as usize;
+
+        prop_oneof![
+            Just(ScVal::Vec(None)),
+            vec(inner.clone(), elements.clone())
+                .prop_map(|items| ScVal::Vec(Some(ScVec(items.try_into().unwrap())))),
+            vec((inner.clone(), inner), elements).prop_map(|entries| {
+                let entries: Vec<ScMapEntry> = entries
+                    .into_iter()
+                    .map(|(key, val)| ScMapEntry { key, val })
+                    .collect();
+                ScVal::Map(Some(ScMap(entries.try_into().unwrap())))
+            }),
+        ]
+    })
 }
@@ -0,0 +1,103 @@
+//! Runs the standalone binary on bad inputs and checks the exit codes and
+//! error output.
+
+use std::{fs, path::PathBuf};
+
+use assert_cmd::Command;
+use retroshade::{
+    fixture,
+    soroban_env_host::xdr::{Limits, WriteXdr},
+};
+use serde_json::Value;
+
+fn fixture_xdrs() -> (String, String) {
+    let (_, envelope, meta) = fixture::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/mainnet_first_retroshade.json"
+    ))
+    .unwrap();
+
+    (
+        envelope.to_xdr_base64(Limits::none()).unwrap(),
+        meta.to_xdr_base64(Limits::none()).unwrap(),
+    )
+}
+
+/// In a directory that doesn't exist, so that SQLite can't create it either.
+fn missing_db() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("retroshade-missing-{}", std::process::id()))
+        .join("stellar.db")
+}
+
+fn run(subcommand: &str, extra: &[&str]) -> Command {
+    let (envelope, meta) = fixture_xdrs();
+    let mut cmd = Command::cargo_bin("standalone").unwrap();
+    cmd.arg(subcommand)
+        .args(["--envelope-xdr", &envelope, "--meta-xdr", &meta, "--db"])
+        .arg(missing_db())
+        .args(extra);
+    cmd
+}
+
+fn json_error(stdout: &[u8]) -> Value {
+    let output: Value = serde_json::from_slice(stdout).unwrap();
+    output["error"].clone()
+}
+
+#[test]
+fn malformed_envelope_is_a_usage_error() {
+    Command::cargo_bin("standalone")
+        .unwrap()
+        .args(["run", "--envelope-xdr", "not xdr", "--meta-xdr", "AAAA"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn missing_database_is_a_snapshot_error() {
+    let assert = run("run", &[]).assert().code(3);
+
+    let error = json_error(&assert.get_output().stdout);
+    assert_eq!(error["kind"], "Snapshot");
+    let message = error["message"].as_str().unwrap();
+    assert!(message.starts_with("transaction ") && message.contains("--ledger"));
+
+    let assert = run("run", &["--ledger", "5"]).assert().code(3);
+
+    let error = json_error(&assert.get_output().stdout);
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("header of ledger 5 not found"));
+}
+
+#[test]
+fn pretty_output_reports_on_stderr() {
+    let assert = run("run", &["--output", "pretty"]).assert().code(3);
+    let output = assert.get_output();
+
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("error: transaction "));
+}
+
+#[test]
+fn bad_config_is_a_usage_error() {
+    let config = std::env::temp_dir().join(format!("retroshade-cli-{}.toml", std::process::id()));
+    fs::write(&config, "unknown = 1").unwrap();
+
+    let assert = run("run", &["--config", config.to_str().unwrap()])
+        .assert()
+        .code(2);
+    fs::remove_file(&config).unwrap();
+
+    assert_eq!(json_error(&assert.get_output().stdout)["kind"], "Usage");
+}
+
+#[test]
+fn estimate_rejects_sql_output() {
+    let assert = run("estimate", &["--output", "sql"]).assert().code(2);
+
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).to_string();
+    assert!(stderr.contains("doesn't support sql output"));
+}
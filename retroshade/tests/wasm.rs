@@ -0,0 +1,75 @@
+//! Keeps the library building for wasm32-unknown-unknown, where it runs in
+//! browser tools. Natively, checks the build for the target when it's
+//! installed. On wasm32, replays the mainnet fixture from memory.
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn library_checks_for_wasm32() {
+    use std::{path::Path, process::Command};
+
+    const TARGET: &str = "wasm32-unknown-unknown";
+
+    let sysroot = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .expect("rustc is on the path");
+    let sysroot = String::from_utf8(sysroot.stdout).unwrap();
+    if !Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(TARGET)
+        .exists()
+    {
+        eprintln!("skipping, {TARGET} isn't installed");
+        return;
+    }
+
+    // note: a target directory of its own, the outer build holds the lock
+    // of the default one.
+    let status = Command::new(env!("CARGO"))
+        .args([
+            "check",
+            "--lib",
+            "--no-default-features",
+            "--target",
+            TARGET,
+        ])
+        .arg("--manifest-path")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("wasm32"),
+        )
+        .status()
+        .unwrap();
+    assert!(status.success(), "the library doesn't build for {TARGET}");
+}
+
+#[cfg(target_arch = "wasm32")]
+#[test]
+fn replays_from_memory() {
+    use std::collections::HashMap;
+
+    use retroshade::{
+        fixture,
+        soroban_env_host::{xdr::TransactionEnvelope, LedgerInfo},
+        RetroshadesExecution,
+    };
+
+    let (snapshot, envelope, meta) =
+        fixture::load_str(include_str!("../fixtures/mainnet_first_retroshade.json")).unwrap();
+    let TransactionEnvelope::Tx(envelope) = envelope else {
+        panic!("fixture envelope is not a v1 envelope")
+    };
+
+    let mut ledger_info = LedgerInfo::default();
+    ledger_info.protocol_version = 25;
+
+    let mut retroshades = RetroshadesExecution::new(ledger_info);
+    retroshades
+        .build_from_envelope_and_meta(&snapshot, envelope, meta, HashMap::new())
+        .unwrap();
+
+    let result = retroshades.retroshade_packed_seeded([0; 32]).unwrap();
+    assert!(result.call_succeeded);
+    assert!(!result.retroshades.is_empty());
+}